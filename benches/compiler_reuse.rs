@@ -0,0 +1,123 @@
+//! Compiles a batch of small graphs through the free functions, which set up a
+//! metadata provider per call, and through one reused `Compiler`.
+//!
+//! Run with `cargo bench --bench compiler_reuse`.
+
+use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+use graphy::{
+    Connection, ConnectionType, DataType, GraphDescription, NodeInstance, NodeTypes, Pin, PinInstance, PinType,
+    Position, PropertyValue,
+};
+use pbgc::{BlueprintMetadataProvider, CompileOptions, Compiler};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const GRAPHS: usize = 500;
+const PRINTS_PER_GRAPH: usize = 20;
+const ROUNDS: usize = 30;
+
+/// The nodes the batch uses, registered on top of pulsar_std like a project's own nodes
+fn provider() -> BlueprintMetadataProvider {
+    BlueprintMetadataProvider::new()
+        .with_node_metadata(
+            NodeMetadata::new("bench_main", NodeTypes::event, "Events").with_exec_outputs(vec!["body".to_string()]),
+        )
+        .with_node_metadata(
+            NodeMetadata::new("bench_print", NodeTypes::fn_, "Debug")
+                .with_params(vec![ParamInfo::new("message", "String")])
+                .with_return_type(TypeInfo::new("()".to_string()))
+                .with_exec_outputs(vec!["exec_out".to_string()])
+                .with_source("fn bench_print(message: String) {\n    println!(\"{}\", message);\n}"),
+        )
+}
+
+fn pin(node_id: &str, name: &str, data_type: DataType, pin_type: PinType) -> PinInstance {
+    let id = format!("{}_{}", node_id, name);
+    PinInstance { id: id.clone(), pin: Pin { id, name: name.to_string(), data_type, pin_type } }
+}
+
+fn node(id: &str, node_type: &str, inputs: Vec<PinInstance>, outputs: Vec<PinInstance>) -> NodeInstance {
+    NodeInstance {
+        id: id.to_string(),
+        node_type: node_type.to_string(),
+        position: Position { x: 0.0, y: 0.0 },
+        properties: HashMap::new(),
+        inputs,
+        outputs,
+    }
+}
+
+/// `bench_main` followed by a chain of `bench_print`s
+fn graph(index: usize) -> GraphDescription {
+    let mut graph = GraphDescription::new(&format!("graph_{}", index));
+    graph.nodes.insert(
+        "main".to_string(),
+        node("main", "bench_main", Vec::new(), vec![pin("main", "body", DataType::Execution, PinType::Output)]),
+    );
+
+    let mut previous = ("main".to_string(), "main_body".to_string());
+    for i in 0..PRINTS_PER_GRAPH {
+        let id = format!("print_{}", i);
+        let mut print = node(
+            &id,
+            "bench_print",
+            vec![
+                pin(&id, "exec", DataType::Execution, PinType::Input),
+                pin(&id, "message", DataType::Typed(TypeInfo::new("String".to_string())), PinType::Input),
+            ],
+            vec![pin(&id, "exec_out", DataType::Execution, PinType::Output)],
+        );
+        print.properties.insert("message".to_string(), PropertyValue::String(format!("step {}", i)));
+        graph.nodes.insert(id.clone(), print);
+
+        let (source_node, source_pin) = previous;
+        graph.connections.push(Connection {
+            id: format!("c{}", i),
+            source_node,
+            source_pin,
+            target_node: id.clone(),
+            target_pin: format!("{}_exec", id),
+            connection_type: ConnectionType::Execution,
+        });
+        previous = (id.clone(), format!("{}_exec_out", id));
+    }
+    graph
+}
+
+/// Fastest of `ROUNDS` runs of each of `a` and `b`, alternating so both see the same machine load
+fn time(mut a: impl FnMut(), mut b: impl FnMut()) -> (Duration, Duration) {
+    let run = |f: &mut dyn FnMut()| {
+        let start = Instant::now();
+        f();
+        start.elapsed()
+    };
+    (0..ROUNDS).fold((Duration::MAX, Duration::MAX), |(best_a, best_b), _| {
+        (best_a.min(run(&mut a)), best_b.min(run(&mut b)))
+    })
+}
+
+fn main() {
+    let graphs: Vec<GraphDescription> = (0..GRAPHS).map(graph).collect();
+    let options = CompileOptions::default();
+
+    let compiler = Compiler::with_provider(provider()).with_options(options.clone());
+    let (per_call, reused) = time(
+        || {
+            for graph in &graphs {
+                black_box(pbgc::compile_graph_with_provider(graph, &provider(), &options).unwrap());
+            }
+        },
+        || {
+            for graph in &graphs {
+                black_box(compiler.compile(graph).unwrap());
+            }
+        },
+    );
+
+    let per_graph = |total: Duration| total.as_secs_f64() * 1e6 / GRAPHS as f64;
+    println!("{} graphs of {} nodes, best of {} rounds", GRAPHS, PRINTS_PER_GRAPH + 1, ROUNDS);
+    println!("provider per call: {:>8.1?} ({:.1} us/graph)", per_call, per_graph(per_call));
+    println!("reused Compiler:   {:>8.1?} ({:.1} us/graph)", reused, per_graph(reused));
+    println!("speedup:           {:.2}x", per_call.as_secs_f64() / reused.as_secs_f64());
+}
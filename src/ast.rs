@@ -0,0 +1,40 @@
+//! # Syntax Tree Output
+//!
+//! Generated code as `syn` items, for tools that merge it into hand-written
+//! modules at the AST level. Enabled by the `syn` feature.
+//!
+//! Every item is marked with `#[pbgc_generated]`, so a merger can find and
+//! replace the items of a previous compile. The marker is not a real
+//! attribute; it must be stripped (or defined) before the merged file is
+//! compiled.
+
+use crate::error::CompileError;
+
+/// Name of the marker attribute on generated items
+pub const GENERATED_MARKER: &str = "pbgc_generated";
+
+/// The free functions in generated code, each marked with `#[pbgc_generated]`
+///
+/// Fails with [`CompileError::InvalidOutput`] if the code doesn't parse.
+pub fn generated_fns(code: &str) -> Result<Vec<syn::ItemFn>, CompileError> {
+    let file = syn::parse_file(code).map_err(|err| CompileError::InvalidOutput {
+        message: err.to_string(),
+    })?;
+
+    Ok(file
+        .items
+        .into_iter()
+        .filter_map(|item| match item {
+            syn::Item::Fn(mut item_fn) => {
+                item_fn.attrs.push(syn::parse_quote!(#[pbgc_generated]));
+                Some(item_fn)
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// Whether an item's attributes include the generated marker
+pub fn is_generated(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident(GENERATED_MARKER))
+}
@@ -0,0 +1,47 @@
+//! # Compile Cancellation
+//!
+//! Cooperative cancellation of in-flight compilations.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Flag used to abort a running compilation from another thread
+///
+/// Clones share the same flag. The compiler polls it between pipeline phases
+/// and once per node it expands during code generation, so a cancelled compile
+/// stops within a single node's worth of work.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pbgc::{CancellationToken, compile_graph_cancellable};
+/// use graphy::GraphDescription;
+///
+/// let token = CancellationToken::new();
+/// let worker_token = token.clone();
+/// let handle = std::thread::spawn(move || {
+///     compile_graph_cancellable(&GraphDescription::new("test"), &worker_token)
+/// });
+///
+/// // The user kept editing; the result is no longer needed
+/// token.cancel();
+/// let _ = handle.join();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation of every compile using this token
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
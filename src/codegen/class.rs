@@ -0,0 +1,331 @@
+//! # Blueprint Classes
+//!
+//! Struct generation for Blueprints compiled as classes.
+//!
+//! In class mode the Blueprint's variables become fields of a generated struct
+//! and its events become methods in an `impl` block, instead of free functions
+//! reading `thread_local!` storage.
+//!
+//! A struct whose variables all start at their type's default derives
+//! `Default`; otherwise it gets a `new()` setting each field to its initial
+//! value, since a derived `Default` would ignore them.
+
+use super::rust_codegen::{default_value_for_type, is_copy_type};
+use super::variables::{VariableDef, sorted_variables};
+use std::collections::{BTreeMap, HashMap};
+
+/// Describes the struct a Blueprint is compiled into
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassContext {
+    /// Name of the generated struct, e.g. `MyBlueprint`
+    pub name: String,
+    /// Derives emitted on the struct, e.g. `["Debug", "Clone"]`
+    ///
+    /// `Default` is derived whenever the variables all start at their type's
+    /// default, whether listed or not.
+    pub derives: Vec<String>,
+    /// Traits the struct implements through its events
+    pub trait_impls: Vec<TraitImpl>,
+    /// Cleanup statements run on drop, keyed by the variable they tear down
+    ///
+    /// When any are declared, `impl Drop` runs them in reverse field order,
+    /// e.g. `self.socket.shutdown()`.
+    pub teardown: BTreeMap<String, String>,
+}
+
+impl ClassContext {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            derives: Vec::new(),
+            trait_impls: Vec::new(),
+            teardown: BTreeMap::new(),
+        }
+    }
+
+    /// Run `statement` when the struct is dropped, to release `variable`'s resource
+    pub fn with_teardown(mut self, variable: impl Into<String>, statement: impl Into<String>) -> Self {
+        self.teardown.insert(variable.into(), statement.into());
+        self
+    }
+
+    /// Add a derive to the generated struct
+    pub fn with_derive(mut self, derive: impl Into<String>) -> Self {
+        self.derives.push(derive.into());
+        self
+    }
+
+    /// Implement a trait through some of the Blueprint's events
+    pub fn with_trait_impl(mut self, trait_impl: TraitImpl) -> Self {
+        self.trait_impls.push(trait_impl);
+        self
+    }
+}
+
+/// A trait the Blueprint struct implements, with events as its methods
+///
+/// Mapped events are emitted inside `impl Trait for Blueprint { ... }` under
+/// the trait method's name and parameter names instead of as inherent
+/// methods. Each event's data outputs must match the method's parameter types.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraitImpl {
+    /// Path of the trait, e.g. `engine::Behavior`
+    pub trait_path: String,
+    /// Trait methods keyed by the name of the event implementing them
+    pub method_map: HashMap<String, TraitMethod>,
+}
+
+impl TraitImpl {
+    pub fn new(trait_path: impl Into<String>) -> Self {
+        Self {
+            trait_path: trait_path.into(),
+            method_map: HashMap::new(),
+        }
+    }
+
+    /// Implement `method` with the event named `event`
+    pub fn with_method(mut self, event: impl Into<String>, method: TraitMethod) -> Self {
+        self.method_map.insert(event.into(), method);
+        self
+    }
+}
+
+/// Signature of a trait method taking `&mut self`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraitMethod {
+    /// Method name, e.g. `on_tick`
+    pub name: String,
+    /// Parameters as `(name, type)`, excluding the receiver
+    pub params: Vec<(String, String)>,
+}
+
+impl TraitMethod {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Add the next parameter
+    pub fn with_param(mut self, name: impl Into<String>, ty: impl Into<String>) -> Self {
+        self.params.push((name.into(), ty.into()));
+        self
+    }
+}
+
+/// A pure value read by several events, computed once into a private field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CachedField {
+    /// Field name, e.g. `cached_vector_length`
+    pub name: String,
+    /// Type of the cached value
+    pub ty: String,
+}
+
+/// Generate the Blueprint struct declaration, and its `new()` if it needs one
+///
+/// Cached fields follow the variables, as `core::cell::OnceCell`s. When a
+/// variable has a non-default initial value, a requested `Default` derive is
+/// replaced by an `impl Default` calling `new()`.
+pub(crate) fn generate_struct(class: &ClassContext, variables: &HashMap<String, VariableDef>, cached: &[CachedField]) -> String {
+    let mut code = String::new();
+
+    let is_default = |derive: &String| derive.rsplit("::").next() == Some("Default");
+    let starts_at_default = variables.values().all(starts_at_default);
+    let mut derives = class.derives.clone();
+    let default_requested = derives.iter().any(is_default);
+    if !starts_at_default {
+        derives.retain(|derive| !is_default(derive));
+    } else if !default_requested {
+        derives.push("Default".to_string());
+    }
+    if !derives.is_empty() {
+        code.push_str(&format!("#[derive({})]\n", derives.join(", ")));
+    }
+
+    if variables.is_empty() && cached.is_empty() {
+        code.push_str(&format!("pub struct {};\n", class.name));
+        return code;
+    }
+
+    code.push_str(&format!("pub struct {} {{\n", class.name));
+    for (name, var) in sorted_variables(variables) {
+        code.push_str(&format!("    pub {}: {},\n", name, var.ty));
+    }
+    for field in cached {
+        code.push_str(&format!("    {}: core::cell::OnceCell<{}>,\n", field.name, field.ty));
+    }
+    code.push_str("}\n");
+
+    if !starts_at_default {
+        code.push_str(&format!("\nimpl {} {{\n", class.name));
+        code.push_str("    pub fn new() -> Self {\n");
+        code.push_str("        Self {\n");
+        for (name, var) in sorted_variables(variables) {
+            code.push_str(&format!("            {}: {},\n", name, var.initial_expression()));
+        }
+        for field in cached {
+            code.push_str(&format!("            {}: core::cell::OnceCell::new(),\n", field.name));
+        }
+        code.push_str("        }\n");
+        code.push_str("    }\n");
+        code.push_str("}\n");
+
+        if default_requested {
+            code.push_str(&format!("\nimpl Default for {} {{\n", class.name));
+            code.push_str("    fn default() -> Self {\n");
+            code.push_str("        Self::new()\n");
+            code.push_str("    }\n");
+            code.push_str("}\n");
+        }
+    }
+
+    code
+}
+
+/// Whether a variable's initial value is its type's default
+fn starts_at_default(var: &VariableDef) -> bool {
+    var.initial.is_none() || var.initial_expression() == default_value_for_type(&var.ty)
+}
+
+/// Generate `impl Drop` running the class's teardown statements
+///
+/// Fields are torn down in the reverse of their declaration order. Returns
+/// `None` when no variable declares teardown.
+pub(crate) fn generate_drop_impl(class: &ClassContext, variables: &HashMap<String, VariableDef>) -> Option<String> {
+    if class.teardown.is_empty() {
+        return None;
+    }
+
+    let mut code = format!("impl Drop for {} {{\n", class.name);
+    code.push_str("    fn drop(&mut self) {\n");
+    for (name, _) in sorted_variables(variables).into_iter().rev() {
+        if let Some(statement) = class.teardown.get(name) {
+            code.push_str(&format!("        {};\n", statement.trim().trim_end_matches(';')));
+        }
+    }
+    code.push_str("    }\n");
+    code.push_str("}\n");
+
+    Some(code)
+}
+
+/// Check requested derives against the struct's fields
+///
+/// Returns a warning for each derive that can't work with the fields, such as
+/// `Copy` on a struct holding a `String`.
+pub(crate) fn check_derives(class: &ClassContext, variables: &HashMap<String, VariableDef>, cached: &[CachedField]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let has = |derive: &str| class.derives.iter().any(|d| d.rsplit("::").next() == Some(derive));
+
+    if has("Copy") {
+        for (name, var) in sorted_variables(variables) {
+            if !is_copy_type(&var.ty) {
+                warnings.push(format!(
+                    "Class '{}' derives Copy, but field '{}' has non-Copy type '{}'",
+                    class.name, name, var.ty
+                ));
+            }
+        }
+        if !has("Clone") {
+            warnings.push(format!(
+                "Class '{}' derives Copy without Clone",
+                class.name
+            ));
+        }
+        if !class.teardown.is_empty() {
+            warnings.push(format!(
+                "Class '{}' derives Copy but declares teardown; Copy types can't implement Drop",
+                class.name
+            ));
+        }
+        if !cached.is_empty() {
+            warnings.push(format!(
+                "Class '{}' derives Copy, but its cached values are held in OnceCell fields",
+                class.name
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClassContext, check_derives, generate_drop_impl, generate_struct};
+    use crate::codegen::VariableDef;
+    use graphy::PropertyValue;
+    use std::collections::HashMap;
+
+    #[test]
+    fn struct_carries_requested_derives_and_variable_fields() {
+        let variables = HashMap::from([
+            ("name".to_string(), VariableDef::new("String")),
+            ("health".to_string(), VariableDef::new("f32")),
+        ]);
+        let class = ClassContext::new("Player").with_derive("Debug").with_derive("Default");
+
+        assert_eq!(
+            generate_struct(&class, &variables, &[]),
+            "#[derive(Debug, Default)]\npub struct Player {\n    pub health: f32,\n    pub name: String,\n}\n"
+        );
+        assert!(check_derives(&class, &variables, &[]).is_empty());
+    }
+
+    #[test]
+    fn copy_derive_warns_about_non_copy_fields() {
+        let variables = HashMap::from([
+            ("name".to_string(), VariableDef::new("String")),
+            ("health".to_string(), VariableDef::new("f32")),
+        ]);
+        let class = ClassContext::new("Player").with_derive("Clone").with_derive("Copy");
+
+        let warnings = check_derives(&class, &variables, &[]);
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("field 'name' has non-Copy type 'String'"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn default_initials_derive_default() {
+        let variables = HashMap::from([
+            ("alive".to_string(), VariableDef::new("bool").with_initial(PropertyValue::Boolean(false))),
+            ("score".to_string(), VariableDef::new("i32")),
+        ]);
+
+        let code = generate_struct(&ClassContext::new("Player").with_derive("Debug"), &variables, &[]);
+        assert!(code.starts_with("#[derive(Debug, Default)]\n"), "{}", code);
+        assert!(!code.contains("fn new()"), "{}", code);
+    }
+
+    #[test]
+    fn non_default_initial_gets_new_instead_of_derived_default() {
+        let variables = HashMap::from([
+            ("alive".to_string(), VariableDef::new("bool").with_initial(PropertyValue::Boolean(true))),
+            ("score".to_string(), VariableDef::new("i32")),
+        ]);
+
+        let code = generate_struct(&ClassContext::new("Player").with_derive("Default"), &variables, &[]);
+        assert!(code.starts_with("pub struct Player {"), "{}", code);
+        assert!(code.contains("    pub fn new() -> Self {\n        Self {\n            alive: true,\n            score: 0,\n"), "{}", code);
+        assert!(code.contains("impl Default for Player {\n    fn default() -> Self {\n        Self::new()\n"), "{}", code);
+    }
+
+    #[test]
+    fn drop_runs_teardown_in_reverse_field_order() {
+        let variables = HashMap::from([
+            ("log".to_string(), VariableDef::new("File")),
+            ("health".to_string(), VariableDef::new("f32")),
+            ("socket".to_string(), VariableDef::new("TcpStream")),
+        ]);
+        let class = ClassContext::new("Player")
+            .with_teardown("socket", "self.socket.shutdown()")
+            .with_teardown("log", "self.log.flush();");
+
+        assert_eq!(
+            generate_drop_impl(&class, &variables).unwrap(),
+            "impl Drop for Player {\n    fn drop(&mut self) {\n        self.socket.shutdown();\n        self.log.flush();\n    }\n}\n"
+        );
+        assert_eq!(generate_drop_impl(&ClassContext::new("Player"), &variables), None);
+    }
+}
@@ -0,0 +1,763 @@
+//! # Control Flow Post-Processing
+//!
+//! Cleanup applied to control-flow templates after Graphy has inlined them.
+
+use super::templates::code_ranges;
+
+/// Remove `else` blocks whose body is empty
+///
+/// An unconnected `else` exec pin leaves its placeholder empty, producing
+/// `if cond { ... } else { }`. The empty `else` is dropped entirely. `else if`
+/// chains and non-empty blocks are left untouched.
+pub(crate) fn remove_empty_else(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+
+    while let Some(pos) = find_keyword(rest, "else") {
+        let before = &rest[..pos];
+        let after = &rest[pos + "else".len()..];
+
+        let follows_block = before.trim_end().ends_with('}');
+        let empty_block = after
+            .trim_start()
+            .strip_prefix('{')
+            .and_then(|inner| {
+                let inner = inner.trim_start();
+                inner.strip_prefix('}')
+            });
+
+        match (follows_block, empty_block) {
+            (true, Some(remainder)) => {
+                out.push_str(before.trim_end());
+                rest = remainder;
+            }
+            _ => {
+                out.push_str(before);
+                out.push_str("else");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// Add a wildcard arm to the template's `match` if it has none
+///
+/// `arm` is the full arm, e.g. `_ => unreachable!("..."),`. It is marked
+/// `#[allow(unreachable_patterns)]`, since the match may already be
+/// exhaustive when the node's cases cover every variant.
+pub(crate) fn add_match_fallback(body: &str, arm: &str) -> String {
+    let Some(match_pos) = find_keyword(body, "match") else { return body.to_string() };
+    let Some(open) = body[match_pos..].find('{').map(|i| match_pos + i) else { return body.to_string() };
+
+    // Find the block's closing brace, keeping only the block's own top level
+    let mut depth = 0;
+    let mut close = None;
+    let mut top_level = String::new();
+    for (i, c) in body[open..].char_indices() {
+        match c {
+            '{' => {
+                depth += 1;
+                if depth == 1 {
+                    continue;
+                }
+            }
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + i);
+                    break;
+                }
+                if depth == 1 {
+                    top_level.push('}');
+                }
+            }
+            _ => {}
+        }
+        if depth == 1 {
+            top_level.push(c);
+        }
+    }
+    let Some(close) = close else { return body.to_string() };
+
+    // A guarded `_ if ... =>` arm doesn't make the match exhaustive
+    let has_wildcard = top_level.split([',', '}']).any(|arm| {
+        arm.trim_start()
+            .strip_prefix('_')
+            .is_some_and(|rest| rest.trim_start().starts_with("=>"))
+    });
+    if has_wildcard {
+        return body.to_string();
+    }
+
+    let line_start = body[..close].rfind('\n').map_or(0, |i| i + 1);
+    let closing_indent: String = body[line_start..close].chars().take_while(|c| c.is_whitespace()).collect();
+    let arm_indent = format!("{}    ", closing_indent);
+
+    let mut before = body[..close].trim_end().to_string();
+    if !before.ends_with(['{', ',', '}']) {
+        before.push(',');
+    }
+
+    format!(
+        "{}\n{}#[allow(unreachable_patterns)]\n{}{}\n{}{}",
+        before,
+        arm_indent,
+        arm_indent,
+        arm,
+        closing_indent,
+        &body[close..]
+    )
+}
+
+/// Re-indent a block structurally, by bracket depth, under `indent`
+///
+/// Templates may use any brace style, so lines are not prefixed as-is.
+/// Opening braces on their own line (Allman style) are joined onto the line
+/// they belong to, as is an `else` following a closing brace, and every line
+/// is then indented four spaces per enclosing `{`, `(` or `[`. Blank lines
+/// are dropped.
+pub(crate) fn reindent(body: &str, indent: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in body.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(prev) = lines.last_mut() {
+            let opens_block = line.starts_with('{') && !prev.ends_with(['{', '}', ';', ',']) && !prev.starts_with("//");
+            let continues_if = line.starts_with("else") && prev.ends_with('}');
+            if opens_block || continues_if {
+                prev.push(' ');
+                prev.push_str(line);
+                continue;
+            }
+        }
+        lines.push(line.to_string());
+    }
+
+    let mut out = String::new();
+    let mut depth: usize = 0;
+    for line in &lines {
+        let leading_closers = line.chars().take_while(|c| matches!(c, '}' | ')' | ']')).count();
+        let line_depth = depth.saturating_sub(leading_closers);
+        out.push_str(indent);
+        out.push_str(&"    ".repeat(line_depth));
+        out.push_str(line);
+        out.push('\n');
+
+        let (opens, closes) = bracket_counts(line);
+        depth = (depth + opens).saturating_sub(closes);
+    }
+    out
+}
+
+/// Number of opening and closing brackets in a line, outside strings, chars and comments
+fn bracket_counts(line: &str) -> (usize, usize) {
+    let (mut opens, mut closes) = (0, 0);
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '(' | '[' => opens += 1,
+            '}' | ')' | ']' => closes += 1,
+            '/' if chars.peek() == Some(&'/') => break,
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '\'' => {
+                // A char literal ('x' or '\n'); anything else is a lifetime
+                let mut lookahead = chars.clone();
+                let literal_len = match lookahead.next() {
+                    Some('\\') => lookahead.nth(1).filter(|&c| c == '\'').map(|_| 3),
+                    Some(_) => lookahead.next().filter(|&c| c == '\'').map(|_| 2),
+                    None => None,
+                };
+                if let Some(len) = literal_len {
+                    for _ in 0..len {
+                        chars.next();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    (opens, closes)
+}
+
+/// Which of `markers` sit in branches that can never run
+///
+/// `body` is an inlined control-flow template with each exec output replaced
+/// by its marker. A branch is dead when it is the `else` of an `if true`, the
+/// block of an `if false` or `while false`, or a `match` arm whose literal
+/// patterns can't equal the literal being matched. Conditions are folded with
+/// [`const_bool`]. Returns indices into `markers`.
+pub(crate) fn dead_markers(body: &str, markers: &[String]) -> Vec<usize> {
+    let mut dead_regions: Vec<(usize, usize)> = Vec::new();
+
+    for pos in keyword_positions(body, "if") {
+        let Some(open) = body[pos..].find('{').map(|i| pos + i) else { continue };
+        let condition = body[pos + "if".len()..open].trim();
+        let Some(close) = matching_brace(body, open) else { continue };
+
+        match const_bool(condition) {
+            Some(false) => dead_regions.push((open, close)),
+            Some(true) => {
+                if let Some(else_region) = else_region(body, close + 1) {
+                    dead_regions.push(else_region);
+                }
+            }
+            None => {}
+        }
+    }
+
+    for pos in keyword_positions(body, "while") {
+        let Some(open) = body[pos..].find('{').map(|i| pos + i) else { continue };
+        if const_bool(&body[pos + "while".len()..open]) == Some(false) {
+            let Some(close) = matching_brace(body, open) else { continue };
+            dead_regions.push((open, close));
+        }
+    }
+
+    for pos in keyword_positions(body, "match") {
+        let Some(open) = body[pos..].find('{').map(|i| pos + i) else { continue };
+        let scrutinee = strip_parens(body[pos + "match".len()..open].trim());
+        if !is_literal(scrutinee) {
+            continue;
+        }
+        let Some(close) = matching_brace(body, open) else { continue };
+        dead_regions.extend(dead_arms(body, open, close, scrutinee));
+    }
+
+    markers
+        .iter()
+        .enumerate()
+        .filter(|(_, marker)| {
+            body.match_indices(marker.as_str())
+                .any(|(i, _)| dead_regions.iter().any(|&(start, end)| start < i && i < end))
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Regions of the arms in a `match` block that can't match `scrutinee`
+///
+/// Only unguarded arms made of literal patterns are judged; any other arm is
+/// dead only if an earlier arm (a matching literal or a bare `_`) always matches.
+fn dead_arms(body: &str, open: usize, close: usize, scrutinee: &str) -> Vec<(usize, usize)> {
+    let mut regions = Vec::new();
+    let mut matched = false;
+    let mut arm_start = open + 1;
+
+    while arm_start < close {
+        let Some(arrow) = code_matches(&body[arm_start..close], "=>").next().map(|i| arm_start + i) else { break };
+        let pattern = &body[arm_start..arrow];
+        let guarded = code_ranges(pattern).into_iter().any(|range| find_keyword(&pattern[range], "if").is_some());
+        let mut patterns = Vec::new();
+        let mut from = 0;
+        for bar in code_matches(pattern, "|").chain([pattern.len()]) {
+            patterns.push(pattern[from..bar].trim());
+            from = bar + 1;
+        }
+
+        // The arm runs to its block's closing brace, or to the next top-level comma
+        let rest = body[arrow + 2..close].trim_start();
+        let body_start = close - rest.len();
+        let arm_end = if rest.starts_with('{') {
+            let end = matching_brace(body, body_start).unwrap_or(close);
+            if body[end + 1..close].trim_start().starts_with(',') {
+                end + 1 + body[end + 1..close].find(',').unwrap_or(0)
+            } else {
+                end
+            }
+        } else {
+            top_level_comma(body, body_start, close).unwrap_or(close)
+        };
+
+        let literal_arm = !guarded && patterns.iter().all(|pattern| is_literal(pattern));
+        let exact = !guarded && patterns.contains(&scrutinee);
+        if matched || (literal_arm && !exact) {
+            regions.push((arrow, arm_end));
+        }
+        matched |= exact || patterns == ["_"];
+        arm_start = arm_end + 1;
+    }
+
+    regions
+}
+
+/// Rewrite `while` loops whose condition is constant
+///
+/// `while true { .. }` becomes `loop { .. }`, and a `while false` loop is
+/// removed entirely since its body can never run. Also returns how many loops
+/// were folded, and how many of the removed ones had a non-empty body.
+pub(crate) fn fold_constant_loops(body: &str) -> (String, usize, usize) {
+    let mut body = body.to_string();
+    let mut folded = 0;
+    let mut elided = 0;
+    let positions: Vec<usize> = keyword_positions(&body, "while").collect();
+
+    // Back to front, so earlier positions stay valid
+    for pos in positions.into_iter().rev() {
+        let Some(open) = body[pos..].find('{').map(|i| pos + i) else { continue };
+        match const_bool(&body[pos + "while".len()..open]) {
+            Some(true) => body.replace_range(pos..open, "loop "),
+            Some(false) => {
+                let Some(close) = matching_brace(&body, open) else { continue };
+                if !body[open + 1..close].trim().is_empty() {
+                    elided += 1;
+                }
+                body.replace_range(pos..=close, "");
+            }
+            None => continue,
+        }
+        folded += 1;
+    }
+
+    (body, folded, elided)
+}
+
+/// Evaluate a condition made only of literals and operators
+///
+/// Handles `!`, `&&`, `||`, comparisons, unary minus and parentheses over
+/// bool and number literals, which is what chains of constant-fed operator
+/// nodes compile to. Anything else (a variable, a call) gives `None`.
+pub(crate) fn const_bool(expr: &str) -> Option<bool> {
+    let tokens = const_tokens(expr)?;
+    let mut pos = 0;
+    let value = const_or(&tokens, &mut pos)?;
+    match (value, pos == tokens.len()) {
+        (Const::Bool(value), true) => Some(value),
+        _ => None,
+    }
+}
+
+/// Value of a constant sub-expression
+#[derive(Clone, Copy, PartialEq)]
+enum Const {
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+}
+
+/// Split a constant expression into operator and literal tokens
+fn const_tokens(expr: &str) -> Option<Vec<&str>> {
+    const OPERATORS: [&str; 12] = ["&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "-", "(", ")"];
+
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while !rest.is_empty() {
+        let len = match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            Some(op) => op.len(),
+            None => rest
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '.' | '_')))
+                .unwrap_or(rest.len()),
+        };
+        if len == 0 {
+            return None;
+        }
+        tokens.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+    Some(tokens)
+}
+
+fn const_or(tokens: &[&str], pos: &mut usize) -> Option<Const> {
+    let mut value = const_and(tokens, pos)?;
+    while tokens.get(*pos) == Some(&"||") {
+        *pos += 1;
+        let (Const::Bool(a), Const::Bool(b)) = (value, const_and(tokens, pos)?) else { return None };
+        value = Const::Bool(a || b);
+    }
+    Some(value)
+}
+
+fn const_and(tokens: &[&str], pos: &mut usize) -> Option<Const> {
+    let mut value = const_comparison(tokens, pos)?;
+    while tokens.get(*pos) == Some(&"&&") {
+        *pos += 1;
+        let (Const::Bool(a), Const::Bool(b)) = (value, const_comparison(tokens, pos)?) else { return None };
+        value = Const::Bool(a && b);
+    }
+    Some(value)
+}
+
+fn const_comparison(tokens: &[&str], pos: &mut usize) -> Option<Const> {
+    let left = const_unary(tokens, pos)?;
+    let Some(&op) = tokens.get(*pos).filter(|op| matches!(**op, "==" | "!=" | "<" | "<=" | ">" | ">=")) else {
+        return Some(left);
+    };
+    *pos += 1;
+    let right = const_unary(tokens, pos)?;
+
+    let ordering = match (left, right) {
+        (Const::Bool(a), Const::Bool(b)) => a.cmp(&b),
+        (Const::Int(a), Const::Int(b)) => a.cmp(&b),
+        (Const::Float(a), Const::Float(b)) => a.partial_cmp(&b)?,
+        _ => return None,
+    };
+    Some(Const::Bool(match op {
+        "==" => ordering.is_eq(),
+        "!=" => ordering.is_ne(),
+        "<" => ordering.is_lt(),
+        "<=" => ordering.is_le(),
+        ">" => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    }))
+}
+
+fn const_unary(tokens: &[&str], pos: &mut usize) -> Option<Const> {
+    let token = *tokens.get(*pos)?;
+    *pos += 1;
+    match token {
+        "!" => match const_unary(tokens, pos)? {
+            Const::Bool(value) => Some(Const::Bool(!value)),
+            _ => None,
+        },
+        "-" => match const_unary(tokens, pos)? {
+            Const::Int(value) => Some(Const::Int(-value)),
+            Const::Float(value) => Some(Const::Float(-value)),
+            Const::Bool(_) => None,
+        },
+        "(" => {
+            let value = const_or(tokens, pos)?;
+            (tokens.get(*pos) == Some(&")")).then(|| *pos += 1)?;
+            Some(value)
+        }
+        "true" => Some(Const::Bool(true)),
+        "false" => Some(Const::Bool(false)),
+        literal => const_number(literal),
+    }
+}
+
+/// A number literal, ignoring `_` separators and any type suffix
+fn const_number(literal: &str) -> Option<Const> {
+    if !literal.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let digits: String = literal.chars().filter(|c| *c != '_').collect();
+    let (number, suffix) = digits.split_at(digits.find(|c: char| c.is_alphabetic()).unwrap_or(digits.len()));
+    match suffix {
+        "f32" | "f64" => number.parse().ok().map(Const::Float),
+        "" if number.contains('.') => number.parse().ok().map(Const::Float),
+        "" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => {
+            number.parse().ok().map(Const::Int)
+        }
+        _ => None,
+    }
+}
+
+/// The `else` block (or `else if` chain) following an `if` block ending before `from`
+fn else_region(body: &str, from: usize) -> Option<(usize, usize)> {
+    let rest = body[from..].trim_start();
+    let after_else = rest.strip_prefix("else")?;
+    let start = body.len() - after_else.len();
+
+    let mut end = start;
+    loop {
+        let open = body[end..].find('{').map(|i| end + i)?;
+        end = matching_brace(body, open)?;
+        match body[end + 1..].trim_start().strip_prefix("else") {
+            Some(next) => end = body.len() - next.len(),
+            None => return Some((start, end)),
+        }
+    }
+}
+
+/// Index of the brace closing the one opened at `open`
+fn matching_brace(body: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in body[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// First comma in `body[from..to]` outside any brackets
+fn top_level_comma(body: &str, from: usize, to: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in body[from..to].char_indices() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => return Some(from + i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether a pattern or expression is a single plain literal
+///
+/// A range such as `1..5` or an or-pattern of literals is not one.
+pub(crate) fn is_literal(s: &str) -> bool {
+    let s = s.trim();
+    // A lone string or char literal leaves no code outside it
+    let quoted = s.starts_with(['"', '\'']) && code_ranges(s).iter().all(|range| range.is_empty());
+    s == "true"
+        || s == "false"
+        || quoted
+        || s.strip_prefix('-').unwrap_or(s).starts_with(|c: char| c.is_ascii_digit())
+            && !s.contains("..")
+            && s.chars().all(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+fn strip_parens(mut s: &str) -> &str {
+    while let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        s = inner.trim();
+    }
+    s
+}
+
+/// Whether a control-flow template loops over its branches
+///
+/// Comment lines are ignored, so a doc comment mentioning "for" doesn't count.
+pub(crate) fn is_loop_template(source: &str) -> bool {
+    source
+        .lines()
+        .map(str::trim_start)
+        .filter(|line| !line.starts_with("//"))
+        .any(|line| ["for", "while", "loop"].iter().any(|keyword| find_keyword(line, keyword).is_some()))
+}
+
+/// Every position of `pattern` outside string and char literals and comments
+fn code_matches<'s>(s: &'s str, pattern: &'s str) -> impl Iterator<Item = usize> + 's {
+    code_ranges(s)
+        .into_iter()
+        .flat_map(move |range| s[range.clone()].match_indices(pattern).map(move |(i, _)| range.start + i))
+}
+
+/// Every position of a keyword at identifier boundaries, outside string and
+/// char literals and comments
+fn keyword_positions<'s>(s: &'s str, keyword: &'s str) -> impl Iterator<Item = usize> + 's {
+    code_ranges(s).into_iter().flat_map(move |range| {
+        let code = &s[range.clone()];
+        let mut from = 0;
+        std::iter::from_fn(move || {
+            let pos = from + find_keyword(&code[from..], keyword)?;
+            from = pos + keyword.len();
+            Some(range.start + pos)
+        })
+    })
+}
+
+/// Find a keyword at identifier boundaries
+fn find_keyword(s: &str, keyword: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+
+    s.match_indices(keyword).map(|(i, _)| i).find(|&i| {
+        let before_ok = s[..i].chars().next_back().is_none_or(|c| !is_ident(c));
+        let after_ok = s[i + keyword.len()..].chars().next().is_none_or(|c| !is_ident(c));
+        before_ok && after_ok
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{GraphBuilder, provider};
+    use crate::Compiler;
+
+    #[test]
+    fn empty_else_is_dropped() {
+        assert_eq!(remove_empty_else("if c {\n    a();\n} else {\n}"), "if c {\n    a();\n}");
+        assert_eq!(remove_empty_else("if c { a(); } else { b(); }"), "if c { a(); } else { b(); }");
+        assert_eq!(remove_empty_else("if c { a(); } else if d { b(); }"), "if c { a(); } else if d { b(); }");
+    }
+
+    #[test]
+    fn unconnected_else_leaves_no_dangling_block() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("branch", "branch")
+            .node("coin", "random_bool")
+            .node("print", "print_string")
+            .data("coin", "result", "branch", "condition")
+            .exec("main", "body", "branch")
+            .exec("branch", "True", "print")
+            .build();
+
+        let code = Compiler::with_provider(provider()).compile(&graph).unwrap();
+        assert!(code.contains("    if random_bool() {\n        print_string("), "{}", code);
+        assert!(!code.contains("else"), "{}", code);
+    }
+
+    const UNREACHABLE_ARM: &str = "_ => unreachable!(\"unhandled case in node switch\"),";
+
+    #[test]
+    fn match_without_wildcard_gets_the_fallback_arm() {
+        let body = "match dir {\n    Dir::Left => turn(),\n    Dir::Right => { stop(); }\n}";
+        assert_eq!(
+            add_match_fallback(body, UNREACHABLE_ARM),
+            "match dir {\n    Dir::Left => turn(),\n    Dir::Right => { stop(); }\n    #[allow(unreachable_patterns)]\n    _ => unreachable!(\"unhandled case in node switch\"),\n}"
+        );
+    }
+
+    #[test]
+    fn match_with_wildcard_is_left_alone() {
+        let body = "match dir {\n    Dir::Left => { if x { turn(); } }\n    _ => stop(),\n}";
+        assert_eq!(add_match_fallback(body, UNREACHABLE_ARM), body);
+    }
+
+    #[test]
+    fn guarded_wildcard_still_gets_the_fallback_arm() {
+        let body = "match dir {\n    Dir::Left => turn(),\n    _ if fast => stop(),\n}";
+        assert!(add_match_fallback(body, UNREACHABLE_ARM).ends_with("    _ => unreachable!(\"unhandled case in node switch\"),\n}"));
+    }
+
+    #[test]
+    fn allman_and_k_and_r_templates_reindent_identically() {
+        let allman = "if condition\n{\nprintln!(\"{\");\n}\nelse\n{\n        done();\n}";
+        let k_and_r = "if condition {\n  println!(\"{\");\n} else {\ndone();\n      }";
+        let expected = "    if condition {\n        println!(\"{\");\n    } else {\n        done();\n    }\n";
+
+        assert_eq!(reindent(allman, "    "), expected);
+        assert_eq!(reindent(k_and_r, "    "), expected);
+    }
+
+    #[test]
+    fn branch_template_brace_style_does_not_change_the_output() {
+        use graphy::core::{NodeMetadata, ParamInfo};
+        use graphy::{NodeTypes, PropertyValue};
+
+        let compile = |source: &str| {
+            let branch = NodeMetadata::new("branch", NodeTypes::control_flow, "Flow")
+                .with_params(vec![ParamInfo::new("condition", "bool")])
+                .with_exec_outputs(vec!["True".to_string(), "False".to_string()])
+                .with_source(source);
+            let graph = GraphBuilder::new()
+                .node("main", "main")
+                .node("branch", "branch")
+                .node("yes", "print_string")
+                .node("no", "print_string")
+                .property("yes", "message", PropertyValue::String("yes".to_string()))
+                .property("no", "message", PropertyValue::String("no".to_string()))
+                .exec("main", "body", "branch")
+                .exec("branch", "True", "yes")
+                .exec("branch", "False", "no")
+                .build();
+            Compiler::with_provider(provider().with_node_metadata(branch)).compile(&graph).unwrap()
+        };
+
+        let allman = compile(
+            "fn branch(condition: bool)\n{\n    if condition\n    {\n        exec_output!(\"True\");\n    }\n    else\n    {\n        exec_output!(\"False\");\n    }\n}",
+        );
+        let k_and_r = compile(
+            "fn branch(condition: bool) {\n    if condition {\n        exec_output!(\"True\");\n    } else {\n        exec_output!(\"False\");\n    }\n}",
+        );
+        assert_eq!(allman, k_and_r);
+        assert!(
+            allman.contains("    if false {\n        print_string(\"yes\");\n    } else {\n        print_string(\"no\");\n    }\n"),
+            "{}",
+            allman
+        );
+    }
+
+    fn markers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn range_arm_is_not_a_literal() {
+        assert!(!is_literal("1..5"));
+        assert!(!is_literal("\"a\" | \"b\""));
+        assert!(is_literal("\"a|b\""));
+
+        let body = "match 3 {\n    1..5 => { LOW }\n    _ => { HIGH }\n}";
+        assert_eq!(dead_markers(body, &markers(&["LOW", "HIGH"])), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn guarded_arm_neither_dies_nor_always_matches() {
+        let body = "match 1 {\n    1 if flag => { GUARDED }\n    1 => { ONE }\n    _ => { OTHER }\n}";
+        assert_eq!(dead_markers(body, &markers(&["GUARDED", "ONE", "OTHER"])), [2]);
+    }
+
+    #[test]
+    fn or_patterns_split_outside_string_literals() {
+        let body = "match \"a|b\" {\n    \"a|b\" => { BOTH }\n    _ => { OTHER }\n}";
+        assert_eq!(dead_markers(body, &markers(&["BOTH", "OTHER"])), [1]);
+
+        let body = "match 2 {\n    1 | 2 => { LOW }\n    3 => { HIGH }\n}";
+        assert_eq!(dead_markers(body, &markers(&["LOW", "HIGH"])), [1]);
+    }
+
+    #[test]
+    fn constant_while_loops_fold_outside_literals_and_comments() {
+        let (body, folded, elided) = fold_constant_loops("while true {\n    tick();\n}\nwhile false {\n    skip();\n}\nwhile false {\n}");
+        assert_eq!((body.as_str(), folded, elided), ("loop {\n    tick();\n}\n\n", 3, 1));
+
+        let body = "// while true { spin }\nlog(\"while false {\");";
+        assert_eq!(fold_constant_loops(body), (body.to_string(), 0, 0));
+    }
+
+    /// A `while_loop` on a constant, with `tick` in its body unless `template_body` says otherwise
+    ///
+    /// `template_body` replaces the template's loop body, leaving the body pin unconnected.
+    fn constant_loop(condition: bool, template_body: Option<&str>) -> (String, Vec<crate::Diagnostic>) {
+        use graphy::core::{NodeMetadata, ParamInfo};
+        use graphy::NodeTypes;
+
+        let mut builder = GraphBuilder::new()
+            .node("main", "main")
+            .node("loop", "while_loop")
+            .property("loop", "condition", graphy::PropertyValue::Boolean(condition))
+            .node("tick", "print_string")
+            .property("tick", "message", graphy::PropertyValue::String("tick".to_string()))
+            .node("done", "print_string")
+            .property("done", "message", graphy::PropertyValue::String("done".to_string()))
+            .exec("main", "body", "loop")
+            .exec("loop", "completed", "done");
+        let mut provider = provider();
+        match template_body {
+            Some(statement) => {
+                let source = format!(
+                    "fn while_loop(condition: bool) {{\n    while condition {{\n        {}\n        exec_output!(\"body\");\n    }}\n    exec_output!(\"completed\");\n}}",
+                    statement
+                );
+                provider = provider.with_node_metadata(
+                    NodeMetadata::new("while_loop", NodeTypes::control_flow, "Flow")
+                        .with_params(vec![ParamInfo::new("condition", "bool")])
+                        .with_exec_outputs(vec!["body".to_string(), "completed".to_string()])
+                        .with_source(source),
+                );
+            }
+            None => builder = builder.exec("loop", "body", "tick"),
+        }
+        Compiler::with_provider(provider).compile_with_diagnostics(&builder.build()).unwrap()
+    }
+
+    #[test]
+    fn constant_true_while_loop_is_a_loop() {
+        let (code, diagnostics) = constant_loop(true, None);
+        assert!(code.contains("    loop {\n        print_string(\"tick\");\n    }\n    print_string(\"done\");\n"), "{}", code);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn constant_false_while_loop_is_elided_with_a_warning() {
+        let (code, diagnostics) = constant_loop(false, None);
+        assert!(code.contains("pub fn main() {\n    print_string(\"done\");\n}"), "{}", code);
+        let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages, ["Branch 'body' of node 'loop' can never run: its condition is constant"]);
+
+        let (code, diagnostics) = constant_loop(false, Some("retries += 1;"));
+        assert!(!code.contains("retries"), "{}", code);
+        let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages, ["Loop in node 'loop' can never run: its condition is constant false"]);
+    }
+}
@@ -0,0 +1,101 @@
+//! # Event Dispatch
+//!
+//! A generated `dispatch` function calling events by name with dynamically
+//! typed arguments, for hosts (scripting consoles, network RPC) that only
+//! know events as strings.
+//!
+//! The generated module gains a `Value` enum for the arguments and a
+//! `DispatchError` for unknown events and argument mismatches.
+
+use super::functions::EventParam;
+
+/// An event callable through `dispatch`
+pub(crate) struct DispatchEvent<'p> {
+    /// Name of the event's function
+    pub name: &'p str,
+    /// Parameters decoded from the arguments, in order
+    pub params: &'p [EventParam],
+}
+
+/// Whether `dispatch` can decode a parameter of this type
+pub(crate) fn is_decodable(ty: &str) -> bool {
+    decode_expression(ty, 0, "").is_some()
+}
+
+/// Generate `Value`, `DispatchError` and `dispatch` for the given events
+///
+/// Every parameter type must be [decodable](is_decodable).
+pub(crate) fn generate_dispatch(events: &[DispatchEvent]) -> String {
+    let mut code = String::new();
+
+    code.push_str("/// Argument passed to an event through `dispatch`\n");
+    code.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+    code.push_str("pub enum Value {\n");
+    code.push_str("    Bool(bool),\n");
+    code.push_str("    Int(i64),\n");
+    code.push_str("    Float(f64),\n");
+    code.push_str("    String(String),\n");
+    code.push_str("}\n\n");
+
+    code.push_str("/// Why `dispatch` could not call an event\n");
+    code.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+    code.push_str("pub enum DispatchError {\n");
+    code.push_str("    UnknownEvent(String),\n");
+    code.push_str("    ArgCount { event: &'static str, expected: usize, found: usize },\n");
+    code.push_str("    ArgType { event: &'static str, index: usize, expected: &'static str },\n");
+    code.push_str("}\n\n");
+
+    code.push_str("/// Call the event named `event` with `args` decoded into its parameters\n");
+    code.push_str("pub fn dispatch(event: &str, args: &[Value]) -> Result<(), DispatchError> {\n");
+    code.push_str("    match event {\n");
+    for event in events {
+        code.push_str(&format!("        {:?} => {{\n", event.name));
+        code.push_str(&format!(
+            "            if args.len() != {} {{\n                return Err(DispatchError::ArgCount {{ event: {:?}, expected: {}, found: args.len() }});\n            }}\n",
+            event.params.len(),
+            event.name,
+            event.params.len()
+        ));
+        for (index, param) in event.params.iter().enumerate() {
+            let decode = decode_expression(&param.ty, index, event.name).expect("parameter types are decodable");
+            code.push_str(&format!("            let {}: {} = {};\n", param.name, param.ty, decode));
+        }
+        let args: Vec<&str> = event.params.iter().map(|param| param.name.as_str()).collect();
+        code.push_str(&format!("            {}({});\n", event.name, args.join(", ")));
+        code.push_str("            Ok(())\n");
+        code.push_str("        }\n");
+    }
+    code.push_str("        _ => Err(DispatchError::UnknownEvent(event.to_string())),\n");
+    code.push_str("    }\n");
+    code.push_str("}\n");
+
+    code
+}
+
+/// Expression decoding `args[index]` into a value of type `ty`
+///
+/// Integers are range-checked; floats also accept integer arguments.
+fn decode_expression(ty: &str, index: usize, event: &str) -> Option<String> {
+    let mismatch = format!(
+        "return Err(DispatchError::ArgType {{ event: {:?}, index: {}, expected: {:?} }})",
+        event, index, ty
+    );
+    let arg = format!("&args[{}]", index);
+
+    let decode = match ty {
+        "bool" => format!("match {} {{ Value::Bool(v) => *v, _ => {} }}", arg, mismatch),
+        "f32" | "f64" => format!(
+            "match {} {{ Value::Float(v) => *v as {ty}, Value::Int(v) => *v as {ty}, _ => {} }}",
+            arg,
+            mismatch,
+            ty = ty
+        ),
+        "String" => format!("match {} {{ Value::String(v) => v.clone(), _ => {} }}", arg, mismatch),
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => format!(
+            "match {} {{ Value::Int(v) => match {}::try_from(*v) {{ Ok(v) => v, Err(_) => {} }}, _ => {} }}",
+            arg, ty, mismatch, mismatch
+        ),
+        _ => return None,
+    };
+    Some(decode)
+}
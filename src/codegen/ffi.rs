@@ -0,0 +1,52 @@
+//! # C Exports
+//!
+//! Events exported with the C ABI, for loading compiled Blueprints as a
+//! dynamic library. Only parameters with a fixed C representation can cross
+//! the boundary: primitives and raw pointers to them.
+
+/// C type matching a Rust parameter type, if it is FFI-safe
+///
+/// Pointers to non-primitive types become `void *`.
+pub(crate) fn c_type(ty: &str) -> Option<String> {
+    let ty = ty.trim();
+    let pointer = |pointee: &str, constness: &str| {
+        let pointee = c_type(pointee).unwrap_or_else(|| "void".to_string());
+        format!("{}{} *", constness, pointee)
+    };
+    if let Some(pointee) = ty.strip_prefix("*const ") {
+        return Some(pointer(pointee, "const "));
+    }
+    if let Some(pointee) = ty.strip_prefix("*mut ") {
+        return Some(pointer(pointee, ""));
+    }
+
+    let c = match ty {
+        "bool" => "bool",
+        "i8" => "int8_t",
+        "i16" => "int16_t",
+        "i32" => "int32_t",
+        "i64" => "int64_t",
+        "u8" => "uint8_t",
+        "u16" => "uint16_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "isize" => "intptr_t",
+        "usize" => "uintptr_t",
+        "f32" => "float",
+        "f64" => "double",
+        _ => return None,
+    };
+    Some(c.to_string())
+}
+
+/// C prototype of an exported event, e.g. `void on_hit(float damage);`
+///
+/// `params` are `(name, Rust type)`; every type must have a [`c_type`].
+pub(crate) fn c_declaration(name: &str, params: &[(String, String)]) -> Option<String> {
+    let params = params
+        .iter()
+        .map(|(param, ty)| Some(format!("{} {}", c_type(ty)?, param)))
+        .collect::<Option<Vec<_>>>()?;
+    let params = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+    Some(format!("void {}({});", name, params))
+}
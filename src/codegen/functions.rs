@@ -0,0 +1,66 @@
+//! # Generated Function Signatures
+//!
+//! A structured record of every function the generator emits, so tools such
+//! as FFI binding generators can consume PBGC output without parsing it.
+
+/// Signature of a function emitted for an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedFunction {
+    /// Function name, e.g. `on_hit`
+    pub name: String,
+    /// Parameters as `(name, type)`, in declaration order
+    ///
+    /// A method's `&mut self` receiver is not included.
+    pub params: Vec<(String, String)>,
+    /// Return type, or `None` for functions returning `()`
+    pub return_type: Option<String>,
+}
+
+impl GeneratedFunction {
+    /// The parameter list as emitted, e.g. `other: Entity, damage: f32`
+    pub fn param_list(&self) -> String {
+        self.params
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// An event node data output passed to its function as a parameter
+#[derive(Debug, Clone)]
+pub(crate) struct EventParam {
+    /// Output pin carrying the value inside the event body
+    pub pin_id: String,
+    /// Parameter name
+    pub name: String,
+    /// Parameter type
+    pub ty: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{GraphBuilder, provider};
+    use crate::Compiler;
+
+    #[test]
+    fn event_function_records_its_signature() {
+        let graph = GraphBuilder::new()
+            .node("key_pressed", "key_pressed")
+            .node("print", "print_string")
+            .exec("key_pressed", "body", "print")
+            .build();
+
+        let (code, functions) = Compiler::with_provider(provider()).compile_with_functions(&graph).unwrap();
+        assert_eq!(
+            functions,
+            vec![GeneratedFunction {
+                name: "key_pressed".to_string(),
+                params: vec![("state".to_string(), "bool".to_string())],
+                return_type: None,
+            }]
+        );
+        assert!(code.contains(&format!("pub fn key_pressed({})", functions[0].param_list())), "{}", code);
+    }
+}
@@ -0,0 +1,259 @@
+//! # Import Handling
+//!
+//! Parsing and conflict detection for node-provided `use` statements.
+
+use crate::error::CompileError;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Check that no two imports bind the same identifier from different paths
+///
+/// Node libraries may re-export the same name through different paths
+/// (`std::io::Result` vs `std::fmt::Result`), which rustc rejects with E0252.
+pub(crate) fn check_import_conflicts(imports: &[String]) -> Result<(), CompileError> {
+    let mut bindings: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    for import in imports {
+        for (ident, path) in bound_identifiers(import) {
+            bindings.entry(ident).or_default().insert(path);
+        }
+    }
+
+    for (ident, paths) in bindings {
+        if paths.len() > 1 {
+            return Err(CompileError::ImportConflict {
+                ident,
+                paths: paths.into_iter().collect(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// `use` statements declaring type aliases not already bound by `imports`
+///
+/// An alias whose name some import already binds to the same path needs no
+/// statement of its own; rustc rejects binding a name twice even then.
+pub(crate) fn alias_imports(aliases: &HashMap<String, String>, imports: &[String]) -> Vec<String> {
+    let bound: BTreeSet<(String, String)> = imports.iter().flat_map(|import| bound_identifiers(import)).collect();
+
+    let mut statements: Vec<String> = aliases
+        .iter()
+        .filter(|(path, alias)| !bound.contains(&((*alias).clone(), (*path).clone())))
+        .map(|(path, alias)| format!("use {} as {};", path, alias))
+        .collect();
+    statements.sort();
+    statements
+}
+
+/// Replace every aliased type path in `code` with its alias
+///
+/// Paths only match whole: `a::Vec3` is not replaced inside `a::Vec3Ext` or
+/// `b::a::Vec3`. Longer paths are replaced first, so nested aliases resolve
+/// to the most specific one.
+pub(crate) fn apply_type_aliases(code: &str, aliases: &HashMap<String, String>) -> String {
+    let is_path_char = |c: char| c.is_alphanumeric() || c == '_' || c == ':';
+
+    let mut paths: Vec<(&String, &String)> = aliases.iter().collect();
+    paths.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(b.0)));
+
+    let mut code = code.to_string();
+    for (path, alias) in paths {
+        let mut out = String::with_capacity(code.len());
+        let mut rest = code.as_str();
+        while let Some(pos) = rest.find(path.as_str()) {
+            let after = &rest[pos + path.len()..];
+            let starts_path = rest[..pos].chars().next_back().is_none_or(|c| !is_path_char(c));
+            let ends_ident = after.chars().next().is_none_or(|c| !(c.is_alphanumeric() || c == '_'));
+
+            out.push_str(&rest[..pos]);
+            out.push_str(if starts_path && ends_ident { alias } else { path });
+            rest = after;
+        }
+        out.push_str(rest);
+        code = out;
+    }
+    code
+}
+
+/// Identifiers bound by a `use` statement, paired with their full paths
+///
+/// `use std::collections::{HashMap, HashSet as Set};` yields
+/// `[("HashMap", "std::collections::HashMap"), ("Set", "std::collections::HashSet")]`.
+/// Glob imports bind nothing nameable and are skipped.
+pub(crate) fn bound_identifiers(import: &str) -> Vec<(String, String)> {
+    let tree = import
+        .trim()
+        .trim_start_matches("pub ")
+        .trim_start_matches("use ")
+        .trim_end_matches(';')
+        .trim();
+
+    let mut bindings = Vec::new();
+    collect_bindings("", tree, &mut bindings);
+    bindings
+}
+
+fn collect_bindings(prefix: &str, tree: &str, out: &mut Vec<(String, String)>) {
+    let tree = tree.trim();
+    if tree.is_empty() {
+        return;
+    }
+
+    // `prefix::{a, b::{c, d}}`
+    if let Some(open) = tree.find('{') {
+        if let Some(inner) = tree[open + 1..].strip_suffix('}') {
+            let head = join_path(prefix, tree[..open].trim_end_matches("::"));
+            for item in split_items(inner) {
+                collect_bindings(&head, &item, out);
+            }
+        }
+        return;
+    }
+
+    let (path, alias) = match tree.split_once(" as ") {
+        Some((path, alias)) => (path.trim(), Some(alias.trim())),
+        None => (tree, None),
+    };
+
+    if path.ends_with('*') || alias == Some("_") {
+        return;
+    }
+
+    let full = if path == "self" {
+        prefix.to_string()
+    } else {
+        join_path(prefix, path)
+    };
+
+    let ident = match alias {
+        Some(alias) => alias.to_string(),
+        None => full.rsplit("::").next().unwrap_or(&full).to_string(),
+    };
+
+    out.push((ident, full));
+}
+
+fn join_path(prefix: &str, path: &str) -> String {
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, _) => path.to_string(),
+        (false, true) => prefix.to_string(),
+        (false, false) => format!("{}::{}", prefix, path),
+    }
+}
+
+/// Split a brace group's contents on top-level commas
+fn split_items(s: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+
+    for c in s.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    items.push(current);
+
+    items.into_iter().filter(|item| !item.trim().is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{GraphBuilder, library, provider};
+    use crate::Compiler;
+
+    #[test]
+    fn same_name_from_two_paths_conflicts() {
+        let imports = vec!["use std::io::Result;".to_string(), "use std::fmt::{Display, Result};".to_string()];
+
+        match check_import_conflicts(&imports) {
+            Err(CompileError::ImportConflict { ident, paths }) => {
+                assert_eq!(ident, "Result");
+                assert_eq!(paths, ["std::fmt::Result", "std::io::Result"]);
+            }
+            other => panic!("expected an import conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn conflicting_node_imports_fail_the_compile() {
+        let with_import = |name: &str, import: &str| {
+            let node = library().into_iter().find(|node| node.name == name).unwrap();
+            node.with_imports(vec![import.to_string()])
+        };
+        let compiler = Compiler::with_provider(
+            provider()
+                .with_node_metadata(with_import("print_string", "use std::io::Result;"))
+                .with_node_metadata(with_import("delay", "use std::fmt::Result;")),
+        );
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("print", "print_string")
+            .node("delay", "delay")
+            .exec("main", "body", "print")
+            .exec("print", "exec_out", "delay")
+            .build();
+
+        match compiler.compile(&graph) {
+            Err(CompileError::ImportConflict { ident, paths }) => {
+                assert_eq!(ident, "Result");
+                assert_eq!(paths, ["std::fmt::Result", "std::io::Result"]);
+            }
+            other => panic!("expected an import conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn aliases_replace_whole_paths_only() {
+        let aliases = HashMap::from([
+            ("engine::math::Vec3".to_string(), "Vec3".to_string()),
+            ("engine::math".to_string(), "math".to_string()),
+        ]);
+        let code = "let a: engine::math::Vec3 = engine::math::Vec3Ext::zero(); other::engine::math::Vec3::new()";
+
+        assert_eq!(
+            apply_type_aliases(code, &aliases),
+            "let a: Vec3 = math::Vec3Ext::zero(); other::engine::math::Vec3::new()"
+        );
+    }
+
+    #[test]
+    fn alias_already_imported_by_a_node_is_not_repeated() {
+        let aliases = HashMap::from([
+            ("engine::math::Vec3".to_string(), "Vec3".to_string()),
+            ("engine::color::Rgba".to_string(), "Color".to_string()),
+        ]);
+        let imports = vec!["use engine::math::{Vec3, Quat};".to_string()];
+
+        assert_eq!(alias_imports(&aliases, &imports), ["use engine::color::Rgba as Color;"]);
+    }
+
+    #[test]
+    fn aliased_variable_type_is_imported_and_shortened() {
+        use crate::options::CompileOptions;
+
+        let graph = GraphBuilder::new().node("main", "main").build();
+        let options = CompileOptions {
+            type_aliases: HashMap::from([("engine::math::Vector3".to_string(), "Vector3".to_string())]),
+            ..CompileOptions::default()
+        };
+        let variables = HashMap::from([("velocity".to_string(), "engine::math::Vector3")]);
+
+        let code = Compiler::with_provider(provider())
+            .with_options(options)
+            .compile_with_variables(&graph, variables)
+            .unwrap();
+        assert!(code.contains("use engine::math::Vector3 as Vector3;\n"), "{}", code);
+        assert!(code.contains("static VELOCITY: std::cell::RefCell<Vector3> ="), "{}", code);
+        assert_eq!(code.matches("engine::math::Vector3").count(), 1, "{}", code);
+    }
+}
@@ -0,0 +1,304 @@
+//! # Literal Rendering
+//!
+//! Converts editor-set values into Rust literal expressions.
+
+use crate::error::CompileError;
+use crate::options::{CompositeConstructors, TargetSpec};
+use graphy::{DataType, PropertyValue};
+
+/// Heap-allocating constructors and their `alloc` crate paths
+const ALLOC_PATHS: &[(&str, &str)] = &[
+    ("String::", "alloc::string::String::"),
+    ("Vec::", "alloc::vec::Vec::"),
+    ("Box::", "alloc::boxed::Box::"),
+];
+
+/// `std` items that compile for `wasm32-unknown-unknown` but fail or panic at runtime
+const WASM_UNSUPPORTED_PATHS: &[&str] = &[
+    "std::time::Instant::",
+    "std::time::SystemTime::",
+    "Instant::now",
+    "SystemTime::now",
+    "std::thread::",
+    "std::process::",
+    "std::fs::",
+    "std::net::",
+];
+
+/// Rewrite an expression so it compiles on the given target
+///
+/// Under `no_std`, heap constructors are routed through `alloc` when an
+/// allocator is available and rejected otherwise. Under `wasm`, constructs
+/// that don't work in the browser are rejected.
+pub(crate) fn adapt_to_target(expr: String, target: &TargetSpec) -> Result<String, CompileError> {
+    if target.wasm && WASM_UNSUPPORTED_PATHS.iter().any(|path| contains_path(&expr, path)) {
+        return Err(CompileError::UnsupportedTarget {
+            construct: expr,
+            target: "wasm32".to_string(),
+        });
+    }
+    if !target.no_std {
+        return Ok(expr);
+    }
+
+    let mut adapted = expr;
+    for (std_path, alloc_path) in ALLOC_PATHS {
+        if !contains_path(&adapted, std_path) {
+            continue;
+        }
+        if !target.alloc {
+            return Err(CompileError::UnsupportedTarget {
+                construct: adapted,
+                target: "no_std without alloc".to_string(),
+            });
+        }
+        adapted = replace_path(&adapted, std_path, alloc_path);
+    }
+
+    Ok(adapted)
+}
+
+/// Check for `path` at an identifier boundary (so `MyString::` doesn't match `String::`)
+fn contains_path(expr: &str, path: &str) -> bool {
+    expr.match_indices(path).any(|(i, _)| is_path_start(expr, i))
+}
+
+fn replace_path(expr: &str, path: &str, replacement: &str) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for (i, _) in expr.match_indices(path) {
+        if is_path_start(expr, i) {
+            out.push_str(&expr[last..i]);
+            out.push_str(replacement);
+            last = i + path.len();
+        }
+    }
+    out.push_str(&expr[last..]);
+    out
+}
+
+fn is_path_start(expr: &str, i: usize) -> bool {
+    expr[..i]
+        .chars()
+        .next_back()
+        .is_none_or(|c| !(c.is_alphanumeric() || c == '_' || c == ':'))
+}
+
+/// Render an editor property value as a Rust expression of type `ty`
+pub fn render_property_value(value: &PropertyValue, ty: &str) -> String {
+    match value {
+        PropertyValue::String(s) => {
+            if ty == "String" {
+                format!("String::from({:?})", s)
+            } else {
+                format!("{:?}", s)
+            }
+        }
+        PropertyValue::Number(n) => render_number(*n, ty),
+        PropertyValue::Boolean(b) => b.to_string(),
+        PropertyValue::Vector2(x, y) => {
+            format!("({}, {})", render_f32(*x), render_f32(*y))
+        }
+        PropertyValue::Vector3(x, y, z) => format!(
+            "({}, {}, {})",
+            render_f32(*x),
+            render_f32(*y),
+            render_f32(*z),
+        ),
+        PropertyValue::Color(r, g, b, a) => format!(
+            "({}, {}, {}, {})",
+            render_f32(*r),
+            render_f32(*g),
+            render_f32(*b),
+            render_f32(*a),
+        ),
+    }
+}
+
+/// Render a vector or color value component-wise
+///
+/// Uses the registered constructor for the value's kind, or a tuple if there
+/// is none. Returns `None` for values that aren't vectors or colors.
+pub(crate) fn render_composite(value: &PropertyValue, constructors: &CompositeConstructors) -> Option<String> {
+    let (constructor, components) = match value {
+        PropertyValue::Vector2(x, y) => (&constructors.vector2, vec![*x, *y]),
+        PropertyValue::Vector3(x, y, z) => (&constructors.vector3, vec![*x, *y, *z]),
+        PropertyValue::Color(r, g, b, a) => (&constructors.color, vec![*r, *g, *b, *a]),
+        _ => return None,
+    };
+
+    let components: Vec<String> = components
+        .into_iter()
+        .map(render_f32)
+        .collect();
+
+    Some(match constructor {
+        Some(path) => format!("{}({})", path, components.join(", ")),
+        None => format!("({})", components.join(", ")),
+    })
+}
+
+/// Default vector or color literal for a pin type, honoring registered constructors
+pub(crate) fn default_composite(data_type: &DataType, constructors: &CompositeConstructors) -> Option<String> {
+    let zero = match data_type {
+        DataType::Vector2 => PropertyValue::Vector2(0.0, 0.0),
+        DataType::Vector3 => PropertyValue::Vector3(0.0, 0.0, 0.0),
+        DataType::Color => PropertyValue::Color(0.0, 0.0, 0.0, 1.0),
+        _ => return None,
+    };
+    render_composite(&zero, constructors)
+}
+
+/// Render a number as an integer or float literal depending on `ty`
+fn render_number(n: f64, ty: &str) -> String {
+    number_literal(n, ty).unwrap_or_else(|| render_float(n, "f64"))
+}
+
+/// Render a number as a literal of the primitive numeric type `ty`
+///
+/// Integers are truncated. The literal carries a suffix unless `ty` is the
+/// type Rust infers for an unsuffixed literal anyway (`i32`, `f64`), so
+/// `0` becomes `0u64` or `0.0f32` where that's the target. Infinities and NaN
+/// become constants such as `f32::INFINITY`. Returns `None` if `ty` isn't a
+/// primitive number type, or is an integer type and `n` isn't finite.
+pub(crate) fn number_literal(n: f64, ty: &str) -> Option<String> {
+    let ty = ty.trim();
+    let literal = if is_integer_type(ty) && n.is_finite() {
+        format!("{}", n.trunc() as i128)
+    } else if matches!(ty, "f32" | "f64") {
+        render_float(n, ty)
+    } else {
+        return None;
+    };
+    Some(match ty {
+        _ if !n.is_finite() => literal,
+        "i32" | "f64" => literal,
+        _ => format!("{}{}", literal, ty),
+    })
+}
+
+/// Re-render a numeric constant as a literal of the primitive numeric type `ty`
+///
+/// Returns `None` when `value` isn't a plain decimal number (e.g. already
+/// suffixed, hex, or an expression) or `ty` isn't numeric.
+pub(crate) fn retype_number_literal(value: &str, ty: &str) -> Option<String> {
+    if let Some(n) = parse_non_finite(value) {
+        return number_literal(n, ty);
+    }
+    let value = value.trim();
+    if !value.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | 'e' | 'E' | '+')) {
+        return None;
+    }
+    number_literal(value.parse().ok()?, ty)
+}
+
+/// Render a non-finite constant (`inf`, `-inf`, `NaN`) as an `f64` constant path
+///
+/// For constants feeding a pin whose type isn't known; `None` for anything else.
+pub(crate) fn non_finite_literal(value: &str) -> Option<String> {
+    parse_non_finite(value).map(|n| render_float(n, "f64"))
+}
+
+/// Parse `inf`, `-inf`, `infinity` or `NaN` in any case, none of which is a Rust literal
+fn parse_non_finite(value: &str) -> Option<f64> {
+    let value = value.trim().to_ascii_lowercase();
+    let unsigned = value.trim_start_matches(['-', '+']);
+    if !matches!(unsigned, "inf" | "infinity" | "nan") {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Render a float of type `ty` (`f32` or `f64`)
+///
+/// Finite values always keep a decimal point so they stay float-typed;
+/// infinities and NaN become `ty::INFINITY`, `ty::NEG_INFINITY` and `ty::NAN`.
+fn render_float(n: f64, ty: &str) -> String {
+    if n.is_nan() {
+        format!("{}::NAN", ty)
+    } else if n == f64::INFINITY {
+        format!("{}::INFINITY", ty)
+    } else if n == f64::NEG_INFINITY {
+        format!("{}::NEG_INFINITY", ty)
+    } else {
+        format!("{:?}", n)
+    }
+}
+
+/// Render an `f32` component, with the shortest literal that round-trips as `f32`
+fn render_f32(n: f32) -> String {
+    if n.is_finite() {
+        format!("{:?}", n)
+    } else {
+        render_float(f64::from(n), "f32")
+    }
+}
+
+/// Check if a type is a primitive integer
+fn is_integer_type(ty: &str) -> bool {
+    matches!(
+        ty,
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" |
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composite_renders_as_tuple_without_constructor() {
+        let color = PropertyValue::Color(0.2, 0.4, 0.6, 1.0);
+        let rendered = render_composite(&color, &CompositeConstructors::default());
+        assert_eq!(rendered.as_deref(), Some("(0.2, 0.4, 0.6, 1.0)"));
+    }
+
+    #[test]
+    fn composite_renders_with_registered_constructor() {
+        let constructors = CompositeConstructors {
+            vector3: Some("Vec3::new".to_string()),
+            ..CompositeConstructors::default()
+        };
+        let vector = PropertyValue::Vector3(1.0, 2.0, 3.0);
+        assert_eq!(render_composite(&vector, &constructors).as_deref(), Some("Vec3::new(1.0, 2.0, 3.0)"));
+        assert_eq!(default_composite(&DataType::Vector3, &constructors).as_deref(), Some("Vec3::new(0.0, 0.0, 0.0)"));
+        assert_eq!(render_composite(&PropertyValue::Boolean(true), &constructors), None);
+    }
+
+    #[test]
+    fn numbers_render_as_literals_of_their_target_type() {
+        assert_eq!(number_literal(3.7, "i32").as_deref(), Some("3"));
+        assert_eq!(number_literal(-2.0, "i64").as_deref(), Some("-2i64"));
+        assert_eq!(number_literal(0.0, "u64").as_deref(), Some("0u64"));
+        assert_eq!(number_literal(7.0, "usize").as_deref(), Some("7usize"));
+        assert_eq!(number_literal(0.0, "f32").as_deref(), Some("0.0f32"));
+        assert_eq!(number_literal(1.5, "f64").as_deref(), Some("1.5"));
+        assert_eq!(number_literal(1.0, "String"), None);
+
+        assert_eq!(retype_number_literal("0.0", "u8").as_deref(), Some("0u8"));
+        assert_eq!(retype_number_literal("2", "f32").as_deref(), Some("2.0f32"));
+        assert_eq!(retype_number_literal("0x10", "u32"), None);
+        assert_eq!(retype_number_literal("1u16", "u32"), None);
+    }
+
+    #[test]
+    fn non_finite_numbers_render_as_float_constants() {
+        assert_eq!(retype_number_literal("inf", "f64").as_deref(), Some("f64::INFINITY"));
+        assert_eq!(retype_number_literal("-Infinity", "f32").as_deref(), Some("f32::NEG_INFINITY"));
+        assert_eq!(retype_number_literal("NaN", "f32").as_deref(), Some("f32::NAN"));
+        assert_eq!(retype_number_literal("inf", "i32"), None);
+        assert_eq!(non_finite_literal("-inf").as_deref(), Some("f64::NEG_INFINITY"));
+        assert_eq!(non_finite_literal("1.5"), None);
+        assert_eq!(render_property_value(&PropertyValue::Number(2.0), "f64"), "2.0");
+    }
+
+    #[test]
+    fn wasm_target_rejects_browser_unsafe_constructs() {
+        let wasm = TargetSpec { wasm: true, ..TargetSpec::default() };
+        assert_eq!(adapt_to_target("String::new()".to_string(), &wasm).unwrap(), "String::new()");
+
+        let err = adapt_to_target("std::time::Instant::now()".to_string(), &wasm).unwrap_err();
+        assert!(matches!(err, CompileError::UnsupportedTarget { ref target, .. } if target == "wasm32"));
+    }
+}
@@ -2,8 +2,31 @@
 //!
 //! Rust code generation for Blueprint graphs.
 
+mod class;
+mod control_flow;
+mod dispatch;
+mod ffi;
+mod functions;
+mod imports;
+mod literals;
+mod operators;
+mod postprocess;
+mod runtime;
 mod rust_codegen;
+mod state_machine;
+mod templates;
+mod types;
+mod variables;
 #[allow(dead_code)]
 mod node_handlers;
 
 pub use rust_codegen::*;
+pub use class::{ClassContext, TraitImpl, TraitMethod};
+pub use functions::GeneratedFunction;
+pub use operators::Operator;
+pub use postprocess::{PostProcessor, SortImports};
+pub use runtime::{DefaultRuntime, EventSignature, RuntimeProfile, TokioRuntime};
+pub use types::ResolvedTypes;
+pub(crate) use types::declared_type;
+pub(crate) use variables::{const_variables, split_field_path};
+pub use variables::{VariableDef, VariableStorage};
@@ -0,0 +1,179 @@
+//! # Operator Mapping
+//!
+//! Pure nodes like `and`, `or`, or `add` can be emitted as native Rust
+//! operators instead of function calls. Besides being more readable, `&&` and
+//! `||` short-circuit, which matters when an operand is expensive or has side
+//! effects.
+
+/// A Rust operator a pure node can compile to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    /// `a && b`
+    And,
+    /// `a || b`
+    Or,
+    /// `!a`
+    Not,
+    /// `a == b`
+    Eq,
+    /// `a != b`
+    Ne,
+    /// `a < b`
+    Lt,
+    /// `a <= b`
+    Le,
+    /// `a > b`
+    Gt,
+    /// `a >= b`
+    Ge,
+    /// `a + b`
+    Add,
+    /// `a - b`
+    Sub,
+    /// `a * b`
+    Mul,
+    /// `a / b`
+    Div,
+    /// `a % b`
+    Rem,
+    /// `-a`
+    Neg,
+}
+
+/// Precedence of an expression that never needs parentheses (calls, literals, paths)
+pub(crate) const ATOM_PRECEDENCE: u8 = u8::MAX;
+
+impl Operator {
+    /// The operator's Rust token
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Not => "!",
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Rem => "%",
+            Operator::Neg => "-",
+        }
+    }
+
+    /// Number of operands the operator takes
+    pub fn arity(self) -> usize {
+        match self {
+            Operator::Not | Operator::Neg => 1,
+            _ => 2,
+        }
+    }
+
+    /// Binding strength, following the Rust reference (higher binds tighter)
+    pub fn precedence(self) -> u8 {
+        match self {
+            Operator::Not | Operator::Neg => 14,
+            Operator::Mul | Operator::Div | Operator::Rem => 12,
+            Operator::Add | Operator::Sub => 11,
+            Operator::Eq | Operator::Ne | Operator::Lt
+            | Operator::Le | Operator::Gt | Operator::Ge => 9,
+            Operator::And => 5,
+            Operator::Or => 4,
+        }
+    }
+
+    /// Comparisons can't be chained in Rust (`a < b < c` is a syntax error)
+    fn is_comparison(self) -> bool {
+        self.precedence() == 9
+    }
+
+    /// Render the operator applied to operands, each given with its own precedence
+    ///
+    /// Operands are parenthesized only where required: binary operators are
+    /// left-associative, so an equal-precedence right operand needs parentheses
+    /// (`a - (b - c)`), as does any comparison operand of a comparison.
+    pub(crate) fn render(self, operands: &[(String, u8)]) -> String {
+        let prec = self.precedence();
+
+        if self.arity() == 1 {
+            let (expr, operand_prec) = &operands[0];
+            return format!("{}{}", self.symbol(), wrap(expr, *operand_prec < prec));
+        }
+
+        let (lhs, lhs_prec) = &operands[0];
+        let (rhs, rhs_prec) = &operands[1];
+
+        let wrap_lhs = *lhs_prec < prec || (self.is_comparison() && *lhs_prec == prec);
+        let wrap_rhs = *rhs_prec <= prec;
+
+        format!("{} {} {}", wrap(lhs, wrap_lhs), self.symbol(), wrap(rhs, wrap_rhs))
+    }
+}
+
+fn wrap(expr: &str, parenthesize: bool) -> String {
+    if parenthesize {
+        format!("({})", expr)
+    } else {
+        expr.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ATOM_PRECEDENCE, Operator};
+    use crate::metadata::NodeAttributes;
+    use crate::test_support::{GraphBuilder, provider};
+    use crate::Compiler;
+
+    fn atom(expr: &str) -> (String, u8) {
+        (expr.to_string(), ATOM_PRECEDENCE)
+    }
+
+    #[test]
+    fn equal_precedence_right_operand_is_parenthesized() {
+        let rhs = (Operator::Sub.render(&[atom("b"), atom("c")]), Operator::Sub.precedence());
+        assert_eq!(Operator::Sub.render(&[atom("a"), rhs.clone()]), "a - (b - c)");
+        assert_eq!(Operator::Sub.render(&[rhs, atom("a")]), "b - c - a");
+    }
+
+    #[test]
+    fn comparisons_are_not_chained() {
+        let lhs = (Operator::Lt.render(&[atom("a"), atom("b")]), Operator::Lt.precedence());
+        assert_eq!(Operator::Eq.render(&[lhs, atom("c")]), "(a < b) == c");
+    }
+
+    /// `main -> branch`, whose condition is `outer(x, inner(y, z))` over random bools
+    fn compile_nested(outer: &str, inner: &str) -> String {
+        let mut builder = GraphBuilder::new()
+            .node("main", "main")
+            .node("branch", "branch")
+            .node("outer", outer)
+            .node("inner", inner)
+            .data("outer", "result", "branch", "condition")
+            .data("inner", "result", "outer", "b")
+            .exec("main", "body", "branch");
+        for (id, target, input) in [("x", "outer", "a"), ("y", "inner", "a"), ("z", "inner", "b")] {
+            builder = builder.node(id, "random_bool").data(id, "result", target, input);
+        }
+        let provider = provider()
+            .with_attributes("and", NodeAttributes::operator(Operator::And))
+            .with_attributes("or", NodeAttributes::operator(Operator::Or));
+        Compiler::with_provider(provider).compile(&builder.build()).unwrap()
+    }
+
+    #[test]
+    fn looser_operand_is_parenthesized() {
+        let code = compile_nested("and", "or");
+        assert!(code.contains("if random_bool() && (random_bool() || random_bool())"), "{}", code);
+    }
+
+    #[test]
+    fn tighter_operand_is_not_parenthesized() {
+        let code = compile_nested("or", "and");
+        assert!(code.contains("if random_bool() || random_bool() && random_bool()"), "{}", code);
+    }
+}
@@ -0,0 +1,104 @@
+//! # Post-processing
+//!
+//! Transforms applied to the finished output, in the order they are listed in
+//! `CompileOptions::post_processors`. This is the place for house conventions
+//! the generator doesn't know about: banner comments, import grouping, a
+//! formatter run.
+
+use crate::error::CompileError;
+use std::fmt;
+
+/// A transform over the complete generated source
+pub trait PostProcessor: fmt::Debug + Send + Sync {
+    /// Rewrite the generated code, or fail the compilation
+    fn process(&self, code: String) -> Result<String, CompileError>;
+}
+
+/// Sorts the leading `use` block into std, external, and crate groups
+///
+/// Groups are separated by a blank line and sorted within; duplicate imports
+/// are dropped. An attribute line directly above an import (such as a
+/// `#[cfg(..)]`) moves with it. Multi-line imports end the block.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SortImports;
+
+impl PostProcessor for SortImports {
+    fn process(&self, code: String) -> Result<String, CompileError> {
+        let lines: Vec<&str> = code.lines().collect();
+
+        // Imports (with their attribute) from the first one up to the first other code
+        let mut units: Vec<&[&str]> = Vec::new();
+        let mut start = None;
+        let mut end = 0;
+        let mut i = 0;
+        while i < lines.len() {
+            let attributed = lines[i].starts_with("#[") && lines.get(i + 1).is_some_and(|next| is_import(next));
+            if attributed || is_import(lines[i]) {
+                let len = if attributed { 2 } else { 1 };
+                start.get_or_insert(i);
+                units.push(&lines[i..i + len]);
+                i += len;
+                end = i;
+            } else if start.is_none() || lines[i].trim().is_empty() {
+                i += 1;
+            } else {
+                break;
+            }
+        }
+        let Some(start) = start else { return Ok(code) };
+
+        let mut groups: [Vec<&[&str]>; 3] = Default::default();
+        for unit in units {
+            let import = unit[unit.len() - 1];
+            let path = import.trim_start_matches("pub ").trim_start_matches("use ").trim_start_matches("::");
+            let group = if ["std::", "core::", "alloc::"].iter().any(|root| path.starts_with(root)) {
+                0
+            } else if ["crate::", "self::", "super::"].iter().any(|root| path.starts_with(root)) {
+                2
+            } else {
+                1
+            };
+            groups[group].push(unit);
+        }
+
+        let mut sorted: Vec<String> = Vec::new();
+        for group in groups.iter_mut().filter(|group| !group.is_empty()) {
+            group.sort_by_key(|unit| (unit[unit.len() - 1], unit.len()));
+            group.dedup();
+            if !sorted.is_empty() {
+                sorted.push(String::new());
+            }
+            sorted.extend(group.iter().flat_map(|unit| unit.iter().map(|line| line.to_string())));
+        }
+
+        let mut out: Vec<String> = lines[..start].iter().map(|line| line.to_string()).collect();
+        out.extend(sorted);
+        out.extend(lines[end..].iter().map(|line| line.to_string()));
+        let mut processed = out.join("\n");
+        if code.ends_with('\n') {
+            processed.push('\n');
+        }
+        Ok(processed)
+    }
+}
+
+/// Whether a line is a complete top-level `use` declaration
+fn is_import(line: &str) -> bool {
+    (line.starts_with("use ") || line.starts_with("pub use ")) && line.trim_end().ends_with(';')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_are_grouped_std_external_crate() {
+        let code = "// header\n\nuse serde::Serialize;\nuse crate::util::helper;\n#[cfg(feature = \"log\")]\nuse log::info;\nuse std::fmt;\nuse serde::Serialize;\nuse std::collections::HashMap;\n\npub fn main() {}\n";
+
+        let sorted = SortImports.process(code.to_string()).unwrap();
+        assert_eq!(
+            sorted,
+            "// header\n\nuse std::collections::HashMap;\nuse std::fmt;\n\n#[cfg(feature = \"log\")]\nuse log::info;\nuse serde::Serialize;\n\nuse crate::util::helper;\n\npub fn main() {}\n"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! # Runtime Profiles
+//!
+//! Host runtimes want different scaffolding around the generated logic: which
+//! executor drives async events, how a program starts, what gets imported. A
+//! [`RuntimeProfile`] produces that scaffolding; the generator only emits the
+//! Blueprint's own statements.
+
+use std::fmt;
+
+/// How an event function is declared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventSignature<'s> {
+    /// Function name
+    pub name: &'s str,
+    /// Rendered parameter list, including any receiver
+    pub params: &'s str,
+    /// Whether the function is `pub`
+    pub public: bool,
+    /// Whether the event is async
+    pub is_async: bool,
+    /// ABI the function is exported with, e.g. `"C"`
+    pub abi: Option<&'s str>,
+}
+
+/// Scaffolding the generated program is wrapped in
+pub trait RuntimeProfile: fmt::Debug + Send + Sync {
+    /// Lines emitted after the header comments, before the node imports
+    fn program_header(&self) -> String;
+
+    /// Declaration of an event function, up to (not including) its opening brace
+    ///
+    /// May span several lines, e.g. to add attributes.
+    fn event_signature(&self, signature: &EventSignature) -> String;
+
+    /// Statement running `body` (statements that may `.await`) from synchronous code
+    fn async_block(&self, body: &str) -> String;
+}
+
+/// The standard scaffolding, without an async executor
+///
+/// Async events become `async fn`s for the host to drive; starting one from a
+/// synchronous event blocks on it with `futures::executor::block_on`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DefaultRuntime;
+
+impl RuntimeProfile for DefaultRuntime {
+    fn program_header(&self) -> String {
+        "// NOTE: Replace with actual pulsar_std import in production\n// use pulsar_std::*;\n".to_string()
+    }
+
+    fn event_signature(&self, signature: &EventSignature) -> String {
+        format!(
+            "{}{}{}fn {}({})",
+            if signature.public { "pub " } else { "" },
+            if signature.is_async { "async " } else { "" },
+            signature.abi.map(|abi| format!("extern {:?} ", abi)).unwrap_or_default(),
+            signature.name,
+            signature.params
+        )
+    }
+
+    fn async_block(&self, body: &str) -> String {
+        format!("futures::executor::block_on(async move {{ {} }});", body)
+    }
+}
+
+/// Scaffolding for the Tokio runtime
+///
+/// An async `main` event becomes the `#[tokio::main]` entry point, and async
+/// events started from synchronous code are spawned onto the runtime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokioRuntime;
+
+impl RuntimeProfile for TokioRuntime {
+    fn program_header(&self) -> String {
+        DefaultRuntime.program_header()
+    }
+
+    fn event_signature(&self, signature: &EventSignature) -> String {
+        if signature.is_async && signature.name == "main" {
+            return format!("#[tokio::main]\nasync fn main({})", signature.params);
+        }
+        DefaultRuntime.event_signature(signature)
+    }
+
+    fn async_block(&self, body: &str) -> String {
+        format!("tokio::spawn(async move {{ {} }});", body)
+    }
+}
@@ -2,13 +2,49 @@
 //!
 //! Generates Rust source code from Blueprint graphs.
 
-use crate::metadata::BlueprintMetadataProvider;
+use crate::cancellation::CancellationToken;
+use crate::diagnostics::Diagnostic;
+use crate::error::CompileError;
+use crate::metadata::{BlueprintMetadataProvider, ExitVariant, FallibleBranches, NodeAttributes, VariadicStyle};
+use crate::options::{ClosureTarget, CompileOptions, ErrorPolicy, EventContext, SwitchFallback, TargetSpec};
+use crate::report::OptimizationReport;
+use crate::subgraph::{SUBGRAPH_INPUTS, SUBGRAPH_OUTPUTS, SubgraphExample, boundary_node};
+use super::dispatch::{DispatchEvent, generate_dispatch, is_decodable};
+use super::ffi::{c_declaration, c_type};
+use super::control_flow::{
+    add_match_fallback, const_bool, dead_markers, fold_constant_loops, is_literal, is_loop_template, reindent,
+    remove_empty_else,
+};
+use super::functions::{EventParam, GeneratedFunction};
+use super::imports::{alias_imports, apply_type_aliases, check_import_conflicts};
+use super::literals::{
+    adapt_to_target, default_composite, non_finite_literal, number_literal, render_composite, retype_number_literal,
+};
+use super::operators::ATOM_PRECEDENCE;
+use super::runtime::{DefaultRuntime, EventSignature, RuntimeProfile};
+use super::state_machine::generate_state_machine;
+use super::templates::{substitute_placeholders, unknown_placeholder, uses_placeholder};
+use super::types::ResolvedTypes;
+use super::class::{CachedField, ClassContext, TraitImpl, TraitMethod, check_derives, generate_drop_impl, generate_struct};
+use super::variables::{
+    VariableDef, VariableStorage, const_variables, generate_variable_declarations, read_expression,
+    sorted_variables, split_field_path, write_statement,
+};
 use graphy::{
     GraphDescription, GraphyError, NodeTypes, NodeInstance,
     DataResolver, ExecutionRouting,
 };
 use graphy::core::NodeMetadataProvider;
-use std::collections::{HashMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+
+/// `(name, expression)` pairs, e.g. an event's declared parameters and their types
+type NamedExprs = Vec<(String, String)>;
+
+/// Getter reads by variable: how many reads there are across every inlined
+/// path, and which getter outputs `(node ID, pin ID)` they come from
+type GetterReads = HashMap<String, (usize, BTreeSet<(String, String)>)>;
 
 /// Blueprint-specific Rust code generator
 pub struct BlueprintCodeGenerator<'a> {
@@ -16,8 +52,51 @@ pub struct BlueprintCodeGenerator<'a> {
     metadata_provider: &'a BlueprintMetadataProvider,
     data_resolver: &'a DataResolver,
     exec_routing: &'a ExecutionRouting,
-    variables: HashMap<String, String>,
+    options: &'a CompileOptions,
+    variables: HashMap<String, VariableDef>,
+    types: Rc<ResolvedTypes>,
+    consumer_counts: Rc<HashMap<String, usize>>,
+    /// Local names of the pure nodes evaluated once into a binding, by node ID
+    pure_binding_names: Rc<HashMap<String, String>>,
+    /// Result variable of each node, made unique across the graph
+    result_variables: Rc<HashMap<String, String>>,
+    /// Class fields caching pure values shared by several events, by node ID
+    cached_pure: Rc<HashMap<String, CachedField>>,
     visited: HashSet<String>,
+    /// Branch-scoped values from enclosing control-flow nodes, keyed by (node ID, pin ID)
+    scope_bindings: HashMap<(String, String), String>,
+    /// Non-deterministic pure nodes already bound to a local in this scope
+    bound_pure: RefCell<HashSet<String>>,
+    /// Set while generating a loop's inputs, which are evaluated on every iteration
+    in_loop_header: Cell<bool>,
+    /// `let` statements that must be emitted before the next statement
+    pending_bindings: RefCell<Vec<String>>,
+    /// Diagnostics reported by this generator and all of its forks
+    diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
+    /// Sites transformed by the optimizing passes, counted by this generator and all of its forks
+    report: Rc<RefCell<OptimizationReport>>,
+    /// Signatures of the event functions generated so far
+    functions: Rc<RefCell<Vec<GeneratedFunction>>>,
+    /// Token polled once per expanded node to abort generation early
+    cancellation: Option<CancellationToken>,
+    /// Nodes expanded so far by this generator and all of its forks
+    nodes_expanded: Rc<Cell<usize>>,
+    /// Execution order index of each node generated in the current function, by node ID
+    exec_order: Rc<RefCell<HashMap<String, usize>>>,
+    /// Whether the event being generated is async, so async calls can be awaited
+    in_async: bool,
+    /// Whether code is generated into a method of a Blueprint class, where `self` is in scope
+    in_method: bool,
+    /// Output of the levels a generation error has passed through; see `preceded_by`
+    partial_output: Rc<RefCell<String>>,
+    /// Variables declared as `const` because nothing writes them
+    const_variables: HashSet<String>,
+    /// Pure nodes currently being inlined, outermost first
+    inlining: RefCell<Vec<String>>,
+    /// Exec nodes queued by the innermost `generate_exec_chain` still to be generated
+    exec_stack: Vec<&'a NodeInstance>,
+    /// Whether statement nodes continue into the nodes their exec outputs lead to
+    pub(super) follow_exec: bool,
 }
 
 impl<'a> BlueprintCodeGenerator<'a> {
@@ -26,41 +105,244 @@ impl<'a> BlueprintCodeGenerator<'a> {
         metadata_provider: &'a BlueprintMetadataProvider,
         data_resolver: &'a DataResolver,
         exec_routing: &'a ExecutionRouting,
-        variables: HashMap<String, String>,
+        variables: HashMap<String, VariableDef>,
+        options: &'a CompileOptions,
     ) -> Self {
+        let consumer_counts = count_consumers(graph, data_resolver);
+        let result_variables = result_variable_names(graph, data_resolver, &variables);
+        let pure_binding_names = pure_binding_names(graph, metadata_provider, data_resolver, &variables, &result_variables);
+        let types = ResolvedTypes::build(graph, metadata_provider, data_resolver);
+        let cached_pure = if options.cache_shared_pure && options.class.is_some() {
+            shared_pure_fields(graph, metadata_provider, data_resolver, exec_routing, &types, &variables)
+        } else {
+            HashMap::new()
+        };
         Self {
             graph,
             metadata_provider,
             data_resolver,
             exec_routing,
+            options,
+            const_variables: match (&options.class, &options.closures) {
+                (None, None) => const_variables(&variables, [graph]),
+                _ => HashSet::new(),
+            },
             variables,
+            types: Rc::new(types),
+            pure_binding_names: Rc::new(pure_binding_names),
+            result_variables: Rc::new(result_variables),
+            cached_pure: Rc::new(cached_pure),
+            consumer_counts: Rc::new(consumer_counts),
             visited: HashSet::new(),
+            scope_bindings: HashMap::new(),
+            bound_pure: RefCell::new(HashSet::new()),
+            in_loop_header: Cell::new(false),
+            pending_bindings: RefCell::new(Vec::new()),
+            diagnostics: Rc::new(RefCell::new(Vec::new())),
+            report: Rc::new(RefCell::new(OptimizationReport::default())),
+            functions: Rc::new(RefCell::new(Vec::new())),
+            cancellation: None,
+            nodes_expanded: Rc::new(Cell::new(0)),
+            exec_order: Rc::new(RefCell::new(HashMap::new())),
+            in_async: false,
+            in_method: false,
+            partial_output: Rc::new(RefCell::new(String::new())),
+            inlining: RefCell::new(Vec::new()),
+            exec_stack: Vec::new(),
+            follow_exec: true,
         }
     }
 
+    /// Declare exactly these variables `const`, instead of those unwritten in this graph
+    ///
+    /// Needed when other graphs compiled into the same program (e.g. shared
+    /// sub-graph functions) may write the variables too.
+    pub fn with_const_variables(mut self, names: HashSet<String>) -> Self {
+        self.const_variables = names;
+        self
+    }
+
+    /// Abort generation with `CompileError::Cancelled` once `token` is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Diagnostics reported so far
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.borrow().clone()
+    }
+
+    /// What the optimizing passes have done so far
+    pub fn optimization_report(&self) -> OptimizationReport {
+        *self.report.borrow()
+    }
+
+    /// Signatures of the event functions generated so far
+    pub fn generated_functions(&self) -> Vec<GeneratedFunction> {
+        self.functions.borrow().clone()
+    }
+
+    /// Record `code` as the output generated before a failure at this level
+    ///
+    /// Each level an error passes through prepends its own output, so
+    /// `partial_output` ends up holding everything generated up to the failure.
+    fn preceded_by(&self, code: &str, err: CompileError) -> CompileError {
+        self.partial_output.borrow_mut().insert_str(0, code);
+        err
+    }
+
+    /// Record a diagnostic
+    fn report(&self, diagnostic: Diagnostic) {
+        tracing::warn!("[PBGC] {}", diagnostic.message);
+        self.diagnostics.borrow_mut().push(diagnostic);
+    }
+
     /// Generate complete Rust program from the graph
-    pub fn generate_program(&self) -> Result<String, GraphyError> {
+    ///
+    /// Errors with a free-form message (code generation and custom errors)
+    /// become [`CompileError::Generation`], carrying the line and byte offset
+    /// in the output at which generation stopped.
+    pub fn generate_program(&self) -> Result<String, CompileError> {
         let mut code = String::new();
+        self.partial_output.borrow_mut().clear();
+
+        match self.write_program(&mut code) {
+            Ok(()) => Ok(code),
+            Err(err) => {
+                code.push_str(&self.partial_output.borrow());
+                Err(at_output_location(err, &code))
+            }
+        }
+    }
 
+    /// Write the program into `code`, which holds everything generated so far if this fails
+    fn write_program(&self, code: &mut String) -> Result<(), CompileError> {
         // Add header
         code.push_str("// Auto-generated code from Pulsar Blueprint\n");
         code.push_str("// DO NOT EDIT - Changes will be overwritten\n");
-        code.push_str("// Compiled with PBGC (Pulsar Blueprint Graph Compiler)\n\n");
+        code.push_str("// Compiled with PBGC (Pulsar Blueprint Graph Compiler)\n");
+        if let Some(edition) = &self.target().edition {
+            code.push_str(&format!("// Target edition: {}\n", edition));
+        }
+        code.push('\n');
+
+        if self.target().no_std {
+            code.push_str("#![no_std]\n");
+            if self.target().alloc {
+                code.push_str("extern crate alloc;\n");
+            }
+            code.push('\n');
+        }
 
         // Add imports
-        code.push_str("// NOTE: Replace with actual pulsar_std import in production\n");
-        code.push_str("// use pulsar_std::*;\n\n");
+        code.push_str(&self.runtime().program_header());
+        if !self.options.wasm_exports.is_empty() {
+            if self.options.class.is_some() {
+                return Err(CompileError::UnsupportedTarget {
+                    construct: "#[wasm_bindgen] exports".to_string(),
+                    target: "a Blueprint class".to_string(),
+                });
+            }
+            code.push_str("use wasm_bindgen::prelude::*;\n");
+        }
+        if !self.options.c_exports.is_empty() && self.options.class.is_some() {
+            return Err(CompileError::UnsupportedTarget {
+                construct: "extern \"C\" exports".to_string(),
+                target: "a Blueprint class".to_string(),
+            });
+        }
+        if self.options.emit_dispatch && self.options.class.is_some() {
+            return Err(CompileError::UnsupportedTarget {
+                construct: "event dispatch".to_string(),
+                target: "a Blueprint class".to_string(),
+            });
+        }
+        if self.options.emit_benches && self.options.class.is_some() {
+            return Err(CompileError::UnsupportedTarget {
+                construct: "event benches".to_string(),
+                target: "a Blueprint class".to_string(),
+            });
+        }
+        if self.options.closures.is_some() {
+            let conflicting = if self.options.class.is_some() {
+                Some("a Blueprint class")
+            } else if self.options.emit_dispatch {
+                Some("event dispatch")
+            } else if self.options.emit_benches {
+                Some("event benches")
+            } else if !self.options.c_exports.is_empty() {
+                Some("extern \"C\" exports")
+            } else {
+                None
+            };
+            if let Some(construct) = conflicting {
+                return Err(CompileError::UnsupportedTarget {
+                    construct: construct.to_string(),
+                    target: "event closures".to_string(),
+                });
+            }
+        }
+        code.push('\n');
 
         // Collect node-specific imports
-        let node_imports = self.collect_node_imports();
+        let node_imports = self.collect_node_imports()?;
         for import_stmt in node_imports {
             code.push_str(&import_stmt);
             code.push_str("\n");
         }
         code.push_str("\n");
 
-        // Find event nodes
-        let event_nodes: Vec<_> = self.graph
+        // Declare class variables
+        let variables = self.aliased_variables();
+        match &self.options.class {
+            Some(class) => {
+                let mut cached: Vec<(&String, &CachedField)> = self.cached_pure.iter().collect();
+                cached.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+                for (node_id, field) in &cached {
+                    if let Some(node) = self.graph.nodes.get(*node_id) {
+                        self.report(
+                            Diagnostic::info(format!("Value of '{}' is shared by several events; cached in '{}'", node_id, field.name))
+                                .with_node(node),
+                        );
+                    }
+                }
+                let cached: Vec<CachedField> = cached.into_iter().map(|(_, field)| field.clone()).collect();
+                self.report.borrow_mut().pure_values_cached += cached.len();
+
+                for warning in check_derives(class, &variables, &cached) {
+                    self.report(Diagnostic::warning(warning));
+                }
+                if let Some(var) = class.teardown.keys().find(|var| !variables.contains_key(*var)) {
+                    return Err(CompileError::VariableNotFound { var: var.clone() });
+                }
+                code.push_str(&generate_struct(class, &variables, &cached));
+                code.push('\n');
+            }
+            // Variables are fields of the closures' environment
+            None if self.options.closures.is_some() => {}
+            None => {
+                if self.target().no_std && !self.variables.is_empty() {
+                    return Err(CompileError::UnsupportedTarget {
+                        construct: "class variables (thread_local!)".to_string(),
+                        target: "no_std".to_string(),
+                    });
+                }
+                for (_, var) in sorted_variables(&variables) {
+                    adapt_to_target(var.initial_expression(), self.target())?;
+                }
+                let declarations = generate_variable_declarations(&variables, &self.const_variables);
+                self.report.borrow_mut().variables_made_const +=
+                    variables.keys().filter(|name| self.const_variables.contains(*name)).count();
+                if !declarations.is_empty() {
+                    code.push_str(&declarations);
+                    code.push('\n');
+                }
+            }
+        }
+
+        // Find event nodes, in ID order so output doesn't depend on map iteration
+        let mut event_nodes: Vec<_> = self.graph
             .nodes
             .values()
             .filter(|node| {
@@ -70,448 +352,4737 @@ impl<'a> BlueprintCodeGenerator<'a> {
                     .unwrap_or(false)
             })
             .collect();
+        event_nodes.sort_by(|a, b| a.id.cmp(&b.id));
 
         if event_nodes.is_empty() {
-            return Err(GraphyError::CodeGeneration(
-                "No event nodes found in graph - add a 'main' or 'begin_play' event".to_string(),
-            ));
+            if self.options.allow_empty {
+                code.push_str("// empty blueprint\n");
+                return Ok(());
+            }
+            return Err(CompileError::NoEntryPoints);
         }
 
-        // Generate each event function
-        for event_node in event_nodes {
-            let event_code = self.generate_event_function(event_node)?;
-            code.push_str(&event_code);
-            code.push_str("\n");
-        }
+        // Generate each event function (as methods when compiling a class)
+        match &self.options.class {
+            Some(class) => {
+                let trait_events: HashSet<&str> = class.trait_impls
+                    .iter()
+                    .flat_map(|trait_impl| trait_impl.method_map.keys().map(String::as_str))
+                    .collect();
+                let (trait_nodes, inherent_nodes): (Vec<_>, Vec<_>) = event_nodes
+                    .into_iter()
+                    .partition(|node| trait_events.contains(node.node_type.as_str()));
 
-        Ok(code)
-    }
+                if !inherent_nodes.is_empty() {
+                    code.push_str(&format!("impl {} {{\n", class.name));
+                    for (i, event_node) in inherent_nodes.into_iter().enumerate() {
+                        if i > 0 {
+                            code.push('\n');
+                        }
+                        code.push_str(&self.generate_event_function(event_node, 1)?);
+                        self.check_output_size(code)?;
+                    }
+                    code.push_str("}\n");
+                }
 
-    /// Collect imports from all nodes
-    fn collect_node_imports(&self) -> Vec<String> {
-        let mut imports: HashSet<String> = HashSet::new();
+                for trait_impl in &class.trait_impls {
+                    code.push('\n');
+                    code.push_str(&self.generate_trait_impl(class, trait_impl, &trait_nodes)?);
+                    self.check_output_size(code)?;
+                }
 
-        for node in self.graph.nodes.values() {
-            if let Some(metadata) = self.metadata_provider.get_node_metadata(&node.node_type) {
-                for import in &metadata.imports {
-                    imports.insert(import.clone());
+                if let Some(drop_impl) = generate_drop_impl(class, &self.variables) {
+                    code.push('\n');
+                    code.push_str(&drop_impl);
                 }
             }
+            None => match &self.options.closures {
+                Some(target) => {
+                    code.push_str(&self.generate_closure_builder(&event_nodes, target)?);
+                    self.check_output_size(code)?;
+                }
+                None => {
+                    for event_node in &event_nodes {
+                        let event_code = self.generate_event_function(event_node, 0)?;
+                        code.push_str(&event_code);
+                        code.push('\n');
+                        self.check_output_size(code)?;
+                    }
+
+                    if self.options.emit_dispatch {
+                        code.push_str(&self.generate_dispatch(&event_nodes)?);
+                        self.check_output_size(code)?;
+                    }
+
+                    if self.options.emit_benches {
+                        code.push_str(&self.generate_benches(&event_nodes)?);
+                        self.check_output_size(code)?;
+                    }
+                }
+            },
         }
 
-        let mut import_vec: Vec<_> = imports.into_iter().collect();
-        import_vec.sort();
-        import_vec
+        Ok(())
     }
 
-    /// Generate an event function
-    fn generate_event_function(&self, event_node: &NodeInstance) -> Result<String, GraphyError> {
-        let mut code = String::new();
+    /// Generate `dispatch` over the events it can call, warning about the rest
+    fn generate_dispatch(&self, event_nodes: &[&NodeInstance]) -> Result<String, CompileError> {
+        let mut events = Vec::new();
+        for event_node in event_nodes {
+            let metadata = self.node_metadata(event_node)?;
+            let params = self.event_params(event_node);
 
-        // Get event metadata
-        let metadata = self.metadata_provider
-            .get_node_metadata(&event_node.node_type)
-            .ok_or_else(|| GraphyError::NodeNotFound(event_node.node_type.clone()))?;
+            let reason = if self.reaches_latent_node(event_node) {
+                Some("it contains latent nodes".to_string())
+            } else if self.event_context(event_node).is_some() {
+                Some("it takes a context parameter".to_string())
+            } else if self.metadata_provider.attributes(&event_node.node_type).is_async {
+                Some("it is async".to_string())
+            } else {
+                params
+                    .iter()
+                    .find(|param| !is_decodable(&param.ty))
+                    .map(|param| format!("parameter '{}' has type '{}', which can't be decoded", param.name, param.ty))
+            };
+            match reason {
+                Some(reason) => self.report(
+                    Diagnostic::warning(format!("Event '{}' is left out of dispatch: {}", metadata.name, reason))
+                        .with_node(event_node),
+                ),
+                None => events.push((metadata.name.as_str(), params)),
+            }
+        }
+        events.sort_by(|a, b| a.0.cmp(b.0));
 
-        // Generate function signature
-        code.push_str(&format!("pub fn {}() {{\n", metadata.name));
+        let events: Vec<DispatchEvent> = events
+            .iter()
+            .map(|(name, params)| DispatchEvent { name, params })
+            .collect();
+        Ok(generate_dispatch(&events))
+    }
 
-        // Find execution output pins and follow them
-        // We need to look up by pin ID (from the node instance), not pin name (from metadata)
-        for output_pin in &event_node.outputs {
-            if matches!(output_pin.pin.data_type, graphy::DataType::Execution) {
-                tracing::debug!("[CODEGEN] Looking up exec connections for node {} pin ID: {}", 
-                    event_node.id, output_pin.id);
-                
-                let connected = self.exec_routing.get_connected_nodes(&event_node.id, &output_pin.id);
-                
-                tracing::debug!("[CODEGEN] Found {} connected nodes", connected.len());
-                
-                for next_node_id in connected {
-                    if let Some(next_node) = self.graph.nodes.get(next_node_id) {
-                        let mut generator = self.clone_with_new_visited();
-                        let node_code = generator.generate_exec_chain(next_node, 1)?;
-                        code.push_str(&node_code);
-                    }
-                }
+    /// Generate a `#[bench]` per parameterless, synchronous, non-latent event
+    fn generate_benches(&self, event_nodes: &[&NodeInstance]) -> Result<String, CompileError> {
+        let mut names = Vec::new();
+        for event_node in event_nodes {
+            let benchable = self.event_params(event_node).is_empty()
+                && self.event_context(event_node).is_none()
+                && !self.reaches_latent_node(event_node)
+                && !self.metadata_provider.attributes(&event_node.node_type).is_async;
+            if benchable {
+                names.push(self.node_metadata(event_node)?.name.as_str());
             }
         }
+        names.sort();
 
+        let mut code = String::from("\n#[cfg(test)]\nmod benches {\n");
+        code.push_str("    extern crate test;\n\n");
+        code.push_str("    use super::*;\n");
+        code.push_str("    use test::Bencher;\n");
+        for name in names {
+            code.push_str(&format!(
+                "\n    #[bench]\n    fn bench_{name}(b: &mut Bencher) {{\n        b.iter(|| {name}());\n    }}\n",
+                name = name
+            ));
+        }
         code.push_str("}\n");
-
         Ok(code)
     }
 
-    /// Generate execution chain starting from a node
-    fn generate_exec_chain(&mut self, node: &NodeInstance, indent_level: usize) -> Result<String, GraphyError> {
-        let mut code = String::new();
+    /// Generate `impl Trait for Class`, with the mapped events as its methods
+    ///
+    /// Fails if a mapped event is missing from the graph or its data outputs
+    /// don't match the method's parameter types.
+    fn generate_trait_impl(
+        &self,
+        class: &ClassContext,
+        trait_impl: &TraitImpl,
+        event_nodes: &[&NodeInstance],
+    ) -> Result<String, CompileError> {
+        let mismatch = |method: &TraitMethod, reason: String| CompileError::TraitImplMismatch {
+            trait_path: trait_impl.trait_path.clone(),
+            method: method.name.clone(),
+            reason,
+        };
 
-        // Prevent infinite loops
-        if self.visited.contains(&node.id) {
-            return Ok(code);
-        }
-        self.visited.insert(node.id.clone());
+        let mut methods: Vec<_> = trait_impl.method_map.iter().collect();
+        methods.sort_by(|a, b| a.1.name.cmp(&b.1.name));
 
-        // Check if this is a variable getter or setter
-        if node.node_type.starts_with("get_") {
-            // Getter nodes are pure (no exec chain), skip
-            return Ok(code);
-        } else if node.node_type.starts_with("set_") {
-            // Setter nodes have exec chain
-            return self.generate_setter_node(node, indent_level);
-        }
+        let mut code = format!("impl {} for {} {{\n", trait_impl.trait_path, class.name);
 
-        let node_meta = self.metadata_provider
-            .get_node_metadata(&node.node_type)
-            .ok_or_else(|| GraphyError::NodeNotFound(node.node_type.clone()))?;
+        for (i, (event_name, method)) in methods.into_iter().enumerate() {
+            let event_node = event_nodes
+                .iter()
+                .find(|node| &node.node_type == event_name)
+                .ok_or_else(|| mismatch(method, format!("event '{}' is not in the graph", event_name)))?;
 
-        match node_meta.node_type {
-            NodeTypes::pure => {
-                // Pure nodes are pre-evaluated, skip in exec chain
-                Ok(code)
-            }
-            NodeTypes::fn_ => {
-                self.generate_function_node(node, node_meta, indent_level)
-            }
-            NodeTypes::control_flow => {
-                self.generate_control_flow_node(node, node_meta, indent_level)
-            }
-            NodeTypes::event => {
-                // Event nodes define the outer function, skip in exec chain
-                Ok(code)
+            let params = self.event_params(event_node);
+            if params.len() != method.params.len() {
+                return Err(mismatch(method, format!(
+                    "event '{}' provides {} parameter(s), the method takes {}",
+                    event_name,
+                    params.len(),
+                    method.params.len()
+                )));
             }
-        }
-    }
-
-    /// Generate code for a function node
-    fn generate_function_node(
-        &mut self,
-        node: &NodeInstance,
-        node_meta: &graphy::core::NodeMetadata,
-        indent_level: usize,
-    ) -> Result<String, GraphyError> {
-        let mut code = String::new();
-        let indent = "    ".repeat(indent_level);
 
-        // Collect arguments
-        let args = self.collect_arguments(node, node_meta)?;
+            let params = params
+                .into_iter()
+                .zip(&method.params)
+                .map(|(param, (name, ty))| {
+                    if param.ty.replace(' ', "") != ty.replace(' ', "") {
+                        return Err(mismatch(method, format!(
+                            "parameter '{}' has type '{}', but event '{}' provides '{}'",
+                            name, ty, event_name, param.ty
+                        )));
+                    }
+                    Ok(EventParam { name: name.clone(), ..param })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
 
-        // Check if this function returns a value
-        let has_return = node_meta.return_type.is_some();
+            if i > 0 {
+                code.push('\n');
+            }
+            let method_code = self.emit_event_function(event_node, &method.name, false, params, None, 1)
+                .map_err(|err| self.preceded_by(&code, err))?;
+            code.push_str(&method_code);
+        }
 
-        if has_return {
-            // Store result in variable
-            let result_var = self.data_resolver
-                .get_result_variable(&node.id)
-                .ok_or_else(|| GraphyError::Custom(format!("No result variable for node: {}", node.id)))?;
+        code.push_str("}\n");
+        Ok(code)
+    }
 
-            code.push_str(&format!(
-                "{}let {} = {}({});\n",
-                indent,
-                result_var,
-                node_meta.name,
-                args.join(", ")
-            ));
-        } else {
-            // Just call the function
-            code.push_str(&format!(
-                "{}{}({});\n",
-                indent,
-                node_meta.name,
-                args.join(", ")
-            ));
+    /// The code emitted for one node, without its function or successors
+    ///
+    /// A statement node gives its call (with any bindings it needs first); the
+    /// nodes its exec outputs lead to, including branch bodies, are left out.
+    /// A pure node gives its expression. Event parameters read by the node
+    /// appear under their parameter names.
+    pub fn generate_node_snippet(&self, node_id: &str) -> Result<String, CompileError> {
+        let node = self.graph.nodes
+            .get(node_id)
+            .ok_or_else(|| GraphyError::NodeNotFound(node_id.to_string()))?;
+        if self.metadata_provider.get_node_metadata(&node.node_type).is_some_and(|meta| meta.node_type == NodeTypes::pure) {
+            return self.generate_pure_node_expression(node);
         }
 
-        // Follow execution chain - look up by actual pin IDs from node instance
-        for output_pin in &node.outputs {
-            if matches!(output_pin.pin.data_type, graphy::DataType::Execution) {
-                let connected = self.exec_routing.get_connected_nodes(&node.id, &output_pin.id);
-                for next_node_id in connected {
-                    if let Some(next_node) = self.graph.nodes.get(next_node_id) {
-                        let next_code = self.generate_exec_chain(next_node, indent_level)?;
-                        code.push_str(&next_code);
-                    }
+        // Every other node counts as generated, so no exec chain is followed from this one
+        let visited = self.graph.nodes.keys().filter(|id| id.as_str() != node_id).cloned().collect();
+        let mut generator = self.fork(visited);
+        generator.follow_exec = false;
+        for event_node in self.graph.nodes.values() {
+            let is_event = self.metadata_provider
+                .get_node_metadata(&event_node.node_type)
+                .is_some_and(|meta| meta.node_type == NodeTypes::event);
+            if is_event {
+                for param in self.event_params(event_node) {
+                    generator.bind_scope_value(&event_node.id, &param.pin_id, param.name.clone());
                 }
             }
         }
 
-        Ok(code)
+        generator.generate_exec_chain(node, 0)
     }
 
-    /// Generate code for a control flow node
-    fn generate_control_flow_node(
-        &mut self,
-        node: &NodeInstance,
-        node_meta: &graphy::core::NodeMetadata,
-        indent_level: usize,
-    ) -> Result<String, GraphyError> {
-        let mut code = String::new();
-        let indent = "    ".repeat(indent_level);
+    /// Compile a purely-data graph to a single expression
+    ///
+    /// `get_<name>` nodes read the named entry of `inputs`. The result is the
+    /// graph's one pure node whose output nothing else consumes.
+    pub fn generate_expression(&self, inputs: &HashMap<String, String>) -> Result<String, CompileError> {
+        let mut generator = self.clone_with_new_visited();
+        let mut sinks = Vec::new();
 
-        // Build exec_output replacements - need to map pin names to pin IDs
-        let mut exec_replacements = HashMap::new();
+        for node in self.graph.nodes.values() {
+            if let Some(input_name) = node.node_type.strip_prefix("get_") {
+                let expr = inputs.get(input_name).ok_or_else(|| GraphyError::CodeGeneration(format!(
+                    "Expression input '{}' (read by node '{}') was not provided",
+                    input_name, node.id
+                )))?;
+                let expr = if expr.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | ':')) {
+                    expr.clone()
+                } else {
+                    format!("({})", expr)
+                };
+                for output in &node.outputs {
+                    generator.bind_scope_value(&node.id, &output.id, expr.clone());
+                }
+                continue;
+            }
 
-        for output_pin in &node.outputs {
-            if matches!(output_pin.pin.data_type, graphy::DataType::Execution) {
-                let connected = self.exec_routing.get_connected_nodes(&node.id, &output_pin.id);
+            let node_meta = self.node_metadata(node)?;
+            if node_meta.node_type != NodeTypes::pure {
+                return Err(GraphyError::CodeGeneration(format!(
+                    "Expression graphs may only contain pure nodes, but '{}' ({}) is not pure",
+                    node.id, node.node_type
+                )).into());
+            }
+            if self.consumer_counts.get(&node.id).copied().unwrap_or(0) == 0 {
+                sinks.push(node);
+            }
+        }
 
-                let mut exec_code = String::new();
-                let local_visited = self.visited.clone();
+        let output = match sinks.as_slice() {
+            [output] => *output,
+            [] => return Err(GraphyError::CodeGeneration(
+                "Expression graph has no output: every pure node feeds another".to_string(),
+            ).into()),
+            _ => {
+                let mut ids: Vec<_> = sinks.iter().map(|node| node.id.as_str()).collect();
+                ids.sort();
+                return Err(GraphyError::CodeGeneration(format!(
+                    "Expression graph has several unconsumed outputs: {}",
+                    ids.join(", ")
+                )).into());
+            }
+        };
 
-                for next_node_id in connected {
-                    if let Some(next_node) = self.graph.nodes.get(next_node_id) {
-                        let mut sub_gen = BlueprintCodeGenerator {
-                            graph: self.graph,
-                            metadata_provider: self.metadata_provider,
-                            data_resolver: self.data_resolver,
-                            exec_routing: self.exec_routing,
-                            variables: self.variables.clone(),
-                            visited: local_visited.clone(),
-                        };
+        let expr = generator.inline_pure_node(output)?;
+        let bindings = generator.take_pending_bindings("");
+        if bindings.is_empty() {
+            Ok(expr)
+        } else {
+            Ok(format!("{{ {} {} }}", bindings.trim_end().replace('\n', " "), expr))
+        }
+    }
 
-                        let next_code = sub_gen.generate_exec_chain(next_node, 0)?;
-                        exec_code.push_str(&next_code);
+    /// Collect imports from all nodes
+    ///
+    /// Fails if two imports would bind the same identifier from different paths.
+    fn collect_node_imports(&self) -> Result<Vec<String>, CompileError> {
+        // The cfg predicates an import is needed under; `None` if any user is ungated
+        let mut imports: HashMap<String, Option<BTreeSet<String>>> = HashMap::new();
+
+        for node in self.graph.nodes.values() {
+            if let Some(metadata) = self.metadata_provider.get_node_metadata(&node.node_type) {
+                let cfg = &self.metadata_provider.attributes(&node.node_type).cfg;
+                for import in &metadata.imports {
+                    let gates = imports.entry(import.clone()).or_insert_with(|| Some(BTreeSet::new()));
+                    match (gates.as_mut(), cfg) {
+                        (Some(predicates), Some(predicate)) => {
+                            predicates.insert(predicate.clone());
+                        }
+                        _ => *gates = None,
                     }
                 }
-
-                // Use the pin NAME for the template substitution (e.g., "then", "else")
-                exec_replacements.insert(output_pin.pin.name.clone(), exec_code.trim().to_string());
             }
         }
 
-        // Build parameter substitutions - need to look up by pin ID
-        let mut param_substitutions = HashMap::new();
-        for param in &node_meta.params {
-            // Find the actual pin ID from the node instance
-            let pin_id = node.inputs.iter()
-                .find(|input| input.pin.name == param.name)
-                .map(|input| input.id.clone())
-                .ok_or_else(|| GraphyError::Custom(
-                    format!("Input pin not found for parameter '{}' on node '{}'", param.name, node.id)
-                ))?;
-
-            let value = self.generate_input_expression(&node.id, &pin_id)?;
-            param_substitutions.insert(param.name.clone(), value);
+        if self.options.trace_nodes {
+            imports.insert("use tracing::trace;".to_string(), None);
         }
 
-        // Inline the function with substitutions
-        let inlined_body = graphy::utils::inline_control_flow_function(
-            &node_meta.function_source,
-            exec_replacements,
-            param_substitutions,
-        )?;
+        let mut import_vec: Vec<_> = imports.keys().cloned().collect();
+        import_vec.sort();
 
-        // Add inlined code with proper indentation
-        for line in inlined_body.lines() {
-            if !line.trim().is_empty() {
-                code.push_str(&format!("{}{}\n", indent, line));
-            }
-        }
+        let alias_vec = alias_imports(&self.options.type_aliases, &import_vec);
+        let all_imports: Vec<String> = import_vec.iter().chain(&alias_vec).cloned().collect();
+        check_import_conflicts(&all_imports)?;
 
-        Ok(code)
+        Ok(import_vec
+            .into_iter()
+            .map(|import| match &imports[&import] {
+                Some(predicates) if predicates.len() == 1 => {
+                    format!("#[cfg({})]\n{}", predicates.iter().next().expect("one predicate"), import)
+                }
+                Some(predicates) if !predicates.is_empty() => {
+                    let predicates: Vec<&str> = predicates.iter().map(String::as_str).collect();
+                    format!("#[cfg(any({}))]\n{}", predicates.join(", "), import)
+                }
+                _ => import,
+            })
+            .chain(alias_vec)
+            .collect())
     }
 
-    /// Generate code for a setter node
-    fn generate_setter_node(&mut self, node: &NodeInstance, indent_level: usize) -> Result<String, GraphyError> {
-        let mut code = String::new();
-        let indent = "    ".repeat(indent_level);
+    /// `ty` with every aliased path replaced by its alias
+    fn aliased(&self, ty: String) -> String {
+        if self.options.type_aliases.is_empty() {
+            return ty;
+        }
+        apply_type_aliases(&ty, &self.options.type_aliases)
+    }
 
-        // Extract variable name from node type (remove "set_" prefix)
-        let var_name = node.node_type
-            .strip_prefix("set_")
-            .ok_or_else(|| GraphyError::Custom(format!("Invalid setter node type: {}", node.node_type)))?;
+    /// Variable definitions with their types written through the type aliases
+    fn aliased_variables(&self) -> HashMap<String, VariableDef> {
+        self.variables
+            .iter()
+            .map(|(name, var)| {
+                let var = VariableDef { ty: self.aliased(var.ty.clone()), ..var.clone() };
+                (name.clone(), var)
+            })
+            .collect()
+    }
 
-        // Find the "value" input pin ID
-        let value_pin_id = node.inputs.iter()
-            .find(|input| input.pin.name == "value")
-            .map(|input| input.id.clone())
-            .ok_or_else(|| GraphyError::Custom(format!("Value input not found on setter node: {}", node.id)))?;
+    /// Resolved concrete pin types for this graph
+    pub fn resolved_types(&self) -> &ResolvedTypes {
+        &self.types
+    }
 
-        // Get the value to set
-        let value_expr = self.generate_input_expression(&node.id, &value_pin_id)?;
+    /// Target the code is generated for
+    fn target(&self) -> &TargetSpec {
+        self.options.target.as_ref().unwrap_or(&TargetSpec::DEFAULT)
+    }
 
-        // Get variable type to determine Cell vs RefCell
-        let var_type = self.variables
-            .get(var_name)
-            .ok_or_else(|| GraphyError::Custom(format!("Variable '{}' not found", var_name)))?;
+    /// Scaffolding profile for this compilation
+    fn runtime(&self) -> &dyn RuntimeProfile {
+        match &self.options.runtime {
+            Some(runtime) => runtime.as_ref(),
+            None => &DefaultRuntime,
+        }
+    }
 
-        // Generate setter code
-        let is_copy_type = is_copy_type(var_type);
-        if is_copy_type {
-            code.push_str(&format!(
-                "{}{}.with(|v| v.set({}));\n",
-                indent,
-                var_name.to_uppercase(),
-                value_expr
-            ));
+    /// Where variables are stored for this compilation
+    fn variable_storage(&self) -> VariableStorage {
+        if self.options.class.is_some() {
+            VariableStorage::StructField
+        } else if self.options.closures.is_some() {
+            VariableStorage::Environment
         } else {
-            code.push_str(&format!(
-                "{}{}.with(|v| *v.borrow_mut() = {});\n",
-                indent,
-                var_name.to_uppercase(),
-                value_expr
-            ));
+            VariableStorage::ThreadLocal
         }
+    }
 
-        // Follow execution chain - use actual pin IDs from node instance
-        for output_pin in &node.outputs {
-            if matches!(output_pin.pin.data_type, graphy::DataType::Execution) {
-                let connected = self.exec_routing.get_connected_nodes(&node.id, &output_pin.id);
-                for next_node_id in connected {
-                    if let Some(next_node) = self.graph.nodes.get(next_node_id) {
-                        let next_code = self.generate_exec_chain(next_node, indent_level)?;
-                        code.push_str(&next_code);
-                    }
+    /// Generate an event function
+    ///
+    /// `indent_level` is the level of the signature; the body is one deeper.
+    fn generate_event_function(&self, event_node: &NodeInstance, indent_level: usize) -> Result<String, CompileError> {
+        // Get event metadata
+        let metadata = self.metadata_provider
+            .get_node_metadata(&event_node.node_type)
+            .ok_or_else(|| GraphyError::NodeNotFound(event_node.node_type.clone()))?;
+
+        let context = self.event_context(event_node);
+
+        // Events that wait on latent nodes become resumable state machines
+        if self.reaches_latent_node(event_node) {
+            if self.options.c_exports.contains(&metadata.name) {
+                return Err(CompileError::UnsupportedTarget {
+                    construct: format!("latent event '{}'", metadata.name),
+                    target: "extern \"C\" exports".to_string(),
+                });
+            }
+            if context.is_some() {
+                return Err(GraphyError::CodeGeneration(format!(
+                    "Event '{}' contains latent nodes, so it can't take a context parameter",
+                    metadata.name
+                )).into());
+            }
+            if self.options.class.is_some() {
+                return Err(GraphyError::CodeGeneration(format!(
+                    "Event '{}' contains latent nodes, which are not supported when compiling a class",
+                    metadata.name
+                )).into());
+            }
+            return generate_state_machine(self, event_node, &metadata.name);
+        }
+
+        let params = self.event_params(event_node);
+        self.emit_event_function(event_node, &metadata.name, true, params, context, indent_level)
+    }
+
+    /// Generate the shared function for the sub-graph this generator was built on
+    ///
+    /// Parameters come from the data outputs of the sub-graph's inputs node and
+    /// the return type from its instance metadata. A sub-graph without exec
+    /// pins returns its outputs as the function's tail expression; otherwise
+    /// reaching the outputs node returns them.
+    ///
+    /// `Any`-typed inputs become type parameters, bounded in a `where` clause
+    /// by whatever the nodes consuming them require.
+    ///
+    /// A pure sub-graph is a helper: it is emitted as `const fn` when every
+    /// node in it is const-evaluable, and as `#[inline] fn` otherwise.
+    pub fn generate_subgraph_function(&self, name: &str) -> Result<String, CompileError> {
+        let inputs = boundary_node(self.graph, SUBGRAPH_INPUTS);
+        let outputs = boundary_node(self.graph, SUBGRAPH_OUTPUTS);
+        let (params, type_params) = inputs.map(|node| self.subgraph_params(node)).unwrap_or_default();
+        let return_type = self.metadata_provider
+            .get_node_metadata(name)
+            .and_then(|meta| meta.return_type.as_ref())
+            .map(|ty| ty.type_string.clone());
+
+        let param_list: Vec<String> = params.iter().map(|param| format!("{}: {}", param.name, param.ty)).collect();
+        let is_pure = self.metadata_provider
+            .get_node_metadata(name)
+            .is_some_and(|meta| meta.node_type == NodeTypes::pure);
+        let mut code = if is_pure {
+            let node_types = self.graph.nodes
+                .values()
+                .map(|node| node.node_type.as_str())
+                .filter(|node_type| ![SUBGRAPH_INPUTS, SUBGRAPH_OUTPUTS].contains(node_type));
+            format!("{} {}", self.metadata_provider.helper_qualifier(node_types).signature("pub "), name)
+        } else {
+            format!("pub fn {}", name)
+        };
+        if !type_params.is_empty() {
+            let names: Vec<&str> = type_params.iter().map(|(name, _)| name.as_str()).collect();
+            code.push_str(&format!("<{}>", names.join(", ")));
+        }
+        code.push_str(&format!("({})", param_list.join(", ")));
+        if let Some(ty) = &return_type {
+            code.push_str(&format!(" -> {}", ty));
+        }
+        let predicates: Vec<String> = type_params
+            .iter()
+            .filter(|(_, bounds)| !bounds.is_empty())
+            .map(|(name, bounds)| format!("{}: {}", name, bounds.iter().cloned().collect::<Vec<_>>().join(" + ")))
+            .collect();
+        if !predicates.is_empty() {
+            code.push_str(&format!("\nwhere\n    {},\n", predicates.join(",\n    ")));
+            code.push_str("{\n");
+        } else {
+            code.push_str(" {\n");
+        }
+
+        self.functions.borrow_mut().push(GeneratedFunction {
+            name: name.to_string(),
+            params: params.iter().map(|param| (param.name.clone(), param.ty.clone())).collect(),
+            return_type,
+        });
+
+        let mut generator = self.clone_with_new_visited();
+        generator.exec_order = Rc::new(RefCell::new(HashMap::new()));
+        if let Some(inputs) = inputs {
+            for param in &params {
+                generator.bind_scope_value(&inputs.id, &param.pin_id, param.name.clone());
+            }
+            for next_node in self.exec_successors(inputs) {
+                code.push_str(&generator.generate_exec_chain(next_node, 1)?);
+            }
+        }
+
+        let exec_exit = outputs.is_some_and(|node| {
+            node.inputs.iter().any(|pin| matches!(pin.pin.data_type, graphy::DataType::Execution))
+        });
+        if let (Some(outputs), false) = (outputs, exec_exit) {
+            if let Some(value) = generator.subgraph_outputs(outputs)? {
+                code.push_str(&generator.take_pending_bindings("    "));
+                if self.options.explicit_returns {
+                    code.push_str(&format!("    return {};\n", value));
+                } else {
+                    code.push_str(&format!("    {}\n", value));
                 }
             }
         }
 
+        code.push_str("}\n");
         Ok(code)
     }
 
-    /// Collect arguments for a function call
-    fn collect_arguments(&self, node: &NodeInstance, node_meta: &graphy::core::NodeMetadata) -> Result<Vec<String>, GraphyError> {
-        let mut args = Vec::new();
+    /// Generate a `#[cfg(test)]` module asserting each example against the sub-graph function `name`
+    ///
+    /// Every input of the sub-graph needs a value in every example.
+    pub fn generate_subgraph_examples(&self, name: &str, examples: &[SubgraphExample]) -> Result<String, CompileError> {
+        let inputs = boundary_node(self.graph, SUBGRAPH_INPUTS);
+        let params = inputs.map(|node| self.subgraph_params(node).0).unwrap_or_default();
+        let pin_name = |param: &EventParam| {
+            inputs
+                .and_then(|node| node.outputs.iter().find(|pin| pin.id == param.pin_id))
+                .map_or(param.name.clone(), |pin| pin.pin.name.clone())
+        };
+        let return_type = self.metadata_provider
+            .get_node_metadata(name)
+            .and_then(|meta| meta.return_type.as_ref())
+            .map(|ty| ty.type_string.clone());
 
-        for param in &node_meta.params {
-            // Find the actual pin ID from the node instance
-            // Pin IDs are typically "{node_id}_{param_name}"
-            let pin_id = node.inputs.iter()
-                .find(|input| {
-                    // Match by name - the pin's name should match the param name
-                    input.pin.name == param.name
-                })
-                .map(|input| input.id.clone())
-                .ok_or_else(|| GraphyError::Custom(
-                    format!("Input pin not found for parameter '{}' on node '{}'", param.name, node.id)
-                ))?;
+        let mut code = format!("#[cfg(test)]\nmod {}_examples {{\n    use super::*;\n", name);
+        for (i, example) in examples.iter().enumerate() {
+            let invalid = |reason: String| CompileError::InvalidExample {
+                subgraph: name.to_string(),
+                index: i + 1,
+                reason,
+            };
+            let pin_names: Vec<String> = params.iter().map(pin_name).collect();
+            if let Some(unknown) = example.inputs.keys().find(|pin| !pin_names.contains(pin)) {
+                return Err(invalid(format!("sets '{}', which is not one of its inputs", unknown)));
+            }
 
-            let value = self.generate_input_expression(&node.id, &pin_id)?;
-            args.push(value);
+            let mut args = Vec::new();
+            for (param, pin) in params.iter().zip(&pin_names) {
+                let value = example.inputs
+                    .get(pin)
+                    .ok_or_else(|| invalid(format!("has no value for input '{}'", pin)))?;
+                let ty = param.ty.trim_start_matches('&').trim();
+                let value = retype_number_literal(value, ty).unwrap_or_else(|| value.clone());
+                args.push(if param.ty.starts_with('&') { format!("&{}", value) } else { value });
+            }
+            let expected = return_type
+                .as_deref()
+                .and_then(|ty| retype_number_literal(&example.expected, ty))
+                .unwrap_or_else(|| example.expected.clone());
+
+            code.push_str(&format!(
+                "\n    #[test]\n    fn {name}_example_{}() {{\n        assert_eq!({name}({}), {});\n    }}\n",
+                i + 1,
+                args.join(", "),
+                expected,
+                name = name
+            ));
         }
+        code.push_str("}\n");
 
-        Ok(args)
+        Ok(code)
     }
 
-    /// Generate expression for an input value
-    /// pin_id should be the actual pin ID from the node instance (e.g., "print_1_value")
-    fn generate_input_expression(&self, node_id: &str, pin_id: &str) -> Result<String, GraphyError> {
+    /// Parameters of a sub-graph function, and the type parameters its `Any` inputs need
+    ///
+    /// An `Any` input takes its type from the first consumer whose parameter
+    /// is concrete. Otherwise it gets a type parameter bounded by every bound
+    /// its consumers place on theirs (from `impl Trait` parameters or bounded
+    /// type parameters); this may over-constrain. Consumers taking `&T` make
+    /// the parameter `&T` too.
+    fn subgraph_params(&self, inputs: &NodeInstance) -> (Vec<EventParam>, Vec<(String, BTreeSet<String>)>) {
         use graphy::analysis::DataSource;
 
-        match self.data_resolver.get_input_source(node_id, pin_id) {
-            Some(DataSource::Connection { source_node_id, source_pin }) => {
-                let source_node = self.graph.nodes.get(source_node_id)
-                    .ok_or_else(|| GraphyError::NodeNotFound(source_node_id.clone()))?;
+        let mut params = Vec::new();
+        let mut generic: Vec<(usize, bool, BTreeSet<String>)> = Vec::new();
+        let resolved = self.event_params(inputs);
 
-                // Check if source is a variable getter
-                if source_node.node_type.starts_with("get_") {
-                    let var_name = source_node.node_type.strip_prefix("get_").unwrap();
-                    let var_type = self.variables.get(var_name)
-                        .ok_or_else(|| GraphyError::Custom(format!("Variable '{}' not found", var_name)))?;
-
-                    let is_copy = is_copy_type(var_type);
-                    return if is_copy {
-                        Ok(format!("{}.with(|v| v.get())", var_name.to_uppercase()))
-                    } else {
-                        Ok(format!("{}.with(|v| v.borrow().clone())", var_name.to_uppercase()))
+        for pin in &inputs.outputs {
+            if let Some(param) = resolved.iter().find(|param| param.pin_id == pin.id) {
+                params.push(param.clone());
+                continue;
+            }
+            if !matches!(pin.pin.data_type, graphy::DataType::Any) {
+                continue;
+            }
+
+            let mut concrete = None;
+            let mut borrowed = false;
+            let mut bounds = BTreeSet::new();
+            let mut consumers: Vec<&NodeInstance> = self.graph.nodes.values().collect();
+            consumers.sort_by(|a, b| a.id.cmp(&b.id));
+            for consumer in consumers {
+                let Some(consumer_meta) = self.metadata_provider.get_node_metadata(&consumer.node_type) else { continue };
+                for input in &consumer.inputs {
+                    let feeds = matches!(
+                        self.data_resolver.get_input_source(&consumer.id, &input.id),
+                        Some(DataSource::Connection { source_node_id, source_pin })
+                            if *source_node_id == inputs.id && *source_pin == pin.id
+                    );
+                    let Some(param) = consumer_meta.params.iter().find(|param| feeds && param.name == input.pin.name) else {
+                        continue;
                     };
-                }
 
-                // Check if source is pure - if so, inline it
-                if let Some(node_meta) = self.metadata_provider.get_node_metadata(&source_node.node_type) {
-                    if node_meta.node_type == NodeTypes::pure {
-                        return self.generate_pure_node_expression(source_node);
+                    let full_ty = param.param_type.type_string.trim();
+                    let ty = match full_ty.strip_prefix('&') {
+                        Some(referent) if !referent.trim_start().starts_with("mut ") => referent.trim(),
+                        _ => full_ty,
+                    };
+                    let type_params = super::types::generic_params(&consumer_meta.function_source);
+                    if let Some(impl_bounds) = ty.strip_prefix("impl ") {
+                        bounds.extend(impl_bounds.split('+').map(|bound| bound.trim().to_string()));
+                        borrowed |= ty != full_ty;
+                    } else if type_params.iter().any(|type_param| type_param == ty) {
+                        bounds.extend(super::types::generic_bounds(&consumer_meta.function_source, ty));
+                        borrowed |= ty != full_ty;
+                    } else if !super::types::is_generic(full_ty, &type_params) && concrete.is_none() {
+                        concrete = Some(full_ty.to_string());
                     }
                 }
+            }
 
-                // Non-pure: use result variable
-                if let Some(var_name) = self.data_resolver.get_result_variable(source_node_id) {
-                    Ok(var_name.clone())
-                } else {
-                    Err(GraphyError::Custom(format!("No variable for source node: {}", source_node_id)))
-                }
+            params.push(EventParam {
+                pin_id: pin.id.clone(),
+                name: sanitize_identifier(&pin.pin.name),
+                ty: concrete.clone().unwrap_or_default(),
+            });
+            if concrete.is_none() {
+                generic.push((params.len() - 1, borrowed, bounds));
             }
-            Some(DataSource::Constant(value)) => Ok(value.clone()),
-            Some(DataSource::Default) => {
-                // Use default value for the type
-                if let Some(node) = self.graph.nodes.get(node_id) {
-                    if let Some(pin) = node.inputs.iter().find(|p| p.id == pin_id) {
-                        Ok(get_default_value(&pin.pin.data_type))
-                    } else {
-                        Err(GraphyError::PinNotFound {
-                            node: node_id.to_string(),
-                            pin: pin_id.to_string(),
-                        })
+        }
+
+        // A lone type parameter is `T`; several are numbered
+        let count = generic.len();
+        let type_params = generic
+            .into_iter()
+            .enumerate()
+            .map(|(i, (param_index, borrowed, bounds))| {
+                let name = if count == 1 { "T".to_string() } else { format!("T{}", i + 1) };
+                params[param_index].ty = if borrowed { format!("&{}", name) } else { name.clone() };
+                (name, bounds)
+            })
+            .collect();
+
+        (params, type_params)
+    }
+
+    /// Return from a sub-graph function when its outputs node is reached
+    fn generate_subgraph_return(&self, node: &NodeInstance, indent_level: usize) -> Result<String, CompileError> {
+        let indent = "    ".repeat(indent_level);
+        let value = self.subgraph_outputs(node)?;
+        let mut code = self.take_pending_bindings(&indent);
+        let statement = match value {
+            Some(value) => format!("{}return {};\n", indent, value),
+            None => format!("{}return;\n", indent),
+        };
+        code.push_str(&self.with_order_comment(node, statement));
+        Ok(code)
+    }
+
+    /// Values returned through a sub-graph's outputs node, as one expression
+    fn subgraph_outputs(&self, node: &NodeInstance) -> Result<Option<String>, CompileError> {
+        let values = node.inputs
+            .iter()
+            .filter(|pin| !matches!(pin.pin.data_type, graphy::DataType::Execution))
+            .map(|pin| self.generate_input_expression(&node.id, &pin.id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(match values.as_slice() {
+            [] => None,
+            [value] => Some(value.clone()),
+            _ => Some(format!("({})", values.join(", "))),
+        })
+    }
+
+    /// Emit an event's function under a given name, visibility and parameter list
+    ///
+    /// With a context, the function takes it as its only parameter and `params`
+    /// are read from its fields.
+    fn emit_event_function(
+        &self,
+        event_node: &NodeInstance,
+        name: &str,
+        public: bool,
+        params: Vec<EventParam>,
+        context: Option<&EventContext>,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let mut code = String::new();
+        let indent = "    ".repeat(indent_level);
+
+        // Parameters as declared, and the expression each event output reads as
+        let (declared, bindings): (NamedExprs, NamedExprs) = match context {
+            Some(context) => {
+                let param = self.options.context_param.as_ref().map_or("ctx", |spec| spec.param.as_str());
+                let bindings = params
+                    .iter()
+                    .map(|event_param| {
+                        let pin_name = event_node.outputs
+                            .iter()
+                            .find(|pin| pin.id == event_param.pin_id)
+                            .map_or(event_param.name.as_str(), |pin| pin.pin.name.as_str());
+                        let field = context.fields.get(pin_name).map_or(event_param.name.as_str(), String::as_str);
+                        let read = if is_copy_type(&event_param.ty) {
+                            format!("{}.{}", param, field)
+                        } else {
+                            format!("{}.{}.clone()", param, field)
+                        };
+                        (event_param.pin_id.clone(), read)
+                    })
+                    .collect();
+                (vec![(param.to_string(), format!("&{}", context.ty))], bindings)
+            }
+            None => (
+                params.iter().map(|param| (param.name.clone(), param.ty.clone())).collect(),
+                params.iter().map(|param| (param.pin_id.clone(), param.name.clone())).collect(),
+            ),
+        };
+
+        // Generate function signature
+        let receiver = match self.variable_storage() {
+            VariableStorage::StructField => Some("&mut self".to_string()),
+            VariableStorage::ThreadLocal | VariableStorage::Environment => None,
+        };
+        let signature: Vec<String> = receiver
+            .into_iter()
+            .chain(declared.iter().map(|(name, ty)| format!("{}: {}", name, ty)))
+            .collect();
+        let is_async = self.metadata_provider.attributes(&event_node.node_type).is_async;
+        let c_export = public && self.options.c_exports.contains(name);
+        let declaration = self.runtime().event_signature(&EventSignature {
+            name,
+            params: &signature.join(", "),
+            public,
+            is_async,
+            abi: c_export.then_some("C"),
+        });
+        if public && self.options.wasm_exports.contains(name) {
+            code.push_str(&format!("{}#[wasm_bindgen]\n", indent));
+        }
+        if c_export {
+            code.push_str(&self.c_export_attributes(name, is_async, &declared, &indent)?);
+        }
+        for line in declaration.lines() {
+            code.push_str(&format!("{}{}\n", indent, line));
+        }
+        code.pop();
+        code.push_str(" {\n");
+
+        self.functions.borrow_mut().push(GeneratedFunction {
+            name: name.to_string(),
+            params: declared.clone(),
+            return_type: None,
+        });
+
+        // With spans, the body runs inside the event's span: entered, or instrumenting an async block
+        let body_indent = "    ".repeat(indent_level + 1);
+        let mut body_level = indent_level + 1;
+        let mut epilogue = String::new();
+        if self.options.emit_spans {
+            let fields: String = declared.iter().map(|(name, _)| format!(", {} = ?{}", name, name)).collect();
+            let span = format!("tracing::info_span!({:?}{})", name, fields);
+            if is_async {
+                code.push_str(&format!("{}let span = {};\n", body_indent, span));
+                code.push_str(&format!("{}tracing::Instrument::instrument(async move {{\n", body_indent));
+                body_level += 1;
+                epilogue = format!("{}}}, span)\n{}.await;\n", body_indent, body_indent);
+            } else {
+                code.push_str(&format!("{}let _span = {}.entered();\n", body_indent, span));
+            }
+        }
+
+        let body = self.generate_event_body(event_node, &bindings, is_async, body_level)
+            .map_err(|err| self.preceded_by(&code, err))?;
+        code.push_str(&body);
+        code.push_str(&epilogue);
+        code.push_str(&format!("{}}}\n", indent));
+
+        Ok(code)
+    }
+
+    /// Doc comment with the C prototype and `#[no_mangle]` for an event exported with the C ABI
+    fn c_export_attributes(
+        &self,
+        name: &str,
+        is_async: bool,
+        params: &[(String, String)],
+        indent: &str,
+    ) -> Result<String, CompileError> {
+        let unsupported = |construct: String| CompileError::UnsupportedTarget {
+            construct,
+            target: "extern \"C\" exports".to_string(),
+        };
+        if is_async {
+            return Err(unsupported(format!("async event '{}'", name)));
+        }
+        if let Some((param, ty)) = params.iter().find(|(_, ty)| c_type(ty).is_none()) {
+            return Err(unsupported(format!("parameter '{}: {}' of event '{}'", param, ty, name)));
+        }
+        let prototype = c_declaration(name, params).expect("parameter types are FFI-safe");
+
+        // Edition 2024 makes `no_mangle` an unsafe attribute
+        let no_mangle = match self.target().edition.as_deref() {
+            Some(edition) if edition >= "2024" => "#[unsafe(no_mangle)]",
+            _ => "#[no_mangle]",
+        };
+        Ok(format!("{indent}/// C: `{}`\n{indent}{}\n", prototype, no_mangle, indent = indent))
+    }
+
+    /// Generate the statements of an event's body, at `indent_level`
+    ///
+    /// `bindings` are the expressions the event's outputs read as, by pin ID.
+    fn generate_event_body(
+        &self,
+        event_node: &NodeInstance,
+        bindings: &[(String, String)],
+        is_async: bool,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let mut code = String::new();
+        let exec_order = Rc::new(RefCell::new(HashMap::new()));
+
+        // Find execution output pins and follow them
+        // We need to look up by pin ID (from the node instance), not pin name (from metadata)
+        for output_pin in &event_node.outputs {
+            if matches!(output_pin.pin.data_type, graphy::DataType::Execution) {
+                tracing::debug!("[CODEGEN] Looking up exec connections for node {} pin ID: {}", 
+                    event_node.id, output_pin.id);
+                
+                let connected = self.exec_routing.get_connected_nodes(&event_node.id, &output_pin.id);
+                
+                tracing::debug!("[CODEGEN] Found {} connected nodes", connected.len());
+                
+                for next_node_id in connected {
+                    if let Some(next_node) = self.graph.nodes.get(next_node_id) {
+                        let mut generator = self.clone_with_new_visited();
+                        generator.exec_order = exec_order.clone();
+                        generator.in_async = is_async;
+                        generator.in_method = self.variable_storage() == VariableStorage::StructField;
+                        for (pin_id, expr) in bindings {
+                            generator.bind_scope_value(&event_node.id, pin_id, expr.clone());
+                        }
+                        let node_code = generator.generate_exec_chain(next_node, indent_level)
+                            .map_err(|err| self.preceded_by(&code, err))?;
+                        code.push_str(&node_code);
                     }
-                } else {
-                    Err(GraphyError::NodeNotFound(node_id.to_string()))
                 }
             }
-            None => Err(GraphyError::Custom(format!("No data source for input: {}.{}", node_id, pin_id))),
         }
+
+        Ok(code)
     }
 
-    /// Generate inlined expression for a pure node
-    fn generate_pure_node_expression(&self, node: &NodeInstance) -> Result<String, GraphyError> {
-        let node_meta = self.metadata_provider
-            .get_node_metadata(&node.node_type)
-            .ok_or_else(|| GraphyError::NodeNotFound(node.node_type.clone()))?;
+    /// Generate the builder returning one closure per event, per `CompileOptions::closures`
+    fn generate_closure_builder(&self, event_nodes: &[&NodeInstance], target: &ClosureTarget) -> Result<String, CompileError> {
+        let map_type = "std::collections::HashMap<&'static str, Box<dyn FnMut()>>";
+        let mut code = format!(
+            "pub fn {}(env: std::rc::Rc<std::cell::RefCell<{}>>) -> {} {{\n",
+            target.builder, target.env_type, map_type
+        );
+        code.push_str(&format!("    let mut events: {} = std::collections::HashMap::new();\n", map_type));
 
-        // Recursively generate arguments
-        let mut args = Vec::new();
-        for param in &node_meta.params {
-            // Find the actual pin ID from the node instance
-            let pin_id = node.inputs.iter()
-                .find(|input| input.pin.name == param.name)
-                .map(|input| input.id.clone())
-                .ok_or_else(|| GraphyError::Custom(
-                    format!("Input pin not found for parameter '{}' on node '{}'", param.name, node.id)
-                ))?;
+        for event_node in event_nodes {
+            let metadata = self.node_metadata(event_node)?;
+            let unsupported = if !self.event_params(event_node).is_empty() {
+                Some("events with parameters")
+            } else if self.reaches_latent_node(event_node) {
+                Some("events with latent nodes")
+            } else if self.metadata_provider.attributes(&event_node.node_type).is_async {
+                Some("async events")
+            } else {
+                None
+            };
+            if let Some(construct) = unsupported {
+                return Err(CompileError::UnsupportedTarget {
+                    construct: format!("{} ('{}')", construct, metadata.name),
+                    target: "event closures".to_string(),
+                });
+            }
 
-            let arg_expr = self.generate_input_expression(&node.id, &pin_id)?;
-            args.push(arg_expr);
+            let mut closure = String::from("    {\n");
+            closure.push_str("        let env = env.clone();\n");
+            closure.push_str(&format!("        events.insert({:?}, Box::new(move || {{\n", metadata.name));
+            closure.push_str("            let mut env = env.borrow_mut();\n");
+            closure.push_str("            let env = &mut *env;\n");
+            let body = self.generate_event_body(event_node, &[], false, 3)
+                .map_err(|err| self.preceded_by(&format!("{}{}", code, closure), err))?;
+            closure.push_str(&body);
+            closure.push_str("        }));\n");
+            closure.push_str("    }\n");
+            code.push_str(&closure);
         }
 
-        Ok(format!("{}({})", node_meta.name, args.join(", ")))
+        code.push_str("    events\n");
+        code.push_str("}\n");
+        Ok(code)
     }
 
-    /// Clone with new visited set
-    fn clone_with_new_visited(&self) -> Self {
-        Self {
-            graph: self.graph,
-            metadata_provider: self.metadata_provider,
-            data_resolver: self.data_resolver,
-            exec_routing: self.exec_routing,
-            variables: self.variables.clone(),
-            visited: HashSet::new(),
+    /// Parameters of an event function, one per typed data output of the event
+    ///
+    /// Outputs whose type can't be determined are skipped.
+    pub(super) fn event_params(&self, event_node: &NodeInstance) -> Vec<EventParam> {
+        event_node.outputs
+            .iter()
+            .filter(|pin| !matches!(pin.pin.data_type, graphy::DataType::Execution))
+            .filter_map(|pin| {
+                let ty = self.types
+                    .pin_type(&event_node.id, &pin.id)
+                    .map(str::to_string)
+                    .or_else(|| super::types::declared_type(&pin.pin.data_type))?;
+                Some(EventParam {
+                    pin_id: pin.id.clone(),
+                    name: sanitize_identifier(&pin.pin.name),
+                    ty,
+                })
+            })
+            .collect()
+    }
+
+    /// Context an event reads its inputs from, per `CompileOptions::context_param`
+    fn event_context(&self, event_node: &NodeInstance) -> Option<&'a EventContext> {
+        let spec = self.options.context_param.as_ref()?;
+        let name = &self.metadata_provider.get_node_metadata(&event_node.node_type)?.name;
+        spec.events.get(name)
+    }
+
+    /// Make a node output read as `expr` for everything this generator emits
+    pub(super) fn bind_scope_value(&mut self, node_id: &str, pin_id: &str, expr: String) {
+        self.scope_bindings.insert((node_id.to_string(), pin_id.to_string()), expr);
+    }
+
+    /// Whether any node reachable through exec flow from `node` is latent
+    fn reaches_latent_node(&self, node: &NodeInstance) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = self.exec_successors(node);
+
+        while let Some(next) = stack.pop() {
+            if !seen.insert(next.id.clone()) {
+                continue;
+            }
+            if self.is_latent(next) {
+                return true;
+            }
+            stack.extend(self.exec_successors(next));
         }
+
+        false
     }
-}
 
-/// Check if a type is Copy (uses Cell) or not (uses RefCell)
-fn is_copy_type(type_str: &str) -> bool {
-    matches!(
-        type_str,
-        "i32" | "i64" | "u32" | "u64" | "f32" | "f64" | "bool" | "char" |
-        "usize" | "isize" | "i8" | "i16" | "u8" | "u16"
-    )
-}
+    /// Whether a node suspends its event until it completes
+    pub(super) fn is_latent(&self, node: &NodeInstance) -> bool {
+        self.metadata_provider.attributes(&node.node_type).latent
+    }
 
-/// Get default value for a data type
-fn get_default_value(data_type: &graphy::DataType) -> String {
-    use graphy::DataType;
+    /// Whether any node reads a node's result
+    pub(super) fn is_read(&self, node_id: &str) -> bool {
+        self.consumer_counts.get(node_id).copied().unwrap_or(0) > 0
+    }
 
-    match data_type {
-        DataType::Execution => "()".to_string(),
-        DataType::Typed(type_info) => {
-            graphy::utils::get_default_value_for_type(&type_info.type_string)
+    /// Type of the value bound to a node's result variable or pure binding, looked up by the local's name
+    pub(super) fn binding_type(&self, name: &str) -> Option<String> {
+        let (node_id, _) = self.result_variables
+            .iter()
+            .chain(self.pure_binding_names.iter())
+            .find(|(_, local)| *local == name)?;
+        let node = self.graph.nodes.get(node_id)?;
+
+        self.types
+            .output_type(node)
+            .map(str::to_string)
+            .or_else(|| Some(self.node_metadata(node).ok()?.return_type.as_ref()?.type_string.clone()))
+    }
+
+    /// Nodes connected to any of a node's exec outputs, in pin order
+    pub(super) fn exec_successors(&self, node: &NodeInstance) -> Vec<&'a NodeInstance> {
+        node.outputs
+            .iter()
+            .filter(|pin| matches!(pin.pin.data_type, graphy::DataType::Execution))
+            .flat_map(|pin| self.exec_routing.get_connected_nodes(&node.id, &pin.id))
+            .filter_map(|id| self.graph.nodes.get(id))
+            .collect()
+    }
+
+    /// Continue the enclosing exec chain with each of a node's exec successors, in order
+    fn queue_exec_successors(&mut self, node: &NodeInstance) {
+        let successors = self.exec_successors(node);
+        self.exec_stack.extend(successors.into_iter().rev());
+    }
+
+    /// Path a node's function is called through
+    ///
+    /// Fails for a method node outside the methods of a Blueprint class.
+    pub(super) fn symbol(&self, node_meta: &graphy::core::NodeMetadata) -> Result<String, CompileError> {
+        if self.metadata_provider.attributes(&node_meta.name).self_method {
+            if !self.in_method {
+                return Err(CompileError::UnsupportedTarget {
+                    construct: format!("method node '{}'", node_meta.name),
+                    target: "a function without a `self` receiver".to_string(),
+                });
+            }
+            return Ok(format!("self.{}", node_meta.name));
         }
-        DataType::Number => "0.0".to_string(),
-        DataType::String => "String::new()".to_string(),
-        DataType::Boolean => "false".to_string(),
-        DataType::Vector2 => "(0.0, 0.0)".to_string(),
-        DataType::Vector3 => "(0.0, 0.0, 0.0)".to_string(),
-        DataType::Color => "(0.0, 0.0, 0.0, 1.0)".to_string(),
-        DataType::Any => "Default::default()".to_string(),
+
+        let symbol = self.metadata_provider.symbol(node_meta);
+        match &self.options.closures {
+            Some(closures) if closures.route_calls => Ok(format!("env.{}", symbol)),
+            _ => Ok(symbol),
+        }
+    }
+
+    /// Name of the macro print nodes are emitted as, without the `!`
+    fn print_macro(&self) -> &str {
+        match self.options.print_macro.trim().trim_end_matches('!') {
+            "" => "println",
+            name => name,
+        }
+    }
+
+    /// Metadata of a node's type
+    pub(super) fn node_metadata(&self, node: &NodeInstance) -> Result<&'a graphy::core::NodeMetadata, CompileError> {
+        self.metadata_provider
+            .get_node_metadata(&node.node_type)
+            .ok_or_else(|| GraphyError::NodeNotFound(node.node_type.clone()).into())
+    }
+
+    /// Generate execution chain starting from a node
+    ///
+    /// Straight-line successors are queued on a work stack instead of being
+    /// recursed into, so long sequential chains don't grow the call stack.
+    /// Only control-flow branches recurse, bounded by their nesting depth.
+    pub(super) fn generate_exec_chain(&mut self, node: &'a NodeInstance, indent_level: usize) -> Result<String, CompileError> {
+        let base = self.exec_stack.len();
+        self.exec_stack.push(node);
+        self.drain_exec_stack(base, indent_level)
+    }
+
+    /// Generate queued exec nodes until the stack is back down to `base`
+    fn drain_exec_stack(&mut self, base: usize, indent_level: usize) -> Result<String, CompileError> {
+        let mut code = String::new();
+        while self.exec_stack.len() > base {
+            let next = self.exec_stack.pop().expect("stack is above its base");
+            match self.generate_exec_node(next, indent_level) {
+                Ok(node_code) => code.push_str(&node_code),
+                Err(err) => {
+                    self.exec_stack.truncate(base);
+                    return Err(self.preceded_by(&code, err));
+                }
+            }
+        }
+        Ok(code)
+    }
+
+    /// Generate a single exec node, queueing its successors if it has no branches
+    fn generate_exec_node(&mut self, node: &NodeInstance, indent_level: usize) -> Result<String, CompileError> {
+        let mut code = String::new();
+
+        // Prevent infinite loops
+        if self.visited.contains(&node.id) {
+            return Ok(code);
+        }
+        self.visited.insert(node.id.clone());
+        self.count_expansion()?;
+
+        // Check if this is a variable getter or setter
+        if node.node_type.starts_with("get_") {
+            // Getter nodes are pure (no exec chain), skip
+            self.report(
+                Diagnostic::warning(format!(
+                    "Execution flows into getter node '{}', which has no exec semantics and will never run",
+                    node.id
+                ))
+                .with_node(node),
+            );
+            return Ok(code);
+        }
+
+        if self.options.emit_order_comments {
+            let mut exec_order = self.exec_order.borrow_mut();
+            let index = exec_order.len() + 1;
+            exec_order.insert(node.id.clone(), index);
+        }
+
+        if node.node_type.starts_with("set_") {
+            // Setter nodes have exec chain
+            return self.generate_setter_node(node, indent_level);
+        } else if let Some(event_node) = self.called_event(node) {
+            // Calls to another event are real calls, so recursion works at runtime
+            return self.generate_event_call_node(node, event_node, indent_level);
+        } else if node.node_type == SUBGRAPH_OUTPUTS {
+            return self.generate_subgraph_return(node, indent_level);
+        }
+
+        let node_meta = self.metadata_provider
+            .get_node_metadata(&node.node_type)
+            .ok_or_else(|| GraphyError::NodeNotFound(node.node_type.clone()))?;
+
+        let attributes = self.metadata_provider.attributes(&node.node_type);
+        if let Some(predicate) = &attributes.cfg {
+            let gateable = matches!(node_meta.node_type, NodeTypes::fn_ | NodeTypes::control_flow) && !attributes.latent;
+            if gateable {
+                return self.generate_gated_node(node, node_meta, predicate, indent_level);
+            }
+        }
+
+        match node_meta.node_type {
+            NodeTypes::pure => {
+                // Pure nodes are pre-evaluated, skip in exec chain
+                self.report(
+                    Diagnostic::warning(format!(
+                        "Execution flows into pure node '{}' ({}), which has no exec semantics and will never run",
+                        node.id, node.node_type
+                    ))
+                    .with_node(node),
+                );
+                Ok(code)
+            }
+            NodeTypes::fn_ if self.metadata_provider.attributes(&node.node_type).latent => {
+                // The state machine splits the event at latent nodes on its main path only
+                Err(GraphyError::CodeGeneration(format!(
+                    "Latent node '{}' ({}) must be on its event's main exec path, not inside control flow",
+                    node.id, node.node_type
+                )).into())
+            }
+            NodeTypes::fn_ => {
+                self.generate_function_node(node, node_meta, indent_level)
+            }
+            NodeTypes::control_flow => {
+                self.generate_control_flow_node(node, node_meta, indent_level)
+            }
+            NodeTypes::event => {
+                // Event nodes define the outer function, skip in exec chain
+                Ok(code)
+            }
+        }
+    }
+
+    /// Emit a `cfg`-gated node and the rest of its chain in a `#[cfg(..)]` block
+    fn generate_gated_node(
+        &self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+        predicate: &str,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let indent = "    ".repeat(indent_level);
+        let mut gated = self.fork(self.visited.clone());
+
+        let mut code = format!("{}#[cfg({})]\n{}{{\n", indent, predicate, indent);
+        code.push_str(&match node_meta.node_type {
+            NodeTypes::control_flow => gated.generate_control_flow_node(node, node_meta, indent_level + 1)?,
+            _ => gated.generate_function_node(node, node_meta, indent_level + 1)?,
+        });
+        code.push_str(&gated.drain_exec_stack(0, indent_level + 1)?);
+        code.push_str(&format!("{}}}\n", indent));
+
+        Ok(code)
+    }
+
+    /// The event a `call_<event>` node invokes, if it is one
+    ///
+    /// Library nodes that happen to start with `call_` take precedence.
+    fn called_event(&self, node: &NodeInstance) -> Option<&'a NodeInstance> {
+        if self.metadata_provider.get_node_metadata(&node.node_type).is_some() {
+            return None;
+        }
+        let event_type = node.node_type.strip_prefix("call_")?;
+
+        self.graph.nodes
+            .values()
+            .filter(|candidate| {
+                candidate.node_type == event_type
+                    && self.metadata_provider
+                        .get_node_metadata(&candidate.node_type)
+                        .is_some_and(|meta| meta.node_type == NodeTypes::event)
+            })
+            .min_by(|a, b| a.id.cmp(&b.id))
+    }
+
+    /// Generate a call to another event's function
+    ///
+    /// The call node's data inputs are matched to the event's parameters by
+    /// pin name. The event is never inlined, so an event may (transitively)
+    /// call itself.
+    fn generate_event_call_node(
+        &mut self,
+        node: &NodeInstance,
+        event_node: &NodeInstance,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let mut code = String::new();
+        let indent = "    ".repeat(indent_level);
+        let event_meta = self.node_metadata(event_node)?;
+        if self.event_context(event_node).is_some() {
+            return Err(GraphyError::CodeGeneration(format!(
+                "Event '{}' takes a context parameter, so it can't be called from node '{}'",
+                event_meta.name, node.id
+            )).into());
+        }
+
+        let mut args = Vec::new();
+        for param in self.event_params(event_node) {
+            let event_pin = event_node.outputs.iter().find(|pin| pin.id == param.pin_id);
+            let input = node.inputs
+                .iter()
+                .find(|input| Some(&input.pin.name) == event_pin.map(|pin| &pin.pin.name))
+                .ok_or_else(|| CompileError::MissingEventArgument {
+                    node_id: node.id.clone(),
+                    param: param.name.clone(),
+                    event: event_meta.name.clone(),
+                })?;
+            args.push(self.generate_input_expression(&node.id, &input.id)?);
+        }
+
+        code.push_str(&self.trace_statement(node, &indent));
+        code.push_str(&self.take_pending_bindings(&indent));
+
+        let callee = match self.variable_storage() {
+            VariableStorage::StructField => format!("self.{}", event_meta.name),
+            VariableStorage::ThreadLocal => event_meta.name.clone(),
+            VariableStorage::Environment => {
+                return Err(CompileError::UnsupportedTarget {
+                    construct: format!("calls to event '{}'", event_meta.name),
+                    target: "event closures".to_string(),
+                });
+            }
+        };
+        let call = format!("{}({})", callee, args.join(", "));
+        let statement = if !self.metadata_provider.attributes(&event_node.node_type).is_async {
+            format!("{}{};\n", indent, call)
+        } else if self.in_async {
+            format!("{}{}.await;\n", indent, call)
+        } else {
+            let body = format!("{}.await;", call);
+            format!("{}{}\n", indent, self.runtime().async_block(&body))
+        };
+        code.push_str(&self.with_order_comment(node, statement));
+
+        // Follow execution chain
+        if self.follow_exec {
+            self.queue_exec_successors(node);
+        }
+
+        Ok(code)
+    }
+
+    /// Generate code for a function node
+    fn generate_function_node(
+        &mut self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let mut code = String::new();
+        let indent = "    ".repeat(indent_level);
+
+        code.push_str(&self.trace_statement(node, &indent));
+
+        // Collect arguments
+        let attributes = self.metadata_provider.attributes(&node.node_type);
+        let borrows = if attributes.body_template.is_none() && attributes.print_args.is_none() {
+            self.borrowable_getters(node, node_meta)
+        } else {
+            HashMap::new()
+        };
+        let args = self.collect_arguments_borrowing(node, node_meta, &borrows)?;
+        code.push_str(&self.take_pending_bindings(&indent));
+        if self.options.emit_debug_asserts {
+            code.push_str(&self.debug_assertions(node, node_meta, &args, &borrows, &indent));
+        }
+
+        // Check if this function returns a value
+        let has_return = returns_value(node_meta);
+        let call = self.wrap_borrows(
+            format!("{}({})", self.symbol(node_meta)?, args.join(", ")),
+            &borrows,
+        );
+
+        if let Some(branches) = &attributes.fallible {
+            // Result-returning node: the match arms are its success/failure chains
+            let statement = self.generate_fallible_match(node, branches, &call, indent_level)?;
+            code.push_str(&self.with_order_comment(node, statement));
+            return Ok(code);
+        } else if !attributes.exits.is_empty() {
+            // Multi-exit node: one match arm per variant of its enum
+            let statement = self.generate_exit_match(node, &attributes.exits, &call, indent_level)?;
+            code.push_str(&self.with_order_comment(node, statement));
+            return Ok(code);
+        } else if let Some(print_args) = &attributes.print_args {
+            // Print node: its arguments go to the configured print macro
+            let values: HashMap<String, String> = node_meta.params
+                .iter()
+                .map(|param| param.name.clone())
+                .zip(args)
+                .collect();
+            let known: Vec<&str> = values.keys().map(String::as_str).collect();
+            if let Some(placeholder) = unknown_placeholder(print_args, &known) {
+                return Err(CompileError::UnsubstitutedPlaceholder {
+                    node_type: node.node_type.clone(),
+                    placeholder: placeholder.to_string(),
+                });
+            }
+            let statement = format!("{}{}!({});\n", indent, self.print_macro(), substitute_placeholders(print_args, &values));
+            code.push_str(&self.with_order_comment(node, statement));
+        } else if let Some(template) = &attributes.body_template {
+            // Macro-style node: expand its statement template
+            self.check_body_template(node, node_meta, template, has_return)?;
+            let mut values: HashMap<String, String> = node_meta.params
+                .iter()
+                .map(|param| param.name.clone())
+                .zip(args)
+                .collect();
+            if has_return {
+                let result_var = self
+                    .result_variable(&node.id)
+                    .ok_or_else(|| CompileError::MissingResultVariable { node_id: node.id.clone() })?;
+                values.insert("result".to_string(), result_var.clone());
+            }
+
+            let statements: String = substitute_placeholders(template, &values)
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| format!("{}{}\n", indent, line.trim_end()))
+                .collect();
+            code.push_str(&self.with_order_comment(node, statements));
+        } else if has_return {
+            // Store result in variable
+            let result_var = self
+                .result_variable(&node.id)
+                .ok_or_else(|| CompileError::MissingResultVariable { node_id: node.id.clone() })?;
+
+            let annotation = self.result_type_annotation(node, node_meta)
+                .map(|ty| format!(": {}", self.aliased(ty)))
+                .unwrap_or_default();
+
+            let statement = format!("{}let {}{} = {};\n", indent, result_var, annotation, call);
+            code.push_str(&self.with_order_comment(node, statement));
+        } else {
+            // Just call the function
+            code.push_str(&self.with_order_comment(node, format!("{}{};\n", indent, call)));
+        }
+
+        // Follow execution chain
+        if self.follow_exec {
+            self.queue_exec_successors(node);
+        }
+
+        Ok(code)
+    }
+
+    /// Check that a body template's placeholders match the node's parameters and result
+    fn check_body_template(
+        &self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+        template: &str,
+        has_return: bool,
+    ) -> Result<(), CompileError> {
+        let mut known: Vec<&str> = node_meta.params.iter().map(|param| param.name.as_str()).collect();
+        if has_return {
+            known.push("result");
+        }
+        if let Some(placeholder) = unknown_placeholder(template, &known) {
+            return Err(CompileError::UnsubstitutedPlaceholder {
+                node_type: node.node_type.clone(),
+                placeholder: placeholder.to_string(),
+            });
+        }
+
+        let result_read = self.consumer_counts.get(&node.id).copied().unwrap_or(0) > 0;
+        if has_return && result_read && !uses_placeholder(template, "result") {
+            return Err(CompileError::MissingPlaceholder {
+                node_type: node.node_type.clone(),
+                placeholder: "result".to_string(),
+            });
+        }
+
+        for param in node_meta.params.iter().filter(|param| !uses_placeholder(template, &param.name)) {
+            self.report(
+                Diagnostic::warning(format!(
+                    "Template of node type '{}' doesn't use '{{{}}}', so its input is never evaluated",
+                    node.node_type, param.name
+                ))
+                .with_node(node),
+            );
+        }
+        Ok(())
+    }
+
+    /// `debug_assert!`s for a node's declared preconditions, over its call arguments
+    fn debug_assertions(
+        &self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+        args: &[String],
+        borrows: &HashMap<String, String>,
+        indent: &str,
+    ) -> String {
+        let values: HashMap<String, String> = node_meta.params
+            .iter()
+            .map(|param| param.name.clone())
+            .zip(args.iter().cloned())
+            .collect();
+
+        let mut code = String::new();
+        for condition in &self.metadata_provider.attributes(&node.node_type).debug_assertions {
+            let message = format!(
+                "{:?}",
+                format!("{} node '{}': precondition `{}` failed", node.node_type, node.id, condition)
+            )
+            .replace('{', "{{")
+            .replace('}', "}}");
+            let check = format!("debug_assert!({}, {})", substitute_placeholders(condition, &values), message);
+            code.push_str(&format!("{}{};\n", indent, self.wrap_borrows(check, borrows)));
+        }
+        code
+    }
+
+    /// Match a fallible node's `Result`, running its success or failure chain
+    fn generate_fallible_match(
+        &self,
+        node: &NodeInstance,
+        branches: &FallibleBranches,
+        call: &str,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let indent = "    ".repeat(indent_level);
+        let node_ident = sanitize_identifier(&node.id);
+
+        let mut code = format!("{}match {} {{\n", indent, call);
+        let arms = [
+            ("Ok", &branches.success_exec, &branches.value_pin, format!("{}_value", node_ident)),
+            ("Err", &branches.failure_exec, &branches.error_pin, format!("{}_error", node_ident)),
+        ];
+
+        for (variant, exec_output, data_pin, binding) in arms {
+            let handled = node.outputs.iter().any(|pin| {
+                matches!(pin.pin.data_type, graphy::DataType::Execution)
+                    && &pin.pin.name == exec_output
+                    && !self.exec_routing.get_connected_nodes(&node.id, &pin.id).is_empty()
+            });
+            if variant == "Err" && !handled {
+                code.push_str(&format!("{}    {}\n", indent, self.unhandled_error_arm(node)));
+                continue;
+            }
+
+            code.push_str(&self.generate_exit_arm(node, variant, exec_output, Some(data_pin), binding, indent_level)?);
+        }
+
+        code.push_str(&format!("{}}}\n", indent));
+        Ok(code)
+    }
+
+    /// Match a multi-exit node's enum, running the chain of the variant returned
+    fn generate_exit_match(
+        &self,
+        node: &NodeInstance,
+        exits: &[ExitVariant],
+        call: &str,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let indent = "    ".repeat(indent_level);
+        let node_ident = sanitize_identifier(&node.id);
+
+        let mut code = format!("{}match {} {{\n", indent, call);
+        for exit in exits {
+            let binding = format!("{}_{}", node_ident, sanitize_identifier(exit.payload_pin.as_deref().unwrap_or("value")));
+            code.push_str(&self.generate_exit_arm(
+                node,
+                &exit.variant,
+                &exit.exec_output,
+                exit.payload_pin.as_ref(),
+                binding,
+                indent_level,
+            )?);
+        }
+        code.push_str(&format!("{}}}\n", indent));
+        Ok(code)
+    }
+
+    /// One arm of a matched node result: `variant(payload) => { exec chain }`
+    ///
+    /// With no `data_pin` the variant is matched without a payload.
+    fn generate_exit_arm(
+        &self,
+        node: &NodeInstance,
+        variant: &str,
+        exec_output: &str,
+        data_pin: Option<&String>,
+        binding: String,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let indent = "    ".repeat(indent_level);
+        let mut arm_gen = self.fork(self.visited.clone());
+        arm_gen.follow_exec = true;
+
+        // Name the payload only if something reads it, to avoid unused-variable warnings
+        let data_output = data_pin.and_then(|data_pin| node.outputs.iter().find(|pin| &pin.pin.name == data_pin));
+        let used = data_output.is_some_and(|pin| {
+            self.graph.connections.iter().any(|c| c.source_node == node.id && c.source_pin == pin.id)
+        });
+        let pattern = match data_output {
+            Some(pin) if used => {
+                arm_gen.bind_scope_value(&node.id, &pin.id, binding.clone());
+                format!("{}({})", variant, binding)
+            }
+            _ if data_pin.is_some() => format!("{}(_)", variant),
+            _ => variant.to_string(),
+        };
+
+        let mut code = format!("{}    {} => {{\n", indent, pattern);
+        let exec_pin = node.outputs.iter().find(|pin| {
+            matches!(pin.pin.data_type, graphy::DataType::Execution) && pin.pin.name == exec_output
+        });
+        if let Some(exec_pin) = exec_pin {
+            for next_node_id in self.exec_routing.get_connected_nodes(&node.id, &exec_pin.id) {
+                if let Some(next_node) = self.graph.nodes.get(next_node_id) {
+                    code.push_str(&arm_gen.generate_exec_chain(next_node, indent_level + 2)?);
+                }
+            }
+        }
+        code.push_str(&format!("{}    }}\n", indent));
+        Ok(code)
+    }
+
+    /// `Err` arm for a fallible node whose failure branch is unconnected, per `CompileOptions::error_policy`
+    fn unhandled_error_arm(&self, node: &NodeInstance) -> String {
+        let message = format!("node {} failed: {{:?}}", node.id.replace('{', "{{").replace('}', "}}"));
+        match &self.options.error_policy {
+            ErrorPolicy::Propagate | ErrorPolicy::Panic => format!("Err(err) => panic!({:?}, err),", message),
+            ErrorPolicy::LogAndContinue => format!("Err(err) => tracing::error!({:?}, err),", message),
+            ErrorPolicy::Hook(hook) => format!("Err(err) => {}({:?}, err),", hook, node.id),
+        }
+    }
+
+    /// Type annotation for a node's result binding, if one is needed
+    ///
+    /// Results are annotated when `annotate_result_types` is set, and always when
+    /// the declared return type mentions a type parameter that was resolved from
+    /// the node's connections (rustc often can't infer those on its own).
+    fn result_type_annotation(
+        &self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+    ) -> Option<String> {
+        let declared = node_meta.return_type.as_ref()?.type_string.clone();
+        let resolved = self.types.output_type(node).map(|ty| ty.to_string());
+
+        let generic_params = super::types::generic_params(&node_meta.function_source);
+
+        if super::types::is_generic(&declared, &generic_params) {
+            // Only annotate once the parameter has actually been monomorphized
+            return resolved.filter(|ty| *ty != declared);
+        }
+
+        if self.options.annotate_result_types {
+            return Some(resolved.unwrap_or(declared));
+        }
+
+        None
+    }
+
+    /// Generate code for a control flow node
+    fn generate_control_flow_node(
+        &mut self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+        indent_level: usize,
+    ) -> Result<String, CompileError> {
+        let mut code = String::new();
+        let indent = "    ".repeat(indent_level);
+
+        // A loop re-evaluates its inputs on every iteration, so nothing it
+        // reads may be hoisted into a local computed once before it
+        let is_loop = is_loop_template(&node_meta.function_source);
+
+        // Variables read several times in this scope are read once up front
+        if !is_loop {
+            self.hoist_repeated_getters(node)?;
+        }
+
+        let attributes = self.metadata_provider.attributes(&node.node_type);
+
+        // Build parameter substitutions. These are resolved before the
+        // branches so that any bindings they introduce are in scope for the
+        // branch bodies.
+        self.in_loop_header.set(is_loop);
+        let param_substitutions = self.control_flow_params(node, node_meta, attributes);
+        self.in_loop_header.set(false);
+        let param_substitutions = param_substitutions?;
+
+        // A borrow in an input can outlive the input's evaluation (`for` and
+        // `match` keep their temporaries until the end), so the branches must
+        // not write what it borrows
+        let written = self.variables_written_after(node);
+        for param in &node_meta.params {
+            let borrowed = param_substitutions.get(&param.name).and_then(|value| borrowed_variable(value));
+            if let Some(var) = borrowed.filter(|var| written.contains(*var)) {
+                return Err(CompileError::PotentialDoubleBorrow {
+                    var: var.to_string(),
+                    node_id: node.id.clone(),
+                });
+            }
+        }
+
+        let dead = self.dead_branches(node, node_meta, &param_substitutions);
+
+        // Branches that rejoin stop at the merge node, which follows the construct instead
+        let merge = if is_loop {
+            None
+        } else {
+            self.merge_point(node)
+        };
+
+        // Build exec_output replacements - need to map pin names to pin IDs
+        let mut exec_replacements = HashMap::new();
+
+        for output_pin in &node.outputs {
+            if matches!(output_pin.pin.data_type, graphy::DataType::Execution) {
+                let connected = self.exec_routing.get_connected_nodes(&node.id, &output_pin.id);
+                if self.options.prune_dead_branches && dead.contains(&output_pin.pin.name) {
+                    if !connected.is_empty() {
+                        self.report.borrow_mut().dead_branches_pruned += 1;
+                    }
+                    exec_replacements.insert(output_pin.pin.name.clone(), String::new());
+                    continue;
+                }
+
+                let mut exec_code = String::new();
+                let mut local_visited = self.visited.clone();
+                if let Some(merge) = merge {
+                    local_visited.insert(merge.id.clone());
+                }
+
+                for next_node_id in connected {
+                    if let Some(next_node) = self.graph.nodes.get(next_node_id) {
+                        let mut sub_gen = self.fork(local_visited.clone());
+                        sub_gen.follow_exec = true;
+                        sub_gen.bind_branch_outputs(node, &output_pin.pin.name);
+
+                        let next_code = sub_gen.generate_exec_chain(next_node, 0)?;
+                        exec_code.push_str(&next_code);
+                    }
+                }
+
+                // Use the pin NAME for the template substitution (e.g., "then", "else")
+                exec_replacements.insert(output_pin.pin.name.clone(), exec_code.trim().to_string());
+            }
+        }
+
+        // A placeholder no pin supplies would be left in the output verbatim
+        let known: Vec<&str> = node_meta.params
+            .iter()
+            .map(|param| param.name.as_str())
+            .chain(node.outputs.iter().filter(|pin| matches!(pin.pin.data_type, graphy::DataType::Execution)).map(|pin| pin.pin.name.as_str()))
+            .collect();
+        if let Some(placeholder) = unknown_placeholder(&node_meta.function_source, &known) {
+            return Err(CompileError::UnsubstitutedPlaceholder {
+                node_type: node.node_type.clone(),
+                placeholder: placeholder.to_string(),
+            });
+        }
+
+        // Inline the function with substitutions
+        let inlined_body = graphy::utils::inline_control_flow_function(
+            &node_meta.function_source,
+            exec_replacements,
+            param_substitutions,
+        )?;
+
+        // Unconnected branches leave empty blocks behind; constant-condition loops fold away
+        let (mut inlined_body, folded, elided) = fold_constant_loops(&remove_empty_else(&inlined_body));
+        self.report.borrow_mut().constants_folded += folded;
+
+        // A connected dead branch was already reported; anything else the loop did is lost silently
+        let dead_branch_reported = node.outputs.iter().any(|pin| {
+            dead.contains(&pin.pin.name) && !self.exec_routing.get_connected_nodes(&node.id, &pin.id).is_empty()
+        });
+        if elided > 0 && !dead_branch_reported {
+            self.report(
+                Diagnostic::warning(format!("Loop in node '{}' can never run: its condition is constant false", node.id))
+                    .with_node(node),
+            );
+        }
+
+        if attributes.switch {
+            inlined_body = add_match_fallback(&inlined_body, &self.switch_fallback_arm(node));
+        }
+
+        code.push_str(&self.trace_statement(node, &indent));
+        code.push_str(&self.take_pending_bindings(&indent));
+
+        // Add inlined code, indented by its structure whatever the template's brace style
+        code.push_str(&self.with_order_comment(node, reindent(&inlined_body, &indent)));
+
+        if let (Some(merge), true) = (merge, self.follow_exec) {
+            self.exec_stack.push(merge);
+        }
+
+        Ok(code)
+    }
+
+    /// The expression for each of a control-flow node's inputs, by parameter name
+    fn control_flow_params(
+        &self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+        attributes: &NodeAttributes,
+    ) -> Result<HashMap<String, String>, CompileError> {
+        let mut param_substitutions = HashMap::new();
+        for param in &node_meta.params {
+            // Find the actual pin ID from the node instance
+            let pin_id = node.inputs.iter()
+                .find(|input| input.pin.name == param.name)
+                .map(|input| input.id.clone())
+                .ok_or_else(|| CompileError::MissingInputPin { node_id: node.id.clone(), pin: param.name.clone() })?;
+
+            let value = match self.enum_variant_condition(&node.id, &pin_id)? {
+                Some(condition) => condition,
+                None => self.generate_input_expression(&node.id, &pin_id)?,
+            };
+            let value = if attributes.loop_collection.as_deref() == Some(param.name.as_str()) {
+                self.loop_collection_expression(&node.id, &pin_id, value, &node_meta.function_source, &param.name)
+            } else {
+                value
+            };
+            param_substitutions.insert(param.name.clone(), value);
+        }
+        Ok(param_substitutions)
+    }
+
+    /// Names of a control-flow node's exec outputs that can never run, warning about connected ones
+    ///
+    /// The template is inlined with a marker in place of each branch, and the
+    /// markers under a constant condition that excludes them are dead.
+    fn dead_branches(
+        &self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+        param_substitutions: &HashMap<String, String>,
+    ) -> HashSet<String> {
+        if !param_substitutions.values().any(|value| is_literal(value) || const_bool(value).is_some()) {
+            return HashSet::new();
+        }
+
+        let exec_outputs: Vec<&graphy::PinInstance> = node.outputs
+            .iter()
+            .filter(|pin| matches!(pin.pin.data_type, graphy::DataType::Execution))
+            .collect();
+        let markers: Vec<String> = (0..exec_outputs.len()).map(|i| format!("__pbgc_branch_{}()", i)).collect();
+        let replacements = exec_outputs
+            .iter()
+            .zip(&markers)
+            .map(|(pin, marker)| (pin.pin.name.clone(), marker.clone()))
+            .collect();
+
+        let Ok(body) = graphy::utils::inline_control_flow_function(
+            &node_meta.function_source,
+            replacements,
+            param_substitutions.clone(),
+        ) else {
+            return HashSet::new();
+        };
+
+        let mut dead = HashSet::new();
+        for index in dead_markers(&body, &markers) {
+            let pin = exec_outputs[index];
+            if !self.exec_routing.get_connected_nodes(&node.id, &pin.id).is_empty() {
+                self.report(
+                    Diagnostic::warning(format!(
+                        "Branch '{}' of node '{}' can never run: its condition is constant",
+                        pin.pin.name, node.id
+                    ))
+                    .with_node(node),
+                );
+            }
+            dead.insert(pin.pin.name.clone());
+        }
+        dead
+    }
+
+    /// The node every exec branch of a control-flow node rejoins at, if there is one
+    ///
+    /// Reconstructs the common diamond: when each exec output eventually
+    /// reaches the same node, the earliest such node (one not reachable from
+    /// any other common node) can be generated once after the construct
+    /// instead of at the end of every branch.
+    ///
+    /// An unconnected branch falls through: it does nothing, then continues
+    /// at the merge of the connected branches. Since a lone chain reaches many
+    /// nodes, the merge must then also be a join, entered by more than one
+    /// exec connection from within the construct; with a single connected
+    /// branch it is the first such join on that branch. Connections from
+    /// elsewhere in the graph (another event's chain) don't make a join.
+    /// Several candidate merges, and merges that lead back
+    /// into the node itself, leave the branches generated independently.
+    fn merge_point(&self, node: &NodeInstance) -> Option<&'a NodeInstance> {
+        let exec_outputs: Vec<_> = node.outputs
+            .iter()
+            .filter(|pin| matches!(pin.pin.data_type, graphy::DataType::Execution))
+            .collect();
+        let branches: Vec<HashSet<String>> = exec_outputs
+            .iter()
+            .map(|pin| self.exec_routing.get_connected_nodes(&node.id, &pin.id))
+            .filter(|starts| !starts.is_empty())
+            .map(|starts| self.exec_reachable(starts.iter().filter_map(|id| self.graph.nodes.get(id))))
+            .collect();
+        let falls_through = branches.len() < exec_outputs.len();
+        if exec_outputs.len() < 2 || branches.is_empty() {
+            return None;
+        }
+
+        let within: HashSet<&str> = branches
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .chain([node.id.as_str()])
+            .collect();
+        let common: HashSet<&String> = branches[0]
+            .iter()
+            .filter(|id| branches[1..].iter().all(|branch| branch.contains(*id)))
+            .filter(|id| !falls_through || self.exec_fan_in(id, &within) > 1)
+            .collect();
+        if common.contains(&node.id) {
+            return None;
+        }
+
+        let mut first = common.iter().filter(|id| {
+            !common.iter().any(|other| {
+                other != *id
+                    && self.graph.nodes
+                        .get(other.as_str())
+                        .is_some_and(|other| self.exec_reachable([other]).contains(id.as_str()))
+            })
+        });
+        match (first.next(), first.next()) {
+            (Some(id), None) if !self.visited.contains(id.as_str()) => self.graph.nodes.get(id.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Number of exec connections entering a node from one of the `sources`
+    fn exec_fan_in(&self, node_id: &str, sources: &HashSet<&str>) -> usize {
+        self.graph.connections
+            .iter()
+            .filter(|c| c.target_node == node_id && matches!(c.connection_type, graphy::ConnectionType::Execution))
+            .filter(|c| sources.contains(c.source_node.as_str()))
+            .count()
+    }
+
+    /// IDs of the exec nodes reachable from `starts`, including the starts themselves
+    fn exec_reachable(&self, starts: impl IntoIterator<Item = &'a NodeInstance>) -> HashSet<String> {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<&NodeInstance> = starts.into_iter().collect();
+        while let Some(next) = stack.pop() {
+            if reachable.insert(next.id.clone()) {
+                stack.extend(self.exec_successors(next));
+            }
+        }
+        reachable
+    }
+
+    /// Wildcard arm for a switch node, per `CompileOptions::switch_fallback`
+    fn switch_fallback_arm(&self, node: &NodeInstance) -> String {
+        let message = match &self.options.switch_fallback {
+            SwitchFallback::Ignore => return "_ => {}".to_string(),
+            SwitchFallback::Unreachable => "unhandled case in node {id}",
+            SwitchFallback::Panic(message) => message.as_str(),
+        };
+
+        // The message is plain text, not code, so `{id}` is replaced even inside quotes
+        let message = message
+            .replace("{id}", &node.id)
+            .replace('{', "{{")
+            .replace('}', "}}");
+
+        match self.options.switch_fallback {
+            SwitchFallback::Unreachable => format!("_ => unreachable!({:?}),", message),
+            _ => format!("_ => panic!({:?}),", message),
+        }
+    }
+
+    /// Iterate a loop's collection by reference if it is read again elsewhere
+    ///
+    /// A result variable with other consumers must survive the loop, so it is
+    /// borrowed; anything else (a single-use result, an inlined expression, a
+    /// cloned variable read) is consumed by the loop. The borrow is
+    /// parenthesized where the loop's template calls a method on the
+    /// collection, since `&items.iter()` would borrow the iterator instead.
+    fn loop_collection_expression(&self, node_id: &str, pin_id: &str, value: String, template: &str, param: &str) -> String {
+        use graphy::analysis::DataSource;
+
+        let Some(DataSource::Connection { source_node_id, .. }) = self.data_resolver.get_input_source(node_id, pin_id) else {
+            return value;
+        };
+        let shared = self.consumer_counts.get(source_node_id).copied().unwrap_or(0) > 1;
+        let is_result_variable = self.result_variable(source_node_id) == Some(&value)
+            || self.pure_binding_names.get(source_node_id) == Some(&value);
+
+        if !(shared && is_result_variable) {
+            return value;
+        }
+
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+        let method_call = template.match_indices(param).any(|(i, _)| {
+            !template[..i].ends_with(is_ident) && template[i + param.len()..].starts_with('.')
+        });
+        if method_call {
+            format!("(&{})", value)
+        } else {
+            format!("&{}", value)
+        }
+    }
+
+    /// Bind variables read more than once by a control-flow node and its branches to one local
+    ///
+    /// Each getter read is a separate `with`/borrow, so a condition and a
+    /// branch reading the same variable could otherwise observe different
+    /// values. Variables written by a setter inside the branches are left
+    /// alone, since the branches must see their own writes.
+    fn hoist_repeated_getters(&mut self, node: &NodeInstance) -> Result<(), CompileError> {
+        let mut reads = GetterReads::new();
+        let mut pure_reads = HashMap::new();
+        let mut written = HashSet::new();
+
+        self.collect_getter_reads(node, &mut reads, &mut pure_reads);
+
+        let mut seen = HashSet::new();
+        let mut stack = self.exec_successors(node);
+        while let Some(next) = stack.pop() {
+            if !seen.insert(next.id.clone()) {
+                continue;
+            }
+            if let Some(name) = next.node_type.strip_prefix("set_") {
+                written.insert(split_field_path(name).0.to_string());
+            }
+            self.collect_getter_reads(next, &mut reads, &mut pure_reads);
+            stack.extend(self.exec_successors(next));
+        }
+
+        let mut reads: Vec<_> = reads.into_iter().collect();
+        reads.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (name, (count, getters)) in reads {
+            let (var_name, field) = split_field_path(&name);
+            if count < 2 || written.contains(var_name) || self.const_variables.contains(var_name) {
+                continue;
+            }
+            let Some(var) = self.variables.get(var_name) else { continue };
+            if field.is_some_and(|path| var.field_type(path).is_err()) {
+                continue;
+            }
+
+            let local = format!("{}_value", sanitize_identifier(&name));
+            self.queue_binding(&node.id, &local, None, read_expression(var_name, var, field, self.variable_storage()))?;
+            self.report.borrow_mut().getters_hoisted += 1;
+
+            // Every read gets its own copy, so one consumer can't move the value out from under another
+            let copy = match field {
+                Some(path) => var.field_type(path).ok().flatten().is_some_and(is_copy_type),
+                None => is_copy_type(&var.ty),
+            };
+            let read = if copy { local } else { format!("{}.clone()", local) };
+            for key in getters {
+                self.scope_bindings.insert(key, read.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Variables written by setters reachable through a node's exec outputs
+    fn variables_written_after(&self, node: &NodeInstance) -> HashSet<String> {
+        let mut written = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut stack = self.exec_successors(node);
+        while let Some(next) = stack.pop() {
+            if !seen.insert(next.id.clone()) {
+                continue;
+            }
+            if let Some(name) = next.node_type.strip_prefix("set_") {
+                written.insert(split_field_path(name).0.to_string());
+            }
+            stack.extend(self.exec_successors(next));
+        }
+        written
+    }
+
+    /// Record the getter outputs a node reads, directly or through inlined pure nodes
+    ///
+    /// A pure node inlined at several uses reads its getters once per use, so
+    /// reads are counted per path. Each pure node's reads are summarized once
+    /// in `pure_reads`, which keeps deep pure fan-out linear to walk.
+    fn collect_getter_reads(
+        &self,
+        node: &NodeInstance,
+        reads: &mut GetterReads,
+        pure_reads: &mut HashMap<String, GetterReads>,
+    ) {
+        use graphy::analysis::DataSource;
+
+        for input in &node.inputs {
+            let Some(DataSource::Connection { source_node_id, source_pin }) =
+                self.data_resolver.get_input_source(&node.id, &input.id)
+            else {
+                continue;
+            };
+            if self.scope_bindings.contains_key(&(source_node_id.clone(), source_pin.clone())) {
+                continue;
+            }
+            let Some(source) = self.graph.nodes.get(source_node_id) else { continue };
+
+            if let Some(var_name) = source.node_type.strip_prefix("get_") {
+                let (count, getters) = reads.entry(var_name.to_string()).or_default();
+                *count += 1;
+                getters.insert((source.id.clone(), source_pin.clone()));
+            } else if self.metadata_provider
+                .get_node_metadata(&source.node_type)
+                .is_some_and(|meta| meta.node_type == NodeTypes::pure)
+            {
+                if !pure_reads.contains_key(&source.id) {
+                    let mut summary = GetterReads::new();
+                    self.collect_getter_reads(source, &mut summary, pure_reads);
+                    pure_reads.insert(source.id.clone(), summary);
+                }
+                for (var_name, (count, getters)) in &pure_reads[&source.id] {
+                    let entry = reads.entry(var_name.clone()).or_default();
+                    entry.0 = entry.0.saturating_add(*count);
+                    entry.1.extend(getters.iter().cloned());
+                }
+            }
+        }
+    }
+
+    /// Render an enum variant comparison feeding a control-flow input as `matches!`
+    ///
+    /// When the input comes from an `==`/`!=` operator node comparing a value
+    /// against a constant variant of its enum type (`Direction::Left`), the
+    /// condition becomes `matches!(value, Direction::Left)`, which doesn't need
+    /// the enum to implement `PartialEq`. Editors store a picked variant as a
+    /// string property, so the constant may arrive quoted (`"Direction::Left"`).
+    /// Returns `None` for any other input.
+    fn enum_variant_condition(&self, node_id: &str, pin_id: &str) -> Result<Option<String>, CompileError> {
+        use graphy::analysis::DataSource;
+        use super::operators::Operator;
+
+        let Some(DataSource::Connection { source_node_id, .. }) = self.data_resolver.get_input_source(node_id, pin_id) else {
+            return Ok(None);
+        };
+        let Some(compare) = self.graph.nodes.get(source_node_id) else { return Ok(None) };
+
+        let attributes = self.metadata_provider.attributes(&compare.node_type);
+        let negate = match attributes.operator {
+            Some(Operator::Eq) => false,
+            Some(Operator::Ne) => true,
+            _ => return Ok(None),
+        };
+        if !attributes.deterministic {
+            return Ok(None);
+        }
+
+        let operands: Vec<&str> = compare.inputs
+            .iter()
+            .filter(|pin| !matches!(pin.pin.data_type, graphy::DataType::Execution))
+            .map(|pin| pin.id.as_str())
+            .collect();
+        let [lhs, rhs] = operands.as_slice() else { return Ok(None) };
+
+        for (value_pin, variant_pin) in [(lhs, rhs), (rhs, lhs)] {
+            let Some(DataSource::Constant(variant)) = self.data_resolver.get_input_source(&compare.id, variant_pin) else {
+                continue;
+            };
+            let Some(ty) = self.types.pin_type(&compare.id, value_pin) else { continue };
+            let variant = variant
+                .trim()
+                .strip_prefix('"')
+                .and_then(|quoted| quoted.strip_suffix('"'))
+                .unwrap_or(variant);
+
+            if super::types::is_enum_variant_of(variant, ty) {
+                let value = self.generate_input_expression(&compare.id, value_pin)?;
+                let bang = if negate { "!" } else { "" };
+                return Ok(Some(format!("{}matches!({}, {})", bang, value, variant.trim())));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Make a control-flow node's branch-scoped outputs visible to this generator
+    fn bind_branch_outputs(&mut self, node: &NodeInstance, exec_output: &str) {
+        let attributes = self.metadata_provider.attributes(&node.node_type);
+
+        for binding in attributes.body_bindings.iter().filter(|b| b.exec_output == exec_output) {
+            if let Some(pin) = node.outputs.iter().find(|p| p.pin.name == binding.pin) {
+                self.scope_bindings.insert(
+                    (node.id.clone(), pin.id.clone()),
+                    binding.expression.clone(),
+                );
+            }
+        }
+    }
+
+    /// Generate code for a setter node
+    fn generate_setter_node(&mut self, node: &NodeInstance, indent_level: usize) -> Result<String, CompileError> {
+        let mut code = String::new();
+        let indent = "    ".repeat(indent_level);
+
+        // Extract variable name and field path from node type (remove "set_" prefix)
+        let name = node.node_type
+            .strip_prefix("set_")
+            .ok_or_else(|| CompileError::InvalidSetter { node_type: node.node_type.clone() })?;
+        let (var_name, var, field) = self.accessed_variable(node, name)?;
+
+        // Find the "value" input pin ID
+        let value_pin_id = node.inputs.iter()
+            .find(|input| input.pin.name == "value")
+            .map(|input| input.id.clone())
+            .ok_or_else(|| CompileError::MissingInputPin { node_id: node.id.clone(), pin: "value".to_string() })?;
+
+        // Get the value to set
+        let value_expr = self.generate_input_expression(&node.id, &value_pin_id)?;
+        code.push_str(&self.take_pending_bindings(&indent));
+
+        // A field is checked against its declared type, if there is one
+        let expected = match field {
+            Some(path) => var.field_type(path).ok().flatten(),
+            None => Some(var.ty.as_str()),
+        };
+        if let (Some(found), Some(expected)) = (self.source_type(&node.id, &value_pin_id), expected) {
+            if found.replace(' ', "") != expected.replace(' ', "") {
+                return Err(CompileError::VariableTypeMismatch {
+                    var: name.to_string(),
+                    expected: expected.to_string(),
+                    found,
+                    node_id: node.id.clone(),
+                });
+            }
+        }
+
+        code.push_str(&self.trace_statement(node, &indent));
+
+        // Generate setter code
+        let statement = format!(
+            "{}{}\n",
+            indent,
+            write_statement(var_name, var, field, &value_expr, self.variable_storage())
+        );
+        code.push_str(&self.with_order_comment(node, statement));
+
+        // Follow execution chain
+        if self.follow_exec {
+            self.queue_exec_successors(node);
+        }
+
+        Ok(code)
+    }
+
+    /// The variable a getter or setter named `name` accesses, and the field path inside it
+    ///
+    /// Fails if the variable isn't declared, or declares its fields and the
+    /// path isn't one of them.
+    fn accessed_variable<'n>(
+        &self,
+        node: &NodeInstance,
+        name: &'n str,
+    ) -> Result<(&'n str, &VariableDef, Option<&'n str>), CompileError> {
+        let (var_name, field) = split_field_path(name);
+        let var = self.variables
+            .get(var_name)
+            .ok_or_else(|| CompileError::VariableNotFound { var: var_name.to_string() })?;
+        if let Some(path) = field {
+            var.field_type(path).map_err(|()| CompileError::UnknownField {
+                var: var_name.to_string(),
+                path: path.to_string(),
+                node_id: node.id.clone(),
+            })?;
+        }
+        Ok((var_name, var, field))
+    }
+
+    /// Concrete type of the value connected to an input, if it is known
+    ///
+    /// Constants, defaults and generic sources whose type couldn't be
+    /// resolved yield `None`.
+    fn source_type(&self, node_id: &str, pin_id: &str) -> Option<String> {
+        use graphy::analysis::DataSource;
+
+        let Some(DataSource::Connection { source_node_id, source_pin }) =
+            self.data_resolver.get_input_source(node_id, pin_id)
+        else {
+            return None;
+        };
+        let source = self.graph.nodes.get(source_node_id)?;
+
+        if let Some(name) = source.node_type.strip_prefix("get_") {
+            let (var_name, field) = split_field_path(name);
+            let var = self.variables.get(var_name)?;
+            return match field {
+                Some(path) => var.field_type(path).ok().flatten().map(str::to_string),
+                None => Some(var.ty.clone()),
+            };
+        }
+
+        let ty = match self.types.pin_type(source_node_id, source_pin) {
+            Some(resolved) => resolved.to_string(),
+            None => {
+                let pin = source.outputs.iter().find(|p| &p.id == source_pin)?;
+                super::types::declared_type(&pin.pin.data_type)?
+            }
+        };
+
+        let generic_params = self.metadata_provider
+            .get_node_metadata(&source.node_type)
+            .map(|meta| super::types::generic_params(&meta.function_source))
+            .unwrap_or_default();
+        (!super::types::is_generic(&ty, &generic_params)).then_some(ty)
+    }
+
+    /// Collect arguments for a function call
+    pub(super) fn collect_arguments(&self, node: &NodeInstance, node_meta: &graphy::core::NodeMetadata) -> Result<Vec<String>, CompileError> {
+        self.collect_arguments_borrowing(node, node_meta, &HashMap::new())
+    }
+
+    /// Collect arguments, passing the getters in `borrows` (pin ID → variable) by reference
+    ///
+    /// Thread-local borrows refer to the closure parameter introduced by
+    /// [`wrap_borrows`](Self::wrap_borrows), which must enclose the call.
+    fn collect_arguments_borrowing(
+        &self,
+        node: &NodeInstance,
+        node_meta: &graphy::core::NodeMetadata,
+        borrows: &HashMap<String, String>,
+    ) -> Result<Vec<String>, CompileError> {
+        let mut args = Vec::new();
+
+        for (i, param) in node_meta.params.iter().enumerate() {
+            if i + 1 == node_meta.params.len() {
+                if let Some(style) = self.metadata_provider.attributes(&node.node_type).variadic {
+                    args.extend(self.variadic_arguments(node, &param.name, style)?);
+                    continue;
+                }
+            }
+
+            // Find the actual pin ID from the node instance
+            // Pin IDs are typically "{node_id}_{param_name}"
+            let pin_id = node.inputs.iter()
+                .find(|input| {
+                    // Match by name - the pin's name should match the param name
+                    input.pin.name == param.name
+                })
+                .map(|input| input.id.clone())
+                .ok_or_else(|| CompileError::MissingInputPin { node_id: node.id.clone(), pin: param.name.clone() })?;
+
+            let value = match borrows.get(&pin_id) {
+                Some(var_name) => match self.variable_storage() {
+                    VariableStorage::StructField => format!("&self.{}", var_name),
+                    VariableStorage::Environment => format!("&env.{}", var_name),
+                    VariableStorage::ThreadLocal => format!("&{}_ref.borrow()", var_name),
+                },
+                None => self.generate_input_expression(&node.id, &pin_id)?,
+            };
+            args.push(value);
+        }
+
+        Ok(args)
+    }
+
+    /// Getter-fed `&T` parameters that can borrow the variable instead of cloning it
+    ///
+    /// Returns pin ID → variable name. Only non-Copy variables read directly by
+    /// a shared-reference parameter qualify, and only when the call's result
+    /// can't hold on to the borrow.
+    fn borrowable_getters(&self, node: &NodeInstance, node_meta: &graphy::core::NodeMetadata) -> HashMap<String, String> {
+        use graphy::analysis::DataSource;
+
+        let mut borrows = HashMap::new();
+        let returns_borrow = node_meta.return_type
+            .as_ref()
+            .is_some_and(|ty| ty.type_string.contains(['&', '\'']));
+        if returns_borrow {
+            return borrows;
+        }
+
+        for input in &node.inputs {
+            // The consumer's declared parameter type decides; the pin's type is a fallback
+            let param_type = node_meta.params
+                .iter()
+                .find(|param| param.name == input.pin.name)
+                .map(|param| param.param_type.type_string.clone())
+                .or_else(|| super::types::declared_type(&input.pin.data_type));
+            let by_ref = param_type.is_some_and(|ty| ty.starts_with('&') && !ty.starts_with("&mut"));
+            if !by_ref {
+                continue;
+            }
+            let Some(DataSource::Connection { source_node_id, source_pin }) =
+                self.data_resolver.get_input_source(&node.id, &input.id)
+            else {
+                continue;
+            };
+            if self.scope_bindings.contains_key(&(source_node_id.clone(), source_pin.clone())) {
+                continue;
+            }
+            let Some(var_name) = self.graph.nodes
+                .get(source_node_id)
+                .and_then(|source| source.node_type.strip_prefix("get_"))
+            else {
+                continue;
+            };
+            if self.variables.get(var_name).is_some_and(|var| !is_copy_type(&var.ty)) {
+                borrows.insert(input.id.clone(), var_name.to_string());
+            }
+        }
+
+        borrows
+    }
+
+    /// Run a call inside the `with` closures its thread-local borrows need
+    ///
+    /// The borrow never escapes: the closure returns the call's (owned) result.
+    fn wrap_borrows(&self, call: String, borrows: &HashMap<String, String>) -> String {
+        if self.variable_storage() != VariableStorage::ThreadLocal {
+            return call;
+        }
+
+        let mut vars: Vec<&String> = borrows.values().collect();
+        vars.sort();
+        vars.dedup();
+
+        vars.into_iter().rev().fold(call, |inner, var_name| {
+            format!("{}.with(|{}_ref| {})", var_name.to_uppercase(), var_name, inner)
+        })
+    }
+
+    /// Arguments for a variadic parameter, from its `{param}_{index}` pins
+    fn variadic_arguments(&self, node: &NodeInstance, param: &str, style: VariadicStyle) -> Result<Vec<String>, CompileError> {
+        let prefix = format!("{}_", param);
+        let mut pins: Vec<(usize, &str)> = node.inputs
+            .iter()
+            .filter_map(|input| {
+                let index = input.pin.name.strip_prefix(&prefix)?.parse().ok()?;
+                Some((index, input.id.as_str()))
+            })
+            .collect();
+        pins.sort_by_key(|(index, _)| *index);
+
+        let values = pins
+            .into_iter()
+            .map(|(_, pin_id)| self.generate_input_expression(&node.id, pin_id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(match style {
+            VariadicStyle::Arguments => values,
+            VariadicStyle::Slice => vec![format!("&[{}]", values.join(", "))],
+        })
+    }
+
+    /// Generate expression for an input value
+    /// pin_id should be the actual pin ID from the node instance (e.g., "print_1_value")
+    fn generate_input_expression(&self, node_id: &str, pin_id: &str) -> Result<String, CompileError> {
+        use graphy::analysis::DataSource;
+
+        match self.data_resolver.get_input_source(node_id, pin_id) {
+            Some(DataSource::Connection { source_node_id, source_pin }) => {
+                // Values bound by an enclosing control-flow branch (loop element, index, ...)
+                if let Some(expr) = self.scope_bindings.get(&(source_node_id.clone(), source_pin.clone())) {
+                    return Ok(expr.clone());
+                }
+
+                let source_node = self.graph.nodes.get(source_node_id)
+                    .ok_or_else(|| GraphyError::NodeNotFound(source_node_id.clone()))?;
+
+                // A branch-scoped value read from outside its branch
+                let source_pin_name = source_node.outputs.iter()
+                    .find(|p| &p.id == source_pin)
+                    .map(|p| p.pin.name.as_str());
+                if let Some(binding) = self.metadata_provider
+                    .attributes(&source_node.node_type)
+                    .body_bindings
+                    .iter()
+                    .find(|b| Some(b.pin.as_str()) == source_pin_name)
+                {
+                    return Err(CompileError::BranchScopedOutput {
+                        node_id: source_node_id.clone(),
+                        pin: binding.pin.clone(),
+                        branch: binding.exec_output.clone(),
+                        reader: node_id.to_string(),
+                    });
+                }
+
+                // Check if source is a variable getter
+                if let Some(name) = source_node.node_type.strip_prefix("get_") {
+                    let (var_name, var, field) = self.accessed_variable(source_node, name)?;
+
+                    if self.const_variables.contains(var_name) {
+                        return Ok(match field {
+                            Some(path) => format!("{}.{}", var_name.to_uppercase(), path),
+                            None => var_name.to_uppercase(),
+                        });
+                    }
+                    return Ok(read_expression(var_name, var, field, self.variable_storage()));
+                }
+
+                // Check if source is pure - if so, inline it
+                let is_pure = self.metadata_provider
+                    .get_node_metadata(&source_node.node_type)
+                    .is_some_and(|node_meta| node_meta.node_type == NodeTypes::pure);
+                let value = if let Some(field) = self.cached_pure.get(source_node_id) {
+                    format!("self.{}.get_or_init(|| {}).clone()", field.name, self.inline_pure_node(source_node)?)
+                } else if is_pure {
+                    self.generate_pure_node_expression(source_node)?
+                } else if let Some(var_name) = self.result_variable(source_node_id) {
+                    // Non-pure: use result variable
+                    var_name.clone()
+                } else {
+                    return Err(CompileError::MissingResultVariable { node_id: source_node_id.clone() });
+                };
+
+                Ok(match tuple_field(source_node, source_pin, self.metadata_provider) {
+                    Some(index) => format!("{}.{}", value, index),
+                    None => value,
+                })
+            }
+            Some(DataSource::Constant(value)) => {
+                // Editor-set vectors and colors are rendered from their components
+                let composite = self.graph.nodes
+                    .get(node_id)
+                    .and_then(|node| {
+                        let pin = node.inputs.iter().find(|p| p.id == pin_id)?;
+                        node.properties.get(&pin.id).or_else(|| node.properties.get(&pin.pin.name))
+                    })
+                    .and_then(|property| render_composite(property, &self.options.composite_constructors));
+                let retyped = || {
+                    self.input_target_type(node_id, pin_id)
+                        .and_then(|ty| retype_number_literal(value, &ty))
+                };
+                Ok(composite
+                    .or_else(retyped)
+                    .or_else(|| non_finite_literal(value))
+                    .unwrap_or_else(|| value.clone()))
+            }
+            Some(DataSource::Default) => {
+                // Use default value for the type
+                if let Some(node) = self.graph.nodes.get(node_id) {
+                    if let Some(pin) = node.inputs.iter().find(|p| p.id == pin_id) {
+                        if self.options.strict_inputs {
+                            return Err(CompileError::MissingInput {
+                                node_id: node_id.to_string(),
+                                pin: pin.pin.name.clone(),
+                            });
+                        }
+                        // Generic pins take the type propagated from their connections
+                        let composite = default_composite(&pin.pin.data_type, &self.options.composite_constructors);
+                        let default = match (composite, self.input_target_type(node_id, pin_id)) {
+                            (Some(composite), _) => composite,
+                            (None, Some(target)) => default_value_for_type(&target),
+                            (None, None) => get_default_value(&pin.pin.data_type),
+                        };
+                        Ok(adapt_to_target(self.aliased(default), self.target())?)
+                    } else {
+                        Err(GraphyError::PinNotFound {
+                            node: node_id.to_string(),
+                            pin: pin_id.to_string(),
+                        }
+                        .into())
+                    }
+                } else {
+                    Err(GraphyError::NodeNotFound(node_id.to_string()).into())
+                }
+            }
+            None => Err(CompileError::NoDataSource { node_id: node_id.to_string(), pin_id: pin_id.to_string() }),
+        }
+    }
+
+    /// Type the value fed into an input pin must have
+    ///
+    /// The parameter type the node's metadata declares wins over the pin's
+    /// editor type (a `Number` pin may feed an `i32` parameter); generic
+    /// parameters fall back to the type resolved for the pin.
+    fn input_target_type(&self, node_id: &str, pin_id: &str) -> Option<String> {
+        let node = self.graph.nodes.get(node_id)?;
+        let pin = node.inputs.iter().find(|pin| pin.id == pin_id)?;
+        let declared = self.metadata_provider.get_node_metadata(&node.node_type).and_then(|meta| {
+            let param = meta.params.iter().find(|param| param.name == pin.pin.name)?;
+            let generics = super::types::generic_params(&meta.function_source);
+            let ty = param.param_type.type_string.trim();
+            (!super::types::is_generic(ty, &generics) && !ty.starts_with("impl ")).then(|| ty.to_string())
+        });
+        declared.or_else(|| self.types.pin_type(node_id, pin_id).map(str::to_string))
+    }
+
+    /// Generate expression for a pure node
+    ///
+    /// Deterministic pure nodes are inlined at every use. Non-deterministic ones
+    /// are evaluated once into a local binding before the statement that first
+    /// reads them, so they run in exec order and every consumer observes the
+    /// same value. A loop's inputs are the exception: they are inlined so the
+    /// loop reads a fresh value on every iteration.
+    fn generate_pure_node_expression(&self, node: &NodeInstance) -> Result<String, CompileError> {
+        let deterministic = self.metadata_provider.attributes(&node.node_type).deterministic;
+
+        if deterministic || self.in_loop_header.get() {
+            return self.inline_pure_node(node);
+        }
+
+        let var_name = self.pure_binding_name(&node.id);
+
+        if self.bound_pure.borrow().contains(&node.id) {
+            return Ok(var_name);
+        }
+
+        let expr = self.inline_pure_node(node)?;
+        let ty = self.metadata_provider
+            .get_node_metadata(&node.node_type)
+            .and_then(|node_meta| self.result_type_annotation(node, node_meta));
+        self.bound_pure.borrow_mut().insert(node.id.clone());
+        self.queue_binding(&node.id, &var_name, ty, expr)?;
+        self.report.borrow_mut().pure_nodes_hoisted += 1;
+
+        Ok(var_name)
+    }
+
+    /// Variable a node's result is bound to
+    pub(super) fn result_variable(&self, node_id: &str) -> Option<&String> {
+        self.result_variables.get(node_id)
+    }
+
+    /// Local a non-deterministic pure node is bound to
+    fn pure_binding_name(&self, node_id: &str) -> String {
+        self.pure_binding_names
+            .get(node_id)
+            .or_else(|| self.result_variable(node_id))
+            .cloned()
+            .unwrap_or_else(|| format!("{}_result", sanitize_identifier(node_id)))
+    }
+
+    /// Generate inlined call expression for a pure node
+    ///
+    /// Data cycles are rejected before generation; a node reached again while
+    /// it is still being inlined fails with `CompileError::DataCycle` rather
+    /// than recursing forever.
+    fn inline_pure_node(&self, node: &NodeInstance) -> Result<String, CompileError> {
+        let cycle_start = self.inlining.borrow().iter().position(|id| *id == node.id);
+        if let Some(start) = cycle_start {
+            let nodes = self.inlining.borrow()[start..].to_vec();
+            return Err(CompileError::DataCycle { nodes });
+        }
+
+        self.inlining.borrow_mut().push(node.id.clone());
+        let expr = self.expand_pure_node(node);
+        self.inlining.borrow_mut().pop();
+        expr
+    }
+
+    /// Inlined call expression for a pure node, with its arguments expanded
+    fn expand_pure_node(&self, node: &NodeInstance) -> Result<String, CompileError> {
+        self.count_expansion()?;
+
+        let node_meta = self.metadata_provider
+            .get_node_metadata(&node.node_type)
+            .ok_or_else(|| GraphyError::NodeNotFound(node.node_type.clone()))?;
+
+        // Recursively generate arguments
+        let mut args = Vec::new();
+        let mut pin_ids = Vec::new();
+        for (i, param) in node_meta.params.iter().enumerate() {
+            if i + 1 == node_meta.params.len() {
+                if let Some(style) = self.metadata_provider.attributes(&node.node_type).variadic {
+                    args.extend(self.variadic_arguments(node, &param.name, style)?);
+                    continue;
+                }
+            }
+
+            // Find the actual pin ID from the node instance
+            let pin_id = node.inputs.iter()
+                .find(|input| input.pin.name == param.name)
+                .map(|input| input.id.clone())
+                .ok_or_else(|| CompileError::MissingInputPin { node_id: node.id.clone(), pin: param.name.clone() })?;
+
+            let arg_expr = self.generate_input_expression(&node.id, &pin_id)?;
+            args.push(arg_expr);
+            pin_ids.push(pin_id);
+        }
+
+        // Operator-mapped nodes become infix/prefix expressions
+        if let Some(operator) = self.metadata_provider.attributes(&node.node_type).operator {
+            if operator.arity() == args.len() {
+                let operands: Vec<(String, u8)> = args
+                    .into_iter()
+                    .zip(pin_ids.iter())
+                    .map(|(expr, pin_id)| (expr, self.input_precedence(&node.id, pin_id)))
+                    .collect();
+                return Ok(operator.render(&operands));
+            }
+        }
+
+        Ok(format!("{}({})", self.symbol(node_meta)?, args.join(", ")))
+    }
+
+    /// Precedence of the expression `generate_input_expression` yields for a pin
+    ///
+    /// Only inlined operator nodes have a precedence below an atom.
+    fn input_precedence(&self, node_id: &str, pin_id: &str) -> u8 {
+        use graphy::analysis::DataSource;
+
+        let Some(DataSource::Connection { source_node_id, .. }) =
+            self.data_resolver.get_input_source(node_id, pin_id)
+        else {
+            return ATOM_PRECEDENCE;
+        };
+        let Some(source_node) = self.graph.nodes.get(source_node_id) else {
+            return ATOM_PRECEDENCE;
+        };
+
+        let attributes = self.metadata_provider.attributes(&source_node.node_type);
+        let is_pure = self.metadata_provider
+            .get_node_metadata(&source_node.node_type)
+            .map(|meta| meta.node_type == NodeTypes::pure)
+            .unwrap_or(false);
+        let inlined = is_pure
+            && !source_node.node_type.starts_with("get_")
+            && !self.cached_pure.contains_key(source_node_id)
+            && (attributes.deterministic || self.in_loop_header.get());
+
+        match attributes.operator {
+            Some(operator) if inlined => operator.precedence(),
+            _ => ATOM_PRECEDENCE,
+        }
+    }
+
+    /// Clone with new visited set
+    pub(super) fn clone_with_new_visited(&self) -> Self {
+        self.fork(HashSet::new())
+    }
+
+    /// Clone sharing everything but the visited set
+    fn fork(&self, visited: HashSet<String>) -> Self {
+        Self {
+            graph: self.graph,
+            metadata_provider: self.metadata_provider,
+            data_resolver: self.data_resolver,
+            exec_routing: self.exec_routing,
+            options: self.options,
+            variables: self.variables.clone(),
+            types: self.types.clone(),
+            consumer_counts: self.consumer_counts.clone(),
+            pure_binding_names: self.pure_binding_names.clone(),
+            result_variables: self.result_variables.clone(),
+            cached_pure: self.cached_pure.clone(),
+            visited,
+            scope_bindings: self.scope_bindings.clone(),
+            bound_pure: RefCell::new(self.bound_pure.borrow().clone()),
+            in_loop_header: Cell::new(false),
+            pending_bindings: RefCell::new(Vec::new()),
+            diagnostics: self.diagnostics.clone(),
+            report: self.report.clone(),
+            functions: self.functions.clone(),
+            cancellation: self.cancellation.clone(),
+            nodes_expanded: self.nodes_expanded.clone(),
+            exec_order: self.exec_order.clone(),
+            in_async: self.in_async,
+            in_method: self.in_method,
+            partial_output: self.partial_output.clone(),
+            const_variables: self.const_variables.clone(),
+            inlining: RefCell::new(Vec::new()),
+            exec_stack: Vec::new(),
+            follow_exec: self.follow_exec,
+        }
+    }
+
+    /// Count one node expansion against `max_nodes_expanded`
+    ///
+    /// Also the point where the cancellation token is polled.
+    fn count_expansion(&self) -> Result<(), CompileError> {
+        if self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            return Err(CompileError::Cancelled);
+        }
+
+        let expanded = self.nodes_expanded.get() + 1;
+        self.nodes_expanded.set(expanded);
+
+        match self.options.max_nodes_expanded {
+            Some(limit) if expanded > limit => Err(CompileError::BudgetExceeded {
+                budget: "max_nodes_expanded".to_string(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check generated code against `max_output_bytes`
+    fn check_output_size(&self, code: &str) -> Result<(), CompileError> {
+        match self.options.max_output_bytes {
+            Some(limit) if code.len() > limit => Err(CompileError::BudgetExceeded {
+                budget: "max_output_bytes".to_string(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Queue `let binding = expr;` for the next statement, annotated with `ty` if given
+    ///
+    /// The binding outlives the statement that needed it, so a borrowed
+    /// expression (whose borrow ends with that statement, or with the `with`
+    /// closure around it) is refused rather than emitted as code that fails
+    /// to borrow-check.
+    fn queue_binding(&self, node_id: &str, binding: &str, ty: Option<String>, expr: String) -> Result<(), CompileError> {
+        if expression_lifetime(&expr) == ExprLifetime::Borrowed {
+            return Err(CompileError::BorrowedBinding {
+                node_id: node_id.to_string(),
+                binding: binding.to_string(),
+                expr,
+            });
+        }
+        let annotation = ty.map(|ty| format!(": {}", self.aliased(ty))).unwrap_or_default();
+        self.pending_bindings.borrow_mut().push(format!("let {}{} = {};", binding, annotation, expr));
+        Ok(())
+    }
+
+    /// Emit the `let` bindings queued while building the next statement
+    pub(super) fn take_pending_bindings(&self, indent: &str) -> String {
+        self.pending_bindings
+            .borrow_mut()
+            .drain(..)
+            .map(|binding| format!("{}{}\n", indent, binding))
+            .collect()
+    }
+
+    /// Emit a runtime trace statement for a node, if enabled
+    pub(super) fn trace_statement(&self, node: &NodeInstance, indent: &str) -> String {
+        if !self.options.trace_nodes {
+            return String::new();
+        }
+
+        format!("{}trace!(\"node {} ({})\");\n", indent, node.id, node.node_type)
+    }
+
+    /// `code` with a node's execution order comment after its first line, if enabled
+    fn with_order_comment(&self, node: &NodeInstance, code: String) -> String {
+        let Some(index) = self.exec_order.borrow().get(&node.id).copied() else {
+            return code;
+        };
+        let comment = format!(" // [#{}] node {}", index, node.id);
+        match code.find('\n') {
+            Some(end) => format!("{}{}{}", &code[..end], comment, &code[end..]),
+            None => code + &comment,
+        }
+    }
+}
+
+/// Check whether a node's function returns something other than `()`
+fn returns_value(node_meta: &graphy::core::NodeMetadata) -> bool {
+    node_meta
+        .return_type
+        .as_ref()
+        .map(|ty| ty.type_string.trim() != "()")
+        .unwrap_or(false)
+}
+
+/// Count how many input pins read from each node's outputs
+fn count_consumers(graph: &GraphDescription, data_resolver: &DataResolver) -> HashMap<String, usize> {
+    use graphy::analysis::DataSource;
+
+    let mut counts = HashMap::new();
+    for node in graph.nodes.values() {
+        for input in &node.inputs {
+            if let Some(DataSource::Connection { source_node_id, .. }) =
+                data_resolver.get_input_source(&node.id, &input.id)
+            {
+                *counts.entry(source_node_id.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Result variable names from the data resolver, sanitized and made unique
+///
+/// Nodes are visited in ID order, so when two nodes want the same name the
+/// first keeps it and the others get a `_2`, `_3`, ... suffix, the same way
+/// every compile. Names a class variable is emitted under (`score`, its
+/// `SCORE` static or const, its hoisted `score_value` local) are taken from
+/// the start, so no local shadows or is confused with a variable.
+fn result_variable_names(
+    graph: &GraphDescription,
+    data_resolver: &DataResolver,
+    variables: &HashMap<String, VariableDef>,
+) -> HashMap<String, String> {
+    let mut node_ids: Vec<&String> = graph.nodes.keys().collect();
+    node_ids.sort();
+
+    let mut taken: HashSet<String> = variables
+        .keys()
+        .flat_map(|name| [name.clone(), name.to_uppercase(), format!("{}_value", sanitize_identifier(name))])
+        .collect();
+    let mut names = HashMap::new();
+    for node_id in node_ids {
+        let Some(preferred) = data_resolver.get_result_variable(node_id) else { continue };
+        let base = sanitize_identifier(preferred);
+
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while taken.contains(&name) {
+            suffix += 1;
+            name = format!("{}_{}", base, suffix);
+        }
+        taken.insert(name.clone());
+        names.insert(node_id.clone(), name);
+    }
+    names
+}
+
+/// Names for the locals non-deterministic pure nodes are bound to
+///
+/// Names are `{node_type}_result_{n}`, numbering nodes of each type in node
+/// ID order from 1, so they stay stable across compiles and read well in
+/// diffs. A name already taken by another result variable, a name a class
+/// variable is emitted under, or an earlier binding gets a `_2`, `_3`, ...
+/// suffix.
+fn pure_binding_names(
+    graph: &GraphDescription,
+    provider: &BlueprintMetadataProvider,
+    data_resolver: &DataResolver,
+    variables: &HashMap<String, VariableDef>,
+    result_variables: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut taken: HashSet<String> = result_variables
+        .values()
+        .cloned()
+        .chain(variables.keys().flat_map(|name| [name.clone(), name.to_uppercase(), format!("{}_value", sanitize_identifier(name))]))
+        .collect();
+
+    let mut node_ids: Vec<&String> = data_resolver.get_pure_evaluation_order().iter().collect();
+    node_ids.sort();
+
+    let mut per_type: HashMap<&str, usize> = HashMap::new();
+    let mut names = HashMap::new();
+    for node_id in node_ids {
+        let Some(node) = graph.nodes.get(node_id) else { continue };
+        if provider.attributes(&node.node_type).deterministic {
+            continue;
+        }
+
+        let index = per_type.entry(node.node_type.as_str()).or_insert(0);
+        *index += 1;
+        let base = format!("{}_result_{}", sanitize_identifier(&node.node_type), index);
+
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while taken.contains(&name) {
+            suffix += 1;
+            name = format!("{}_{}", base, suffix);
+        }
+        taken.insert(name.clone());
+        names.insert(node_id.clone(), name);
+    }
+    names
+}
+
+/// Class fields for the pure values worth computing once per instance
+///
+/// A deterministic pure node qualifies when everything it reads, directly or
+/// through other pure nodes, is a constant, and more than one event reads it.
+/// Only the outermost such node on each path is cached; what it reads is
+/// computed along with it. Fields are named `cached_{node_type}`, numbered in
+/// node ID order when a name is taken.
+fn shared_pure_fields(
+    graph: &GraphDescription,
+    provider: &BlueprintMetadataProvider,
+    data_resolver: &DataResolver,
+    exec_routing: &ExecutionRouting,
+    types: &ResolvedTypes,
+    variables: &HashMap<String, VariableDef>,
+) -> HashMap<String, CachedField> {
+    use graphy::analysis::DataSource;
+
+    let source_of = |node: &NodeInstance, pin_id: &str| match data_resolver.get_input_source(&node.id, pin_id) {
+        Some(DataSource::Connection { source_node_id, .. }) => Some(source_node_id.clone()),
+        _ => None,
+    };
+
+    // Pure nodes whose value only depends on constants
+    let mut constant: HashSet<String> = HashSet::new();
+    for node_id in data_resolver.get_pure_evaluation_order() {
+        let Some(node) = graph.nodes.get(node_id) else { continue };
+        let qualifies = !node.node_type.starts_with("get_")
+            && provider.attributes(&node.node_type).deterministic
+            && node.inputs.iter().all(|input| source_of(node, &input.id).is_none_or(|source| constant.contains(&source)));
+        if qualifies {
+            constant.insert(node_id.clone());
+        }
+    }
+
+    // Number of events whose chain reads each of them
+    let mut readers: HashMap<String, usize> = HashMap::new();
+    let events = graph.nodes.values().filter(|node| {
+        provider.get_node_metadata(&node.node_type).is_some_and(|meta| meta.node_type == NodeTypes::event)
+    });
+    for event in events {
+        let mut reachable = HashSet::new();
+        let mut stack = vec![event];
+        while let Some(node) = stack.pop() {
+            if !reachable.insert(node.id.clone()) {
+                continue;
+            }
+            for pin in node.outputs.iter().filter(|pin| matches!(pin.pin.data_type, graphy::DataType::Execution)) {
+                stack.extend(
+                    exec_routing
+                        .get_connected_nodes(&node.id, &pin.id)
+                        .iter()
+                        .filter_map(|next_id| graph.nodes.get(next_id)),
+                );
+            }
+        }
+
+        let mut read = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut pending: Vec<&NodeInstance> = reachable.iter().filter_map(|id| graph.nodes.get(id)).collect();
+        while let Some(node) = pending.pop() {
+            for input in &node.inputs {
+                let Some(source_id) = source_of(node, &input.id) else { continue };
+                if constant.contains(&source_id) {
+                    read.insert(source_id);
+                } else if reachable.contains(&source_id) || !seen.insert(source_id.clone()) {
+                    continue;
+                } else if let Some(source) = graph.nodes.get(&source_id) {
+                    pending.push(source);
+                }
+            }
+        }
+        for node_id in read {
+            *readers.entry(node_id).or_insert(0) += 1;
+        }
+    }
+
+    let mut node_ids: Vec<&String> = readers.keys().collect();
+    node_ids.sort();
+
+    let mut taken: HashSet<String> = variables.keys().cloned().collect();
+    let mut fields = HashMap::new();
+    for node_id in node_ids {
+        if readers.get(node_id).copied().unwrap_or(0) < 2 {
+            continue;
+        }
+        let Some(node) = graph.nodes.get(node_id) else { continue };
+        let Some(ty) = types.output_type(node).filter(|ty| !ty.starts_with("impl ")) else { continue };
+
+        let base = format!("cached_{}", sanitize_identifier(&node.node_type));
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while taken.contains(&name) {
+            suffix += 1;
+            name = format!("{}_{}", base, suffix);
+        }
+        taken.insert(name.clone());
+        fields.insert(node_id.clone(), CachedField { name, ty: ty.to_string() });
+    }
+    fields
+}
+
+/// Tuple field a data output reads, for nodes returning one field per output
+fn tuple_field(node: &NodeInstance, pin_id: &str, provider: &BlueprintMetadataProvider) -> Option<usize> {
+    if !provider.attributes(&node.node_type).tuple_outputs {
+        return None;
+    }
+    node.outputs
+        .iter()
+        .filter(|pin| !matches!(pin.pin.data_type, graphy::DataType::Execution))
+        .position(|pin| pin.id == pin_id)
+}
+
+/// Turn an arbitrary node ID into a valid Rust identifier
+pub(crate) fn sanitize_identifier(id: &str) -> String {
+    let mut ident: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    ident
+}
+
+/// Record where generation stopped in the partial `output` on an error
+///
+/// Only errors carrying a free-form message are located; typed errors
+/// already name the node or pin they concern.
+fn at_output_location(err: CompileError, output: &str) -> CompileError {
+    match err {
+        CompileError::Graphy(error @ (GraphyError::CodeGeneration(_) | GraphyError::Custom(_))) => {
+            CompileError::Generation {
+                error,
+                line: output.matches('\n').count() + 1,
+                byte: output.len(),
+            }
+        }
+        other => other,
+    }
+}
+
+/// Whether an emitted expression yields an owned value or a borrow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExprLifetime {
+    /// A value the expression's user owns, safe to store anywhere
+    Owned,
+    /// A reference tied to the expression's surroundings (a variable borrow,
+    /// a `with` closure); only usable immediately
+    Borrowed,
+}
+
+/// Classify an expression the generator emitted
+///
+/// Getters borrow with a leading `&` (`&self.foo`, `&foo_ref.borrow()`) and
+/// otherwise clone or copy, so the outermost operation decides.
+pub(crate) fn expression_lifetime(expr: &str) -> ExprLifetime {
+    let expr = expr.trim();
+    let borrowing_suffix = [".borrow()", ".borrow_mut()", ".as_ref()", ".as_str()", ".as_slice()"]
+        .iter()
+        .any(|suffix| expr.ends_with(suffix));
+    if expr.starts_with('&') || borrowing_suffix {
+        ExprLifetime::Borrowed
+    } else {
+        ExprLifetime::Owned
+    }
+}
+
+/// The variable a borrowed expression borrows, if it borrows one
+///
+/// Recognizes the borrows getters emit: `&self.name`, `&env.name` and
+/// `&name_ref.borrow()`, possibly followed by a field path.
+fn borrowed_variable(expr: &str) -> Option<&str> {
+    if expression_lifetime(expr) != ExprLifetime::Borrowed {
+        return None;
+    }
+    let place = expr.trim().strip_prefix('&')?;
+    let name = if let Some(rest) = place.strip_prefix("self.").or_else(|| place.strip_prefix("env.")) {
+        rest
+    } else {
+        place.split_once("_ref.borrow()")?.0
+    };
+    let end = name.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(name.len());
+    (end > 0).then(|| &name[..end])
+}
+
+/// Check if a type is Copy (uses Cell) or not (uses RefCell)
+///
+/// Tuples of Copy types, such as the `(f32, f32)` of a vector, are Copy too.
+pub(crate) fn is_copy_type(type_str: &str) -> bool {
+    if let Some(elements) = tuple_elements(type_str) {
+        return elements.into_iter().all(is_copy_type);
+    }
+    matches!(
+        type_str.trim(),
+        "i32" | "i64" | "u32" | "u64" | "f32" | "f64" | "bool" | "char" |
+        "usize" | "isize" | "i8" | "i16" | "u8" | "u16" | "i128" | "u128"
+    )
+}
+
+/// Default value expression for a Rust type
+///
+/// Covers the integer widths and tuples Graphy's table lacks, deferring to it
+/// for everything else.
+pub(crate) fn default_value_for_type(type_str: &str) -> String {
+    if let Some(elements) = tuple_elements(type_str) {
+        let values: Vec<String> = elements.into_iter().map(default_value_for_type).collect();
+        return match values.as_slice() {
+            [value] => format!("({},)", value),
+            _ => format!("({})", values.join(", ")),
+        };
+    }
+    let type_str = type_str.trim();
+    number_literal(0.0, type_str).unwrap_or_else(|| graphy::utils::get_default_value_for_type(type_str))
+}
+
+/// Element types of a tuple type, split on its top-level commas
+///
+/// Returns `None` for non-tuple types, including parenthesized ones like `(T)`.
+fn tuple_elements(type_str: &str) -> Option<Vec<&str>> {
+    let inner = type_str.trim().strip_prefix('(')?.strip_suffix(')')?;
+
+    let mut elements = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' | '<' | '[' => depth += 1,
+            ')' | '>' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                elements.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if elements.is_empty() && !inner.trim().is_empty() {
+        // A parenthesized type, not a tuple
+        return None;
+    }
+    elements.push(&inner[start..]);
+    elements.retain(|element| !element.trim().is_empty());
+    Some(elements)
+}
+
+/// Get default value for a data type
+fn get_default_value(data_type: &graphy::DataType) -> String {
+    use graphy::DataType;
+
+    match data_type {
+        DataType::Execution => "()".to_string(),
+        DataType::Typed(type_info) => default_value_for_type(&type_info.type_string),
+        DataType::Number => "0.0".to_string(),
+        DataType::String => "String::new()".to_string(),
+        DataType::Boolean => "false".to_string(),
+        DataType::Vector2 => "(0.0, 0.0)".to_string(),
+        DataType::Vector3 => "(0.0, 0.0, 0.0)".to_string(),
+        DataType::Color => "(0.0, 0.0, 0.0, 1.0)".to_string(),
+        DataType::Any => "Default::default()".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codegen::Operator;
+    use crate::error::CompileError;
+    use crate::metadata::{BlueprintMetadataProvider, BodyBinding, NodeAttributes, VariadicStyle};
+    use crate::options::{CompileOptions, SubgraphStrategy};
+    use crate::subgraph::{SUBGRAPH_INPUTS, SUBGRAPH_OUTPUTS, SubgraphLibrary};
+    use crate::test_support::{GraphBuilder, provider};
+    use crate::Compiler;
+    use graphy::{GraphDescription, GraphyError, PropertyValue};
+    use std::collections::HashMap;
+
+    /// Add a `print_string` node printing `message`
+    fn print(builder: GraphBuilder, id: &str, message: &str) -> GraphBuilder {
+        builder
+            .node(id, "print_string")
+            .property(id, "message", PropertyValue::String(message.to_string()))
+    }
+
+    /// Add a `branch` node on a random condition
+    fn branch(builder: GraphBuilder, id: &str) -> GraphBuilder {
+        let condition = format!("{}_condition", id);
+        builder
+            .node(id, "branch")
+            .node(&condition, "random_bool")
+            .data(&condition, "result", id, "condition")
+    }
+
+    #[test]
+    fn diamond_emits_its_merge_once_after_the_if() {
+        // main -> if { a } else { b }, with both arms joining at m -> n
+        let builder = branch(GraphBuilder::new().node("main", "main"), "branch");
+        let builder = print(print(print(print(builder, "a", "a"), "b", "b"), "m", "m"), "n", "n");
+        let graph = builder
+            .exec("main", "body", "branch")
+            .exec("branch", "True", "a")
+            .exec("branch", "False", "b")
+            .exec("a", "exec_out", "m")
+            .exec("b", "exec_out", "m")
+            .exec("m", "exec_out", "n")
+            .build();
+
+        let code = Compiler::with_provider(provider()).compile(&graph).unwrap();
+        assert!(
+            code.contains(
+                "    if random_bool() {\n        print_string(\"a\");\n    } else {\n        print_string(\"b\");\n    }\n    print_string(\"m\");\n    print_string(\"n\");\n}"
+            ),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn one_armed_if_falls_through_to_the_join_of_its_branch() {
+        // main -> if outer { a -> if inner { } else { c } } -> m, with both inner arms joining at m
+        let builder = branch(branch(GraphBuilder::new().node("main", "main"), "outer"), "inner");
+        let builder = print(print(print(builder, "a", "a"), "c", "c"), "m", "m");
+        let graph = builder
+            .exec("main", "body", "outer")
+            .exec("outer", "True", "a")
+            .exec("a", "exec_out", "inner")
+            .exec("inner", "True", "m")
+            .exec("inner", "False", "c")
+            .exec("c", "exec_out", "m")
+            .build();
+
+        let code = Compiler::with_provider(provider()).compile(&graph).unwrap();
+        assert_eq!(code.matches("print_string(\"m\")").count(), 1);
+        assert!(code.contains("    }\n    print_string(\"m\");\n}"), "m should follow the outer if:\n{}", code);
+    }
+
+    #[test]
+    fn join_from_another_event_does_not_make_a_merge() {
+        // main -> if { a -> m }, begin_play -> m: m belongs inside the if
+        let builder = branch(GraphBuilder::new().node("main", "main").node("begin_play", "begin_play"), "branch");
+        let builder = print(print(builder, "a", "a"), "m", "m");
+        let graph = builder
+            .exec("main", "body", "branch")
+            .exec("branch", "True", "a")
+            .exec("a", "exec_out", "m")
+            .exec("begin_play", "body", "m")
+            .build();
+
+        let code = Compiler::with_provider(provider()).compile(&graph).unwrap();
+        assert!(
+            code.contains("        print_string(\"a\");\n        print_string(\"m\");\n    }"),
+            "m should stay inside the if:\n{}",
+            code
+        );
+    }
+
+    #[test]
+    fn long_linear_chain_does_not_overflow_the_stack() {
+        const LENGTH: usize = 5000;
+        let mut builder = GraphBuilder::new().node("main", "main");
+        let mut previous = ("main".to_string(), "body");
+        for i in 0..LENGTH {
+            let id = format!("print_{}", i);
+            builder = print(builder, &id, "step").exec(&previous.0, previous.1, &id);
+            previous = (id, "exec_out");
+        }
+
+        let code = Compiler::with_provider(provider()).compile(&builder.build()).unwrap();
+        assert_eq!(code.matches("print_string(\"step\");").count(), LENGTH);
+    }
+
+    #[test]
+    fn closure_map_mutates_the_captured_environment() {
+        use crate::codegen::VariableDef;
+        use crate::options::ClosureTarget;
+
+        let graph = print(GraphBuilder::new().node("main", "main").node("begin_play", "begin_play"), "hello", "hi")
+            .bare("set_score", "set_score")
+            .input("set_score", "exec", "()")
+            .input("set_score", "value", "u32")
+            .property("set_score", "value", PropertyValue::Number(5.0))
+            .bare("get_score", "get_score")
+            .output("get_score", "value", "u32")
+            .node("despawn", "despawn")
+            .exec("main", "body", "hello")
+            .exec("hello", "exec_out", "set_score")
+            .exec("begin_play", "body", "despawn")
+            .data("get_score", "value", "despawn", "entity")
+            .build();
+        let variables = HashMap::from([("score".to_string(), VariableDef::new("u32"))]);
+        let options = CompileOptions {
+            closures: Some(ClosureTarget::new("build_events", "World").with_routed_calls()),
+            ..CompileOptions::default()
+        };
+
+        let code = Compiler::with_provider(provider()).with_options(options).compile_with_variables(&graph, variables).unwrap();
+        assert!(code.contains("pub fn build_events(env: std::rc::Rc<std::cell::RefCell<World>>) -> "), "{}", code);
+        assert!(
+            code.contains(concat!(
+                "        events.insert(\"main\", Box::new(move || {\n",
+                "            let mut env = env.borrow_mut();\n",
+                "            let env = &mut *env;\n",
+                "            env.print_string(\"hi\");\n",
+                "            env.score = 5u32;\n",
+                "        }));\n",
+            )),
+            "{}",
+            code
+        );
+        assert!(code.contains("            env.despawn(env.score);\n"), "{}", code);
+        assert!(code.ends_with("    events\n}\n"), "{}", code);
+    }
+
+    #[test]
+    fn benches_cover_parameterless_events_only() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("begin_play", "begin_play")
+            .node("key_pressed", "key_pressed")
+            .build();
+        let options = CompileOptions { emit_benches: true, ..CompileOptions::default() };
+
+        let code = Compiler::with_provider(provider()).with_options(options).compile(&graph).unwrap();
+        assert!(
+            code.contains(concat!(
+                "#[cfg(test)]\nmod benches {\n    extern crate test;\n\n    use super::*;\n    use test::Bencher;\n",
+                "\n    #[bench]\n    fn bench_begin_play(b: &mut Bencher) {\n        b.iter(|| begin_play());\n    }\n",
+                "\n    #[bench]\n    fn bench_main(b: &mut Bencher) {\n        b.iter(|| main());\n    }\n",
+                "}\n",
+            )),
+            "{}",
+            code
+        );
+        assert!(!code.contains("bench_key_pressed"), "{}", code);
+    }
+
+    #[test]
+    fn reshuffled_node_map_gives_the_same_event_order() {
+        let builder = GraphBuilder::new()
+            .node("main", "main")
+            .node("begin_play", "begin_play")
+            .node("key_pressed", "key_pressed")
+            .node("damaged", "damaged");
+        let builder = print(print(print(builder, "hello", "hello"), "ready", "ready"), "ouch", "ouch")
+            .exec("main", "body", "hello")
+            .exec("begin_play", "body", "ready")
+            .exec("damaged", "body", "ouch");
+        let graph = builder.build();
+
+        let mut reshuffled = graph.clone();
+        let mut nodes: Vec<_> = graph.nodes.clone().into_iter().collect();
+        nodes.sort_by(|a, b| b.0.cmp(&a.0));
+        reshuffled.nodes = nodes.into_iter().collect();
+        reshuffled.connections.reverse();
+
+        let code = Compiler::with_provider(provider()).compile(&graph).unwrap();
+        assert_eq!(Compiler::with_provider(provider()).compile(&reshuffled).unwrap(), code);
+        let order: Vec<usize> = ["fn begin_play()", "fn damaged(", "fn key_pressed(", "fn main()"]
+            .iter()
+            .map(|signature| code.find(signature).unwrap())
+            .collect();
+        assert!(order.windows(2).all(|pair| pair[0] < pair[1]), "{}", code);
+    }
+
+    #[test]
+    fn repeated_compiles_are_byte_identical() {
+        // Every build gets fresh hash maps, so any iteration-order dependence shows up as a diff
+        let build = || {
+            let mut builder = GraphBuilder::new().node("main", "main").node("begin_play", "begin_play");
+            let mut previous = ("main".to_string(), "body");
+            for i in 0..8 {
+                let id = format!("branch_{}", i);
+                builder = branch(builder, &id).exec(&previous.0, previous.1, &id);
+                previous = (id, "True");
+            }
+            print(builder, "print", "done").exec(&previous.0, previous.1, "print").exec("begin_play", "body", "print").build()
+        };
+        let compile = || random_compiler().compile(&build()).unwrap();
+
+        let first = compile();
+        for _ in 0..20 {
+            assert_eq!(compile(), first);
+        }
+    }
+
+    #[test]
+    fn loop_body_reads_element_and_index_bindings() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("loop", "for_each")
+            .output("loop", "element", "String")
+            .output("loop", "index", "usize")
+            .node("print_element", "print_string")
+            .node("print_index", "print_string")
+            .data("loop", "element", "print_element", "message")
+            .data("loop", "index", "print_index", "message")
+            .exec("main", "body", "loop")
+            .exec("loop", "body", "print_element")
+            .exec("print_element", "exec_out", "print_index")
+            .build();
+        let attributes = NodeAttributes::default()
+            .with_body_binding(BodyBinding::new("body", "element", "item.clone()"))
+            .with_body_binding(BodyBinding::new("body", "index", "i"));
+        let compiler = Compiler::with_provider(provider().with_attributes("for_each", attributes));
+
+        let code = compiler.compile(&graph).unwrap();
+        assert!(
+            code.contains(".iter().enumerate() {\n        print_string(item.clone());\n        print_string(i);\n    }"),
+            "{}",
+            code
+        );
+    }
+
+    /// A compiler treating `random_bool` as non-deterministic
+    fn random_compiler() -> Compiler {
+        Compiler::with_provider(provider().with_attributes("random_bool", NodeAttributes::non_deterministic()))
+    }
+
+    #[test]
+    fn shared_non_deterministic_node_is_evaluated_once() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("first", "branch")
+            .node("second", "branch")
+            .node("coin", "random_bool")
+            .data("coin", "result", "first", "condition")
+            .data("coin", "result", "second", "condition")
+            .exec("main", "body", "first")
+            .exec("first", "True", "second")
+            .build();
+
+        let code = random_compiler().compile(&graph).unwrap();
+        assert_eq!(code.matches("random_bool()").count(), 1, "{}", code);
+        assert!(code.contains("let random_bool_result_1 = random_bool();"), "{}", code);
+    }
+
+    #[test]
+    fn single_use_non_deterministic_node_is_bound_where_it_runs() {
+        let graph = branch(GraphBuilder::new().node("main", "main"), "branch")
+            .exec("main", "body", "branch")
+            .build();
+
+        let code = random_compiler().compile(&graph).unwrap();
+        let binding = code.find("let random_bool_result_1 = random_bool();").expect(&code);
+        let branch = code.find("if random_bool_result_1").expect(&code);
+        assert!(binding < branch, "{}", code);
+    }
+
+    #[test]
+    fn loop_condition_is_evaluated_every_iteration() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("loop", "while_loop")
+            .node("coin", "random_bool")
+            .data("coin", "result", "loop", "condition")
+            .exec("main", "body", "loop")
+            .build();
+
+        let code = random_compiler().compile(&graph).unwrap();
+        assert!(code.contains("while random_bool()"), "{}", code);
+        assert!(!code.contains("let random_bool_result_1"), "{}", code);
+    }
+
+    #[test]
+    fn generic_binding_is_annotated_with_the_resolved_type() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("branch", "branch")
+            .node("coin", "random_bool")
+            .node("same", "identity")
+            .data("coin", "result", "same", "value")
+            .data("same", "result", "branch", "condition")
+            .exec("main", "body", "branch")
+            .build();
+        let compiler = Compiler::with_provider(provider().with_attributes("identity", NodeAttributes::non_deterministic()));
+
+        let code = compiler.compile(&graph).unwrap();
+        assert!(code.contains("let identity_result_1: bool = identity(random_bool());"), "{}", code);
+    }
+
+    #[test]
+    fn generation_error_records_where_output_stopped() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("branch", "branch")
+            .node("delay", "delay")
+            .exec("main", "body", "branch")
+            .exec("branch", "True", "delay")
+            .build();
+        let compiler = Compiler::with_provider(provider().with_attributes("delay", NodeAttributes::latent()));
+
+        match compiler.compile(&graph) {
+            Err(CompileError::Generation { error: GraphyError::CodeGeneration(message), line, byte }) => {
+                assert!(message.contains("Latent node 'delay'"));
+                assert!(!message.contains("generation stopped"));
+                assert!(line > 1);
+                assert!(byte > 0);
+            }
+            other => panic!("expected a located generation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn macro_node_expands_its_statements_and_keeps_format_strings() {
+        let graph = print(GraphBuilder::new().node("main", "main"), "spawn", "hero")
+            .exec("main", "body", "spawn")
+            .build();
+        let template = "let label = format!(\"{message}: {}\", {message});\nregister(label);";
+        let compiler = Compiler::with_provider(provider().with_attributes("print_string", NodeAttributes::template(template)));
+
+        let code = compiler.compile(&graph).unwrap();
+        assert!(
+            code.contains("    let label = format!(\"{message}: {}\", \"hero\");\n    register(label);\n"),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn template_emits_method_call_syntax_and_checks_its_placeholders() {
+        use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+        use graphy::NodeTypes;
+
+        let append = NodeMetadata::new("append", NodeTypes::fn_, "Collections")
+            .with_params(vec![ParamInfo::new("list", "Vec<String>"), ParamInfo::new("item", "String")])
+            .with_return_type(TypeInfo::new("usize".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()]);
+        let graph = |counted: bool| {
+            let builder = GraphBuilder::new()
+                .node("main", "main")
+                .node("names", "list_names")
+                .bare("append", "append")
+                .input("append", "exec", "()")
+                .input("append", "list", "Vec<String>")
+                .input("append", "item", "String")
+                .output("append", "count", "usize")
+                .property("append", "item", PropertyValue::String("bob".to_string()))
+                .exec("main", "body", "names")
+                .exec("names", "exec_out", "append")
+                .data("names", "result", "append", "list");
+            let builder = if counted {
+                builder.node("sum", "add").data("append", "count", "sum", "a")
+            } else {
+                builder
+            };
+            builder.build()
+        };
+        let compile = |template: &str, counted: bool| {
+            Compiler::with_provider(provider().with_node_metadata(append.clone()).with_attributes("append", NodeAttributes::template(template)))
+                .compile(&graph(counted))
+        };
+
+        let code = compile("{list}.push({item});", false).unwrap();
+        assert!(code.contains("    let names_result = list_names();\n    names_result.push(\"bob\");\n}"), "{}", code);
+
+        let err = compile("{list}.push({item});\nlog(\"{result}\");", true).unwrap_err();
+        assert!(matches!(err, CompileError::MissingPlaceholder { ref placeholder, .. } if placeholder == "result"), "{:?}", err);
+        let err = compile("{items}.push({item});", false).unwrap_err();
+        assert!(matches!(err, CompileError::UnsubstitutedPlaceholder { ref placeholder, .. } if placeholder == "items"), "{:?}", err);
+    }
+
+    #[test]
+    fn branch_on_an_enum_variant_comparison_uses_matches() {
+        let graph = print(branch_on_facing("Direction::Left"), "left", "left").exec("branch", "True", "left").build();
+        let compiler = Compiler::with_provider(provider().with_attributes("equal", NodeAttributes::operator(Operator::Eq)));
+
+        let code = compiler.compile(&graph).unwrap();
+        assert!(code.contains("if matches!(facing(), Direction::Left) {"), "{}", code);
+    }
+
+    #[test]
+    fn comparison_against_a_non_variant_is_left_to_the_operator() {
+        let graph = print(branch_on_facing("left"), "left", "left").exec("branch", "True", "left").build();
+        let compiler = Compiler::with_provider(provider().with_attributes("equal", NodeAttributes::operator(Operator::Eq)));
+
+        let code = compiler.compile(&graph).unwrap();
+        assert!(!code.contains("matches!"), "{}", code);
+    }
+
+    /// `main -> branch` on `facing() == constant`
+    fn branch_on_facing(constant: &str) -> GraphBuilder {
+        GraphBuilder::new()
+            .node("main", "main")
+            .node("branch", "branch")
+            .node("facing", "facing")
+            .node("equal", "equal")
+            .data("facing", "result", "equal", "a")
+            .property("equal", "b", PropertyValue::String(constant.to_string()))
+            .data("equal", "result", "branch", "condition")
+            .exec("main", "body", "branch")
+    }
+
+    /// `main -> branch` on a tower of `and` nodes, each reading the one below through both inputs
+    fn pure_fan_out(depth: usize) -> GraphDescription {
+        let mut builder = GraphBuilder::new().node("main", "main").node("and_0", "random_bool");
+        for level in 1..=depth {
+            let (below, id) = (format!("and_{}", level - 1), format!("and_{}", level));
+            builder = builder.node(&id, "and").data(&below, "result", &id, "a").data(&below, "result", &id, "b");
+        }
+        builder
+            .node("branch", "branch")
+            .data(&format!("and_{}", depth), "result", "branch", "condition")
+            .exec("main", "body", "branch")
+            .build()
+    }
+
+    #[test]
+    fn deep_pure_fan_out_trips_the_expansion_budget() {
+        // Inlined, the tower expands 2^20 times; the budget stops it long before
+        let options = CompileOptions { max_nodes_expanded: Some(1000), ..CompileOptions::default() };
+        let compiler = Compiler::with_provider(provider()).with_options(options);
+
+        match compiler.compile(&pure_fan_out(20)) {
+            Err(CompileError::BudgetExceeded { budget, limit }) => {
+                assert_eq!(budget, "max_nodes_expanded");
+                assert_eq!(limit, 1000);
+            }
+            other => panic!("expected the expansion budget to be exceeded, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn output_over_the_byte_budget_is_rejected() {
+        let options = CompileOptions { max_output_bytes: Some(200), ..CompileOptions::default() };
+        let compiler = Compiler::with_provider(provider()).with_options(options);
+
+        let err = compiler.compile(&pure_fan_out(6)).unwrap_err();
+        assert!(matches!(err, CompileError::BudgetExceeded { ref budget, limit: 200 } if budget == "max_output_bytes"), "{:?}", err);
+    }
+
+    /// `main -> print(concat(value_0, value_1, value_2))`, with the pins added out of order
+    fn variadic_concat() -> GraphDescription {
+        let mut builder = GraphBuilder::new().node("main", "main").node("concat", "concat");
+        for (index, text) in [(2, "c"), (0, "a"), (1, "b")] {
+            let pin = format!("value_{}", index);
+            builder = builder
+                .input("concat", &pin, "String")
+                .property("concat", &format!("concat_{}", pin), PropertyValue::String(text.to_string()));
+        }
+        builder
+            .node("print", "print_string")
+            .data("concat", "result", "print", "message")
+            .exec("main", "body", "print")
+            .build()
+    }
+
+    #[test]
+    fn variadic_pins_are_passed_in_index_order() {
+        let compiler = Compiler::with_provider(provider().with_attributes("concat", NodeAttributes::variadic(VariadicStyle::Arguments)));
+
+        let code = compiler.compile(&variadic_concat()).unwrap();
+        assert!(code.contains("print_string(concat(\"a\", \"b\", \"c\"));"), "{}", code);
+    }
+
+    #[test]
+    fn variadic_pins_can_be_passed_as_a_slice() {
+        let compiler = Compiler::with_provider(provider().with_attributes("concat", NodeAttributes::variadic(VariadicStyle::Slice)));
+
+        let code = compiler.compile(&variadic_concat()).unwrap();
+        assert!(code.contains("print_string(concat(&[\"a\", \"b\", \"c\"]));"), "{}", code);
+    }
+
+    /// `print_string(copy(\"hi\"))`, where the pure sub-graph `copy` passes its input through `identity`
+    fn compile_with_copy_subgraph(provider: BlueprintMetadataProvider) -> String {
+        let copy = GraphBuilder::new()
+            .bare("in", SUBGRAPH_INPUTS)
+            .output("in", "x", "String")
+            .node("identity", "identity")
+            .bare("out", SUBGRAPH_OUTPUTS)
+            .input("out", "value", "String")
+            .data("in", "x", "identity", "value")
+            .data("identity", "result", "out", "value")
+            .build();
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("copy", "copy")
+            .input("copy", "x", "String")
+            .output("copy", "value", "String")
+            .property("copy", "copy_x", PropertyValue::String("hi".to_string()))
+            .node("print", "print_string")
+            .data("copy", "value", "print", "message")
+            .exec("main", "body", "print")
+            .build();
+
+        let options = CompileOptions { subgraph_strategy: SubgraphStrategy::Functions, ..CompileOptions::default() };
+        Compiler::with_provider(provider)
+            .with_options(options)
+            .with_subgraphs(SubgraphLibrary::new().with_subgraph("copy", copy))
+            .compile(&graph)
+            .unwrap()
+    }
+
+    #[test]
+    fn untyped_subgraph_input_is_generic_over_its_consumers_bounds() {
+        use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+        use graphy::{DataType, NodeTypes};
+
+        let show = NodeMetadata::new("show", NodeTypes::pure, "Text")
+            .with_params(vec![ParamInfo::new("value", "T")])
+            .with_return_type(TypeInfo::new("String".to_string()))
+            .with_source("fn show<T: Clone>(value: T) -> String\nwhere\n    T: Display,\n{\n    format!(\"{} {}\", value.clone(), value)\n}");
+        let mut describe = GraphBuilder::new()
+            .bare("in", SUBGRAPH_INPUTS)
+            .output("in", "x", "T")
+            .bare("show", "show")
+            .input("show", "value", "T")
+            .output("show", "result", "String")
+            .bare("out", SUBGRAPH_OUTPUTS)
+            .input("out", "label", "String")
+            .data("in", "x", "show", "value")
+            .data("show", "result", "out", "label")
+            .build();
+        describe.nodes.get_mut("in").unwrap().outputs[0].pin.data_type = DataType::Any;
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("describe", "describe")
+            .input("describe", "x", "u32")
+            .output("describe", "label", "String")
+            .property("describe", "describe_x", PropertyValue::Number(3.0))
+            .node("print", "print_string")
+            .data("describe", "label", "print", "message")
+            .exec("main", "body", "print")
+            .build();
+
+        let options = CompileOptions { subgraph_strategy: SubgraphStrategy::Functions, ..CompileOptions::default() };
+        let code = Compiler::with_provider(provider().with_node_metadata(show))
+            .with_options(options)
+            .with_subgraphs(SubgraphLibrary::new().with_subgraph("describe", describe))
+            .compile(&graph)
+            .unwrap();
+        assert!(
+            code.contains("pub fn describe<T>(x: T) -> String\nwhere\n    T: Clone + Display,\n{\n    show(x)\n}\n"),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn const_capable_pure_helper_is_a_const_fn() {
+        let code = compile_with_copy_subgraph(provider().with_attributes("identity", NodeAttributes::const_eval()));
+        assert!(code.contains("pub const fn copy(x: String) -> String"), "{}", code);
+    }
+
+    #[test]
+    fn other_pure_helpers_are_inline() {
+        let code = compile_with_copy_subgraph(provider());
+        assert!(code.contains("#[inline]\npub fn copy(x: String) -> String"), "{}", code);
+    }
+
+    #[test]
+    fn variable_read_by_a_condition_and_its_branch_is_read_once() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("get_name", "get_name")
+            .output("get_name", "value", "String")
+            .node("equal", "equal")
+            .data("get_name", "value", "equal", "a")
+            .property("equal", "b", PropertyValue::String("hero".to_string()))
+            .node("branch", "branch")
+            .data("equal", "result", "branch", "condition")
+            .node("print", "print_string")
+            .data("get_name", "value", "print", "message")
+            .exec("main", "body", "branch")
+            .exec("branch", "True", "print")
+            .build();
+        let variables = HashMap::from([("name".to_string(), "String")]);
+
+        let code = Compiler::with_provider(provider()).compile_with_variables(&graph, variables).unwrap();
+        assert_eq!(code.matches("NAME.with(").count(), 1, "{}", code);
+        assert!(code.contains("    let name_value = NAME.with(|v| v.borrow().clone());\n    if equal(name_value.clone(), \"hero\") {"), "{}", code);
+        assert!(code.contains("print_string(name_value.clone());"), "{}", code);
+    }
+
+    /// `main -> list -> first` loop over the listed names, with `extra` more loops over them after it
+    fn loops_over_names(extra: usize) -> String {
+        let mut builder = GraphBuilder::new()
+            .node("main", "main")
+            .node("list", "list_names")
+            .exec("main", "body", "list");
+        let mut previous = ("list".to_string(), "exec_out");
+        for i in 0..=extra {
+            let id = format!("loop_{}", i);
+            builder = builder.node(&id, "for_each").data("list", "result", &id, "array").exec(&previous.0, previous.1, &id);
+            previous = (id, "completed");
+        }
+        let provider = provider().with_attributes("for_each", NodeAttributes::default().with_loop_collection("array"));
+
+        Compiler::with_provider(provider).compile(&builder.build()).unwrap()
+    }
+
+    #[test]
+    fn collection_read_only_by_its_loop_is_consumed() {
+        let code = loops_over_names(0);
+        assert!(code.contains("for (i, item) in list_result.iter().enumerate()"), "{}", code);
+    }
+
+    #[test]
+    fn collection_read_after_its_loop_is_borrowed() {
+        let code = loops_over_names(1);
+        assert_eq!(code.matches("for (i, item) in (&list_result).iter().enumerate()").count(), 2, "{}", code);
+    }
+
+    #[test]
+    fn event_calling_itself_compiles_to_a_recursive_call() {
+        let graph = GraphBuilder::new()
+            .node("key_pressed", "key_pressed")
+            .bare("again", "call_key_pressed")
+            .input("again", "exec", "()")
+            .input("again", "state", "bool")
+            .exec("key_pressed", "body", "again")
+            .data("key_pressed", "state", "again", "state")
+            .build();
+
+        let code = Compiler::with_provider(provider()).compile(&graph).unwrap();
+        assert!(code.contains("pub fn key_pressed(state: bool) {\n    key_pressed(state);\n}"), "{}", code);
+    }
+
+    #[test]
+    fn events_implement_a_two_method_trait() {
+        use crate::codegen::class::{ClassContext, TraitImpl, TraitMethod};
+
+        let graph = print(
+            print(GraphBuilder::new().node("main", "main").node("key_pressed", "key_pressed"), "hello", "hi"),
+            "pressed",
+            "key",
+        )
+        .exec("main", "body", "hello")
+        .exec("key_pressed", "body", "pressed")
+        .build();
+        let behavior = TraitImpl::new("engine::Behavior")
+            .with_method("main", TraitMethod::new("on_start"))
+            .with_method("key_pressed", TraitMethod::new("on_key").with_param("down", "bool"));
+        let compile = |behavior: TraitImpl| {
+            let options = CompileOptions {
+                class: Some(ClassContext::new("Player").with_trait_impl(behavior)),
+                ..CompileOptions::default()
+            };
+            Compiler::with_provider(provider()).with_options(options).compile(&graph)
+        };
+
+        let code = compile(behavior.clone()).unwrap();
+        assert!(
+            code.contains(
+                "impl engine::Behavior for Player {\n    fn on_key(&mut self, down: bool) {\n        print_string(\"key\");\n    }\n\n    fn on_start(&mut self) {\n        print_string(\"hi\");\n    }\n}\n"
+            ),
+            "{}",
+            code
+        );
+        assert!(!code.contains("impl Player"), "{}", code);
+
+        let mistyped = behavior.with_method("key_pressed", TraitMethod::new("on_key").with_param("down", "u8"));
+        assert!(compile(mistyped).is_err());
+    }
+
+    #[test]
+    fn setter_rejects_a_value_of_another_type() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("sum", "add")
+            .bare("set_score", "set_score")
+            .input("set_score", "exec", "()")
+            .input("set_score", "value", "i32")
+            .exec("main", "body", "set_score")
+            .data("sum", "result", "set_score", "value")
+            .build();
+        let compile = |ty: &str| {
+            let variables = HashMap::from([("score".to_string(), ty)]);
+            Compiler::with_provider(provider()).compile_with_variables(&graph, variables)
+        };
+
+        assert!(compile("i32").is_ok());
+        match compile("String") {
+            Err(CompileError::VariableTypeMismatch { var, expected, found, node_id }) => {
+                assert_eq!((var.as_str(), expected.as_str(), found.as_str()), ("score", "String", "i32"));
+                assert_eq!(node_id, "set_score");
+            }
+            other => panic!("expected a variable type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn getter_feeding_a_reference_borrows_and_by_value_clones() {
+        use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+        use graphy::NodeTypes;
+
+        let measure = NodeMetadata::new("measure", NodeTypes::fn_, "Text")
+            .with_params(vec![ParamInfo::new("text", "&String")])
+            .with_return_type(TypeInfo::new("usize".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn measure(text: &String) -> usize {\n    text.len()\n}");
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("get_name", "get_name")
+            .output("get_name", "value", "String")
+            .bare("measure", "measure")
+            .input("measure", "exec", "()")
+            .input("measure", "text", "&String")
+            .output("measure", "result", "usize")
+            .node("print", "print_string")
+            .exec("main", "body", "print")
+            .exec("print", "exec_out", "measure")
+            .data("get_name", "value", "measure", "text")
+            .data("get_name", "value", "print", "message")
+            .build();
+        let variables = HashMap::from([("name".to_string(), "String")]);
+
+        let code = Compiler::with_provider(provider().with_node_metadata(measure))
+            .compile_with_variables(&graph, variables)
+            .unwrap();
+        assert!(code.contains("    print_string(NAME.with(|v| v.borrow().clone()));\n"), "{}", code);
+        assert!(code.contains("    let measure_result = NAME.with(|name_ref| measure(&name_ref.borrow()));\n"), "{}", code);
+    }
+
+    #[test]
+    fn field_getter_feeding_a_reference_parameter_is_borrowed() {
+        use crate::codegen::ClassContext;
+        use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+        use graphy::NodeTypes;
+
+        let measure = NodeMetadata::new("measure", NodeTypes::fn_, "Text")
+            .with_params(vec![ParamInfo::new("text", "&String")])
+            .with_return_type(TypeInfo::new("usize".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn measure(text: &String) -> usize {\n    text.len()\n}");
+        // The pin only says `String`; the parameter type asks for a reference
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("get_name", "get_name")
+            .output("get_name", "value", "String")
+            .bare("measure", "measure")
+            .input("measure", "exec", "()")
+            .input("measure", "text", "String")
+            .output("measure", "result", "usize")
+            .exec("main", "body", "measure")
+            .data("get_name", "value", "measure", "text")
+            .build();
+        let variables = HashMap::from([("name".to_string(), "String")]);
+        let options = CompileOptions { class: Some(ClassContext::new("Player")), ..CompileOptions::default() };
+
+        let code = Compiler::with_provider(provider().with_node_metadata(measure))
+            .with_options(options)
+            .compile_with_variables(&graph, variables)
+            .unwrap();
+        assert!(code.contains("        let measure_result = measure(&self.name);\n"), "{}", code);
+        assert!(!code.contains("clone()"), "{}", code);
+    }
+
+    #[test]
+    fn pure_value_read_by_two_events_is_cached_in_the_class() {
+        use crate::codegen::ClassContext;
+        use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+        use graphy::NodeTypes;
+
+        let greeting = NodeMetadata::new("greeting", NodeTypes::pure, "Text")
+            .with_params(vec![ParamInfo::new("name", "String")])
+            .with_return_type(TypeInfo::new("String".to_string()))
+            .with_source("fn greeting(name: String) -> String {\n    format!(\"hello {}\", name)\n}");
+        let graph = GraphBuilder::new()
+            .node("begin_play", "begin_play")
+            .node("main", "main")
+            .bare("greet", "greeting")
+            .input("greet", "name", "String")
+            .output("greet", "result", "String")
+            .property("greet", "name", PropertyValue::String("world".to_string()))
+            .node("first", "print_string")
+            .node("second", "print_string")
+            .exec("begin_play", "body", "first")
+            .exec("main", "body", "second")
+            .data("greet", "result", "first", "message")
+            .data("greet", "result", "second", "message")
+            .build();
+        let class = ClassContext::new("Player").with_derive("Default");
+        let compile = |cache_shared_pure: bool| {
+            let options = CompileOptions { class: Some(class.clone()), cache_shared_pure, ..CompileOptions::default() };
+            Compiler::with_provider(provider().with_node_metadata(greeting.clone()))
+                .with_options(options)
+                .compile(&graph)
+                .unwrap()
+        };
+
+        let code = compile(true);
+        assert!(code.contains("pub struct Player {\n    cached_greeting: core::cell::OnceCell<String>,\n}"), "{}", code);
+        let read = "print_string(self.cached_greeting.get_or_init(|| greeting(\"world\")).clone());";
+        assert!(code.contains(&format!("    pub fn begin_play(&mut self) {{\n        {}\n    }}", read)), "{}", code);
+        assert!(code.contains(&format!("    pub fn main(&mut self) {{\n        {}\n    }}", read)), "{}", code);
+
+        let uncached = compile(false);
+        assert!(uncached.contains("pub struct Player;"), "{}", uncached);
+        assert_eq!(uncached.matches("print_string(greeting(\"world\"));").count(), 2, "{}", uncached);
+    }
+
+    #[test]
+    fn fallible_node_matches_into_success_and_failure_chains() {
+        use crate::metadata::FallibleBranches;
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("load", "read_file")
+            .property("load", "path", PropertyValue::String("save.txt".to_string()))
+            .output("load", "value", "String")
+            .output("load", "error", "String")
+            .node("show", "print_string")
+            .node("report", "print_string")
+            .exec("main", "body", "load")
+            .exec("load", "success", "show")
+            .exec("load", "failure", "report")
+            .data("load", "value", "show", "message")
+            .data("load", "error", "report", "message")
+            .build();
+        let provider = provider().with_attributes("read_file", NodeAttributes::fallible(FallibleBranches::default()));
+
+        let code = Compiler::with_provider(provider).compile(&graph).unwrap();
+        assert!(
+            code.contains(concat!(
+                "    match read_file(\"save.txt\") {\n",
+                "        Ok(load_value) => {\n            print_string(load_value);\n        }\n",
+                "        Err(load_error) => {\n            print_string(load_error);\n        }\n",
+                "    }\n",
+            )),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn three_exit_node_matches_each_variant_into_its_chain() {
+        use crate::metadata::ExitVariant;
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("parse", "parse_token")
+            .property("parse", "text", PropertyValue::String("42".to_string()))
+            .output("parse", "value", "u32")
+            .output("parse", "word", "String")
+            .node("show", "print_string")
+            .node("despawn", "despawn")
+            .data("parse", "value", "despawn", "entity")
+            .node("blank", "print_string")
+            .property("blank", "message", PropertyValue::String("blank".to_string()))
+            .exec("main", "body", "parse")
+            .exec("parse", "number", "despawn")
+            .exec("parse", "word", "show")
+            .exec("parse", "empty", "blank")
+            .data("parse", "word", "show", "message")
+            .build();
+        let exits = vec![
+            ExitVariant::with_payload("Token::Number", "number", "value", "u32"),
+            ExitVariant::with_payload("Token::Word", "word", "word", "String"),
+            ExitVariant::unit("Token::Empty", "empty"),
+        ];
+        let provider = provider().with_attributes("parse_token", NodeAttributes::exits(exits));
+
+        let code = Compiler::with_provider(provider).compile(&graph).unwrap();
+        assert!(
+            code.contains(concat!(
+                "    match parse_token(\"42\") {\n",
+                "        Token::Number(parse_value) => {\n            despawn(parse_value);\n        }\n",
+                "        Token::Word(parse_word) => {\n            print_string(parse_word);\n        }\n",
+                "        Token::Empty => {\n            print_string(\"blank\");\n        }\n",
+                "    }\n",
+            )),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn unhandled_failure_follows_the_error_policy() {
+        use crate::metadata::FallibleBranches;
+        use crate::options::ErrorPolicy;
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("load", "read_file")
+            .property("load", "path", PropertyValue::String("save.txt".to_string()))
+            .output("load", "value", "String")
+            .node("show", "print_string")
+            .exec("main", "body", "load")
+            .exec("load", "success", "show")
+            .data("load", "value", "show", "message")
+            .build();
+        let compile = |error_policy| {
+            let provider = provider().with_attributes("read_file", NodeAttributes::fallible(FallibleBranches::default()));
+            let options = CompileOptions { error_policy, ..CompileOptions::default() };
+            Compiler::with_provider(provider).with_options(options).compile(&graph).unwrap()
+        };
+
+        let ok_arm = "    match read_file(\"save.txt\") {\n        Ok(load_value) => {\n            print_string(load_value);\n        }\n";
+        let code = compile(ErrorPolicy::LogAndContinue);
+        assert!(code.contains(&format!("{}        Err(err) => tracing::error!(\"node load failed: {{:?}}\", err),\n    }}\n", ok_arm)), "{}", code);
+        let code = compile(ErrorPolicy::Hook("on_node_error".to_string()));
+        assert!(code.contains(&format!("{}        Err(err) => on_node_error(\"load\", err),\n    }}\n", ok_arm)), "{}", code);
+    }
+
+    #[test]
+    fn async_main_under_tokio_is_the_runtime_entry_point() {
+        use crate::codegen::runtime::TokioRuntime;
+        use std::sync::Arc;
+
+        let graph = print(GraphBuilder::new().node("main", "main"), "hello", "hi")
+            .exec("main", "body", "hello")
+            .build();
+        let options = CompileOptions { runtime: Some(Arc::new(TokioRuntime)), ..CompileOptions::default() };
+
+        let code = Compiler::with_provider(provider().with_attributes("main", NodeAttributes::async_event()))
+            .with_options(options)
+            .compile(&graph)
+            .unwrap();
+        assert!(code.contains("#[tokio::main]\nasync fn main() {\n    print_string(\"hi\");\n}\n"), "{}", code);
+    }
+
+    #[test]
+    fn event_bodies_run_inside_their_spans() {
+        let graph = print(print(GraphBuilder::new().node("begin_play", "begin_play").node("key_pressed", "key_pressed"), "ready", "ready"), "key", "key")
+            .exec("begin_play", "body", "ready")
+            .exec("key_pressed", "body", "key")
+            .build();
+        let options = CompileOptions { emit_spans: true, ..CompileOptions::default() };
+
+        let code = Compiler::with_provider(provider().with_attributes("begin_play", NodeAttributes::async_event()))
+            .with_options(options)
+            .compile(&graph)
+            .unwrap();
+
+        assert!(code.contains(
+            "    let span = tracing::info_span!(\"begin_play\");\n    tracing::Instrument::instrument(async move {\n        print_string(\"ready\");\n    }, span)\n    .await;\n}"
+        ));
+        assert!(code.contains(
+            "    let _span = tracing::info_span!(\"key_pressed\", state = ?state).entered();\n    print_string(\"key\");\n}"
+        ));
+    }
+
+    #[test]
+    fn array_get_checks_its_index_in_debug_builds() {
+        use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+        use graphy::NodeTypes;
+
+        let array_get = NodeMetadata::new("array_get", NodeTypes::fn_, "Arrays")
+            .with_params(vec![ParamInfo::new("array", "Vec<String>"), ParamInfo::new("index", "usize")])
+            .with_return_type(TypeInfo::new("String".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn array_get(array: Vec<String>, index: usize) -> String {\n    array[index].clone()\n}");
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("list", "list_names")
+            .bare("get", "array_get")
+            .input("get", "exec", "()")
+            .input("get", "array", "Vec<String>")
+            .input("get", "index", "usize")
+            .output("get", "result", "String")
+            .property("get", "index", PropertyValue::Number(2.0))
+            .exec("main", "body", "list")
+            .exec("list", "exec_out", "get")
+            .data("list", "result", "get", "array")
+            .build();
+        let compile = |emit_debug_asserts| {
+            let provider = provider()
+                .with_node_metadata(array_get.clone())
+                .with_attributes("array_get", NodeAttributes::default().with_debug_assertion("{index} < {array}.len()"));
+            let options = CompileOptions { emit_debug_asserts, ..CompileOptions::default() };
+            Compiler::with_provider(provider).with_options(options).compile(&graph).unwrap()
+        };
+
+        let code = compile(true);
+        assert!(
+            code.contains(concat!(
+                "    debug_assert!(2usize < list_result.len(), ",
+                "\"array_get node 'get': precondition `{{index}} < {{array}}.len()` failed\");\n",
+                "    let get_result = array_get(list_result, 2usize);\n",
+            )),
+            "{}",
+            code
+        );
+        assert!(!compile(false).contains("debug_assert!"));
+    }
+
+    #[test]
+    fn wasm_exports_are_marked_wasm_bindgen() {
+        use crate::options::TargetSpec;
+        use std::collections::BTreeSet;
+
+        let graph = print(GraphBuilder::new().node("main", "main").node("begin_play", "begin_play"), "hello", "hi")
+            .exec("main", "body", "hello")
+            .build();
+        let options = CompileOptions {
+            target: Some(TargetSpec { wasm: true, ..TargetSpec::default() }),
+            wasm_exports: BTreeSet::from(["main".to_string()]),
+            ..CompileOptions::default()
+        };
+
+        let code = Compiler::with_provider(provider()).with_options(options).compile(&graph).unwrap();
+        assert!(code.contains("use wasm_bindgen::prelude::*;\n"), "{}", code);
+        assert!(code.contains("#[wasm_bindgen]\npub fn main() {"), "{}", code);
+        assert_eq!(code.matches("#[wasm_bindgen]").count(), 1, "{}", code);
+    }
+
+    #[test]
+    fn pure_node_reached_while_inlining_it_is_a_data_cycle() {
+        use super::BlueprintCodeGenerator;
+        use graphy::{DataResolver, ExecutionRouting};
+
+        let graph = GraphBuilder::new().node("a", "add").node("b", "add").data("b", "result", "a", "a").build();
+        let provider = provider();
+        let data_resolver = DataResolver::build(&graph, &provider).unwrap();
+        let exec_routing = ExecutionRouting::build_from_graph(&graph);
+        let options = CompileOptions::default();
+        let generator = BlueprintCodeGenerator::new(&graph, &provider, &data_resolver, &exec_routing, HashMap::new(), &options);
+
+        // As if `b` depended on `a` again, past the cycle check
+        generator.inlining.borrow_mut().push("b".to_string());
+        match generator.inline_pure_node(&graph.nodes["a"]) {
+            Err(CompileError::DataCycle { nodes }) => assert_eq!(nodes, ["b", "a"]),
+            other => panic!("expected DataCycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn borrow_is_used_in_place_but_never_bound() {
+        use super::{borrowed_variable, expression_lifetime, BlueprintCodeGenerator, ExprLifetime};
+        use graphy::{DataResolver, ExecutionRouting};
+
+        assert_eq!(expression_lifetime("&self.name"), ExprLifetime::Borrowed);
+        assert_eq!(expression_lifetime("NAME.with(|name_ref| measure(&name_ref.borrow()))"), ExprLifetime::Owned);
+        assert_eq!(expression_lifetime("self.name.clone()"), ExprLifetime::Owned);
+        assert_eq!(borrowed_variable("&name_ref.borrow()"), Some("name"));
+        assert_eq!(borrowed_variable("&self.stats.health"), Some("stats"));
+
+        let graph = GraphBuilder::new().node("main", "main").build();
+        let provider = provider();
+        let data_resolver = DataResolver::build(&graph, &provider).unwrap();
+        let exec_routing = ExecutionRouting::build_from_graph(&graph);
+        let options = CompileOptions::default();
+        let generator = BlueprintCodeGenerator::new(&graph, &provider, &data_resolver, &exec_routing, HashMap::new(), &options);
+
+        let err = generator.queue_binding("show", "name_value", None, "&self.name".to_string()).unwrap_err();
+        assert!(matches!(err, CompileError::BorrowedBinding { ref binding, .. } if binding == "name_value"), "{:?}", err);
+        generator.queue_binding("show", "name_value", None, "self.name.clone()".to_string()).unwrap();
+        assert_eq!(generator.take_pending_bindings("    "), "    let name_value = self.name.clone();\n");
+    }
+
+    #[test]
+    fn colliding_result_names_are_numbered_in_node_id_order() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("spawn-1", "spawn")
+            .property("spawn-1", "prefab", PropertyValue::String("orc".to_string()))
+            .node("spawn_1", "spawn")
+            .property("spawn_1", "prefab", PropertyValue::String("elf".to_string()))
+            .node("first", "despawn")
+            .node("second", "despawn")
+            .exec("main", "body", "spawn-1")
+            .exec("spawn-1", "exec_out", "spawn_1")
+            .exec("spawn_1", "exec_out", "first")
+            .exec("first", "exec_out", "second")
+            .data("spawn-1", "result", "first", "entity")
+            .data("spawn_1", "result", "second", "entity")
+            .build();
+
+        // `spawn-1` sorts first, so it keeps the plain name
+        let code = Compiler::with_provider(provider()).compile(&graph).unwrap();
+        assert!(
+            code.contains(concat!(
+                "    let spawn_1_result = spawn(\"orc\");\n",
+                "    let spawn_1_result_2 = spawn(\"elf\");\n",
+                "    despawn(spawn_1_result);\n",
+                "    despawn(spawn_1_result_2);\n",
+            )),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn never_set_variable_is_a_const() {
+        use crate::codegen::VariableDef;
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("get_limit", "get_limit")
+            .output("get_limit", "value", "u32")
+            .node("despawn", "despawn")
+            .bare("set_count", "set_count")
+            .input("set_count", "exec", "()")
+            .input("set_count", "value", "u32")
+            .property("set_count", "value", PropertyValue::Number(1.0))
+            .exec("main", "body", "despawn")
+            .exec("despawn", "exec_out", "set_count")
+            .data("get_limit", "value", "despawn", "entity")
+            .build();
+        let variables = HashMap::from([
+            ("limit".to_string(), VariableDef::new("u32").with_initial(PropertyValue::Number(7.0))),
+            ("count".to_string(), VariableDef::new("u32")),
+        ]);
+
+        let code = Compiler::with_provider(provider()).compile_with_variables(&graph, variables).unwrap();
+        assert!(code.contains("const LIMIT: u32 = 7u32;\n"), "{}", code);
+        assert!(code.contains("    static COUNT: std::cell::Cell<u32> = std::cell::Cell::new(0u32);\n"), "{}", code);
+        assert!(code.contains("    despawn(LIMIT);\n"), "{}", code);
+    }
+
+    #[test]
+    fn i128_and_copy_tuple_variables_live_in_cells() {
+        use crate::codegen::VariableDef;
+
+        let graph = branch(GraphBuilder::new().node("main", "main").node("begin_play", "begin_play"), "branch")
+            .bare("set_score", "set_score")
+            .input("set_score", "exec", "()")
+            .input("set_score", "value", "i128")
+            .property("set_score", "value", PropertyValue::Number(5.0))
+            .bare("get_score", "get_score")
+            .output("get_score", "value", "i128")
+            .bare("set_best", "set_best")
+            .input("set_best", "exec", "()")
+            .input("set_best", "value", "i128")
+            .bare("set_position", "set_position")
+            .input("set_position", "exec", "()")
+            .input("set_position", "value", "(f32, f32)")
+            .exec("main", "body", "branch")
+            .exec("branch", "True", "set_score")
+            .exec("branch", "False", "set_best")
+            .exec("begin_play", "body", "set_position")
+            .data("get_score", "value", "set_best", "value")
+            .build();
+        let variables = HashMap::from([
+            ("score".to_string(), VariableDef::new("i128")),
+            ("best".to_string(), VariableDef::new("i128")),
+            ("position".to_string(), VariableDef::new("(f32, f32)")),
+        ]);
+
+        let code = Compiler::with_provider(provider()).compile_with_variables(&graph, variables).unwrap();
+        assert!(code.contains("    static SCORE: std::cell::Cell<i128> = std::cell::Cell::new(0i128);\n"), "{}", code);
+        assert!(code.contains("    static POSITION: std::cell::Cell<(f32, f32)> = std::cell::Cell::new((0.0f32, 0.0f32));\n"), "{}", code);
+        assert!(code.contains("        SCORE.with(|v| v.set(5i128));\n"), "{}", code);
+        assert!(code.contains("        BEST.with(|v| v.set(SCORE.with(|v| v.get())));\n"), "{}", code);
+        assert!(code.contains("    POSITION.with(|v| v.set((0.0f32, 0.0f32)));\n"), "{}", code);
+    }
+
+    #[test]
+    fn dispatch_decodes_two_typed_event_arguments() {
+        let graph = GraphBuilder::new()
+            .node("damaged", "damaged")
+            .node("print", "print_string")
+            .node("despawn", "despawn")
+            .exec("damaged", "body", "print")
+            .exec("print", "exec_out", "despawn")
+            .data("damaged", "source", "print", "message")
+            .data("damaged", "amount", "despawn", "entity")
+            .build();
+        let options = CompileOptions { emit_dispatch: true, ..CompileOptions::default() };
+
+        let code = Compiler::with_provider(provider()).with_options(options).compile(&graph).unwrap();
+        assert!(code.contains("pub fn damaged(amount: u32, source: String) {\n"), "{}", code);
+        assert!(
+            code.contains(concat!(
+                "        \"damaged\" => {\n",
+                "            if args.len() != 2 {\n",
+                "                return Err(DispatchError::ArgCount { event: \"damaged\", expected: 2, found: args.len() });\n",
+                "            }\n",
+                "            let amount: u32 = match &args[0] { Value::Int(v) => match u32::try_from(*v) { Ok(v) => v, ",
+                "Err(_) => return Err(DispatchError::ArgType { event: \"damaged\", index: 0, expected: \"u32\" }) }, ",
+                "_ => return Err(DispatchError::ArgType { event: \"damaged\", index: 0, expected: \"u32\" }) };\n",
+                "            let source: String = match &args[1] { Value::String(v) => v.clone(), ",
+                "_ => return Err(DispatchError::ArgType { event: \"damaged\", index: 1, expected: \"String\" }) };\n",
+                "            damaged(amount, source);\n",
+                "            Ok(())\n",
+                "        }\n",
+                "        _ => Err(DispatchError::UnknownEvent(event.to_string())),\n",
+            )),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn mistyped_control_flow_placeholder_is_rejected() {
+        use graphy::core::{NodeMetadata, ParamInfo};
+        use graphy::NodeTypes;
+
+        let branch_source = "fn branch(condition: bool) {\n    if condition {\n        {Ture}\n    } else {\n        {False}\n    }\n}";
+        let typo = NodeMetadata::new("branch", NodeTypes::control_flow, "Flow")
+            .with_params(vec![ParamInfo::new("condition", "bool")])
+            .with_exec_outputs(vec!["True".to_string(), "False".to_string()])
+            .with_source(branch_source);
+        let graph = print(branch(GraphBuilder::new().node("main", "main"), "branch"), "yes", "yes")
+            .exec("main", "body", "branch")
+            .exec("branch", "True", "yes")
+            .build();
+
+        let err = Compiler::with_provider(provider().with_node_metadata(typo)).compile(&graph).unwrap_err();
+        assert!(
+            matches!(err, CompileError::UnsubstitutedPlaceholder { ref node_type, ref placeholder } if node_type == "branch" && placeholder == "Ture"),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.to_string(), "Template of node type 'branch' has placeholder '{Ture}', which matches none of its pins");
+    }
+
+    #[test]
+    fn cfg_gated_node_wraps_its_chain_and_import() {
+        use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+        use graphy::NodeTypes;
+
+        let spawn = NodeMetadata::new("spawn", NodeTypes::fn_, "World")
+            .with_params(vec![ParamInfo::new("prefab", "String")])
+            .with_return_type(TypeInfo::new("u32".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_imports(vec!["use world::spawn;".to_string()]);
+        let provider = provider()
+            .with_node_metadata(spawn)
+            .with_attributes("spawn", NodeAttributes::cfg("feature = \"world\""));
+        let graph = print(GraphBuilder::new().node("main", "main"), "hello", "hi")
+            .node("spawn", "spawn")
+            .node("despawn", "despawn")
+            .exec("main", "body", "hello")
+            .exec("hello", "exec_out", "spawn")
+            .exec("spawn", "exec_out", "despawn")
+            .data("spawn", "result", "despawn", "entity")
+            .build();
+
+        let code = Compiler::with_provider(provider).compile(&graph).unwrap();
+        assert!(code.contains("#[cfg(feature = \"world\")]\nuse world::spawn;\n"), "{}", code);
+        assert!(
+            code.contains(concat!(
+                "    print_string(\"hi\");\n",
+                "    #[cfg(feature = \"world\")]\n    {\n",
+                "        let spawn_result = spawn(String::new());\n",
+                "        despawn(spawn_result);\n",
+                "    }\n",
+            )),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn pure_bindings_are_numbered_per_type_avoiding_taken_names() {
+        use super::{pure_binding_names, result_variable_names};
+        use crate::codegen::VariableDef;
+        use graphy::DataResolver;
+
+        let graph = GraphBuilder::new()
+            .node("coin_a", "random_bool")
+            .node("coin_b", "random_bool")
+            .node("both", "and")
+            .node("either", "or")
+            .data("coin_a", "result", "both", "a")
+            .data("coin_b", "result", "both", "b")
+            .data("coin_a", "result", "either", "a")
+            .data("coin_b", "result", "either", "b")
+            .build();
+        let provider = provider().with_attributes("random_bool", NodeAttributes::non_deterministic());
+        let data_resolver = DataResolver::build(&graph, &provider).unwrap();
+        let variables = HashMap::from([("random_bool_result_1".to_string(), VariableDef::new("bool"))]);
+        let result_variables = result_variable_names(&graph, &data_resolver, &variables);
+
+        let names = pure_binding_names(&graph, &provider, &data_resolver, &variables, &result_variables);
+        assert_eq!(names.len(), 2, "{:?}", names);
+        assert_eq!(names["coin_a"], "random_bool_result_1_2");
+        assert_eq!(names["coin_b"], "random_bool_result_2");
+    }
+
+    #[test]
+    fn event_reads_its_inputs_off_a_context_struct() {
+        use crate::options::{ContextSpec, EventContext};
+
+        let graph = GraphBuilder::new()
+            .node("damaged", "damaged")
+            .node("print", "print_string")
+            .node("despawn", "despawn")
+            .exec("damaged", "body", "print")
+            .exec("print", "exec_out", "despawn")
+            .data("damaged", "source", "print", "message")
+            .data("damaged", "amount", "despawn", "entity")
+            .build();
+        let context = EventContext::new("DamageContext").with_field("source", "attacker");
+        let options = CompileOptions {
+            context_param: Some(ContextSpec::new().with_event("damaged", context)),
+            ..CompileOptions::default()
+        };
+
+        let code = Compiler::with_provider(provider()).with_options(options).compile(&graph).unwrap();
+        assert!(
+            code.contains("pub fn damaged(ctx: &DamageContext) {\n    print_string(ctx.attacker.clone());\n    despawn(ctx.amount);\n}"),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn nested_field_setter_writes_the_field_in_place() {
+        use crate::codegen::VariableDef;
+
+        let setter = |path: &str| {
+            let node_type = format!("set_player.{}", path);
+            GraphBuilder::new()
+                .node("main", "main")
+                .bare("move", &node_type)
+                .input("move", "exec", "()")
+                .input("move", "value", "f32")
+                .property("move", "value", PropertyValue::Number(3.0))
+                .exec("main", "body", "move")
+                .build()
+        };
+        let compile = |graph: &GraphDescription| {
+            let player = VariableDef::new("Player").with_field("position", "Vec2").with_field("position.x", "f32");
+            Compiler::with_provider(provider()).compile_with_variables(graph, HashMap::from([("player".to_string(), player)]))
+        };
+
+        let code = compile(&setter("position.x")).unwrap();
+        assert!(code.contains("pub fn main() {\n    PLAYER.with(|v| v.borrow_mut().position.x = 3.0f32);\n}"), "{}", code);
+
+        match compile(&setter("position.z")) {
+            Err(CompileError::UnknownField { var, path, node_id }) => {
+                assert_eq!((var.as_str(), path.as_str(), node_id.as_str()), ("player", "position.z", "move"));
+            }
+            other => panic!("expected UnknownField, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn loop_borrowing_a_variable_its_body_writes_is_rejected() {
+        use crate::codegen::ClassContext;
+        use graphy::core::{NodeMetadata, ParamInfo};
+        use graphy::NodeTypes;
+
+        let for_each = NodeMetadata::new("for_each", NodeTypes::control_flow, "Flow")
+            .with_params(vec![ParamInfo::new("array", "&Vec<String>")])
+            .with_exec_outputs(vec!["body".to_string(), "completed".to_string()])
+            .with_source("fn for_each(array: &Vec<String>) {\n    for item in array.iter() {\n        exec_output!(\"body\");\n    }\n    exec_output!(\"completed\");\n}");
+        let graph = |written: &str| {
+            let setter = format!("set_{}", written);
+            GraphBuilder::new()
+                .node("main", "main")
+                .bare("get_names", "get_names")
+                .output("get_names", "value", "Vec<String>")
+                .node("each", "for_each")
+                .bare("clear", &setter)
+                .input("clear", "exec", "()")
+                .input("clear", "value", "Vec<String>")
+                .exec("main", "body", "each")
+                .exec("each", "body", "clear")
+                .data("get_names", "value", "each", "array")
+                .build()
+        };
+        let compile = |graph: &GraphDescription, class: Option<ClassContext>| {
+            let variables = HashMap::from([("names".to_string(), "Vec<String>"), ("seen".to_string(), "Vec<String>")]);
+            Compiler::with_provider(provider().with_node_metadata(for_each.clone()))
+                .with_options(CompileOptions { class, ..CompileOptions::default() })
+                .compile_with_variables(graph, variables)
+        };
+
+        // Getters read an owned copy, so the body's write can't clash with the loop's read
+        let code = compile(&graph("names"), Some(ClassContext::new("Player"))).unwrap();
+        assert!(code.contains("        for item in self.names.clone().iter() {\n            self.names = Default::default();\n"), "{}", code);
+        let code = compile(&graph("names"), None).unwrap();
+        assert!(
+            code.contains("    for item in NAMES.with(|v| v.borrow().clone()).iter() {\n        NAMES.with(|v| *v.borrow_mut() = Default::default());\n"),
+            "{}",
+            code
+        );
+
+        // A borrow of the variable held by the loop is rejected when the body writes it
+        let reject = |graph: GraphDescription| {
+            use super::BlueprintCodeGenerator;
+            use crate::codegen::VariableDef;
+            use graphy::{DataResolver, ExecutionRouting};
+
+            let provider = provider().with_node_metadata(for_each.clone());
+            let data_resolver = DataResolver::build(&graph, &provider).unwrap();
+            let exec_routing = ExecutionRouting::build_from_graph(&graph);
+            let options = CompileOptions { class: Some(ClassContext::new("Player")), ..CompileOptions::default() };
+            let variables = ["names", "seen"].map(|name| (name.to_string(), VariableDef::new("Vec<String>")));
+            let mut generator = BlueprintCodeGenerator::new(&graph, &provider, &data_resolver, &exec_routing, HashMap::from(variables), &options);
+            generator.bind_scope_value("get_names", "get_names_value", "&self.names".to_string());
+            generator.generate_program().map(|_| ())
+        };
+        match reject(graph("names")) {
+            Err(CompileError::PotentialDoubleBorrow { var, node_id }) => assert_eq!((var.as_str(), node_id.as_str()), ("names", "each")),
+            other => panic!("expected PotentialDoubleBorrow, got {:?}", other),
+        }
+        assert!(reject(graph("seen")).is_ok());
+    }
+
+    #[test]
+    fn result_variable_steps_around_a_class_variable_of_the_same_name() {
+        use crate::codegen::ClassContext;
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("spawn", "spawn")
+            .property("spawn", "prefab", PropertyValue::String("orc".to_string()))
+            .node("despawn", "despawn")
+            .bare("get_spawn_result", "get_spawn_result")
+            .output("get_spawn_result", "value", "u32")
+            .node("last", "despawn")
+            .exec("main", "body", "spawn")
+            .exec("spawn", "exec_out", "despawn")
+            .exec("despawn", "exec_out", "last")
+            .data("spawn", "result", "despawn", "entity")
+            .data("get_spawn_result", "value", "last", "entity")
+            .build();
+        let compile = |class: Option<ClassContext>| {
+            let variables = HashMap::from([("spawn_result".to_string(), "u32")]);
+            Compiler::with_provider(provider())
+                .with_options(CompileOptions { class, ..CompileOptions::default() })
+                .compile_with_variables(&graph, variables)
+                .unwrap()
+        };
+
+        let code = compile(None);
+        assert!(
+            code.contains("    let spawn_result_2 = spawn(\"orc\");\n    despawn(spawn_result_2);\n    despawn(SPAWN_RESULT);\n"),
+            "{}",
+            code
+        );
+        let code = compile(Some(ClassContext::new("Player")));
+        assert!(
+            code.contains("        let spawn_result_2 = spawn(\"orc\");\n        despawn(spawn_result_2);\n        despawn(self.spawn_result);\n"),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn c_export_has_the_c_abi_and_rejects_strings() {
+        use std::collections::BTreeSet;
+
+        let graph = print(print(GraphBuilder::new().node("key_pressed", "key_pressed").node("damaged", "damaged"), "key", "key"), "hit", "hit")
+            .exec("key_pressed", "body", "key")
+            .exec("damaged", "body", "hit")
+            .build();
+        let compile = |exports: &[&str]| {
+            let options = CompileOptions {
+                c_exports: exports.iter().map(|name| name.to_string()).collect::<BTreeSet<_>>(),
+                ..CompileOptions::default()
+            };
+            Compiler::with_provider(provider()).with_options(options).compile(&graph)
+        };
+
+        let code = compile(&["key_pressed"]).unwrap();
+        assert!(
+            code.contains("/// C: `void key_pressed(bool state);`\n#[no_mangle]\npub extern \"C\" fn key_pressed(state: bool) {\n"),
+            "{}",
+            code
+        );
+        assert!(code.contains("\npub fn damaged(amount: u32, source: String) {\n"), "{}", code);
+
+        match compile(&["damaged"]) {
+            Err(CompileError::UnsupportedTarget { construct, .. }) => {
+                assert_eq!(construct, "parameter 'source: String' of event 'damaged'");
+            }
+            other => panic!("expected UnsupportedTarget, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn non_finite_constants_become_float_constants() {
+        use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+        use graphy::NodeTypes;
+
+        let move_at = NodeMetadata::new("move_at", NodeTypes::fn_, "Movement")
+            .with_params(vec![ParamInfo::new("speed", "f32")])
+            .with_return_type(TypeInfo::new("()".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()]);
+        let speed = |id: &str, builder: GraphBuilder, value: f64| {
+            builder
+                .bare(id, "move_at")
+                .input(id, "exec", "()")
+                .input(id, "speed", "f32")
+                .property(id, "speed", PropertyValue::Number(value))
+        };
+        let builder = GraphBuilder::new().node("main", "main").node("key_pressed", "key_pressed").node("begin_play", "begin_play");
+        let builder = speed("fast", builder, f64::INFINITY);
+        let builder = speed("back", builder, f64::NEG_INFINITY);
+        let graph = speed("unset", builder, f64::NAN)
+            .exec("main", "body", "fast")
+            .exec("key_pressed", "body", "back")
+            .exec("begin_play", "body", "unset")
+            .build();
+
+        let code = Compiler::with_provider(provider().with_node_metadata(move_at)).compile(&graph).unwrap();
+        assert!(code.contains("pub fn main() {\n    move_at(f32::INFINITY);\n}"), "{}", code);
+        assert!(code.contains("pub fn key_pressed(state: bool) {\n    move_at(f32::NEG_INFINITY);\n}"), "{}", code);
+        assert!(code.contains("pub fn begin_play() {\n    move_at(f32::NAN);\n}"), "{}", code);
+    }
+
+    #[test]
+    fn order_comments_number_statements_in_execution_order() {
+        let graph = print(print(print(branch(GraphBuilder::new().node("main", "main"), "check"), "start", "start"), "yes", "yes"), "no", "no")
+            .node("spawn", "spawn")
+            .property("spawn", "prefab", PropertyValue::String("orc".to_string()))
+            .exec("main", "body", "start")
+            .exec("start", "exec_out", "spawn")
+            .exec("spawn", "exec_out", "check")
+            .exec("check", "True", "yes")
+            .exec("check", "False", "no")
+            .build();
+        let compile = |emit_order_comments: bool| {
+            let options = CompileOptions { emit_order_comments, ..CompileOptions::default() };
+            Compiler::with_provider(provider()).with_options(options).compile(&graph).unwrap()
+        };
+
+        let code = compile(true);
+        assert!(
+            code.contains(concat!(
+                "pub fn main() {\n",
+                "    print_string(\"start\"); // [#1] node start\n",
+                "    let spawn_result = spawn(\"orc\"); // [#2] node spawn\n",
+                "    if random_bool() { // [#3] node check\n",
+                "        print_string(\"yes\"); // [#4] node yes\n",
+                "    } else {\n",
+                "        print_string(\"no\"); // [#5] node no\n",
+                "    }\n",
+                "}\n",
+            )),
+            "{}",
+            code
+        );
+
+        let plain = compile(false);
+        assert_eq!(plain, code.lines().map(|line| line.split(" // [#").next().unwrap()).collect::<Vec<_>>().join("\n") + "\n");
+    }
+
+    #[test]
+    fn method_node_calls_through_self_only_in_a_class() {
+        use crate::codegen::ClassContext;
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("spawn", "spawn")
+            .property("spawn", "prefab", PropertyValue::String("orc".to_string()))
+            .node("despawn", "despawn")
+            .exec("main", "body", "spawn")
+            .exec("spawn", "exec_out", "despawn")
+            .data("spawn", "result", "despawn", "entity")
+            .build();
+        let compile = |class: Option<ClassContext>| {
+            Compiler::with_provider(provider().with_attributes("spawn", NodeAttributes::self_method()))
+                .with_options(CompileOptions { class, ..CompileOptions::default() })
+                .compile(&graph)
+        };
+
+        let code = compile(Some(ClassContext::new("Player"))).unwrap();
+        assert!(
+            code.contains("    pub fn main(&mut self) {\n        let spawn_result = self.spawn(\"orc\");\n        despawn(spawn_result);\n    }"),
+            "{}",
+            code
+        );
+
+        match compile(None) {
+            Err(CompileError::UnsupportedTarget { construct, .. }) => assert_eq!(construct, "method node 'spawn'"),
+            other => panic!("expected UnsupportedTarget, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn print_nodes_go_to_the_configured_macro() {
+        let graph = print(GraphBuilder::new().node("main", "main"), "hello", "hi").exec("main", "body", "hello").build();
+        let compile = |print_macro: &str| {
+            let options = CompileOptions { print_macro: print_macro.to_string(), ..CompileOptions::default() };
+            Compiler::with_provider(provider().with_attributes("print_string", NodeAttributes::print("\"{message}: {}\", {message}")))
+                .with_options(options)
+                .compile(&graph)
+                .unwrap()
+        };
+
+        let code = compile("engine_log!");
+        assert!(code.contains("pub fn main() {\n    engine_log!(\"{message}: {}\", \"hi\");\n}"), "{}", code);
+        let code = compile("");
+        assert!(code.contains("pub fn main() {\n    println!(\"{message}: {}\", \"hi\");\n}"), "{}", code);
     }
 }
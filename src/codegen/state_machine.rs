@@ -0,0 +1,488 @@
+//! # Latent Node State Machines
+//!
+//! Events containing latent nodes (delay, timer, wait) can't run as a single
+//! synchronous function. Instead, each latent node on the event's main exec
+//! path splits it into states, and the event compiles to a struct whose
+//! `step(&mut self)` method runs as far as it can before returning:
+//!
+//! ```ignore
+//! pub enum OnTickState { Start, WaitDelay1, Done }
+//!
+//! impl OnTickMachine {
+//!     pub fn step(&mut self) -> bool {
+//!         loop {
+//!             match self.state {
+//!                 OnTickState::Start => { ...; self.state = OnTickState::WaitDelay1; }
+//!                 OnTickState::WaitDelay1 => {
+//!                     if !delay(1.5) {
+//!                         return false;
+//!                     }
+//!                     ...
+//!                     self.state = OnTickState::Done;
+//!                 }
+//!                 OnTickState::Done => return true,
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! A latent node's function is polled once per `step()`. One returning `bool`
+//! reports whether its wait is over; one returning `Option<T>` yields its
+//! output once ready, bound to the node's result variable. Event parameters
+//! are passed to `new` and stored on the machine, and a value computed in one
+//! state and read in a later one is carried across the wait in an `Option`
+//! field. Generated variant and field names that would clash get a numeric
+//! suffix.
+
+use super::rust_codegen::{BlueprintCodeGenerator, is_copy_type};
+use crate::error::CompileError;
+use graphy::{GraphyError, NodeInstance};
+use std::collections::{HashMap, HashSet};
+
+/// Indentation of statements inside a state's match arm
+const ARM_BODY_INDENT: usize = 5;
+
+/// One state of the machine: the latent node it waits on, and the code of its match arm
+struct State<'a> {
+    wait_on: Option<&'a NodeInstance>,
+    code: String,
+}
+
+/// A local computed in one state and read in a later one
+struct CarriedValue {
+    name: String,
+    /// Machine field the value is kept in between steps
+    field: String,
+    ty: String,
+    /// Index of the state computing it
+    state: usize,
+}
+
+/// Generate the state enum, machine struct and `step` method for an event
+pub(super) fn generate_state_machine(
+    generator: &BlueprintCodeGenerator,
+    event_node: &NodeInstance,
+    event_name: &str,
+) -> Result<String, CompileError> {
+    let params = generator.event_params(event_node);
+
+    // `state` is the machine's own field; parameters and carried values make way for it
+    let mut fields = UniqueNames::new(&["state"], "_");
+    let param_fields: Vec<String> = params.iter().map(|param| fields.claim(&param.name)).collect();
+
+    let mut segment_gen = generator.clone_with_new_visited();
+    segment_gen.follow_exec = false;
+    for (param, field) in params.iter().zip(&param_fields) {
+        let read = if is_copy_type(&param.ty) {
+            format!("self.{}", field)
+        } else {
+            format!("self.{}.clone()", field)
+        };
+        segment_gen.bind_scope_value(&event_node.id, &param.pin_id, read);
+    }
+
+    let arm_indent = "    ".repeat(ARM_BODY_INDENT);
+    let mut states = vec![State { wait_on: None, code: String::new() }];
+    // Types of the latent nodes' outputs, by the local they are bound to
+    let mut latent_outputs = HashMap::new();
+    let mut seen = HashSet::new();
+    let mut next = generator.exec_successors(event_node);
+
+    loop {
+        let node = match next.as_slice() {
+            [] => break,
+            [node] => *node,
+            _ => {
+                // The path forks: the rest runs to completion in the current state
+                segment_gen.follow_exec = true;
+                let state = states.last_mut().expect("machine has a start state");
+                for node in next {
+                    state.code.push_str(&segment_gen.generate_exec_chain(node, ARM_BODY_INDENT)?);
+                }
+                break;
+            }
+        };
+        if !seen.insert(node.id.clone()) {
+            break;
+        }
+
+        if generator.is_latent(node) {
+            let (code, output) = poll_latent(generator, &segment_gen, node, &arm_indent)?;
+            latent_outputs.extend(output);
+            states.push(State {
+                wait_on: Some(node),
+                code,
+            });
+        } else {
+            let code = segment_gen.generate_exec_chain(node, ARM_BODY_INDENT)?;
+            states.last_mut().expect("machine has a start state").code.push_str(&code);
+        }
+
+        next = generator.exec_successors(node);
+    }
+
+    let carried = carried_values(generator, &states, &latent_outputs, &mut fields, event_name)?;
+
+    tracing::info!(
+        "[PBGC] Event '{}' compiled to a state machine with {} wait state(s) and {} carried value(s)",
+        event_name,
+        states.len() - 1,
+        carried.len()
+    );
+
+    let type_prefix = pascal_case(event_name);
+    let state_enum = format!("{}State", type_prefix);
+    let machine = format!("{}Machine", type_prefix);
+    let mut variant_names = UniqueNames::new(&["Start", "Done"], "");
+    let variants: Vec<String> = states
+        .iter()
+        .map(|state| match state.wait_on {
+            Some(node) => variant_names.claim(&format!("Wait{}", pascal_case(&node.id))),
+            None => "Start".to_string(),
+        })
+        .collect();
+
+    let mut code = String::new();
+
+    // State enum
+    code.push_str("#[derive(Debug, Clone, Copy, PartialEq, Eq)]\n");
+    code.push_str(&format!("pub enum {} {{\n", state_enum));
+    for variant in &variants {
+        code.push_str(&format!("    {},\n", variant));
+    }
+    code.push_str("    Done,\n");
+    code.push_str("}\n\n");
+
+    // Machine struct
+    code.push_str(&format!("pub struct {} {{\n", machine));
+    code.push_str(&format!("    state: {},\n", state_enum));
+    for (param, field) in params.iter().zip(&param_fields) {
+        code.push_str(&format!("    {}: {},\n", field, param.ty));
+    }
+    for value in &carried {
+        code.push_str(&format!("    {}: Option<{}>,\n", value.field, value.ty));
+    }
+    code.push_str("}\n\n");
+
+    let param_list: Vec<String> = params.iter().map(|p| format!("{}: {}", p.name, p.ty)).collect();
+    let field_inits: Vec<String> = std::iter::once(format!("state: {}::Start", state_enum))
+        .chain(params.iter().zip(&param_fields).map(|(param, field)| match *field == param.name {
+            true => field.clone(),
+            false => format!("{}: {}", field, param.name),
+        }))
+        .chain(carried.iter().map(|value| format!("{}: None", value.field)))
+        .collect();
+
+    code.push_str(&format!("impl {} {{\n", machine));
+    code.push_str(&format!("    pub fn new({}) -> Self {{\n", param_list.join(", ")));
+    code.push_str(&format!("        Self {{ {} }}\n", field_inits.join(", ")));
+    code.push_str("    }\n\n");
+    code.push_str("    /// Current state of the event\n");
+    code.push_str(&format!("    pub fn state(&self) -> {} {{\n", state_enum));
+    code.push_str("        self.state\n");
+    code.push_str("    }\n\n");
+    code.push_str("    /// Run the event until it waits or finishes; returns `true` once finished\n");
+    code.push_str("    pub fn step(&mut self) -> bool {\n");
+    code.push_str("        loop {\n");
+    code.push_str("            match self.state {\n");
+
+    for (i, state) in states.iter().enumerate() {
+        code.push_str(&format!("                {}::{} => {{\n", state_enum, variants[i]));
+
+        // Restore the carried values this state reads, then keep the ones it computes
+        for value in carried.iter().filter(|value| value.state < i && mentions(&state.code, &value.name)) {
+            let read = if is_copy_type(&value.ty) { "" } else { ".clone()" };
+            code.push_str(&format!(
+                "{}let {} = self.{}{}.expect(\"{} is set before the wait\");\n",
+                arm_indent, value.name, value.field, read, value.name
+            ));
+        }
+        let kept: Vec<&CarriedValue> = carried.iter().filter(|value| value.state == i).collect();
+        code.push_str(&with_stores(&state.code, &kept, &arm_indent));
+
+        let next_state = variants.get(i + 1).map(String::as_str).unwrap_or("Done");
+        code.push_str(&format!("{}self.state = {}::{};\n", arm_indent, state_enum, next_state));
+        code.push_str("                }\n");
+    }
+
+    code.push_str(&format!("                {}::Done => return true,\n", state_enum));
+    code.push_str("            }\n");
+    code.push_str("        }\n");
+    code.push_str("    }\n");
+    code.push_str("}\n");
+
+    if params.is_empty() {
+        code.push_str(&format!("\nimpl Default for {} {{\n", machine));
+        code.push_str("    fn default() -> Self {\n");
+        code.push_str("        Self::new()\n");
+        code.push_str("    }\n");
+        code.push_str("}\n");
+    }
+
+    Ok(code)
+}
+
+/// Code polling a latent node at the start of its wait state
+///
+/// Also returns the local its output is bound to and the output's type, if
+/// the node yields a value that something reads.
+fn poll_latent(
+    generator: &BlueprintCodeGenerator,
+    segment_gen: &BlueprintCodeGenerator,
+    node: &NodeInstance,
+    indent: &str,
+) -> Result<(String, Option<(String, String)>), CompileError> {
+    let meta = generator.node_metadata(node)?;
+    let args = segment_gen.collect_arguments(node, meta)?;
+    let call = format!("{}({})", generator.symbol(meta)?, args.join(", "));
+
+    let mut code = segment_gen.trace_statement(node, indent);
+    code.push_str(&segment_gen.take_pending_bindings(indent));
+
+    let output = meta.return_type
+        .as_ref()
+        .and_then(|ty| ty.type_string.trim().strip_prefix("Option<")?.strip_suffix('>').map(str::trim));
+    let Some(output) = output else {
+        if generator.is_read(&node.id) {
+            return Err(GraphyError::CodeGeneration(format!(
+                "Latent node '{}' is read, but it only reports completion as a bool; return Option<T> to yield a value",
+                node.id
+            ))
+            .into());
+        }
+        code.push_str(&format!("{}if !{} {{\n", indent, call));
+        code.push_str(&format!("{}    return false;\n", indent));
+        code.push_str(&format!("{}}}\n", indent));
+        return Ok((code, None));
+    };
+
+    let binding = generator.result_variable(&node.id).filter(|_| generator.is_read(&node.id));
+    code.push_str(&format!("{}let Some({}) = {} else {{\n", indent, binding.map_or("_", String::as_str), call));
+    code.push_str(&format!("{}    return false;\n", indent));
+    code.push_str(&format!("{}}};\n", indent));
+
+    Ok((code, binding.map(|binding| (binding.clone(), output.to_string()))))
+}
+
+/// Locals declared in one state and read in a later one
+///
+/// Each gets a machine field; its type is the `let` annotation if there is
+/// one, else the type of the latent output or node result it holds.
+fn carried_values(
+    generator: &BlueprintCodeGenerator,
+    states: &[State],
+    latent_outputs: &HashMap<String, String>,
+    fields: &mut UniqueNames,
+    event_name: &str,
+) -> Result<Vec<CarriedValue>, CompileError> {
+    let arm_indent = "    ".repeat(ARM_BODY_INDENT);
+    let mut carried = Vec::new();
+
+    for (i, state) in states.iter().enumerate() {
+        for (name, annotation) in state.code.lines().filter_map(|line| declared_local(line, &arm_indent)) {
+            if name == "_" || !states[i + 1..].iter().any(|later| mentions(&later.code, name)) {
+                continue;
+            }
+
+            let ty = annotation
+                .map(str::to_string)
+                .or_else(|| latent_outputs.get(name).cloned())
+                .or_else(|| generator.binding_type(name))
+                .ok_or_else(|| GraphyError::CodeGeneration(format!(
+                    "Event '{}' reads '{}' after a wait, but its type is unknown, so it can't be kept across the wait",
+                    event_name, name
+                )))?;
+            carried.push(CarriedValue {
+                name: name.to_string(),
+                field: fields.claim(name),
+                ty,
+                state: i,
+            });
+        }
+    }
+
+    Ok(carried)
+}
+
+/// A state's code with each carried value stored into its field once its `let` statement ends
+fn with_stores(code: &str, kept: &[&CarriedValue], indent: &str) -> String {
+    let mut out = String::with_capacity(code.len());
+    let mut waiting: Vec<&CarriedValue> = Vec::new();
+
+    for line in code.lines() {
+        out.push_str(line);
+        out.push('\n');
+
+        if let Some((name, _)) = declared_local(line, indent) {
+            waiting.extend(kept.iter().filter(|value| value.name == name).copied());
+        }
+        let statement_ends = line.strip_prefix(indent).is_some_and(|rest| !rest.starts_with(char::is_whitespace))
+            && line.ends_with(';');
+        if statement_ends {
+            for value in waiting.drain(..) {
+                let read = if is_copy_type(&value.ty) { "" } else { ".clone()" };
+                out.push_str(&format!("{}self.{} = Some({}{});\n", indent, value.field, value.name, read));
+            }
+        }
+    }
+
+    out
+}
+
+/// Name and type annotation of a local declared by a `let` at exactly `indent`
+fn declared_local<'l>(line: &'l str, indent: &str) -> Option<(&'l str, Option<&'l str>)> {
+    let rest = line.strip_prefix(indent)?.strip_prefix("let ")?;
+    let rest = rest.strip_prefix("mut ").or_else(|| rest.strip_prefix("Some(")).unwrap_or(rest);
+    let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+    let annotation = rest[end..].strip_prefix(": ").and_then(|ty| ty.split(" = ").next());
+
+    (end > 0).then_some((&rest[..end], annotation))
+}
+
+/// Whether code refers to the local `name`, other than as a field
+fn mentions(code: &str, name: &str) -> bool {
+    code.match_indices(name).any(|(i, _)| {
+        let before = code[..i].chars().next_back();
+        let after = code[i + name.len()..].chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.')
+            && !after.is_some_and(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+/// Names handed out so far, so a clashing one gets a numeric suffix
+struct UniqueNames {
+    taken: HashSet<String>,
+    separator: &'static str,
+}
+
+impl UniqueNames {
+    fn new(reserved: &[&str], separator: &'static str) -> Self {
+        Self {
+            taken: reserved.iter().map(|name| name.to_string()).collect(),
+            separator,
+        }
+    }
+
+    /// `name`, or `name2`, `name3`, ... (with the separator between) if it is taken
+    fn claim(&mut self, name: &str) -> String {
+        let mut claimed = name.to_string();
+        let mut suffix = 1;
+        while self.taken.contains(&claimed) {
+            suffix += 1;
+            claimed = format!("{}{}{}", name, self.separator, suffix);
+        }
+        self.taken.insert(claimed.clone());
+        claimed
+    }
+}
+
+/// Convert an identifier like `on_tick` or `delay-1` to `OnTick` / `Delay1`
+fn pascal_case(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::NodeAttributes;
+    use crate::test_support::{GraphBuilder, provider};
+    use crate::Compiler;
+    use graphy::PropertyValue;
+
+    /// A compiler treating `delay` and `wait_for_key` as latent
+    fn latent_compiler() -> Compiler {
+        Compiler::with_provider(
+            provider()
+                .with_attributes("delay", NodeAttributes::latent())
+                .with_attributes("wait_for_key", NodeAttributes::latent()),
+        )
+    }
+
+    #[test]
+    fn value_read_after_a_delay_is_kept_on_the_machine() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("spawn", "spawn")
+            .property("spawn", "prefab", PropertyValue::String("crate".to_string()))
+            .node("delay", "delay")
+            .node("despawn", "despawn")
+            .exec("main", "body", "spawn")
+            .exec("spawn", "exec_out", "delay")
+            .exec("delay", "exec_out", "despawn")
+            .data("spawn", "result", "despawn", "entity")
+            .build();
+
+        let code = latent_compiler().compile(&graph).unwrap();
+        assert!(code.contains("    spawn_result: Option<u32>,\n"), "{}", code);
+        assert!(code.contains("Self { state: MainState::Start, spawn_result: None }"), "{}", code);
+        assert!(code.contains("                    self.spawn_result = Some(spawn_result);\n"), "{}", code);
+        assert!(
+            code.contains(
+                "                MainState::WaitDelay => {\n                    let spawn_result = self.spawn_result.expect(\"spawn_result is set before the wait\");\n"
+            ),
+            "{}",
+            code
+        );
+        assert!(code.contains("despawn(spawn_result);"), "{}", code);
+    }
+
+    #[test]
+    fn latent_output_is_bound_when_its_wait_ends() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("wait", "wait_for_key")
+            .node("print", "print_string")
+            .exec("main", "body", "wait")
+            .exec("wait", "exec_out", "print")
+            .data("wait", "result", "print", "message")
+            .build();
+
+        let code = latent_compiler().compile(&graph).unwrap();
+        assert!(
+            code.contains("let Some(wait_result) = wait_for_key() else {\n                        return false;\n                    };\n"),
+            "{}",
+            code
+        );
+        assert!(code.contains("print_string(wait_result"), "{}", code);
+    }
+
+    #[test]
+    fn reading_a_bool_latent_node_is_rejected() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("delay", "delay")
+            .node("print", "print_string")
+            .exec("main", "body", "delay")
+            .exec("delay", "exec_out", "print")
+            .data("delay", "result", "print", "message")
+            .build();
+
+        let err = latent_compiler().compile(&graph).unwrap_err();
+        assert!(err.to_string().contains("only reports completion as a bool"), "{}", err);
+    }
+
+    #[test]
+    fn clashing_generated_names_get_a_suffix() {
+        let graph = GraphBuilder::new()
+            .node("key_pressed", "key_pressed")
+            .node("wait-1", "delay")
+            .node("wait_1", "delay")
+            .exec("key_pressed", "body", "wait-1")
+            .exec("wait-1", "exec_out", "wait_1")
+            .build();
+
+        let code = latent_compiler().compile(&graph).unwrap();
+        assert!(code.contains("    WaitWait1,\n    WaitWait12,\n    Done,\n"), "{}", code);
+        assert!(code.contains("    state: KeyPressedState,\n    state_2: bool,\n"), "{}", code);
+        assert!(code.contains("pub fn new(state: bool) -> Self {\n        Self { state: KeyPressedState::Start, state_2: state }"), "{}", code);
+    }
+}
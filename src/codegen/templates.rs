@@ -0,0 +1,142 @@
+//! # Code Templates
+//!
+//! Placeholder substitution for node-provided code templates.
+//!
+//! Templates are plain Rust snippets in which `{name}` stands for a value
+//! supplied by the code generator, e.g. `let {result} = spawn({prefab});`.
+//! Only placeholders whose name is known are replaced, so ordinary block
+//! braces in the template are left alone.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Replace every known `{name}` placeholder in a template
+pub(crate) fn substitute_placeholders(template: &str, values: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut copied = 0;
+
+    for (span, name) in placeholders(template) {
+        if let Some(value) = values.get(name) {
+            out.push_str(&template[copied..span.start]);
+            out.push_str(value);
+            copied = span.end;
+        }
+    }
+    out.push_str(&template[copied..]);
+
+    out
+}
+
+/// Whether a template uses the `{name}` placeholder
+pub(crate) fn uses_placeholder(template: &str, name: &str) -> bool {
+    placeholders(template).any(|(_, placeholder)| placeholder == name)
+}
+
+/// The first `{name}` placeholder in a template that isn't one of `known`
+pub(crate) fn unknown_placeholder<'t>(template: &'t str, known: &[&str]) -> Option<&'t str> {
+    placeholders(template)
+        .map(|(_, name)| name)
+        .find(|name| !known.contains(name))
+}
+
+/// Every `{name}` placeholder in a template, with the byte span of its braces
+///
+/// String and char literals and comments are skipped, so `format!("{x}")`
+/// in a template is neither substituted nor reported as a placeholder.
+fn placeholders(template: &str) -> impl Iterator<Item = (Range<usize>, &str)> + '_ {
+    code_ranges(template).into_iter().flat_map(move |range| {
+        let code = &template[range.clone()];
+        code.match_indices('{').filter_map(move |(open, _)| {
+            let rest = &code[open + 1..];
+            let name = &rest[..rest.find('}')?];
+            let is_ident = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            is_ident.then(|| (range.start + open..range.start + open + name.len() + 2, name))
+        })
+    })
+}
+
+/// Byte ranges of a Rust snippet that are code, i.e. not inside a string or
+/// char literal or a comment
+pub(crate) fn code_ranges(source: &str) -> Vec<Range<usize>> {
+    let bytes = source.as_bytes();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let end = match (bytes[i], bytes.get(i + 1)) {
+            (b'/', Some(b'/')) => source[i..].find('\n').map_or(source.len(), |end| i + end),
+            (b'/', Some(b'*')) => source[i + 2..].find("*/").map_or(source.len(), |end| i + end + 4),
+            (b'"', _) => string_end(bytes, i + 1),
+            (b'\'', _) => match char_literal_len(&source[i + 1..]) {
+                Some(len) => i + 1 + len,
+                // A lifetime has no closing quote and is code
+                None => {
+                    i += 1;
+                    continue;
+                }
+            },
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        ranges.push(start..i);
+        start = end;
+        i = end;
+    }
+    ranges.push(start..source.len());
+
+    ranges
+}
+
+/// Offset just past the closing quote of a string literal whose contents start at `from`
+fn string_end(bytes: &[u8], from: usize) -> usize {
+    let mut i = from;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Length of a char literal's contents and closing quote, if `rest` starts one
+fn char_literal_len(rest: &str) -> Option<usize> {
+    match rest.strip_prefix('\\') {
+        Some(escaped) => escaped.get(1..)?.find('\'').map(|end| end + 3),
+        None => rest.chars().next().filter(|c| rest[c.len_utf8()..].starts_with('\'')).map(|c| c.len_utf8() + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_in_format_strings_are_left_alone() {
+        let template = "let {result} = format!(\"{name}: {}\", {name});";
+        let values = HashMap::from([("result".to_string(), "label".to_string()), ("name".to_string(), "player".to_string())]);
+
+        assert_eq!(substitute_placeholders(template, &values), "let label = format!(\"{name}: {}\", player);");
+        assert_eq!(unknown_placeholder(template, &["result", "name"]), None);
+    }
+
+    #[test]
+    fn substitution_and_validation_skip_the_same_literals_and_comments() {
+        let template = "log('{', \"{x}\" /* {y} */, '\\'', {a}); // {z}\n{b}";
+        let values = HashMap::from([("a".to_string(), "1".to_string())]);
+
+        assert_eq!(substitute_placeholders(template, &values), "log('{', \"{x}\" /* {y} */, '\\'', 1); // {z}\n{b}");
+        assert_eq!(unknown_placeholder(template, &["a"]), Some("b"));
+    }
+
+    #[test]
+    fn lifetimes_do_not_start_a_literal() {
+        let template = "fn f<'a>(v: &'a str) -> &'a str { {value} }";
+        assert!(uses_placeholder(template, "value"));
+    }
+}
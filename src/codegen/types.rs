@@ -0,0 +1,409 @@
+//! # Pin Type Resolution
+//!
+//! Monomorphization-lite for generic Blueprint nodes.
+//!
+//! Nodes such as `identity` or `make_array` declare `Any`-typed pins or use a
+//! type parameter (`fn identity<T>(value: T) -> T`). Their concrete type is
+//! whatever gets wired into them, so we propagate concrete types from connected
+//! sources through the generic pins until nothing changes.
+
+use crate::metadata::BlueprintMetadataProvider;
+use graphy::{DataResolver, DataType, GraphDescription, NodeInstance};
+use graphy::core::NodeMetadataProvider;
+use std::collections::HashMap;
+
+/// Placeholder type parameter used for `Any`-typed pins
+///
+/// All `Any` pins on a single node are assumed to share one type, just like a
+/// named type parameter would.
+const ANY_PARAM: &str = "_";
+
+/// Concrete types resolved for every data pin in a graph
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedTypes {
+    pins: HashMap<(String, String), String>,
+}
+
+impl ResolvedTypes {
+    /// Resolve pin types for the whole graph
+    pub fn build(
+        graph: &GraphDescription,
+        metadata_provider: &BlueprintMetadataProvider,
+        data_resolver: &DataResolver,
+    ) -> Self {
+        use graphy::analysis::DataSource;
+
+        let mut resolved = Self::default();
+
+        // Visited in ID order, so which of two conflicting sources wins doesn't
+        // depend on map iteration
+        let mut nodes: Vec<&NodeInstance> = graph.nodes.values().collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        // Generic parameters declared by each node's function source
+        let mut generics: HashMap<&str, Vec<String>> = HashMap::new();
+        for node in &nodes {
+            let params = metadata_provider
+                .get_node_metadata(&node.node_type)
+                .map(|meta| generic_params(&meta.function_source))
+                .unwrap_or_default();
+            generics.insert(node.id.as_str(), params);
+        }
+
+        // Seed with every pin that is already concrete
+        for node in &nodes {
+            for pin in node.inputs.iter().chain(node.outputs.iter()) {
+                if let Some(ty) = declared_type(&pin.pin.data_type) {
+                    if !is_generic(&ty, &generics[node.id.as_str()]) {
+                        resolved.pins.insert((node.id.clone(), pin.id.clone()), ty);
+                    }
+                }
+            }
+        }
+
+        // Payloads of multi-exit nodes have the type their variant declares
+        for node in &nodes {
+            for exit in &metadata_provider.attributes(&node.node_type).exits {
+                let (Some(pin_name), Some(ty)) = (&exit.payload_pin, &exit.payload_type) else { continue };
+                if let Some(pin) = node.outputs.iter().find(|pin| &pin.pin.name == pin_name) {
+                    resolved.pins.entry((node.id.clone(), pin.id.clone())).or_insert_with(|| ty.clone());
+                }
+            }
+        }
+
+        // Propagate through generic pins until a fixpoint is reached. Every
+        // productive iteration resolves at least one pin, so the pin count
+        // bounds the number of passes.
+        let max_passes = nodes.iter()
+            .map(|node| node.inputs.len() + node.outputs.len())
+            .sum::<usize>() + 1;
+
+        for _ in 0..max_passes {
+            let mut changed = false;
+
+            for node in &nodes {
+                let params = &generics[node.id.as_str()];
+                let mut bindings: HashMap<String, String> = HashMap::new();
+
+                // Bind type parameters from concrete sources
+                for input in &node.inputs {
+                    let Some(declared) = pattern_type(&input.pin.data_type) else { continue };
+                    if !is_generic(&declared, params) {
+                        continue;
+                    }
+
+                    if let Some(DataSource::Connection { source_node_id, source_pin }) =
+                        data_resolver.get_input_source(&node.id, &input.id)
+                    {
+                        if let Some(source_ty) = resolved.pins.get(&(source_node_id.clone(), source_pin.clone())) {
+                            unify(&declared, source_ty, params, &mut bindings);
+                        }
+                    }
+                }
+
+                if bindings.is_empty() {
+                    continue;
+                }
+
+                // Substitute the bindings into every generic pin of the node
+                for pin in node.inputs.iter().chain(node.outputs.iter()) {
+                    let key = (node.id.clone(), pin.id.clone());
+                    if resolved.pins.contains_key(&key) {
+                        continue;
+                    }
+                    let Some(declared) = pattern_type(&pin.pin.data_type) else { continue };
+                    let concrete = substitute(&declared, &bindings);
+                    if !is_generic(&concrete, params) {
+                        resolved.pins.insert(key, concrete);
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        resolved
+    }
+
+    /// Concrete type of a pin, if it could be resolved
+    pub fn pin_type(&self, node_id: &str, pin_id: &str) -> Option<&str> {
+        self.pins
+            .get(&(node_id.to_string(), pin_id.to_string()))
+            .map(|s| s.as_str())
+    }
+
+    /// Concrete type of a node's data output (its result variable)
+    pub fn output_type(&self, node: &NodeInstance) -> Option<&str> {
+        node.outputs
+            .iter()
+            .filter(|pin| !matches!(pin.pin.data_type, DataType::Execution))
+            .find_map(|pin| self.pin_type(&node.id, &pin.id))
+    }
+}
+
+/// Rust type named by a pin's data type, if it is concrete
+pub(crate) fn declared_type(data_type: &DataType) -> Option<String> {
+    match data_type {
+        DataType::Execution | DataType::Any => None,
+        DataType::Typed(type_info) => Some(type_info.type_string.clone()),
+        DataType::Number => Some("f64".to_string()),
+        DataType::String => Some("String".to_string()),
+        DataType::Boolean => Some("bool".to_string()),
+        DataType::Vector2 => Some("(f32, f32)".to_string()),
+        DataType::Vector3 => Some("(f32, f32, f32)".to_string()),
+        DataType::Color => Some("(f32, f32, f32, f32)".to_string()),
+    }
+}
+
+/// Like [`declared_type`], but maps `Any` to the implicit type parameter
+fn pattern_type(data_type: &DataType) -> Option<String> {
+    match data_type {
+        DataType::Any => Some(ANY_PARAM.to_string()),
+        other => declared_type(other),
+    }
+}
+
+/// Extract the type parameters declared in a function's source
+///
+/// `fn identity<T: Clone, 'a>(value: T) -> T` yields `["T"]`. Lifetimes and
+/// const generics are ignored.
+pub(crate) fn generic_params(function_source: &str) -> Vec<String> {
+    let Some(list) = generic_list(function_source) else { return Vec::new() };
+
+    split_top_level(list)
+        .into_iter()
+        .filter(|param| !param.starts_with('\'') && !param.starts_with("const "))
+        .map(|param| param.split(':').next().unwrap_or("").trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Trait bounds a function's source places on one of its type parameters
+///
+/// Bounds come from both the parameter list and a `where` clause, so
+/// `fn show<T: Clone>(value: T) where T: Display` yields `["Clone", "Display"]`.
+pub(crate) fn generic_bounds(function_source: &str, param: &str) -> Vec<String> {
+    let mut predicates = generic_list(function_source).map(split_top_level).unwrap_or_default();
+
+    if let Some(fn_pos) = function_source.find("fn ") {
+        let signature = &function_source[fn_pos..];
+        let signature = &signature[..signature.find('{').unwrap_or(signature.len())];
+        let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+        let where_pos = signature.match_indices("where").map(|(i, _)| i).find(|&i| {
+            !signature[..i].ends_with(is_ident) && !signature[i + "where".len()..].starts_with(is_ident)
+        });
+        if let Some(where_pos) = where_pos {
+            predicates.extend(split_top_level(&signature[where_pos + "where".len()..]));
+        }
+    }
+
+    predicates
+        .iter()
+        .filter_map(|predicate| predicate.split_once(':'))
+        .filter(|(name, _)| name.trim() == param)
+        .flat_map(|(_, bounds)| bounds.split('+'))
+        .map(|bound| bound.trim().to_string())
+        .filter(|bound| !bound.is_empty() && !bound.starts_with('\''))
+        .collect()
+}
+
+/// The text between the `<>` of a function's type parameter list
+fn generic_list(function_source: &str) -> Option<&str> {
+    let fn_pos = function_source.find("fn ")?;
+    let signature = &function_source[fn_pos..];
+    let paren = signature.find('(').unwrap_or(signature.len());
+
+    let open = signature[..paren].find('<')?;
+
+    let mut depth = 0;
+    let mut close = None;
+    let mut prev = ' ';
+    for (i, c) in signature[open..].char_indices() {
+        let after_dash = std::mem::replace(&mut prev, c) == '-';
+        match c {
+            '<' => depth += 1,
+            // The `>` of an `->` in an `Fn(T) -> U` bound closes nothing
+            '>' if after_dash => {}
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    close.map(|close| &signature[open + 1..close])
+}
+
+/// Check whether a type string still mentions a type parameter
+pub(crate) fn is_generic(ty: &str, params: &[String]) -> bool {
+    type_idents(ty)
+        .iter()
+        .any(|ident| ident == ANY_PARAM || params.iter().any(|p| p == ident))
+}
+
+/// Check whether a constant names a unit variant of the enum type `ty`
+///
+/// `"Direction::Left"` is a variant of `Direction` and of `game::Direction`;
+/// the variant must start with an uppercase letter.
+pub(crate) fn is_enum_variant_of(constant: &str, ty: &str) -> bool {
+    let constant = constant.trim();
+    let Some((enum_path, variant)) = constant.rsplit_once("::") else { return false };
+
+    let is_ident = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !is_ident(variant) || !variant.starts_with(|c: char| c.is_uppercase()) {
+        return false;
+    }
+
+    let enum_name = enum_path.rsplit("::").next().unwrap_or(enum_path);
+    let type_name = ty.trim().rsplit("::").next().unwrap_or(ty);
+    is_ident(enum_name) && enum_name == type_name
+}
+
+/// Structurally match `pattern` against `concrete`, recording parameter bindings
+fn unify(pattern: &str, concrete: &str, params: &[String], bindings: &mut HashMap<String, String>) {
+    let pattern = pattern.trim();
+    let concrete = concrete.trim();
+
+    if pattern == ANY_PARAM || params.iter().any(|p| p == pattern) {
+        bindings.entry(pattern.to_string()).or_insert_with(|| concrete.to_string());
+        return;
+    }
+
+    let (pattern_head, pattern_args) = split_type(pattern);
+    let (concrete_head, concrete_args) = split_type(concrete);
+    if pattern_head != concrete_head || pattern_args.len() != concrete_args.len() {
+        return;
+    }
+
+    for (p, c) in pattern_args.iter().zip(concrete_args.iter()) {
+        unify(p, c, params, bindings);
+    }
+}
+
+/// Replace bound type parameters in a type string
+fn substitute(ty: &str, bindings: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut ident = String::new();
+
+    let flush = |ident: &mut String, out: &mut String| {
+        if !ident.is_empty() {
+            out.push_str(bindings.get(ident.as_str()).map(|s| s.as_str()).unwrap_or(ident));
+            ident.clear();
+        }
+    };
+
+    for c in ty.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            ident.push(c);
+        } else {
+            flush(&mut ident, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut ident, &mut out);
+
+    out
+}
+
+/// Split `Vec<Option<T>>` into `("Vec", ["Option<T>"])`; tuples use `"()"` as head
+fn split_type(ty: &str) -> (String, Vec<String>) {
+    let ty = ty.trim();
+
+    if let Some(inner) = ty.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        return ("()".to_string(), split_top_level(inner));
+    }
+
+    match (ty.find('<'), ty.ends_with('>')) {
+        (Some(open), true) => (
+            ty[..open].trim().to_string(),
+            split_top_level(&ty[open + 1..ty.len() - 1]),
+        ),
+        _ => (ty.to_string(), Vec::new()),
+    }
+}
+
+/// Split a comma-separated list, ignoring commas nested in `<>`/`()`/`[]`
+///
+/// The `>` of an `->` (as in `F: Fn(T) -> U`) isn't a closing bracket.
+fn split_top_level(s: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut current = String::new();
+
+    for c in s.chars() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' if current.ends_with('-') => {}
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+
+    parts
+}
+
+/// All identifiers appearing in a type string
+fn type_idents(ty: &str) -> Vec<String> {
+    ty.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ResolvedTypes, generic_bounds, generic_params};
+    use crate::test_support::{GraphBuilder, provider};
+    use graphy::DataResolver;
+
+    #[test]
+    fn generic_passthrough_takes_its_source_type() {
+        // add -> identity -> add
+        let graph = GraphBuilder::new()
+            .node("first", "add")
+            .node("same", "identity")
+            .node("second", "add")
+            .data("first", "result", "same", "value")
+            .data("same", "result", "second", "a")
+            .build();
+        let provider = provider();
+        let data_resolver = DataResolver::build(&graph, &provider).unwrap();
+
+        let types = ResolvedTypes::build(&graph, &provider, &data_resolver);
+        assert_eq!(types.pin_type("same", "same_value"), Some("i32"));
+        assert_eq!(types.pin_type("same", "same_result"), Some("i32"));
+        assert_eq!(types.output_type(&graph.nodes["same"]), Some("i32"));
+    }
+
+    #[test]
+    fn fn_bound_arrow_does_not_close_the_parameter_list() {
+        let source = "fn apply<F: Fn(T) -> U, T: Clone, U>(f: F, value: T) -> U {\n    f(value.clone())\n}";
+
+        assert_eq!(generic_params(source), ["F", "T", "U"]);
+        assert_eq!(generic_bounds(source, "F"), ["Fn(T) -> U"]);
+        assert_eq!(generic_bounds(source, "T"), ["Clone"]);
+    }
+
+    #[test]
+    fn fn_bound_arrow_in_a_where_clause_keeps_later_bounds() {
+        let source = "fn apply<F, T, U>(f: F, value: T) -> U where F: Fn(T) -> U, T: Clone + Display {\n    f(value)\n}";
+
+        assert_eq!(generic_bounds(source, "F"), ["Fn(T) -> U"]);
+        assert_eq!(generic_bounds(source, "T"), ["Clone", "Display"]);
+    }
+}
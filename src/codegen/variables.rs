@@ -0,0 +1,259 @@
+//! # Blueprint Class Variables
+//!
+//! Declarations for Blueprint member variables.
+//!
+//! By default variables are stored in `thread_local!` cells: Copy types use
+//! `Cell`, everything else uses `RefCell`. Getter and setter nodes access them
+//! through `NAME.with(|v| ...)`. When compiling a class, variables are struct
+//! fields accessed through `self` instead.
+//!
+//! Outside a class, primitive variables that no setter ever writes are plain
+//! `const` items, read by name.
+//!
+//! A getter or setter whose node type continues with a field path after the
+//! variable name (`get_position.x`, `set_transform.scale.y`) reads or writes
+//! that field of a struct-typed variable in place.
+
+use crate::inspect::variable_access;
+use super::literals::render_property_value;
+use super::rust_codegen::{default_value_for_type, is_copy_type};
+use graphy::{GraphDescription, PropertyValue};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// A Blueprint class variable definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableDef {
+    /// Rust type of the variable
+    pub ty: String,
+    /// Value set in the editor, if any
+    pub initial: Option<PropertyValue>,
+    /// Types of the fields getters and setters may access, by path, e.g. `"x"` or `"position.x"`
+    ///
+    /// When empty, field paths aren't checked.
+    pub fields: BTreeMap<String, String>,
+}
+
+impl VariableDef {
+    /// Create a variable initialized to its type's default value
+    pub fn new(ty: impl Into<String>) -> Self {
+        Self {
+            ty: ty.into(),
+            initial: None,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Set the editor-provided initial value
+    pub fn with_initial(mut self, value: PropertyValue) -> Self {
+        self.initial = Some(value);
+        self
+    }
+
+    /// Declare a field path getters and setters may access, and its type
+    pub fn with_field(mut self, path: impl Into<String>, ty: impl Into<String>) -> Self {
+        self.fields.insert(path.into(), ty.into());
+        self
+    }
+
+    /// Type of the field at `path`
+    ///
+    /// `Ok(None)` when no fields are declared, so the path can't be checked.
+    pub(crate) fn field_type(&self, path: &str) -> Result<Option<&str>, ()> {
+        if self.fields.is_empty() {
+            return Ok(None);
+        }
+        self.fields.get(path).map(|ty| Some(ty.as_str())).ok_or(())
+    }
+
+    /// Rust expression for the variable's starting value
+    ///
+    /// Falls back to the type's default when no initial value was set.
+    pub fn initial_expression(&self) -> String {
+        match &self.initial {
+            Some(value) => render_property_value(value, &self.ty),
+            None => default_value_for_type(&self.ty),
+        }
+    }
+}
+
+impl From<String> for VariableDef {
+    fn from(ty: String) -> Self {
+        Self::new(ty)
+    }
+}
+
+impl From<&str> for VariableDef {
+    fn from(ty: &str) -> Self {
+        Self::new(ty)
+    }
+}
+
+/// Where class variables live at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VariableStorage {
+    /// `thread_local!` statics wrapped in `Cell`/`RefCell`
+    #[default]
+    ThreadLocal,
+    /// Fields of the generated Blueprint struct, accessed through `self`
+    StructField,
+    /// Fields of the environment captured by event closures, accessed through `env`
+    Environment,
+}
+
+/// Split a getter or setter's variable name from the field path following it
+///
+/// `position.x` is the `x` field of `position`.
+pub(crate) fn split_field_path(name: &str) -> (&str, Option<&str>) {
+    match name.split_once('.') {
+        Some((var, path)) => (var, Some(path)),
+        None => (name, None),
+    }
+}
+
+/// Expression reading a variable's current value, or one of its fields
+///
+/// A field is cloned out unless its declared type is `Copy`.
+pub(crate) fn read_expression(name: &str, var: &VariableDef, field: Option<&str>, storage: VariableStorage) -> String {
+    let Some(path) = field else {
+        let copy = is_copy_type(&var.ty);
+        return match storage {
+            VariableStorage::ThreadLocal if copy => format!("{}.with(|v| v.get())", name.to_uppercase()),
+            VariableStorage::ThreadLocal => format!("{}.with(|v| v.borrow().clone())", name.to_uppercase()),
+            VariableStorage::StructField if copy => format!("self.{}", name),
+            VariableStorage::StructField => format!("self.{}.clone()", name),
+            VariableStorage::Environment if copy => format!("env.{}", name),
+            VariableStorage::Environment => format!("env.{}.clone()", name),
+        };
+    };
+
+    let clone = if var.field_type(path).ok().flatten().is_some_and(is_copy_type) { "" } else { ".clone()" };
+    match storage {
+        VariableStorage::ThreadLocal if is_copy_type(&var.ty) => format!("{}.with(|v| v.get().{})", name.to_uppercase(), path),
+        VariableStorage::ThreadLocal => format!("{}.with(|v| v.borrow().{}{})", name.to_uppercase(), path, clone),
+        VariableStorage::StructField => format!("self.{}.{}{}", name, path, clone),
+        VariableStorage::Environment => format!("env.{}.{}{}", name, path, clone),
+    }
+}
+
+/// Statement assigning a new value to a variable, or one of its fields
+pub(crate) fn write_statement(name: &str, var: &VariableDef, field: Option<&str>, value: &str, storage: VariableStorage) -> String {
+    let target = match field {
+        Some(path) => format!("{}.{}", name, path),
+        None => name.to_string(),
+    };
+    match (storage, field) {
+        (VariableStorage::ThreadLocal, None) if is_copy_type(&var.ty) => {
+            format!("{}.with(|v| v.set({}));", name.to_uppercase(), value)
+        }
+        // A `Cell` can't be written in place: copy the value out, update it, put it back
+        (VariableStorage::ThreadLocal, Some(path)) if is_copy_type(&var.ty) => format!(
+            "{}.with(|v| {{ let mut current = v.get(); current.{} = {}; v.set(current); }});",
+            name.to_uppercase(),
+            path,
+            value
+        ),
+        (VariableStorage::ThreadLocal, None) => {
+            format!("{}.with(|v| *v.borrow_mut() = {});", name.to_uppercase(), value)
+        }
+        (VariableStorage::ThreadLocal, Some(path)) => {
+            format!("{}.with(|v| v.borrow_mut().{} = {});", name.to_uppercase(), path, value)
+        }
+        (VariableStorage::StructField, _) => format!("self.{} = {};", target, value),
+        (VariableStorage::Environment, _) => format!("env.{} = {};", target, value),
+    }
+}
+
+/// Variables of a primitive type that no `set_` node in `graphs` writes
+///
+/// Their initial value is a literal, so they can be declared `const`.
+pub(crate) fn const_variables<'g>(
+    variables: &HashMap<String, VariableDef>,
+    graphs: impl IntoIterator<Item = &'g GraphDescription>,
+) -> HashSet<String> {
+    let written: HashSet<String> = graphs
+        .into_iter()
+        .flat_map(variable_access)
+        .filter(|(_, access)| access.writes > 0)
+        .map(|(name, _)| name)
+        .collect();
+
+    variables
+        .iter()
+        .filter(|(name, var)| is_copy_type(&var.ty) && !written.contains(*name))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Generate the declarations of all class variables
+///
+/// Variables in `constants` become `const` items; the rest are declared in a
+/// `thread_local!` block. Declarations are sorted by name so output is stable
+/// across runs.
+pub(crate) fn generate_variable_declarations(
+    variables: &HashMap<String, VariableDef>,
+    constants: &HashSet<String>,
+) -> String {
+    let mut code = String::new();
+    for (name, var) in sorted_variables(variables) {
+        if constants.contains(name) {
+            code.push_str(&format!("const {}: {} = {};\n", name.to_uppercase(), var.ty, var.initial_expression()));
+        }
+    }
+
+    let cells: Vec<_> = sorted_variables(variables)
+        .into_iter()
+        .filter(|(name, _)| !constants.contains(*name))
+        .collect();
+    if cells.is_empty() {
+        return code;
+    }
+    if !code.is_empty() {
+        code.push('\n');
+    }
+    code.push_str("thread_local! {\n");
+
+    for (name, var) in cells {
+        let cell = if is_copy_type(&var.ty) { "Cell" } else { "RefCell" };
+        code.push_str(&format!(
+            "    static {}: std::cell::{}<{}> = std::cell::{}::new({});\n",
+            name.to_uppercase(),
+            cell,
+            var.ty,
+            cell,
+            var.initial_expression()
+        ));
+    }
+
+    code.push_str("}\n");
+    code
+}
+
+/// Variables in name order, for deterministic emission
+pub(crate) fn sorted_variables(variables: &HashMap<String, VariableDef>) -> Vec<(&String, &VariableDef)> {
+    let mut sorted: Vec<_> = variables.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VariableDef, generate_variable_declarations};
+    use std::collections::{HashMap, HashSet};
+
+    fn variables(names: &[&str]) -> HashMap<String, VariableDef> {
+        names.iter().map(|name| (name.to_string(), VariableDef::new("String"))).collect()
+    }
+
+    #[test]
+    fn declarations_are_sorted_by_name() {
+        let forward = generate_variable_declarations(&variables(&["zeta", "alpha", "mid", "beta"]), &HashSet::new());
+        let backward = generate_variable_declarations(&variables(&["beta", "mid", "alpha", "zeta"]), &HashSet::new());
+
+        assert_eq!(forward, backward);
+        let positions: Vec<usize> = ["ALPHA", "BETA", "MID", "ZETA"]
+            .iter()
+            .map(|name| forward.find(&format!("static {}:", name)).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]), "{}", forward);
+    }
+}
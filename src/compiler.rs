@@ -2,10 +2,23 @@
 //!
 //! Main entry points for compiling Blueprint graphs to Rust code.
 
-use crate::metadata::{BlueprintMetadataProvider, get_node_metadata};
-use crate::codegen::BlueprintCodeGenerator;
-use graphy::{GraphDescription, GraphyError, DataResolver, ExecutionRouting};
-use std::collections::HashMap;
+use crate::cancellation::CancellationToken;
+use crate::metadata::BlueprintMetadataProvider;
+use graphy::core::NodeMetadataProvider;
+use crate::codegen::{BlueprintCodeGenerator, ClassContext, GeneratedFunction, VariableDef, const_variables};
+use crate::diagnostics::{Diagnostic, Severity};
+#[cfg(feature = "serde")]
+use crate::diagnostics::{diagnostics_to_json, error_to_json};
+use crate::error::CompileError;
+use crate::ir::BlueprintIR;
+use crate::migration::{MigrationRegistry, SCHEMA_VERSION, schema_version};
+use crate::normalize::{check_data_cycles, normalize, normalize_with_diagnostics};
+use crate::options::{CompileOptions, SubgraphStrategy, TargetSpec};
+use serde::Deserialize;
+use crate::report::OptimizationReport;
+use crate::subgraph::SubgraphLibrary;
+use graphy::{GraphDescription, DataResolver, ExecutionRouting};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 /// Compile a Blueprint graph to Rust source code
 ///
@@ -19,7 +32,7 @@ use std::collections::HashMap;
 /// # Returns
 ///
 /// * `Ok(String)` - The generated Rust source code
-/// * `Err(GraphyError)` - A descriptive error if compilation fails
+/// * `Err(CompileError)` - A descriptive error if compilation fails
 ///
 /// # Examples
 ///
@@ -33,10 +46,93 @@ use std::collections::HashMap;
 ///     Err(e) => eprintln!("Error: {}", e),
 /// }
 /// ```
-pub fn compile_graph(graph: &GraphDescription) -> Result<String, GraphyError> {
+pub fn compile_graph(graph: &GraphDescription) -> Result<String, CompileError> {
     compile_graph_with_library_manager(graph, None)
 }
 
+/// Compile a Blueprint graph from its JSON representation
+///
+/// Convenience wrapper for tooling that keeps graphs as JSON on disk. The JSON
+/// is parsed with [`parse_graph_json`] and then compiled with the target the
+/// graph declares (see [`graph_target`]).
+/// Graphs saved by a schema newer than [`SCHEMA_VERSION`] are rejected with
+/// [`CompileError::UnsupportedSchemaVersion`]; older ones need a
+/// [`Compiler`] with migrations.
+///
+/// # Arguments
+///
+/// * `json` - The serialized `GraphDescription`
+///
+/// # Returns
+///
+/// * `Ok(String)` - The generated Rust source code
+/// * `Err(CompileError)` - A parse error, an unsupported schema version, or a compilation error
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let json = std::fs::read_to_string("my_blueprint.json")?;
+/// let code = pbgc::compile_graph_json(&json)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn compile_graph_json(json: &str) -> Result<String, CompileError> {
+    compile_graph_json_with_options(json, &CompileOptions::default())
+}
+
+/// Compile a Blueprint graph from JSON with custom code generation options
+///
+/// `options.target` overrides the target the graph declares; left at `None`,
+/// the graph's own target is used.
+pub fn compile_graph_json_with_options(json: &str, options: &CompileOptions) -> Result<String, CompileError> {
+    let graph = parse_graph_json(json)?;
+    if let Some(found) = schema_version(&graph).filter(|&version| version > SCHEMA_VERSION) {
+        return Err(CompileError::UnsupportedSchemaVersion { found, current: SCHEMA_VERSION });
+    }
+
+    let mut options = options.clone();
+    if options.target.is_none() {
+        options.target = graph_target(json)?;
+    }
+    compile_graph_with_options(&graph, &options)
+}
+
+/// Target constraints a graph file declares in its `metadata.target` object
+///
+/// `GraphMetadata` has no field for them, so they are read from the saved
+/// JSON, e.g. `"target": { "edition": "2021", "no_std": true }`. Fields left
+/// out take their defaults; a graph without a target yields `None`.
+pub fn graph_target(json: &str) -> Result<Option<TargetSpec>, CompileError> {
+    let document: serde_json::Value = serde_json::from_str(json)?;
+    match document.get("metadata").and_then(|metadata| metadata.get("target")) {
+        Some(target) => Ok(Some(TargetSpec::deserialize(target)?)),
+        None => Ok(None),
+    }
+}
+
+/// Parse a Blueprint graph from JSON
+///
+/// A document that isn't an object with the `nodes`, `connections` and
+/// `metadata` fields of a graph fails with [`CompileError::MissingGraphField`].
+/// Other parse failures are reported as [`CompileError::Deserialize`],
+/// whose `serde_json` error carries the line and column of the offending
+/// input.
+pub fn parse_graph_json(json: &str) -> Result<GraphDescription, CompileError> {
+    let document: serde_json::Value = serde_json::from_str(json)?;
+    if let Some(field) = ["nodes", "connections", "metadata"].into_iter().find(|field| document.get(field).is_none()) {
+        return Err(CompileError::MissingGraphField { field: field.to_string() });
+    }
+
+    // Parsed again from the text so that type errors keep their line and column
+    let graph: GraphDescription = serde_json::from_str(json)?;
+
+    tracing::debug!("[PBGC] Parsed graph '{}' from JSON ({} nodes, {} connections)",
+        graph.metadata.name,
+        graph.nodes.len(),
+        graph.connections.len());
+
+    Ok(graph)
+}
+
 /// Compile a Blueprint graph with sub-graph expansion support
 ///
 /// This extended version of `compile_graph` supports expanding sub-graph instances
@@ -51,19 +147,570 @@ pub fn compile_graph(graph: &GraphDescription) -> Result<String, GraphyError> {
 /// # Returns
 ///
 /// * `Ok(String)` - The generated Rust source code
-/// * `Err(GraphyError)` - A descriptive error if compilation fails
+/// * `Err(CompileError)` - A descriptive error if compilation fails
 pub fn compile_graph_with_library_manager(
     graph: &GraphDescription,
     _library_manager: Option<()>, // TODO: Define LibraryManager type
-) -> Result<String, GraphyError> {
-    tracing::info!("[PBGC] Starting Blueprint compilation");
-    tracing::info!("[PBGC] Graph: {} ({} nodes, {} connections)",
-        graph.metadata.name,
-        graph.nodes.len(),
-        graph.connections.len());
+) -> Result<String, CompileError> {
+    compile_graph_with_options(graph, &CompileOptions::default())
+}
+
+/// Compile a Blueprint graph with custom code generation options
+///
+/// # Arguments
+///
+/// * `graph` - The Blueprint graph to compile
+/// * `options` - Options controlling the generated code
+///
+/// # Returns
+///
+/// * `Ok(String)` - The generated Rust source code
+/// * `Err(CompileError)` - A descriptive error if compilation fails
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pbgc::{compile_graph_with_options, CompileOptions};
+/// use graphy::GraphDescription;
+///
+/// let graph = GraphDescription::new("test");
+/// let options = CompileOptions { trace_nodes: true, ..Default::default() };
+/// let code = compile_graph_with_options(&graph, &options)?;
+/// # Ok::<(), pbgc::CompileError>(())
+/// ```
+pub fn compile_graph_with_options(
+    graph: &GraphDescription,
+    options: &CompileOptions,
+) -> Result<String, CompileError> {
+    let metadata_provider = BlueprintMetadataProvider::new();
+    compile_graph_with_provider(graph, &metadata_provider, options)
+}
+
+/// Compile a Blueprint graph against a specific metadata provider
+///
+/// Use this to supply Blueprint-specific [`NodeAttributes`](crate::NodeAttributes)
+/// for node types, e.g. marking `random` as non-deterministic so it is evaluated
+/// only once.
+///
+/// # Arguments
+///
+/// * `graph` - The Blueprint graph to compile
+/// * `metadata_provider` - Provider for node metadata and attributes
+/// * `options` - Options controlling the generated code
+///
+/// # Returns
+///
+/// * `Ok(String)` - The generated Rust source code
+/// * `Err(CompileError)` - A descriptive error if compilation fails
+pub fn compile_graph_with_provider(
+    graph: &GraphDescription,
+    metadata_provider: &BlueprintMetadataProvider,
+    options: &CompileOptions,
+) -> Result<String, CompileError> {
+    run_pipeline(graph, metadata_provider, HashMap::new(), options, None, None, None).map(|output| output.code)
+}
+
+/// Compile a Blueprint graph, aborting early if `token` is cancelled
+///
+/// The token is checked between pipeline phases and once per node expanded
+/// during code generation. A cancelled compile returns
+/// [`CompileError::Cancelled`].
+pub fn compile_graph_cancellable(
+    graph: &GraphDescription,
+    token: &CancellationToken,
+) -> Result<String, CompileError> {
+    run_pipeline(
+        graph,
+        &BlueprintMetadataProvider::new(),
+        HashMap::new(),
+        &CompileOptions::default(),
+        None,
+        None,
+        Some(token),
+    )
+    .map(|output| output.code)
+}
+
+/// Compile a Blueprint graph, also returning non-fatal diagnostics
+///
+/// # Returns
+///
+/// * `Ok((String, Vec<Diagnostic>))` - The generated code and any warnings
+/// * `Err(CompileError)` - A descriptive error if compilation fails
+pub fn compile_graph_with_diagnostics(
+    graph: &GraphDescription,
+) -> Result<(String, Vec<Diagnostic>), CompileError> {
+    Compiler::new().compile_with_diagnostics(graph)
+}
+
+/// Compile a Blueprint graph, also reporting what the optimizing passes did
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphy::GraphDescription;
+///
+/// let graph = GraphDescription::new("test");
+/// let (code, report) = pbgc::compile_graph_with_report(&graph)?;
+/// println!("{}", report); // e.g. "2 pure nodes hoisted, 1 dead branch pruned"
+/// # Ok::<(), pbgc::CompileError>(())
+/// ```
+pub fn compile_graph_with_report(
+    graph: &GraphDescription,
+) -> Result<(String, OptimizationReport), CompileError> {
+    Compiler::new().compile_with_report(graph)
+}
+
+/// Compile a Blueprint graph and report its diagnostics as JSON
+///
+/// Intended for editor frontends. On success this returns a JSON array of
+/// diagnostics (empty if there were none); on a fatal error it returns a JSON
+/// object of the form `{"error": {"kind": "...", "message": "...", ...}}`
+/// carrying the error's fields (see [`error_to_json`](crate::diagnostics::error_to_json)).
+/// Requires the `serde` feature.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphy::GraphDescription;
+///
+/// let graph = GraphDescription::new("test");
+/// let json = pbgc::compile_graph_diagnostics_json(&graph);
+/// // e.g. [{"severity":"warning","message":"...","node_id":"print_1", ...}]
+/// ```
+#[cfg(feature = "serde")]
+pub fn compile_graph_diagnostics_json(graph: &GraphDescription) -> String {
+    match compile_graph_with_diagnostics(graph) {
+        Ok((_, diagnostics)) => diagnostics_to_json(&diagnostics),
+        Err(err) => error_to_json(&err),
+    }
+}
+
+/// Compile a purely-data graph to a single Rust expression
+///
+/// Each `get_<name>` node in the graph reads `inputs[name]`, an arbitrary Rust
+/// expression. The graph must contain only pure nodes, exactly one of which
+/// is not consumed by another; its fully inlined expression is returned.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphy::GraphDescription;
+/// use std::collections::HashMap;
+///
+/// let graph = GraphDescription::new("damage");
+/// let inputs = HashMap::from([
+///     ("base".to_string(), "stats.attack".to_string()),
+///     ("scale".to_string(), "1.5".to_string()),
+/// ]);
+/// let expr = pbgc::compile_expression(&graph, &inputs)?;
+/// # Ok::<(), pbgc::CompileError>(())
+/// ```
+pub fn compile_expression(
+    graph: &GraphDescription,
+    inputs: &HashMap<String, String>,
+) -> Result<String, CompileError> {
+    Compiler::new().compile_expression(graph, inputs)
+}
+
+/// The Rust code emitted for a single node of a graph
+///
+/// Returns just the node's call and argument expressions (or, for a pure
+/// node, its expression), without the surrounding function or the nodes its
+/// exec outputs lead to. Intended for "show generated code" views in editors.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphy::GraphDescription;
+///
+/// let graph = GraphDescription::new("greeter");
+/// let snippet = pbgc::generate_node_snippet(&graph, "print_1")?;
+/// # Ok::<(), pbgc::CompileError>(())
+/// ```
+pub fn generate_node_snippet(graph: &GraphDescription, node_id: &str) -> Result<String, CompileError> {
+    Compiler::new().generate_node_snippet(graph, node_id)
+}
+
+/// Lower a graph to a backend-neutral [`BlueprintIR`]
+///
+/// The IR holds the pure nodes in evaluation order, each event's exec chain
+/// as a tree with its branches, and the graph's variable accesses. It
+/// serializes with serde, for backends written outside Rust.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphy::GraphDescription;
+///
+/// let graph = GraphDescription::new("vm_target");
+/// let ir = pbgc::lower_graph(&graph)?;
+/// for event in &ir.events {
+///     println!("{}: {} steps", event.name, event.body.len());
+/// }
+/// # Ok::<(), pbgc::CompileError>(())
+/// ```
+pub fn lower_graph(graph: &GraphDescription) -> Result<BlueprintIR, CompileError> {
+    Compiler::new().lower_graph(graph)
+}
+
+/// Compile a graph with class variables
+///
+/// This variant supports Blueprint classes with member variables. The variables
+/// are generated with appropriate thread-safe wrappers (Cell/RefCell + Arc).
+///
+/// Each variable is initialized from its editor-set value when one is given
+/// via [`VariableDef::with_initial`], or from its type's default otherwise.
+/// Plain type strings convert into a [`VariableDef`] without an initial value.
+///
+/// # Arguments
+///
+/// * `graph` - The Blueprint graph to compile
+/// * `variables` - Map of variable names to their definitions (or Rust types)
+///
+/// # Returns
+///
+/// * `Ok(String)` - The generated Rust source code including variable declarations
+/// * `Err(CompileError)` - A descriptive error if compilation fails
+pub fn compile_graph_with_variables<V: Into<VariableDef>>(
+    graph: &GraphDescription,
+    variables: HashMap<String, V>,
+) -> Result<String, CompileError> {
+    Compiler::new().compile_with_variables(graph, variables)
+}
 
-    // Create a mutable copy for expansion
-    let expanded_graph = graph.clone();
+/// Compile several event graphs sharing the same variables into one class
+///
+/// The graphs are merged into a single program: the class struct (holding
+/// `variables`) and each import are emitted once, and the events of every
+/// graph become methods of the class. Node IDs that collide between graphs
+/// are made unique before merging.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pbgc::{ClassContext, VariableDef};
+/// use graphy::GraphDescription;
+/// use std::collections::HashMap;
+///
+/// let graphs = vec![GraphDescription::new("movement"), GraphDescription::new("combat")];
+/// let variables = HashMap::from([("health".to_string(), VariableDef::new("f32"))]);
+/// let code = pbgc::compile_class(&graphs, variables, ClassContext::new("Player"))?;
+/// # Ok::<(), pbgc::CompileError>(())
+/// ```
+pub fn compile_class<V: Into<VariableDef>>(
+    graphs: &[GraphDescription],
+    variables: HashMap<String, V>,
+    class_context: ClassContext,
+) -> Result<String, CompileError> {
+    Compiler::new().compile_class(graphs, variables, class_context)
+}
+
+/// Reusable Blueprint compiler
+///
+/// Holds a [`BlueprintMetadataProvider`] (with any registered node attributes
+/// and extra nodes), options, migrations and sub-graphs, so they are set up
+/// once and shared by every graph it compiles. [`compile`](Self::compile)
+/// only builds the per-graph resolvers.
+///
+/// The pulsar_std node library is loaded once per process either way, so
+/// reusing a `Compiler` saves configuration code rather than compile time:
+/// the `compiler_reuse` benchmark (500 graphs of 21 nodes) compiles at about
+/// 135 us per graph both ways, within 2% of each other.
+///
+/// Output is identical to the equivalent free function.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pbgc::Compiler;
+/// use graphy::GraphDescription;
+///
+/// let compiler = Compiler::new();
+/// let graphs = vec![GraphDescription::new("a"), GraphDescription::new("b")];
+/// for graph in &graphs {
+///     let code = compiler.compile(graph)?;
+/// }
+/// # Ok::<(), pbgc::CompileError>(())
+/// ```
+pub struct Compiler {
+    metadata_provider: BlueprintMetadataProvider,
+    options: CompileOptions,
+    migrations: Option<MigrationRegistry>,
+    subgraphs: Option<SubgraphLibrary>,
+}
+
+impl Compiler {
+    /// Create a compiler using the pulsar_std node library and default options
+    pub fn new() -> Self {
+        Self::with_provider(BlueprintMetadataProvider::new())
+    }
+
+    /// Create a compiler using a specific metadata provider
+    pub fn with_provider(metadata_provider: BlueprintMetadataProvider) -> Self {
+        Self {
+            metadata_provider,
+            options: CompileOptions::default(),
+            migrations: None,
+            subgraphs: None,
+        }
+    }
+
+    /// Set the options used for every compilation
+    pub fn with_options(mut self, options: CompileOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Upgrade graphs through these migrations before compiling them
+    pub fn with_migrations(mut self, migrations: MigrationRegistry) -> Self {
+        self.migrations = Some(migrations);
+        self
+    }
+
+    /// Make these sub-graphs available to compiled graphs
+    ///
+    /// Nodes whose type names a sub-graph are compiled according to
+    /// [`CompileOptions::subgraph_strategy`].
+    pub fn with_subgraphs(mut self, subgraphs: SubgraphLibrary) -> Self {
+        for (metadata, attributes) in subgraphs.instance_metadata() {
+            let node_type = metadata.name.clone();
+            self.metadata_provider = self.metadata_provider
+                .with_node_metadata(metadata)
+                .with_attributes(node_type, attributes);
+        }
+        self.subgraphs = Some(subgraphs);
+        self
+    }
+
+    /// The metadata provider shared by all compilations
+    pub fn metadata_provider(&self) -> &BlueprintMetadataProvider {
+        &self.metadata_provider
+    }
+
+    /// The options shared by all compilations
+    pub fn options(&self) -> &CompileOptions {
+        &self.options
+    }
+
+    /// Compile a graph
+    pub fn compile(&self, graph: &GraphDescription) -> Result<String, CompileError> {
+        self.compile_with_diagnostics(graph).map(|(code, _)| code)
+    }
+
+    /// Compile a graph, also returning non-fatal diagnostics
+    pub fn compile_with_diagnostics(
+        &self,
+        graph: &GraphDescription,
+    ) -> Result<(String, Vec<Diagnostic>), CompileError> {
+        self.run(graph, HashMap::new())
+            .map(|output| (output.code, output.diagnostics))
+    }
+
+    /// Compile a graph, also reporting what the optimizing passes did
+    pub fn compile_with_report(
+        &self,
+        graph: &GraphDescription,
+    ) -> Result<(String, OptimizationReport), CompileError> {
+        self.run(graph, HashMap::new())
+            .map(|output| (output.code, output.report))
+    }
+
+    /// Compile a graph, also returning the signature of every generated event function
+    ///
+    /// Useful for generating bindings (e.g. `extern "C"` shims) around the
+    /// output without parsing it.
+    pub fn compile_with_functions(
+        &self,
+        graph: &GraphDescription,
+    ) -> Result<(String, Vec<GeneratedFunction>), CompileError> {
+        self.run(graph, HashMap::new())
+            .map(|output| (output.code, output.functions))
+    }
+
+    /// Compile a graph into `syn` functions marked `#[pbgc_generated]`
+    ///
+    /// Only free functions are returned, so this is meant for graphs compiled
+    /// without a class. See [`generated_fns`](crate::generated_fns).
+    #[cfg(feature = "syn")]
+    pub fn compile_to_fns(&self, graph: &GraphDescription) -> Result<Vec<syn::ItemFn>, CompileError> {
+        let code = self.compile(graph)?;
+        crate::ast::generated_fns(&code)
+    }
+
+    /// Compile a purely-data graph to a single Rust expression
+    ///
+    /// See [`compile_expression`](crate::compile_expression).
+    pub fn compile_expression(
+        &self,
+        graph: &GraphDescription,
+        inputs: &HashMap<String, String>,
+    ) -> Result<String, CompileError> {
+        let prepared = self.prepare(graph)?;
+        let code_generator = BlueprintCodeGenerator::new(
+            &prepared.graph,
+            &self.metadata_provider,
+            &prepared.data_resolver,
+            &prepared.exec_routing,
+            HashMap::new(),
+            &self.options,
+        );
+        code_generator.generate_expression(inputs)
+    }
+
+    /// The code emitted for a single node of a graph
+    ///
+    /// See [`generate_node_snippet`](crate::generate_node_snippet).
+    pub fn generate_node_snippet(&self, graph: &GraphDescription, node_id: &str) -> Result<String, CompileError> {
+        let prepared = self.prepare(graph)?;
+        let code_generator = BlueprintCodeGenerator::new(
+            &prepared.graph,
+            &self.metadata_provider,
+            &prepared.data_resolver,
+            &prepared.exec_routing,
+            HashMap::new(),
+            &self.options,
+        );
+        code_generator.generate_node_snippet(node_id)
+    }
+
+    /// Lower a graph to a backend-neutral IR
+    ///
+    /// See [`lower_graph`](crate::lower_graph).
+    pub fn lower_graph(&self, graph: &GraphDescription) -> Result<BlueprintIR, CompileError> {
+        let prepared = self.prepare(graph)?;
+        crate::ir::lower(&prepared.graph, &self.metadata_provider, &prepared.data_resolver, &prepared.exec_routing)
+    }
+
+    /// Compile a graph with class variables
+    pub fn compile_with_variables<V: Into<VariableDef>>(
+        &self,
+        graph: &GraphDescription,
+        variables: HashMap<String, V>,
+    ) -> Result<String, CompileError> {
+        tracing::info!("[PBGC] Compiling with {} class variables", variables.len());
+
+        let variables: HashMap<String, VariableDef> = variables
+            .into_iter()
+            .map(|(name, var)| (name, var.into()))
+            .collect();
+
+        self.run(graph, variables).map(|output| output.code)
+    }
+
+    /// Compile several event graphs sharing variables into one class
+    ///
+    /// See [`compile_class`](crate::compile_class). The class context replaces
+    /// any class set in this compiler's options.
+    pub fn compile_class<V: Into<VariableDef>>(
+        &self,
+        graphs: &[GraphDescription],
+        variables: HashMap<String, V>,
+        class_context: ClassContext,
+    ) -> Result<String, CompileError> {
+        tracing::info!("[PBGC] Compiling class '{}' from {} graphs", class_context.name, graphs.len());
+
+        // Migrate each graph on its own, since they may be at different schema versions
+        let mut migrated = Vec::with_capacity(graphs.len());
+        for graph in graphs {
+            let mut graph = graph.clone();
+            if let Some(migrations) = &self.migrations {
+                migrations.migrate(&mut graph)?;
+            }
+            migrated.push(graph);
+        }
+        let merged = merge_graphs(&class_context.name, migrated);
+
+        let variables: HashMap<String, VariableDef> = variables
+            .into_iter()
+            .map(|(name, var)| (name, var.into()))
+            .collect();
+        let options = CompileOptions {
+            class: Some(class_context),
+            ..self.options.clone()
+        };
+
+        run_pipeline(&merged, &self.metadata_provider, variables, &options, None, self.subgraphs.as_ref(), None)
+            .map(|output| output.code)
+    }
+
+    fn prepare(&self, graph: &GraphDescription) -> Result<PreparedGraph, CompileError> {
+        prepare_graph(
+            graph,
+            &self.metadata_provider,
+            &self.options,
+            self.migrations.as_ref(),
+            self.subgraphs.as_ref(),
+            None,
+        )
+    }
+
+    fn run(&self, graph: &GraphDescription, variables: HashMap<String, VariableDef>) -> Result<PipelineOutput, CompileError> {
+        run_pipeline(
+            graph,
+            &self.metadata_provider,
+            variables,
+            &self.options,
+            self.migrations.as_ref(),
+            self.subgraphs.as_ref(),
+            None,
+        )
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Everything produced by one run of the pipeline
+struct PipelineOutput {
+    code: String,
+    diagnostics: Vec<Diagnostic>,
+    functions: Vec<GeneratedFunction>,
+    report: OptimizationReport,
+}
+
+/// A graph ready for code generation, with its data and exec flow analysed
+struct PreparedGraph {
+    graph: GraphDescription,
+    data_resolver: DataResolver,
+    exec_routing: ExecutionRouting,
+    /// Diagnostics from normalization
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Migrate, normalize and analyse a copy of a graph
+///
+/// Instances of sub-graphs in `subgraphs` are checked against the options'
+/// [`SubgraphStrategy`]; with `Functions` they compile to calls of the
+/// sub-graph's shared function.
+fn prepare_graph(
+    graph: &GraphDescription,
+    metadata_provider: &BlueprintMetadataProvider,
+    options: &CompileOptions,
+    migrations: Option<&MigrationRegistry>,
+    subgraphs: Option<&SubgraphLibrary>,
+    cancellation: Option<&CancellationToken>,
+) -> Result<PreparedGraph, CompileError> {
+    let check_cancelled = || match cancellation {
+        Some(token) if token.is_cancelled() => Err(CompileError::Cancelled),
+        _ => Ok(()),
+    };
+
+    // Create a mutable copy for migration and expansion
+    let mut graph = graph.clone();
+
+    // Upgrade graphs saved against older node signatures
+    if let Some(migrations) = migrations {
+        migrations.migrate(&mut graph)?;
+    }
+    let diagnostics = normalize_with_diagnostics(&mut graph)?;
+    check_data_cycles(&graph, metadata_provider)?;
+    if let Some(subgraphs) = subgraphs {
+        instantiated_subgraphs(&graph, subgraphs, options)?;
+    }
 
     // Phase 0: Expand sub-graphs if library manager is provided
     // TODO: Implement sub-graph expansion
@@ -74,70 +721,809 @@ pub fn compile_graph_with_library_manager(
 
     // Phase 1: Get node metadata
     tracing::info!("[PBGC] Phase 1: Loading node metadata...");
-    let metadata_provider = BlueprintMetadataProvider::new();
-    tracing::info!("[PBGC] Loaded {} node types", get_node_metadata().len());
+    tracing::info!("[PBGC] Loaded {} node types", metadata_provider.get_all_nodes().len());
 
     // Phase 2: Build data flow resolver
+    check_cancelled()?;
     tracing::info!("[PBGC] Phase 2: Analyzing data flow...");
-    let data_resolver = DataResolver::build(&expanded_graph, &metadata_provider)?;
+    let data_resolver = DataResolver::build(&graph, metadata_provider)?;
     tracing::info!("[PBGC] Data flow analysis complete");
     tracing::info!("[PBGC]   - {} pure nodes in evaluation order",
         data_resolver.get_pure_evaluation_order().len());
 
     // Phase 3: Build execution routing
+    check_cancelled()?;
     tracing::info!("[PBGC] Phase 3: Analyzing execution flow...");
-    let exec_routing = ExecutionRouting::build_from_graph(&expanded_graph);
+    let exec_routing = ExecutionRouting::build_from_graph(&graph);
     tracing::info!("[PBGC] Execution flow analysis complete");
 
+    Ok(PreparedGraph {
+        graph,
+        data_resolver,
+        exec_routing,
+        diagnostics,
+    })
+}
+
+/// Run the full compilation pipeline on a graph
+fn run_pipeline(
+    graph: &GraphDescription,
+    metadata_provider: &BlueprintMetadataProvider,
+    variables: HashMap<String, VariableDef>,
+    options: &CompileOptions,
+    migrations: Option<&MigrationRegistry>,
+    subgraphs: Option<&SubgraphLibrary>,
+    cancellation: Option<&CancellationToken>,
+) -> Result<PipelineOutput, CompileError> {
+    let check_cancelled = || match cancellation {
+        Some(token) if token.is_cancelled() => Err(CompileError::Cancelled),
+        _ => Ok(()),
+    };
+
+    tracing::info!("[PBGC] Starting Blueprint compilation");
+    tracing::info!("[PBGC] Graph: {} ({} nodes, {} connections)",
+        graph.metadata.name,
+        graph.nodes.len(),
+        graph.connections.len());
+
+    let PreparedGraph {
+        graph: expanded_graph,
+        data_resolver,
+        exec_routing,
+        diagnostics: normalize_diagnostics,
+    } = prepare_graph(graph, metadata_provider, options, migrations, subgraphs, cancellation)?;
+
     // Phase 4: Generate code
+    check_cancelled()?;
     tracing::info!("[PBGC] Phase 4: Generating Rust code...");
-    let variables = HashMap::new();
-    let code_generator = BlueprintCodeGenerator::new(
+    // Shared sub-graph functions may write variables too, so constness is decided across all graphs
+    let constants = subgraphs
+        .filter(|_| options.class.is_none())
+        .map(|subgraphs| const_variables(&variables, std::iter::once(&expanded_graph).chain(subgraphs.graphs())));
+    let shared = subgraphs
+        .map(|subgraphs| {
+            compile_subgraphs(&expanded_graph, subgraphs, metadata_provider, &variables, constants.as_ref(), options, cancellation)
+        })
+        .transpose()?;
+    let mut code_generator = BlueprintCodeGenerator::new(
         &expanded_graph,
-        &metadata_provider,
+        metadata_provider,
         &data_resolver,
         &exec_routing,
         variables,
+        options,
     );
-    let code = code_generator.generate_program()?;
+    if let Some(token) = cancellation {
+        code_generator = code_generator.with_cancellation(token.clone());
+    }
+    if let Some(constants) = constants {
+        code_generator = code_generator.with_const_variables(constants);
+    }
+    let mut code = code_generator.generate_program()?;
+    let mut diagnostics = normalize_diagnostics;
+    diagnostics.extend(code_generator.diagnostics());
+    let mut functions = code_generator.generated_functions();
+    let mut report = code_generator.optimization_report();
+
+    if let Some(shared) = shared {
+        code.push_str(&shared.code);
+        diagnostics.extend(shared.diagnostics);
+        functions.extend(shared.functions);
+        report += shared.report;
+    }
+
+    for post_processor in &options.post_processors {
+        code = post_processor.process(code)?;
+    }
 
     tracing::info!("[PBGC] Code generation complete ({} bytes)", code.len());
-    tracing::info!("[PBGC] Compilation successful!");
 
-    Ok(code)
+    if options.deny_warnings {
+        let warnings: Vec<Diagnostic> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+            .cloned()
+            .collect();
+        if !warnings.is_empty() {
+            return Err(CompileError::DiagnosticsAsErrors(warnings));
+        }
+    }
+    tracing::info!("[PBGC] Compilation successful! ({} diagnostics)", diagnostics.len());
+
+    Ok(PipelineOutput {
+        code,
+        diagnostics,
+        functions,
+        report,
+    })
 }
 
-/// Compile a graph with class variables
-///
-/// This variant supports Blueprint classes with member variables. The variables
-/// are generated with appropriate thread-safe wrappers (Cell/RefCell + Arc).
+/// Merge graphs into one, renaming nodes whose IDs are already taken
 ///
-/// # Arguments
-///
-/// * `graph` - The Blueprint graph to compile
-/// * `variables` - Map of variable names to their Rust types
+/// A renamed node gets the ID `g{index}_{id}`, with a `_2`, `_3`, ... suffix
+/// if a node of either graph already has that ID; its pin IDs and
+/// connections are updated to match.
+fn merge_graphs(name: &str, graphs: Vec<GraphDescription>) -> GraphDescription {
+    let mut graphs = graphs.into_iter().enumerate();
+    let Some((_, mut merged)) = graphs.next() else {
+        return GraphDescription::new(name);
+    };
+    merged.metadata.name = name.to_string();
+
+    for (index, mut graph) in graphs {
+        let mut taken: HashSet<String> = merged.nodes.keys().chain(graph.nodes.keys()).cloned().collect();
+        let mut clashing: Vec<&String> = graph.nodes.keys().filter(|id| merged.nodes.contains_key(*id)).collect();
+        clashing.sort();
+        let renames: HashMap<String, String> = clashing
+            .into_iter()
+            .map(|id| (id.clone(), unused_id(format!("g{}_{}", index, id), &mut taken)))
+            .collect();
+        let mut taken_connections: HashSet<String> = merged.connections
+            .iter()
+            .chain(&graph.connections)
+            .map(|connection| connection.id.clone())
+            .collect();
+
+        for (id, mut node) in graph.nodes.drain() {
+            if let Some(new_id) = renames.get(&id) {
+                for pin in node.inputs.iter_mut().chain(node.outputs.iter_mut()) {
+                    pin.id = rename_pin(&pin.id, &id, new_id);
+                }
+                node.id = new_id.clone();
+            }
+            merged.nodes.insert(node.id.clone(), node);
+        }
+
+        for mut connection in graph.connections {
+            if let Some(new_id) = renames.get(&connection.source_node) {
+                connection.source_pin = rename_pin(&connection.source_pin, &connection.source_node, new_id);
+                connection.source_node = new_id.clone();
+            }
+            if let Some(new_id) = renames.get(&connection.target_node) {
+                connection.target_pin = rename_pin(&connection.target_pin, &connection.target_node, new_id);
+                connection.target_node = new_id.clone();
+            }
+            if merged.connections.iter().any(|c| c.id == connection.id) {
+                connection.id = unused_id(format!("g{}_{}", index, connection.id), &mut taken_connections);
+            }
+            merged.connections.push(connection);
+        }
+    }
+
+    merged
+}
+
+/// `id`, or `id_2`, `id_3`, ... if that is taken, marking the result taken
+fn unused_id(id: String, taken: &mut HashSet<String>) -> String {
+    let mut candidate = id.clone();
+    let mut suffix = 1;
+    while taken.contains(&candidate) {
+        suffix += 1;
+        candidate = format!("{}_{}", id, suffix);
+    }
+    taken.insert(candidate.clone());
+    candidate
+}
+
+/// Pin ID `{old_node}_{pin}` rewritten for a renamed node
+fn rename_pin(pin_id: &str, old_node: &str, new_node: &str) -> String {
+    match pin_id.strip_prefix(old_node) {
+        Some(rest) => format!("{}{}", new_node, rest),
+        None => pin_id.to_string(),
+    }
+}
+
+/// Emit one shared function per sub-graph instantiated by `graph`
 ///
-/// # Returns
+/// Sub-graphs instantiated from other sub-graphs are included, each once.
+fn compile_subgraphs(
+    graph: &GraphDescription,
+    subgraphs: &SubgraphLibrary,
+    metadata_provider: &BlueprintMetadataProvider,
+    variables: &HashMap<String, VariableDef>,
+    constants: Option<&HashSet<String>>,
+    options: &CompileOptions,
+    cancellation: Option<&CancellationToken>,
+) -> Result<PipelineOutput, CompileError> {
+    let mut output = PipelineOutput {
+        code: String::new(),
+        diagnostics: Vec::new(),
+        functions: Vec::new(),
+        report: OptimizationReport::default(),
+    };
+    let mut emitted = BTreeSet::new();
+    let mut pending = instantiated_subgraphs(graph, subgraphs, options)?;
+
+    while let Some(name) = pending.pop_first() {
+        if !emitted.insert(name.clone()) {
+            continue;
+        }
+        tracing::info!("[PBGC] Emitting shared function for sub-graph '{}'", name);
+
+        let mut definition = subgraphs.get(&name).cloned().expect("instantiated sub-graphs are registered");
+        normalize(&mut definition)?;
+        check_data_cycles(&definition, metadata_provider)?;
+        pending.extend(instantiated_subgraphs(&definition, subgraphs, options)?);
+
+        let data_resolver = DataResolver::build(&definition, metadata_provider)?;
+        let exec_routing = ExecutionRouting::build_from_graph(&definition);
+        let mut code_generator = BlueprintCodeGenerator::new(
+            &definition,
+            metadata_provider,
+            &data_resolver,
+            &exec_routing,
+            variables.clone(),
+            options,
+        );
+        if let Some(token) = cancellation {
+            code_generator = code_generator.with_cancellation(token.clone());
+        }
+        if let Some(constants) = constants {
+            code_generator = code_generator.with_const_variables(constants.clone());
+        }
+
+        output.code.push('\n');
+        output.code.push_str(&code_generator.generate_subgraph_function(&name)?);
+        if options.emit_tests && !subgraphs.examples(&name).is_empty() {
+            output.code.push('\n');
+            output.code.push_str(&code_generator.generate_subgraph_examples(&name, subgraphs.examples(&name))?);
+        }
+        output.diagnostics.extend(code_generator.diagnostics());
+        output.functions.extend(code_generator.generated_functions());
+        output.report += code_generator.optimization_report();
+    }
+
+    Ok(output)
+}
+
+/// Names of the sub-graphs instantiated directly by `graph`
 ///
-/// * `Ok(String)` - The generated Rust source code including variable declarations
-/// * `Err(GraphyError)` - A descriptive error if compilation fails
-pub fn compile_graph_with_variables(
+/// Fails if the options ask for instances to be inlined.
+fn instantiated_subgraphs(
     graph: &GraphDescription,
-    variables: HashMap<String, String>,
-) -> Result<String, GraphyError> {
-    tracing::info!("[PBGC] Compiling with {} class variables", variables.len());
+    subgraphs: &SubgraphLibrary,
+    options: &CompileOptions,
+) -> Result<BTreeSet<String>, CompileError> {
+    let mut names = BTreeSet::new();
+    for node in graph.nodes.values() {
+        if subgraphs.get(&node.node_type).is_none() {
+            continue;
+        }
+        if options.subgraph_strategy == SubgraphStrategy::Inline {
+            return Err(CompileError::UnexpandedSubgraph {
+                node_id: node.id.clone(),
+                subgraph: node.node_type.clone(),
+            });
+        }
+        names.insert(node.node_type.clone());
+    }
+    Ok(names)
+}
 
-    let metadata_provider = BlueprintMetadataProvider::new();
-    let data_resolver = DataResolver::build(&graph, &metadata_provider)?;
-    let exec_routing = ExecutionRouting::build_from_graph(&graph);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subgraph::{SUBGRAPH_INPUTS, SUBGRAPH_OUTPUTS};
+    use crate::test_support::{GraphBuilder, provider};
+    use graphy::{GraphyError, PropertyValue};
 
-    let code_generator = BlueprintCodeGenerator::new(
-        &graph,
-        &metadata_provider,
-        &data_resolver,
-        &exec_routing,
-        variables,
-    );
+    fn compile(graph: &GraphDescription) -> Result<String, CompileError> {
+        Compiler::with_provider(provider()).compile(graph)
+    }
+
+    fn two_node_graph() -> GraphDescription {
+        GraphBuilder::new()
+            .node("main", "main")
+            .node("print", "print_string")
+            .exec("main", "body", "print")
+            .build()
+    }
+
+    #[test]
+    fn unknown_node_type_is_a_graphy_error() {
+        let mut graph = two_node_graph();
+        graph.nodes.get_mut("print").unwrap().node_type = "teleport".to_string();
+
+        let err = compile(&graph).unwrap_err();
+        assert!(matches!(&err, CompileError::Graphy(GraphyError::NodeNotFound(node_type)) if node_type == "teleport"));
+        assert_eq!(err.code(), "graphy");
+    }
+
+    #[test]
+    fn misnamed_pin_is_an_invalid_pin_id() {
+        let mut graph = GraphBuilder::new().node("main", "main").build();
+        graph.nodes.get_mut("main").unwrap().outputs[0].id = "main_out".to_string();
+
+        match compile(&graph) {
+            Err(CompileError::InvalidPinId { node_id, pin_id, expected }) => {
+                assert_eq!(node_id, "main");
+                assert_eq!(pin_id, "main_out");
+                assert_eq!(expected, "main_body");
+            }
+            other => panic!("expected InvalidPinId, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn connection_to_a_missing_node_is_dangling() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .exec("main", "body", "gone")
+            .build();
+
+        let err = compile(&graph).unwrap_err();
+        assert!(matches!(err, CompileError::DanglingConnection { ref connection_id, .. } if connection_id == "c0"));
+        assert_eq!(err.code(), "dangling_connection");
+    }
+
+    #[test]
+    fn connection_to_a_missing_pin_is_dangling() {
+        let mut graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("log", "print_string")
+            .exec("main", "body", "log")
+            .build();
+        graph.connections[0].target_pin = "log_renamed".to_string();
+
+        match compile(&graph) {
+            Err(CompileError::DanglingConnection { connection_id, missing_endpoint }) => {
+                assert_eq!(connection_id, "c0");
+                assert_eq!(missing_endpoint, "input pin 'log_renamed' on node 'log'");
+            }
+            other => panic!("expected DanglingConnection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_graph_has_no_entry_points() {
+        match compile(&GraphDescription::new("placeholder")) {
+            Err(CompileError::NoEntryPoints) => {}
+            other => panic!("expected NoEntryPoints, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_graph_compiles_when_allowed() {
+        let options = CompileOptions { allow_empty: true, ..CompileOptions::default() };
+        let code = Compiler::with_provider(provider())
+            .with_options(options)
+            .compile(&GraphDescription::new("placeholder"))
+            .unwrap();
+
+        assert!(code.contains("// empty blueprint"), "{}", code);
+    }
+
+    #[test]
+    fn deny_warnings_fails_on_a_dead_branch() {
+        // The False branch can never run, which is reported as a warning
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("branch", "branch")
+            .node("print", "print_string")
+            .property("branch", "condition", PropertyValue::Boolean(true))
+            .exec("main", "body", "branch")
+            .exec("branch", "False", "print")
+            .build();
+        let compiler = Compiler::with_provider(provider());
+        let (_, diagnostics) = compiler.compile_with_diagnostics(&graph).unwrap();
+        assert!(diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Warning));
+
+        let options = CompileOptions { deny_warnings: true, ..CompileOptions::default() };
+        match compiler.with_options(options).compile(&graph) {
+            Err(CompileError::DiagnosticsAsErrors(warnings)) => {
+                assert_eq!(warnings.len(), 1);
+                assert!(warnings[0].message.contains("can never run"), "{}", warnings[0].message);
+            }
+            other => panic!("expected DiagnosticsAsErrors, got {:?}", other),
+        }
+    }
+
+    /// An empty graph saved with `target` in its metadata
+    fn json_with_target(target: serde_json::Value) -> String {
+        let mut document = serde_json::to_value(GraphDescription::new("test")).unwrap();
+        document["metadata"]["target"] = target;
+        document.to_string()
+    }
+
+    #[test]
+    fn graph_target_is_read_from_metadata() {
+        let json = json_with_target(serde_json::json!({ "edition": "2021", "no_std": true }));
+
+        let target = graph_target(&json).unwrap().unwrap();
+        assert_eq!(target, TargetSpec { edition: Some("2021".to_string()), no_std: true, ..TargetSpec::default() });
+        assert_eq!(graph_target(&serde_json::to_string(&GraphDescription::new("test")).unwrap()).unwrap(), None);
+    }
+
+    #[test]
+    fn options_target_overrides_the_graphs() {
+        let json = json_with_target(serde_json::json!({ "no_std": true }));
+        let options = CompileOptions { allow_empty: true, ..CompileOptions::default() };
+
+        let code = compile_graph_json_with_options(&json, &options).unwrap();
+        assert!(code.contains("#![no_std]"), "{}", code);
+
+        // Even the default target overrides the graph's
+        let target = Some(TargetSpec::default());
+        let code = compile_graph_json_with_options(&json, &CompileOptions { target, ..options }).unwrap();
+        assert!(!code.contains("#![no_std]"), "{}", code);
+    }
+
+    #[test]
+    fn json_round_trip_compiles_the_same() {
+        let graph = two_node_graph();
+        let json = serde_json::to_string_pretty(&graph).unwrap();
+
+        let parsed = parse_graph_json(&json).unwrap();
+        assert_eq!(parsed.nodes.len(), 2);
+        assert_eq!(parsed.connections.len(), 1);
+        assert_eq!(compile(&parsed).unwrap(), compile(&graph).unwrap());
+    }
+
+    #[test]
+    fn json_syntax_error_has_its_position() {
+        match parse_graph_json("{\n  \"nodes\": {,\n}") {
+            Err(CompileError::Deserialize(err)) => {
+                assert!(err.is_syntax());
+                assert_eq!(err.line(), 2);
+            }
+            other => panic!("expected a deserialize error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn json_without_graph_fields_is_rejected() {
+        let err = parse_graph_json(r#"{"nodes": {}, "connections": []}"#).unwrap_err();
+        assert!(matches!(err, CompileError::MissingGraphField { ref field } if field == "metadata"));
+    }
+
+    #[test]
+    fn json_from_a_newer_schema_is_rejected() {
+        let mut graph = two_node_graph();
+        graph.metadata.version = format!("{}.0.0", SCHEMA_VERSION + 1);
+        let json = serde_json::to_string(&graph).unwrap();
+
+        match compile_graph_json(&json) {
+            Err(CompileError::UnsupportedSchemaVersion { found, current }) => {
+                assert_eq!(found, SCHEMA_VERSION + 1);
+                assert_eq!(current, SCHEMA_VERSION);
+            }
+            other => panic!("expected an unsupported schema version, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn merged_graph_renames_around_existing_ids() {
+        let first = two_node_graph();
+        // `print` clashes with the first graph, and its usual new ID `g1_print` is taken
+        let second = GraphBuilder::new()
+            .node("begin_play", "begin_play")
+            .node("print", "print_string")
+            .node("g1_print", "print_string")
+            .exec("begin_play", "body", "print")
+            .exec("print", "exec_out", "g1_print")
+            .build();
+
+        let merged = merge_graphs("class", vec![first, second]);
+        let mut ids: Vec<&str> = merged.nodes.keys().map(String::as_str).collect();
+        ids.sort();
+        assert_eq!(ids, ["begin_play", "g1_print", "g1_print_2", "main", "print"]);
+
+        let renamed = &merged.nodes["g1_print_2"];
+        assert!(renamed.inputs.iter().all(|pin| pin.id.starts_with("g1_print_2_")));
+        assert!(merged.connections.iter().any(|c| c.source_node == "g1_print_2" && c.target_node == "g1_print"));
+        assert!(merged.connections.iter().any(|c| c.source_node == "begin_play" && c.target_node == "g1_print_2"));
+
+        let mut connection_ids: Vec<&str> = merged.connections.iter().map(|c| c.id.as_str()).collect();
+        connection_ids.sort();
+        connection_ids.dedup();
+        assert_eq!(connection_ids.len(), merged.connections.len());
+    }
+
+    #[test]
+    fn strict_inputs_rejects_what_lenient_mode_defaults() {
+        let graph = two_node_graph();
+        let options = CompileOptions { strict_inputs: true, ..CompileOptions::default() };
+
+        let err = Compiler::with_provider(provider()).with_options(options.clone()).compile(&graph).unwrap_err();
+        assert!(matches!(err, CompileError::MissingInput { ref node_id, ref pin } if node_id == "print" && pin == "message"));
+
+        let code = compile(&graph).unwrap();
+        assert!(code.contains("print_string(String::new());"), "{}", code);
+
+        let mut graph = graph;
+        graph.nodes.get_mut("print").unwrap().properties.insert("message".to_string(), PropertyValue::String("hi".to_string()));
+        let code = Compiler::with_provider(provider()).with_options(options).compile(&graph).unwrap();
+        assert!(code.contains("print_string(\"hi\");"), "{}", code);
+    }
+
+    /// Sub-graph `copy`, passing a `ty` through `identity`
+    fn copy_subgraph(ty: &str) -> SubgraphLibrary {
+        let copy = GraphBuilder::new()
+            .bare("in", SUBGRAPH_INPUTS)
+            .output("in", "x", ty)
+            .node("identity", "identity")
+            .bare("out", SUBGRAPH_OUTPUTS)
+            .input("out", "value", ty)
+            .data("in", "x", "identity", "value")
+            .data("identity", "result", "out", "value")
+            .build();
+        SubgraphLibrary::new().with_subgraph("copy", copy)
+    }
+
+    fn compiler_with_subgraphs(subgraph_strategy: SubgraphStrategy) -> Compiler {
+        let options = CompileOptions { subgraph_strategy, ..CompileOptions::default() };
+        Compiler::with_provider(provider()).with_options(options).with_subgraphs(copy_subgraph("i32"))
+    }
+
+    /// `add(base, copy(scale))`, reading its inputs through getters
+    fn damage_formula() -> GraphDescription {
+        GraphBuilder::new()
+            .bare("get_base", "get_base")
+            .output("get_base", "value", "i32")
+            .bare("get_scale", "get_scale")
+            .output("get_scale", "value", "i32")
+            .bare("scaled", "copy")
+            .input("scaled", "x", "i32")
+            .output("scaled", "value", "i32")
+            .node("sum", "add")
+            .data("get_scale", "value", "scaled", "x")
+            .data("get_base", "value", "sum", "a")
+            .data("scaled", "value", "sum", "b")
+            .build()
+    }
+
+    #[test]
+    fn expression_inlines_inputs_and_calls_subgraph_functions() {
+        let inputs = HashMap::from([
+            ("base".to_string(), "stats.attack".to_string()),
+            ("scale".to_string(), "1 + 2".to_string()),
+        ]);
+
+        let expr = compiler_with_subgraphs(SubgraphStrategy::Functions).compile_expression(&damage_formula(), &inputs);
+        assert_eq!(expr.unwrap(), "add(stats.attack, copy((1 + 2)))");
+
+        let err = compiler_with_subgraphs(SubgraphStrategy::Inline).compile_expression(&damage_formula(), &inputs);
+        assert!(matches!(err, Err(CompileError::UnexpandedSubgraph { ref node_id, .. }) if node_id == "scaled"), "{:?}", err);
+    }
+
+    #[test]
+    fn two_sources_on_one_input_are_reported_but_exec_fan_out_is_not() {
+        let fan_out = GraphBuilder::new()
+            .node("main", "main")
+            .node("first", "print_string")
+            .node("second", "print_string")
+            .exec("main", "body", "first")
+            .exec("main", "body", "second")
+            .build();
+        assert!(compile(&fan_out).is_ok());
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("hello", "concat")
+            .node("bye", "concat")
+            .node("print", "print_string")
+            .exec("main", "body", "print")
+            .data("hello", "result", "print", "message")
+            .data("bye", "result", "print", "message")
+            .build();
+        let err = compile(&graph).unwrap_err();
+        assert_eq!(err.code(), "ambiguous_input");
+        assert_eq!(err.to_string(), "Input pin 'print_message' on node 'print' has multiple data sources: hello, bye");
+    }
+
+    #[test]
+    fn subgraph_instantiated_twice_is_one_shared_function() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("hello", "copy")
+            .input("hello", "x", "String")
+            .output("hello", "value", "String")
+            .property("hello", "hello_x", PropertyValue::String("hi".to_string()))
+            .bare("bye", "copy")
+            .input("bye", "x", "String")
+            .output("bye", "value", "String")
+            .property("bye", "bye_x", PropertyValue::String("bye".to_string()))
+            .node("first", "print_string")
+            .node("second", "print_string")
+            .exec("main", "body", "first")
+            .exec("first", "exec_out", "second")
+            .data("hello", "value", "first", "message")
+            .data("bye", "value", "second", "message")
+            .build();
+        let options = CompileOptions { subgraph_strategy: SubgraphStrategy::Functions, ..CompileOptions::default() };
+
+        let code = Compiler::with_provider(provider())
+            .with_options(options)
+            .with_subgraphs(copy_subgraph("String"))
+            .compile(&graph)
+            .unwrap();
+        assert_eq!(code.matches("pub fn copy(x: String) -> String {").count(), 1, "{}", code);
+        assert!(code.contains("    print_string(copy(\"hi\"));\n    print_string(copy(\"bye\"));\n"), "{}", code);
+    }
+
+    #[test]
+    fn subgraph_function_returns_explicitly_when_asked() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("copied", "copy")
+            .input("copied", "x", "i32")
+            .output("copied", "value", "i32")
+            .property("copied", "copied_x", PropertyValue::Number(4.0))
+            .node("despawn", "despawn")
+            .data("copied", "value", "despawn", "entity")
+            .exec("main", "body", "despawn")
+            .build();
+        let compile = |explicit_returns| {
+            let options = CompileOptions { subgraph_strategy: SubgraphStrategy::Functions, explicit_returns, ..CompileOptions::default() };
+            Compiler::with_provider(provider()).with_options(options).with_subgraphs(copy_subgraph("i32")).compile(&graph).unwrap()
+        };
+
+        assert!(compile(false).contains("pub fn copy(x: i32) -> i32 {\n    identity(x)\n}\n"));
+        assert!(compile(true).contains("pub fn copy(x: i32) -> i32 {\n    return identity(x);\n}\n"));
+    }
+
+    #[cfg(feature = "syn")]
+    #[test]
+    fn compiled_functions_parse_as_marked_item_fns() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("begin_play", "begin_play")
+            .node("print", "print_string")
+            .exec("main", "body", "print")
+            .build();
+
+        let fns = Compiler::with_provider(provider()).compile_to_fns(&graph).unwrap();
+        let names: Vec<String> = fns.iter().map(|item_fn| item_fn.sig.ident.to_string()).collect();
+        assert_eq!(names, ["begin_play", "main"]);
+        assert!(fns.iter().all(|item_fn| crate::ast::is_generated(&item_fn.attrs)));
+    }
+
+    #[test]
+    fn node_snippet_is_one_call_without_what_follows() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("greet", "print_string")
+            .node("bye", "print_string")
+            .node("sum", "add")
+            .property("sum", "a", PropertyValue::Number(1.0))
+            .property("sum", "b", PropertyValue::Number(2.0))
+            .node("spawn", "spawn")
+            .property("spawn", "prefab", PropertyValue::String("orc".to_string()))
+            .property("greet", "message", PropertyValue::String("hi".to_string()))
+            .exec("main", "body", "greet")
+            .exec("greet", "exec_out", "bye")
+            .build();
+
+        let compiler = Compiler::with_provider(provider());
+        assert_eq!(compiler.generate_node_snippet(&graph, "greet").unwrap(), "print_string(\"hi\");\n");
+        assert_eq!(compiler.generate_node_snippet(&graph, "sum").unwrap(), "add(1, 2)");
+        assert_eq!(compiler.generate_node_snippet(&graph, "spawn").unwrap(), "let spawn_result = spawn(\"orc\");\n");
+        let err = compiler.generate_node_snippet(&graph, "missing").unwrap_err();
+        assert!(matches!(err, CompileError::Graphy(GraphyError::NodeNotFound(ref id)) if id == "missing"), "{:?}", err);
+    }
+
+    #[test]
+    fn event_pins_without_ids_are_matched_by_name() {
+        let mut graph = two_node_graph();
+        graph.nodes.get_mut("print").unwrap().properties.insert("message".to_string(), PropertyValue::String("hi".to_string()));
+        graph.nodes.get_mut("main").unwrap().outputs[0].id = String::new();
+        graph.connections[0].source_pin = "body".to_string();
+
+        let (code, diagnostics) = Compiler::with_provider(provider()).compile_with_diagnostics(&graph).unwrap();
+        assert!(code.contains("pub fn main() {\n    print_string(\"hi\");\n}"), "{}", code);
+        let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages, ["Pin 'body' on node 'main' has no ID of its own; matched by name as 'main_body'"]);
+    }
+
+    #[test]
+    fn post_processors_run_in_order_over_the_output() {
+        #[derive(Debug)]
+        struct Trailer(&'static str);
+
+        impl crate::PostProcessor for Trailer {
+            fn process(&self, mut code: String) -> Result<String, CompileError> {
+                code.push_str(&format!("// {}\n", self.0));
+                Ok(code)
+            }
+        }
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("log", "print_string")
+            .exec("main", "body", "log")
+            .property("log", "message", PropertyValue::String("hi".to_string()))
+            .build();
+        let options = CompileOptions {
+            post_processors: vec![std::sync::Arc::new(Trailer("reviewed")), std::sync::Arc::new(Trailer("signed"))],
+            ..CompileOptions::default()
+        };
+
+        let code = Compiler::with_provider(provider()).with_options(options).compile(&graph).unwrap();
+        assert!(code.contains("pub fn main() {\n    print_string(\"hi\");\n}"), "{}", code);
+        assert!(code.ends_with("\n// reviewed\n// signed\n"), "{}", code);
+    }
+
+    #[test]
+    fn subgraph_examples_become_one_test_each() {
+        use crate::SubgraphExample;
+
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .bare("copy", "copy")
+            .input("copy", "x", "u32")
+            .output("copy", "value", "u32")
+            .property("copy", "copy_x", PropertyValue::Number(7.0))
+            .node("despawn", "despawn")
+            .exec("main", "body", "despawn")
+            .data("copy", "value", "despawn", "entity")
+            .build();
+        let subgraphs = copy_subgraph("u32")
+            .with_example("copy", SubgraphExample::new("1").with_input("x", "1"))
+            .with_example("copy", SubgraphExample::new("40").with_input("x", "40"));
+        let options = CompileOptions {
+            subgraph_strategy: SubgraphStrategy::Functions,
+            emit_tests: true,
+            ..CompileOptions::default()
+        };
+        let compile = |subgraphs: SubgraphLibrary| {
+            Compiler::with_provider(provider()).with_options(options.clone()).with_subgraphs(subgraphs).compile(&graph)
+        };
+
+        let code = compile(subgraphs).unwrap();
+        assert_eq!(code.matches("#[test]").count(), 2, "{}", code);
+        assert!(
+            code.contains(concat!(
+                "#[cfg(test)]\n",
+                "mod copy_examples {\n",
+                "    use super::*;\n",
+                "\n",
+                "    #[test]\n",
+                "    fn copy_example_1() {\n",
+                "        assert_eq!(copy(1u32), 1u32);\n",
+                "    }\n",
+                "\n",
+                "    #[test]\n",
+                "    fn copy_example_2() {\n",
+                "        assert_eq!(copy(40u32), 40u32);\n",
+                "    }\n",
+                "}\n",
+            )),
+            "{}",
+            code
+        );
+
+        let err = compile(copy_subgraph("u32").with_example("copy", SubgraphExample::new("1"))).unwrap_err();
+        assert_eq!(err.to_string(), "Example 1 of sub-graph 'copy' has no value for input 'x'");
+    }
+
+    #[test]
+    fn report_counts_pruned_branches_and_folded_loops() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("check", "branch")
+            .property("check", "condition", PropertyValue::Boolean(true))
+            .node("loop", "while_loop")
+            .property("loop", "condition", PropertyValue::Boolean(true))
+            .node("tick", "print_string")
+            .property("tick", "message", PropertyValue::String("tick".to_string()))
+            .node("skipped", "print_string")
+            .property("skipped", "message", PropertyValue::String("skipped".to_string()))
+            .exec("main", "body", "check")
+            .exec("check", "True", "loop")
+            .exec("check", "False", "skipped")
+            .exec("loop", "body", "tick")
+            .build();
+        let options = CompileOptions { prune_dead_branches: true, ..CompileOptions::default() };
+
+        let (code, report) = Compiler::with_provider(provider()).with_options(options).compile_with_report(&graph).unwrap();
+        assert!(code.contains("    if true {\n        loop {\n            print_string(\"tick\");\n        }\n    }\n"), "{}", code);
+        assert_eq!(
+            report,
+            OptimizationReport { constants_folded: 1, dead_branches_pruned: 1, ..OptimizationReport::default() }
+        );
+        assert_eq!(report.to_string(), "1 constant folded, 1 dead branch pruned");
 
-    code_generator.generate_program()
+        let (_, report) = Compiler::with_provider(provider()).compile_with_report(&graph).unwrap();
+        assert_eq!(report, OptimizationReport { constants_folded: 1, ..OptimizationReport::default() });
+    }
 }
@@ -0,0 +1,173 @@
+//! # Compile Diagnostics
+//!
+//! Non-fatal findings reported alongside generated code.
+//!
+//! Diagnostics point at the node (and optionally pin) they concern, so an
+//! editor can highlight the offending part of the graph. With the `serde`
+//! feature, diagnostics and errors also serialize to JSON for editors that
+//! talk to the compiler over a socket.
+
+#[cfg(feature = "serde")]
+use crate::error::CompileError;
+#[cfg(feature = "serde")]
+use graphy::GraphyError;
+use graphy::{NodeInstance, Position};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "lowercase"))]
+pub enum Severity {
+    /// Informational note, e.g. a fallback that was applied
+    Info,
+    /// Likely modeling mistake; the generated code may not do what was intended
+    Warning,
+}
+
+/// A non-fatal issue found while compiling a graph
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Node the diagnostic concerns
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub node_id: Option<String>,
+    /// Editor position of that node
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub position: Option<Position>,
+    /// Pin on the node, if the issue is pin-specific
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub pin: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            node_id: None,
+            position: None,
+            pin: None,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn info(message: impl Into<String>) -> Self {
+        Self::new(Severity::Info, message)
+    }
+
+    /// Attach the node (and its editor position) the diagnostic concerns
+    pub fn with_node(mut self, node: &NodeInstance) -> Self {
+        self.node_id = Some(node.id.clone());
+        self.position = Some(node.position);
+        self
+    }
+
+    /// Attach the pin the diagnostic concerns
+    pub fn with_pin(mut self, pin: impl Into<String>) -> Self {
+        self.pin = Some(pin.into());
+        self
+    }
+}
+
+/// Serialize diagnostics as a JSON array
+#[cfg(feature = "serde")]
+pub fn diagnostics_to_json(diagnostics: &[Diagnostic]) -> String {
+    serde_json::to_string(diagnostics).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Serialize a fatal compilation error as a JSON object
+///
+/// The object has the shape `{"error": {"kind": "...", "message": "...", ...}}`,
+/// followed by the error's own fields, e.g. `"node_id"` and `"pin"` for
+/// [`CompileError::MissingInput`].
+#[cfg(feature = "serde")]
+pub fn error_to_json(error: &CompileError) -> String {
+    let mut object = serde_json::Map::new();
+    object.insert("kind".to_string(), error_kind(error).into());
+    object.insert("message".to_string(), error.to_string().into());
+
+    // Struct variants serialize as `{"Variant": {fields}}`, unit variants as `"Variant"`
+    if let Ok(serde_json::Value::Object(variant)) = serde_json::to_value(error) {
+        if let Some(serde_json::Value::Object(fields)) = variant.into_iter().next().map(|(_, fields)| fields) {
+            object.extend(fields);
+        }
+    }
+
+    serde_json::json!({ "error": object }).to_string()
+}
+
+/// Stable machine-readable name for an error
+///
+/// PBGC's own errors use [`CompileError::code`]; Graphy's are named here.
+#[cfg(feature = "serde")]
+fn error_kind(error: &CompileError) -> &'static str {
+    let (CompileError::Graphy(error) | CompileError::Generation { error, .. }) = error else { return error.code() };
+    match error {
+        GraphyError::NodeNotFound(_) => "node_not_found",
+        GraphyError::PinNotFound { .. } => "pin_not_found",
+        GraphyError::CodeGeneration(_) => "code_generation",
+        GraphyError::Custom(_) => "custom",
+        _ => "other",
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    fn error_json(error: &CompileError) -> serde_json::Value {
+        serde_json::from_str(&error_to_json(error)).unwrap()
+    }
+
+    #[test]
+    fn error_json_carries_variant_fields() {
+        let error = CompileError::MissingInput { node_id: "print".to_string(), pin: "message".to_string() };
+
+        assert_eq!(
+            error_json(&error),
+            serde_json::json!({
+                "error": {
+                    "kind": "missing_input",
+                    "message": error.to_string(),
+                    "node_id": "print",
+                    "pin": "message",
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn graphy_error_json_names_the_node_and_pin() {
+        let error = CompileError::Graphy(GraphyError::PinNotFound { node: "add".to_string(), pin: "c".to_string() });
+        let json = error_json(&error);
+
+        assert_eq!(json["error"]["kind"], "pin_not_found");
+        assert_eq!(json["error"]["node_id"], "add");
+        assert_eq!(json["error"]["pin_id"], "c");
+    }
+
+    #[test]
+    fn unit_variant_json_has_only_kind_and_message() {
+        let json = error_json(&CompileError::Cancelled);
+
+        assert_eq!(json["error"].as_object().unwrap().len(), 2);
+        assert_eq!(json["error"]["kind"], "cancelled");
+    }
+
+    #[test]
+    fn denied_diagnostics_are_serialized_in_the_error() {
+        let error = CompileError::DiagnosticsAsErrors(vec![Diagnostic::warning("unused").with_pin("value")]);
+        let json = error_json(&error);
+
+        assert_eq!(
+            json["error"]["diagnostics"],
+            serde_json::json!([{ "severity": "warning", "message": "unused", "pin": "value" }])
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! # Compile Errors
+//!
+//! Blueprint-specific compilation failures.
+//!
+//! Every public entry point returns [`CompileError`], so callers can match on
+//! the kind of failure. Errors raised by Graphy's analysis passes are carried
+//! as they are in [`CompileError::Graphy`].
+
+use crate::diagnostics::Diagnostic;
+use graphy::GraphyError;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, ser::SerializeMap};
+use thiserror::Error;
+
+/// Errors a compilation can fail with
+///
+/// With the `serde` feature, each variant serializes its fields, e.g.
+/// `{"MissingInput": {"node_id": "print", "pin": "message"}}`.
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum CompileError {
+    /// One of Graphy's analysis passes failed
+    #[error(transparent)]
+    Graphy(#[from] #[cfg_attr(feature = "serde", serde(serialize_with = "graphy_fields"))] GraphyError),
+
+    /// Code generation failed with a free-form Graphy error partway through the output
+    ///
+    /// `line` (1-based) and `byte` locate where generation stopped in the
+    /// partial output, so an editor can point at it in a preview.
+    #[error("{error}")]
+    Generation {
+        #[cfg_attr(feature = "serde", serde(skip))]
+        error: GraphyError,
+        line: usize,
+        byte: usize,
+    },
+
+    /// Two node imports bind the same identifier from different paths
+    #[error("Import conflict: '{ident}' is imported from multiple paths: {}", paths.join(", "))]
+    ImportConflict { ident: String, paths: Vec<String> },
+
+    /// A JSON graph could not be parsed
+    ///
+    /// The `serde_json` error gives the line and column of the offending
+    /// input and classifies the failure (syntax, data, premature end).
+    #[error("Failed to parse graph JSON: {0}")]
+    Deserialize(#[from] #[cfg_attr(feature = "serde", serde(serialize_with = "json_error_fields"))] serde_json::Error),
+
+    /// A JSON document is missing one of the top-level fields of a graph
+    #[error("Graph JSON has no '{field}' field; is it a Blueprint graph?")]
+    MissingGraphField { field: String },
+
+    /// The generated code would need a construct the target doesn't support
+    #[error("'{construct}' is not available when targeting {target}")]
+    UnsupportedTarget { construct: String, target: String },
+
+    /// Generation crossed one of the limits set in `CompileOptions`
+    #[error("Compile budget exceeded: {budget} limit of {limit} reached")]
+    BudgetExceeded { budget: String, limit: usize },
+
+    /// An event mapped to a trait method doesn't fit the method's signature
+    #[error("Cannot implement {trait_path}::{method}: {reason}")]
+    TraitImplMismatch { trait_path: String, method: String, reason: String },
+
+    /// A control-flow input borrows a variable that one of the node's branches writes
+    ///
+    /// The borrow lives as long as the construct, so the write would
+    /// double-borrow the variable (a `RefCell` panic, or a borrow error).
+    #[error("Control-flow node '{node_id}' borrows variable '{var}' for its duration, but its branches write it")]
+    PotentialDoubleBorrow { var: String, node_id: String },
+
+    /// A getter or setter accesses a field path its variable's type doesn't declare
+    #[error("Node '{node_id}' accesses field '{path}' of variable '{var}', which isn't one of its declared fields")]
+    UnknownField { var: String, path: String, node_id: String },
+
+    /// A setter assigns a value whose type differs from the variable's
+    #[error("Setter node '{node_id}' assigns a value of type '{found}' to variable '{var}' of type '{expected}'")]
+    VariableTypeMismatch { var: String, expected: String, found: String, node_id: String },
+
+    /// A pin ID doesn't follow the `{node_id}_{pin_name}` convention
+    #[error("Pin '{pin_id}' on node '{node_id}' should have ID '{expected}'")]
+    InvalidPinId { node_id: String, pin_id: String, expected: String },
+
+    /// Several data connections feed the same input pin
+    #[error("Input pin '{pin}' on node '{node}' has multiple data sources: {}", sources.join(", "))]
+    AmbiguousInput { node: String, pin: String, sources: Vec<String> },
+
+    /// Pure nodes feed each other's inputs in a loop, so no value can be computed first
+    #[error("Data cycle between pure nodes: {}", nodes.join(" -> "))]
+    DataCycle { nodes: Vec<String> },
+
+    /// A sub-graph instance was left for inline expansion, which isn't available
+    #[error("Sub-graph instance '{node_id}' ({subgraph}) cannot be inlined; compile with SubgraphStrategy::Functions")]
+    UnexpandedSubgraph { node_id: String, subgraph: String },
+
+    /// The graph was saved by a newer schema than this compiler knows
+    #[error("Graph schema version {found} is newer than the supported version {current}")]
+    UnsupportedSchemaVersion { found: u32, current: u32 },
+
+    /// No migration is registered for one step of a graph's upgrade
+    #[error("No migration registered from graph schema version {from} to {}", from + 1)]
+    MissingMigration { from: u32 },
+
+    /// The compile was aborted through its `CancellationToken`
+    #[error("Compilation cancelled")]
+    Cancelled,
+
+    /// The graph has no event node to generate an entry point from
+    #[error("No event nodes found in graph - add a 'main' or 'begin_play' event")]
+    NoEntryPoints,
+
+    /// Warnings were reported while `deny_warnings` was set
+    #[error("{} warning(s) denied by deny_warnings; first: {}", .0.len(), .0.first().map_or("", |d| d.message.as_str()))]
+    DiagnosticsAsErrors(#[cfg_attr(feature = "serde", serde(serialize_with = "diagnostics_field"))] Vec<Diagnostic>),
+
+    /// A node's code template uses a placeholder none of its pins supply
+    #[error("Template of node type '{node_type}' has placeholder '{{{placeholder}}}', which matches none of its pins")]
+    UnsubstitutedPlaceholder { node_type: String, placeholder: String },
+
+    /// A function node's body template doesn't bind the result its consumers read
+    #[error("Template of node type '{node_type}' never uses '{{{placeholder}}}', which other nodes read")]
+    MissingPlaceholder { node_type: String, placeholder: String },
+
+    /// The generated code failed to parse as Rust
+    #[error("Generated code is not valid Rust: {message}")]
+    InvalidOutput { message: String },
+
+    /// A value-returning node has no result variable to bind its value to
+    #[error("No result variable for node: {node_id}")]
+    MissingResultVariable { node_id: String },
+
+    /// A getter or setter refers to a variable that wasn't declared
+    #[error("Variable '{var}' not found")]
+    VariableNotFound { var: String },
+
+    /// A node treated as a setter doesn't have a `set_` node type
+    #[error("Invalid setter node type: {node_type}")]
+    InvalidSetter { node_type: String },
+
+    /// A node has no input pin for one of its parameters
+    #[error("Input pin not found for parameter '{pin}' on node '{node_id}'")]
+    MissingInputPin { node_id: String, pin: String },
+
+    /// An event call node has no input for one of the called event's parameters
+    #[error("Event call node '{node_id}' has no input for parameter '{param}' of event '{event}'")]
+    MissingEventArgument { node_id: String, param: String, event: String },
+
+    /// An input has neither a connection nor an editor-set value under `strict_inputs`
+    #[error("Input '{pin}' of node '{node_id}' is not connected and has no value")]
+    MissingInput { node_id: String, pin: String },
+
+    /// Data flow analysis has no source for an input pin
+    #[error("No data source for input: {node_id}.{pin_id}")]
+    NoDataSource { node_id: String, pin_id: String },
+
+    /// A branch-scoped output is read from outside its branch
+    #[error("Output '{pin}' of node '{node_id}' is only available inside its '{branch}' branch (read by node '{reader}')")]
+    BranchScopedOutput { node_id: String, pin: String, branch: String, reader: String },
+
+    /// A connection refers to a node or pin the graph doesn't have (e.g. after a node library update)
+    #[error("Connection '{connection_id}' refers to {missing_endpoint}, which does not exist")]
+    DanglingConnection { connection_id: String, missing_endpoint: String },
+
+    /// A borrowed expression would be stored in a binding that outlives the borrow
+    #[error("Node '{node_id}' would bind the borrowed expression '{expr}' to '{binding}', which outlives the borrow")]
+    BorrowedBinding { node_id: String, binding: String, expr: String },
+
+    /// An example registered for a sub-graph doesn't match its inputs
+    #[error("Example {index} of sub-graph '{subgraph}' {reason}")]
+    InvalidExample { subgraph: String, index: usize, reason: String },
+}
+
+impl CompileError {
+    /// Stable machine-readable code for the error's kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            CompileError::Graphy(_) | CompileError::Generation { .. } => "graphy",
+            CompileError::ImportConflict { .. } => "import_conflict",
+            CompileError::Deserialize(_) => "deserialize",
+            CompileError::MissingGraphField { .. } => "missing_graph_field",
+            CompileError::UnsupportedTarget { .. } => "unsupported_target",
+            CompileError::BudgetExceeded { .. } => "budget_exceeded",
+            CompileError::TraitImplMismatch { .. } => "trait_impl_mismatch",
+            CompileError::VariableTypeMismatch { .. } => "variable_type_mismatch",
+            CompileError::UnknownField { .. } => "unknown_field",
+            CompileError::MissingPlaceholder { .. } => "missing_placeholder",
+            CompileError::PotentialDoubleBorrow { .. } => "potential_double_borrow",
+            CompileError::InvalidPinId { .. } => "invalid_pin_id",
+            CompileError::AmbiguousInput { .. } => "ambiguous_input",
+            CompileError::DataCycle { .. } => "data_cycle",
+            CompileError::UnexpandedSubgraph { .. } => "unexpanded_subgraph",
+            CompileError::UnsupportedSchemaVersion { .. } => "unsupported_schema_version",
+            CompileError::MissingMigration { .. } => "missing_migration",
+            CompileError::Cancelled => "cancelled",
+            CompileError::NoEntryPoints => "no_entry_points",
+            CompileError::DiagnosticsAsErrors(_) => "diagnostics_as_errors",
+            CompileError::UnsubstitutedPlaceholder { .. } => "unsubstituted_placeholder",
+            CompileError::InvalidOutput { .. } => "invalid_output",
+            CompileError::MissingResultVariable { .. } => "missing_result_variable",
+            CompileError::VariableNotFound { .. } => "variable_not_found",
+            CompileError::InvalidSetter { .. } => "invalid_setter",
+            CompileError::MissingInputPin { .. } => "missing_input_pin",
+            CompileError::MissingEventArgument { .. } => "missing_event_argument",
+            CompileError::MissingInput { .. } => "missing_input",
+            CompileError::NoDataSource { .. } => "no_data_source",
+            CompileError::BranchScopedOutput { .. } => "branch_scoped_output",
+            CompileError::BorrowedBinding { .. } => "borrowed_binding",
+            CompileError::DanglingConnection { .. } => "dangling_connection",
+            CompileError::InvalidExample { .. } => "invalid_example",
+        }
+    }
+}
+
+/// The node and pin a Graphy error names, as fields
+#[cfg(feature = "serde")]
+fn graphy_fields<S: Serializer>(error: &GraphyError, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(None)?;
+    match error {
+        GraphyError::NodeNotFound(node_id) => map.serialize_entry("node_id", node_id)?,
+        GraphyError::PinNotFound { node, pin } => {
+            map.serialize_entry("node_id", node)?;
+            map.serialize_entry("pin_id", pin)?;
+        }
+        _ => {}
+    }
+    map.end()
+}
+
+/// Where in the input a JSON parse error occurred, as fields
+#[cfg(feature = "serde")]
+fn json_error_fields<S: Serializer>(error: &serde_json::Error, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(2))?;
+    map.serialize_entry("line", &error.line())?;
+    map.serialize_entry("column", &error.column())?;
+    map.end()
+}
+
+/// Denied warnings under a `diagnostics` field
+#[cfg(feature = "serde")]
+fn diagnostics_field<S: Serializer>(diagnostics: &[Diagnostic], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry("diagnostics", diagnostics)?;
+    map.end()
+}
@@ -0,0 +1,236 @@
+//! # Graph Fingerprints
+//!
+//! A stable hash of everything in a graph and its options that can change the
+//! compiled output, for build systems deciding whether to recompile.
+//!
+//! Editor-only state is left out: node positions and the graph's description
+//! and timestamps. The node library isn't part of it either, so a cache must
+//! also be keyed on that.
+
+use crate::codegen::VariableDef;
+use crate::normalize::normalize;
+use crate::options::CompileOptions;
+use graphy::GraphDescription;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// Content hash of a graph, its class variables and the options it is compiled with
+///
+/// Pass an empty map for graphs compiled without variables. The graph is
+/// normalized first, so graphs that only differ in what
+/// normalization fills in share a fingerprint. The hash (64-bit FNV-1a over a
+/// canonical encoding) doesn't depend on map iteration order, the process, or
+/// the platform. Runtime profiles and post-processors are identified by
+/// their `Debug` output.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphy::GraphDescription;
+/// use pbgc::{CompileOptions, VariableDef};
+/// use std::collections::HashMap;
+///
+/// let graph = GraphDescription::new("test");
+/// let variables = HashMap::from([("health".to_string(), VariableDef::new("f32"))]);
+/// let fingerprint = pbgc::graph_fingerprint(&graph, &variables, &CompileOptions::default());
+/// ```
+pub fn graph_fingerprint(
+    graph: &GraphDescription,
+    variables: &HashMap<String, VariableDef>,
+    options: &CompileOptions,
+) -> u64 {
+    let mut normalized = graph.clone();
+    if normalize(&mut normalized).is_err() {
+        // It won't compile either way; fingerprint it as given
+        normalized = graph.clone();
+    }
+
+    let mut hasher = Fnv1a::new();
+    hasher.write(canonical_graph(&normalized).as_bytes());
+    hasher.write(&[0]);
+    hasher.write(format!("{:?}", sorted(variables)).as_bytes());
+    hasher.write(&[0]);
+    hasher.write(canonical_options(options).as_bytes());
+    hasher.finish()
+}
+
+/// The graph as JSON with sorted keys and without editor-only fields
+fn canonical_graph(graph: &GraphDescription) -> String {
+    let mut value = serde_json::to_value(graph).expect("graphs serialize to JSON");
+    if let Some(nodes) = value.get_mut("nodes").and_then(Value::as_object_mut) {
+        for node in nodes.values_mut().filter_map(Value::as_object_mut) {
+            node.remove("position");
+        }
+    }
+    if let Some(metadata) = value.get_mut("metadata").and_then(Value::as_object_mut) {
+        for field in ["description", "created_at", "modified_at"] {
+            metadata.remove(field);
+        }
+    }
+    value.to_string()
+}
+
+/// Every option, with hash maps written in key order
+///
+/// Destructured so that a new option can't be forgotten here.
+fn canonical_options(options: &CompileOptions) -> String {
+    let CompileOptions {
+        trace_nodes,
+        emit_spans,
+        emit_order_comments,
+        print_macro,
+        wasm_exports,
+        c_exports,
+        emit_dispatch,
+        emit_benches,
+        emit_tests,
+        prune_dead_branches,
+        emit_debug_asserts,
+        target,
+        type_aliases,
+        strict_inputs,
+        allow_empty,
+        deny_warnings,
+        explicit_returns,
+        annotate_result_types,
+        closures,
+        context_param,
+        class,
+        cache_shared_pure,
+        max_output_bytes,
+        max_nodes_expanded,
+        switch_fallback,
+        error_policy,
+        composite_constructors,
+        subgraph_strategy,
+        runtime,
+        post_processors,
+    } = options;
+
+    let context_param = context_param.as_ref().map(|spec| {
+        let events: HashMap<&String, String> = spec.events
+            .iter()
+            .map(|(event, context)| (event, format!("{:?} {:?}", context.ty, sorted(&context.fields))))
+            .collect();
+        format!("{:?} {:?}", spec.param, sorted(&events))
+    });
+    let class = class.as_ref().map(|class| {
+        let trait_impls: Vec<String> = class.trait_impls
+            .iter()
+            .map(|trait_impl| format!("{:?} {:?}", trait_impl.trait_path, sorted(&trait_impl.method_map)))
+            .collect();
+        format!("{:?} {:?} {:?} {:?}", class.name, class.derives, trait_impls, class.teardown)
+    });
+
+    let fields: [&dyn Debug; 30] = [
+        trace_nodes,
+        emit_spans,
+        emit_order_comments,
+        print_macro,
+        wasm_exports,
+        c_exports,
+        emit_dispatch,
+        emit_benches,
+        emit_tests,
+        prune_dead_branches,
+        emit_debug_asserts,
+        target,
+        &sorted(type_aliases),
+        strict_inputs,
+        allow_empty,
+        deny_warnings,
+        explicit_returns,
+        annotate_result_types,
+        closures,
+        &context_param,
+        &class,
+        cache_shared_pure,
+        max_output_bytes,
+        max_nodes_expanded,
+        switch_fallback,
+        error_policy,
+        composite_constructors,
+        subgraph_strategy,
+        runtime,
+        post_processors,
+    ];
+    fields.iter().map(|field| format!("{:?}", field)).collect::<Vec<_>>().join("\n")
+}
+
+/// A map's entries, `Debug`-formatted and sorted
+fn sorted<K: Debug, V: Debug>(map: &HashMap<K, V>) -> Vec<String> {
+    let mut entries: Vec<String> = map.iter().map(|(key, value)| format!("{:?}: {:?}", key, value)).collect();
+    entries.sort();
+    entries
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is specified to stay the same
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::graph_fingerprint;
+    use crate::codegen::VariableDef;
+    use crate::options::CompileOptions;
+    use crate::test_support::GraphBuilder;
+    use graphy::{GraphDescription, Position};
+    use std::collections::HashMap;
+
+    fn fingerprint(graph: &GraphDescription) -> u64 {
+        graph_fingerprint(graph, &HashMap::new(), &CompileOptions::default())
+    }
+
+    fn two_prints() -> GraphBuilder {
+        GraphBuilder::new()
+            .node("main", "main")
+            .node("first", "print_string")
+            .node("second", "print_string")
+            .exec("main", "body", "first")
+    }
+
+    #[test]
+    fn moving_a_node_keeps_the_fingerprint() {
+        let graph = two_prints().build();
+        let mut moved = graph.clone();
+        moved.nodes.get_mut("first").unwrap().position = Position { x: 120.0, y: -40.0 };
+
+        assert_eq!(fingerprint(&graph), fingerprint(&moved));
+    }
+
+    #[test]
+    fn rewiring_changes_the_fingerprint() {
+        let graph = two_prints().build();
+        let rewired = two_prints().exec("first", "exec_out", "second").build();
+
+        assert_ne!(fingerprint(&graph), fingerprint(&rewired));
+    }
+
+    #[test]
+    fn variables_are_part_of_the_fingerprint() {
+        let graph = two_prints().build();
+        let options = CompileOptions::default();
+        let float = HashMap::from([("health".to_string(), VariableDef::new("f32"))]);
+        let int = HashMap::from([("health".to_string(), VariableDef::new("i32"))]);
+
+        assert_ne!(graph_fingerprint(&graph, &float, &options), graph_fingerprint(&graph, &int, &options));
+        assert_ne!(graph_fingerprint(&graph, &float, &options), fingerprint(&graph));
+    }
+}
@@ -0,0 +1,112 @@
+//! # Graph Inspection
+//!
+//! Queries over a graph that don't need node metadata, so they can run before
+//! the node libraries a graph depends on are available.
+
+use crate::codegen::split_field_path;
+use graphy::GraphDescription;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+/// Distinct node types a graph uses
+///
+/// Variable getters and setters (`get_*`/`set_*`, generated per class
+/// variable) and decoration nodes without any pins (such as comments) are
+/// excluded, leaving the types that must come from a node library. Use
+/// [`referenced_node_types_with`] to include variable nodes.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use graphy::GraphDescription;
+///
+/// let graph = GraphDescription::new("test");
+/// for node_type in pbgc::referenced_node_types(&graph) {
+///     println!("needs {}", node_type);
+/// }
+/// ```
+pub fn referenced_node_types(graph: &GraphDescription) -> BTreeSet<String> {
+    referenced_node_types_with(graph, false)
+}
+
+/// Distinct node types a graph uses, optionally including variable getters and setters
+pub fn referenced_node_types_with(graph: &GraphDescription, include_variable_nodes: bool) -> BTreeSet<String> {
+    graph.nodes
+        .values()
+        .filter(|node| !(node.inputs.is_empty() && node.outputs.is_empty()))
+        .filter(|node| {
+            include_variable_nodes
+                || !(node.node_type.starts_with("get_") || node.node_type.starts_with("set_"))
+        })
+        .map(|node| node.node_type.clone())
+        .collect()
+}
+
+/// How often a graph accesses a class variable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct VarAccess {
+    /// Number of `get_<name>` nodes
+    pub reads: usize,
+    /// Number of `set_<name>` nodes
+    pub writes: usize,
+}
+
+/// Reads and writes of each variable a graph accesses, keyed by variable name
+///
+/// Only variables with at least one getter or setter node appear.
+pub fn variable_access(graph: &GraphDescription) -> HashMap<String, VarAccess> {
+    let mut access: HashMap<String, VarAccess> = HashMap::new();
+    for node in graph.nodes.values() {
+        if let Some(name) = node.node_type.strip_prefix("get_") {
+            access.entry(split_field_path(name).0.to_string()).or_default().reads += 1;
+        } else if let Some(name) = node.node_type.strip_prefix("set_") {
+            access.entry(split_field_path(name).0.to_string()).or_default().writes += 1;
+        }
+    }
+    access
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::GraphBuilder;
+
+    /// Two prints, a variable getter and setter, and a pinless comment
+    fn graph() -> GraphDescription {
+        GraphBuilder::new()
+            .node("main", "main")
+            .node("first", "print_string")
+            .node("second", "print_string")
+            .bare("get_score", "get_score")
+            .output("get_score", "value", "i32")
+            .bare("set_score", "set_score")
+            .input("set_score", "value", "i32")
+            .bare("note", "comment")
+            .build()
+    }
+
+    #[test]
+    fn library_node_types_are_listed_once() {
+        let expected: BTreeSet<String> = ["main", "print_string"].map(String::from).into();
+        assert_eq!(referenced_node_types(&graph()), expected);
+    }
+
+    #[test]
+    fn variable_nodes_are_listed_on_request() {
+        let expected: BTreeSet<String> = ["get_score", "main", "print_string", "set_score"].map(String::from).into();
+        assert_eq!(referenced_node_types_with(&graph(), true), expected);
+    }
+
+    #[test]
+    fn variable_access_counts_getters_and_setters() {
+        let mut graph = graph();
+        let limits = GraphBuilder::new().bare("limit_a", "get_limit").bare("limit_b", "get_limit").build();
+        graph.nodes.extend(limits.nodes);
+
+        let expected = HashMap::from([
+            ("score".to_string(), VarAccess { reads: 1, writes: 1 }),
+            ("limit".to_string(), VarAccess { reads: 2, writes: 0 }),
+        ]);
+        assert_eq!(variable_access(&graph), expected);
+    }
+}
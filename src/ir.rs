@@ -0,0 +1,341 @@
+//! # Blueprint IR
+//!
+//! A backend-neutral description of a graph after analysis, for targets other
+//! than Rust (a bytecode VM, an interpreter). It captures what the analysis
+//! passes compute: pure nodes in evaluation order, each event's exec chain as
+//! a tree with its control-flow structure, and how variables are accessed.
+//!
+//! The Rust generator still reads the analyses directly; the IR is built from
+//! the same [`DataResolver`] and [`ExecutionRouting`] so both see one graph.
+
+use crate::codegen::split_field_path;
+use crate::error::CompileError;
+use crate::inspect::{VarAccess, variable_access};
+use crate::metadata::BlueprintMetadataProvider;
+use graphy::analysis::DataSource;
+use graphy::core::NodeMetadataProvider;
+use graphy::{DataResolver, DataType, ExecutionRouting, GraphDescription, GraphyError, NodeInstance, NodeTypes};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// A lowered graph
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlueprintIR {
+    /// Pure nodes in the order their values must be computed
+    pub pure_nodes: Vec<PureIR>,
+    /// Entry points, in node ID order
+    pub events: Vec<EventIR>,
+    /// Reads and writes of each class variable, by name
+    pub variables: BTreeMap<String, VarAccess>,
+}
+
+/// A pure node and its operands
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PureIR {
+    pub node_id: String,
+    pub node_type: String,
+    pub args: Vec<ValueIR>,
+}
+
+/// An event and the exec chain it starts
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventIR {
+    pub node_id: String,
+    /// Name of the event's node type, e.g. `"begin_play"`
+    pub name: String,
+    /// Names of the event's data outputs, which act as its parameters
+    pub params: Vec<String>,
+    pub body: Vec<ExecIR>,
+}
+
+/// One step of an exec chain
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExecIR {
+    /// Call a function node
+    Call {
+        node_id: String,
+        node_type: String,
+        args: Vec<ValueIR>,
+        /// Whether later values read the call's result (as `ValueIR::Node`)
+        has_result: bool,
+    },
+    /// Write a class variable, or a field path inside it
+    SetVariable {
+        node_id: String,
+        var: String,
+        field: Option<String>,
+        value: ValueIR,
+    },
+    /// A control-flow node, with the chain behind each of its exec outputs
+    Branch {
+        node_id: String,
+        node_type: String,
+        args: Vec<ValueIR>,
+        branches: Vec<BranchIR>,
+    },
+    /// Continue at a node already on the current path (a loop back edge)
+    Jump { node_id: String },
+}
+
+/// The chain behind one exec output of a control-flow node
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BranchIR {
+    /// Exec output name, e.g. `"then"`
+    pub exec_output: String,
+    pub body: Vec<ExecIR>,
+}
+
+/// Where an input's value comes from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValueIR {
+    /// An output of another node (a pure node, a call's result or an event parameter)
+    Node { node_id: String, pin: String },
+    /// A class variable, or a field path inside it, read through its getter
+    Variable { var: String, field: Option<String> },
+    /// A literal set in the editor
+    Constant { value: String },
+    /// Nothing connected: the parameter type's default
+    Default,
+}
+
+/// Lower an analyzed graph to [`BlueprintIR`]
+pub(crate) fn lower(
+    graph: &GraphDescription,
+    metadata_provider: &BlueprintMetadataProvider,
+    data_resolver: &DataResolver,
+    exec_routing: &ExecutionRouting,
+) -> Result<BlueprintIR, CompileError> {
+    let lowering = Lowering { graph, metadata_provider, data_resolver, exec_routing };
+
+    let pure_nodes = data_resolver
+        .get_pure_evaluation_order()
+        .iter()
+        .filter_map(|node_id| graph.nodes.get(node_id))
+        .filter(|node| !node.node_type.starts_with("get_"))
+        .map(|node| {
+            Ok(PureIR {
+                node_id: node.id.clone(),
+                node_type: node.node_type.clone(),
+                args: lowering.args(node)?,
+            })
+        })
+        .collect::<Result<_, CompileError>>()?;
+
+    let mut event_nodes: Vec<&NodeInstance> = graph.nodes
+        .values()
+        .filter(|node| lowering.kind(node) == Some(NodeTypes::event))
+        .collect();
+    event_nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let events = event_nodes
+        .into_iter()
+        .map(|node| {
+            Ok(EventIR {
+                node_id: node.id.clone(),
+                name: node.node_type.clone(),
+                params: node.outputs
+                    .iter()
+                    .filter(|pin| !matches!(pin.pin.data_type, DataType::Execution))
+                    .map(|pin| pin.pin.name.clone())
+                    .collect(),
+                body: lowering.successors(node, &mut HashSet::new())?,
+            })
+        })
+        .collect::<Result<_, CompileError>>()?;
+
+    Ok(BlueprintIR {
+        pure_nodes,
+        events,
+        variables: variable_access(graph).into_iter().collect(),
+    })
+}
+
+/// The analyses a graph is lowered from
+struct Lowering<'a> {
+    graph: &'a GraphDescription,
+    metadata_provider: &'a BlueprintMetadataProvider,
+    data_resolver: &'a DataResolver,
+    exec_routing: &'a ExecutionRouting,
+}
+
+impl Lowering<'_> {
+    fn kind(&self, node: &NodeInstance) -> Option<NodeTypes> {
+        self.metadata_provider.get_node_metadata(&node.node_type).map(|meta| meta.node_type)
+    }
+
+    /// Chains behind all of a node's exec outputs, in pin order
+    fn successors(&self, node: &NodeInstance, path: &mut HashSet<String>) -> Result<Vec<ExecIR>, CompileError> {
+        let mut body = Vec::new();
+        for pin in node.outputs.iter().filter(|pin| matches!(pin.pin.data_type, DataType::Execution)) {
+            body.extend(self.chain(node, &pin.id, path)?);
+        }
+        Ok(body)
+    }
+
+    /// Chain behind one exec output
+    fn chain(&self, node: &NodeInstance, pin_id: &str, path: &mut HashSet<String>) -> Result<Vec<ExecIR>, CompileError> {
+        let mut body = Vec::new();
+        for next_id in self.exec_routing.get_connected_nodes(&node.id, pin_id) {
+            let Some(next) = self.graph.nodes.get(next_id) else { continue };
+            if path.contains(&next.id) {
+                body.push(ExecIR::Jump { node_id: next.id.clone() });
+                continue;
+            }
+            path.insert(next.id.clone());
+            body.extend(self.step(next, path)?);
+            path.remove(&next.id);
+        }
+        Ok(body)
+    }
+
+    /// A node reached through exec flow, followed by the rest of its chain
+    fn step(&self, node: &NodeInstance, path: &mut HashSet<String>) -> Result<Vec<ExecIR>, CompileError> {
+        if let Some(name) = node.node_type.strip_prefix("set_") {
+            let (var, field) = split_field_path(name);
+            let value_pin = node.inputs
+                .iter()
+                .find(|input| input.pin.name == "value")
+                .ok_or_else(|| CompileError::MissingInputPin { node_id: node.id.clone(), pin: "value".to_string() })?;
+            let mut body = vec![ExecIR::SetVariable {
+                node_id: node.id.clone(),
+                var: var.to_string(),
+                field: field.map(str::to_string),
+                value: self.value(node, &value_pin.id),
+            }];
+            body.extend(self.successors(node, path)?);
+            return Ok(body);
+        }
+
+        match self.kind(node).ok_or_else(|| GraphyError::NodeNotFound(node.node_type.clone()))? {
+            NodeTypes::control_flow => {
+                let mut branches = Vec::new();
+                for pin in node.outputs.iter().filter(|pin| matches!(pin.pin.data_type, DataType::Execution)) {
+                    branches.push(BranchIR {
+                        exec_output: pin.pin.name.clone(),
+                        body: self.chain(node, &pin.id, path)?,
+                    });
+                }
+                Ok(vec![ExecIR::Branch {
+                    node_id: node.id.clone(),
+                    node_type: node.node_type.clone(),
+                    args: self.args(node)?,
+                    branches,
+                }])
+            }
+            NodeTypes::pure | NodeTypes::event => self.successors(node, path),
+            _ => {
+                let has_result = self.data_resolver.get_result_variable(&node.id).is_some();
+                let mut body = vec![ExecIR::Call {
+                    node_id: node.id.clone(),
+                    node_type: node.node_type.clone(),
+                    args: self.args(node)?,
+                    has_result,
+                }];
+                body.extend(self.successors(node, path)?);
+                Ok(body)
+            }
+        }
+    }
+
+    /// Values of a node's parameters, in declaration order
+    fn args(&self, node: &NodeInstance) -> Result<Vec<ValueIR>, CompileError> {
+        let Some(meta) = self.metadata_provider.get_node_metadata(&node.node_type) else {
+            return Ok(Vec::new());
+        };
+        meta.params
+            .iter()
+            .map(|param| {
+                let pin = node.inputs
+                    .iter()
+                    .find(|input| input.pin.name == param.name)
+                    .ok_or_else(|| CompileError::MissingInputPin { node_id: node.id.clone(), pin: param.name.clone() })?;
+                Ok(self.value(node, &pin.id))
+            })
+            .collect()
+    }
+
+    /// Where the value of one input pin comes from
+    fn value(&self, node: &NodeInstance, pin_id: &str) -> ValueIR {
+        match self.data_resolver.get_input_source(&node.id, pin_id) {
+            Some(DataSource::Connection { source_node_id, source_pin }) => {
+                let getter = self.graph.nodes
+                    .get(source_node_id)
+                    .and_then(|source| source.node_type.strip_prefix("get_"));
+                match getter.map(split_field_path) {
+                    Some((var, field)) => ValueIR::Variable { var: var.to_string(), field: field.map(str::to_string) },
+                    None => ValueIR::Node { node_id: source_node_id.clone(), pin: source_pin.clone() },
+                }
+            }
+            Some(DataSource::Constant(value)) => ValueIR::Constant { value: value.clone() },
+            _ => ValueIR::Default,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{GraphBuilder, provider};
+    use crate::Compiler;
+    use graphy::PropertyValue;
+
+    #[test]
+    fn branch_with_a_call_and_a_setter_lowers_to_a_tree() {
+        let graph = GraphBuilder::new()
+            .node("main", "main")
+            .node("branch", "branch")
+            .node("coin", "random_bool")
+            .bare("get_score", "get_score")
+            .output("get_score", "value", "u32")
+            .node("despawn", "despawn")
+            .bare("set_score", "set_score")
+            .input("set_score", "exec", "()")
+            .input("set_score", "value", "u32")
+            .property("set_score", "value", PropertyValue::Number(1.0))
+            .exec("main", "body", "branch")
+            .exec("branch", "True", "despawn")
+            .exec("branch", "False", "set_score")
+            .data("coin", "result", "branch", "condition")
+            .data("get_score", "value", "despawn", "entity")
+            .build();
+
+        let ir = Compiler::with_provider(provider()).lower_graph(&graph).unwrap();
+        let expected = BlueprintIR {
+            pure_nodes: vec![PureIR { node_id: "coin".to_string(), node_type: "random_bool".to_string(), args: vec![] }],
+            events: vec![EventIR {
+                node_id: "main".to_string(),
+                name: "main".to_string(),
+                params: vec![],
+                body: vec![ExecIR::Branch {
+                    node_id: "branch".to_string(),
+                    node_type: "branch".to_string(),
+                    args: vec![ValueIR::Node { node_id: "coin".to_string(), pin: "coin_result".to_string() }],
+                    branches: vec![
+                        BranchIR {
+                            exec_output: "True".to_string(),
+                            body: vec![ExecIR::Call {
+                                node_id: "despawn".to_string(),
+                                node_type: "despawn".to_string(),
+                                args: vec![ValueIR::Variable { var: "score".to_string(), field: None }],
+                                has_result: false,
+                            }],
+                        },
+                        BranchIR {
+                            exec_output: "False".to_string(),
+                            body: vec![ExecIR::SetVariable {
+                                node_id: "set_score".to_string(),
+                                var: "score".to_string(),
+                                field: None,
+                                value: ValueIR::Constant { value: "1".to_string() },
+                            }],
+                        },
+                    ],
+                }],
+            }],
+            variables: BTreeMap::from([("score".to_string(), VarAccess { reads: 1, writes: 1 })]),
+        };
+        assert_eq!(ir, expected);
+    }
+}
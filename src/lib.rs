@@ -41,12 +41,66 @@
 pub mod metadata;
 pub mod codegen;
 pub mod compiler;
+pub mod cancellation;
+pub mod diagnostics;
+pub mod error;
+pub mod fingerprint;
+pub mod migration;
+pub mod normalize;
+pub mod inspect;
+pub mod ir;
+pub mod options;
+pub mod report;
+pub mod subgraph;
+#[cfg(test)]
+mod test_support;
+#[cfg(feature = "syn")]
+pub mod ast;
 
 // Re-export the main compilation API
 pub use compiler::{
+    compile_class,
     compile_graph,
+    compile_graph_cancellable,
+    compile_expression,
+    compile_graph_json,
+    compile_graph_json_with_options,
+    graph_target,
+    parse_graph_json,
     compile_graph_with_library_manager,
+    compile_graph_with_options,
+    compile_graph_with_provider,
+    compile_graph_with_diagnostics,
+    compile_graph_with_report,
     compile_graph_with_variables,
+    generate_node_snippet,
+    lower_graph,
+    Compiler,
+};
+
+pub use cancellation::CancellationToken;
+pub use diagnostics::{Diagnostic, Severity};
+#[cfg(feature = "serde")]
+pub use compiler::compile_graph_diagnostics_json;
+#[cfg(feature = "serde")]
+pub use diagnostics::{diagnostics_to_json, error_to_json};
+pub use report::OptimizationReport;
+pub use error::CompileError;
+pub use fingerprint::graph_fingerprint;
+pub use normalize::{normalize, normalize_with_diagnostics};
+pub use migration::{Migration, MigrationRegistry, SCHEMA_VERSION, schema_version};
+pub use inspect::{VarAccess, referenced_node_types, referenced_node_types_with, variable_access};
+pub use ir::{BlueprintIR, BranchIR, EventIR, ExecIR, PureIR, ValueIR};
+pub use options::{
+    ClosureTarget, CompileOptions, CompositeConstructors, ContextSpec, ErrorPolicy, EventContext, SubgraphStrategy,
+    SwitchFallback, TargetSpec,
+};
+pub use subgraph::{SubgraphExample, SubgraphLibrary};
+#[cfg(feature = "syn")]
+pub use ast::{GENERATED_MARKER, generated_fns, is_generated};
+pub use codegen::{
+    ClassContext, DefaultRuntime, EventSignature, GeneratedFunction, Operator, PostProcessor, RuntimeProfile,
+    SortImports, TokioRuntime, TraitImpl, TraitMethod, VariableDef, VariableStorage,
 };
 
 // Re-export Graphy types for convenience
@@ -62,5 +116,12 @@ pub use graphy::core::TypeInfo;
 // Re-export metadata types
 pub use metadata::{
     BlueprintMetadataProvider,
+    BodyBinding,
+    ExitVariant,
+    FallibleBranches,
+    HelperQualifier,
+    NodeAttributes,
+    SymbolResolver,
+    VariadicStyle,
     extract_node_metadata,
 };
@@ -5,6 +5,7 @@
 //! This module provides access to Blueprint node metadata through the
 //! compile-time registry generated by the `#[blueprint]` macro in pulsar_std.
 
+use crate::codegen::Operator;
 use graphy::core::{NodeMetadata, NodeMetadataProvider, ParamInfo, TypeInfo};
 use std::collections::HashMap;
 use std::sync::OnceLock;
@@ -12,6 +13,9 @@ use std::sync::OnceLock;
 /// Cached node metadata from pulsar_std
 static NODE_METADATA: OnceLock<HashMap<String, NodeMetadata>> = OnceLock::new();
 
+/// Base library of providers built without pulsar_std
+static NO_METADATA: OnceLock<HashMap<String, NodeMetadata>> = OnceLock::new();
+
 /// Convert pulsar_std NodeMetadata to Graphy NodeMetadata
 fn convert_node_metadata(ps_node: &pulsar_std::registry::NodeMetadata) -> NodeMetadata {
     // Convert node type (the enums match, so we can convert via string representation)
@@ -109,17 +113,523 @@ pub fn get_node_metadata() -> &'static HashMap<String, NodeMetadata> {
     })
 }
 
+/// Blueprint-specific node attributes
+///
+/// Graphy's `NodeMetadata` describes a node's signature; these attributes
+/// carry the extra semantics PBGC needs to generate correct code for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeAttributes {
+    /// Whether the node returns the same result for the same inputs
+    ///
+    /// Non-deterministic pure nodes (`random`, `now`) must be evaluated exactly
+    /// once and in exec order, so they are bound to a `let` before the
+    /// statement that first reads them instead of being inlined. Loop
+    /// conditions are the exception: they read a fresh value each iteration.
+    pub deterministic: bool,
+
+    /// Native Rust operator this pure node compiles to instead of a call
+    ///
+    /// The node's params, in order, become the operator's operands.
+    pub operator: Option<Operator>,
+
+    /// Values a control-flow node makes available to one of its exec branches
+    ///
+    /// A `for_each` node, for instance, binds its `element` and `index` data
+    /// outputs to the loop variables of its template inside the `body` branch.
+    pub body_bindings: Vec<BodyBinding>,
+
+    /// Multi-statement body emitted in place of a function node's single call
+    ///
+    /// Placeholders `{param}` (one per parameter name) and `{result}` (the
+    /// node's result variable) are substituted, e.g.
+    /// `"let {result} = spawn({prefab});\nregister(&{result});"` or
+    /// `"{list}.push({item});"` for method-call syntax. Any other placeholder
+    /// is an error, as is leaving out `{result}` when other nodes read the
+    /// result; a parameter left out is never evaluated, which is reported.
+    pub body_template: Option<String>,
+
+    /// Whether the node suspends the event until it completes (delay, timer, wait)
+    ///
+    /// A latent node's function is polled once per `step()` of the event's
+    /// generated state machine. It returns `true` when the wait is over, or,
+    /// if the node has an output, `Option<T>` that is `Some` once it's ready.
+    pub latent: bool,
+
+    /// How the node's last parameter is passed when it is variadic
+    ///
+    /// A variadic parameter `value` is fed by any number of instance pins
+    /// named `value_0`, `value_1`, ..., which are passed in index order.
+    pub variadic: Option<VariadicStyle>,
+
+    /// Whether the node's function is a `const fn`
+    ///
+    /// Generated helpers built only from const-evaluable nodes are emitted as
+    /// `const fn` themselves; see [`BlueprintMetadataProvider::helper_qualifier`].
+    pub const_eval: bool,
+
+    /// Parameter of a loop node holding the collection it iterates over
+    ///
+    /// A collection that is read again elsewhere is iterated by reference
+    /// (`for x in &items`); one used only by the loop is consumed
+    /// (`for x in items`).
+    pub loop_collection: Option<String>,
+
+    /// Whether the node is a switch whose `match` cases may not cover every value
+    ///
+    /// Switches get a wildcard arm chosen by `CompileOptions::switch_fallback`.
+    pub switch: bool,
+
+    /// Exec and data pins of a function node returning a `Result`
+    ///
+    /// Instead of binding the `Result`, the call is matched and the success or
+    /// failure exec chain runs with the `Ok`/`Err` value in scope.
+    pub fallible: Option<FallibleBranches>,
+
+    /// Variants of the enum a multi-exit function node returns
+    ///
+    /// Like [`fallible`](Self::fallible) for any number of exits: the call
+    /// is matched and each variant runs its exec chain with its payload in
+    /// scope. Every variant must be listed for the `match` to be exhaustive.
+    pub exits: Vec<ExitVariant>,
+
+    /// Whether the event's function is `async`
+    pub is_async: bool,
+
+    /// Preconditions checked before the node's call when
+    /// `CompileOptions::emit_debug_asserts` is set
+    ///
+    /// Each is a boolean expression over `{param}` placeholders, e.g.
+    /// `"{index} < {array}.len()"`, emitted as a `debug_assert!`. Arguments
+    /// are evaluated again for the check, so it should only read them.
+    pub debug_assertions: Vec<String>,
+
+    /// `cfg` predicate the node is only available under, e.g. `feature = "physics"`
+    ///
+    /// A gated function or control-flow node is emitted together with the rest
+    /// of its exec chain in a `#[cfg(..)]` block, since later nodes may use its
+    /// results; with the predicate off the event skips that part of the chain.
+    /// Imports needed only by gated nodes carry the same attribute.
+    pub cfg: Option<String>,
+
+    /// Whether the node returns a tuple with one field per data output
+    ///
+    /// Each data output reads its field of the result (`.0`, `.1`, ...), in
+    /// the order the outputs are listed on the node.
+    pub tuple_outputs: bool,
+
+    /// Whether the node is a method on the Blueprint instance, called as `self.name(..)`
+    ///
+    /// Only available to the events of a Blueprint class, whose methods take
+    /// `&mut self`; the node's name is the method called.
+    pub self_method: bool,
+
+    /// Arguments of the print macro a print node expands to, e.g. `"\"{}\", {message}"`
+    ///
+    /// Placeholders `{param}` are substituted as in
+    /// [`body_template`](Self::body_template). The node is emitted as a call to
+    /// `CompileOptions::print_macro` with these arguments instead of a call to
+    /// its function, so every print node goes to the same logger.
+    pub print_args: Option<String>,
+}
+
+/// Pins through which a fallible node exposes its `Result`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallibleBranches {
+    /// Exec output taken on `Ok` (e.g. `"success"`)
+    pub success_exec: String,
+    /// Exec output taken on `Err` (e.g. `"failure"`)
+    pub failure_exec: String,
+    /// Data output carrying the `Ok` value inside the success chain
+    pub value_pin: String,
+    /// Data output carrying the `Err` value inside the failure chain
+    pub error_pin: String,
+}
+
+impl Default for FallibleBranches {
+    fn default() -> Self {
+        Self {
+            success_exec: "success".to_string(),
+            failure_exec: "failure".to_string(),
+            value_pin: "value".to_string(),
+            error_pin: "error".to_string(),
+        }
+    }
+}
+
+/// One exit of a multi-exit node: an enum variant and the chain it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitVariant {
+    /// Path of the variant as matched, e.g. `"ParseOutcome::Number"`
+    pub variant: String,
+    /// Exec output taken when the node returns this variant
+    pub exec_output: String,
+    /// Data output carrying the variant's single payload field, if it has one
+    pub payload_pin: Option<String>,
+    /// Rust type of the payload, used when the pin itself is `Any`
+    pub payload_type: Option<String>,
+}
+
+impl ExitVariant {
+    /// A variant without a payload
+    pub fn unit(variant: impl Into<String>, exec_output: impl Into<String>) -> Self {
+        Self {
+            variant: variant.into(),
+            exec_output: exec_output.into(),
+            payload_pin: None,
+            payload_type: None,
+        }
+    }
+
+    /// A variant whose payload of type `ty` is read through `payload_pin`
+    pub fn with_payload(
+        variant: impl Into<String>,
+        exec_output: impl Into<String>,
+        payload_pin: impl Into<String>,
+        ty: impl Into<String>,
+    ) -> Self {
+        Self {
+            variant: variant.into(),
+            exec_output: exec_output.into(),
+            payload_pin: Some(payload_pin.into()),
+            payload_type: Some(ty.into()),
+        }
+    }
+}
+
+/// Qualifier emitted on a generated helper function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelperQualifier {
+    /// `#[inline] fn`
+    Inline,
+    /// `const fn`
+    Const,
+}
+
+impl HelperQualifier {
+    /// Start of a helper's signature up to its name, e.g. `"pub const fn"`
+    ///
+    /// `visibility` is placed before `fn` (or `const fn`), e.g. `"pub "`.
+    pub fn signature(self, visibility: &str) -> String {
+        match self {
+            HelperQualifier::Inline => format!("#[inline]\n{}fn", visibility),
+            HelperQualifier::Const => format!("{}const fn", visibility),
+        }
+    }
+}
+
+/// How the pins of a variadic parameter are passed to the node's function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariadicStyle {
+    /// As separate trailing arguments: `concat(a, b, c)`
+    Arguments,
+    /// As a single slice argument: `concat(&[a, b, c])`
+    Slice,
+}
+
+/// A data output of a control-flow node that is only valid inside one branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyBinding {
+    /// Exec output whose chain can see the binding (e.g. `"body"`)
+    pub exec_output: String,
+    /// Name of the data output pin that carries the value (e.g. `"index"`)
+    pub pin: String,
+    /// Expression the template binds for the branch (e.g. `"i"`)
+    pub expression: String,
+}
+
+impl BodyBinding {
+    pub fn new(
+        exec_output: impl Into<String>,
+        pin: impl Into<String>,
+        expression: impl Into<String>,
+    ) -> Self {
+        Self {
+            exec_output: exec_output.into(),
+            pin: pin.into(),
+            expression: expression.into(),
+        }
+    }
+}
+
+impl NodeAttributes {
+    /// Attributes of an ordinary node
+    pub const DEFAULT: Self = Self {
+        deterministic: true,
+        operator: None,
+        body_bindings: Vec::new(),
+        body_template: None,
+        latent: false,
+        variadic: None,
+        const_eval: false,
+        loop_collection: None,
+        switch: false,
+        fallible: None,
+        exits: Vec::new(),
+        is_async: false,
+        debug_assertions: Vec::new(),
+        cfg: None,
+        tuple_outputs: false,
+        self_method: false,
+        print_args: None,
+    };
+
+    /// Attributes of a pure node whose result may change between calls
+    pub fn non_deterministic() -> Self {
+        Self {
+            deterministic: false,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Add a branch-scoped binding for one of the node's data outputs
+    pub fn with_body_binding(mut self, binding: BodyBinding) -> Self {
+        self.body_bindings.push(binding);
+        self
+    }
+
+    /// Add a precondition over the node's parameters
+    pub fn with_debug_assertion(mut self, condition: impl Into<String>) -> Self {
+        self.debug_assertions.push(condition.into());
+        self
+    }
+
+    /// Attributes of a control-flow node that matches its input against cases
+    pub fn switch() -> Self {
+        Self {
+            switch: true,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a function node whose `Result` is split into success/failure chains
+    pub fn fallible(branches: FallibleBranches) -> Self {
+        Self {
+            fallible: Some(branches),
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a function node returning an enum with one exec exit per variant
+    pub fn exits(variants: Vec<ExitVariant>) -> Self {
+        Self {
+            exits: variants,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of an event whose function is `async`
+    pub fn async_event() -> Self {
+        Self {
+            is_async: true,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a node only available under a `cfg` predicate
+    pub fn cfg(predicate: impl Into<String>) -> Self {
+        Self {
+            cfg: Some(predicate.into()),
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a node returning one tuple field per data output
+    pub fn tuple_outputs() -> Self {
+        Self {
+            tuple_outputs: true,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a print node formatting its output with `args`
+    pub fn print(args: impl Into<String>) -> Self {
+        Self {
+            print_args: Some(args.into()),
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a node calling a method on the Blueprint instance
+    pub fn self_method() -> Self {
+        Self {
+            self_method: true,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Mark the parameter a loop node iterates over
+    pub fn with_loop_collection(mut self, param: impl Into<String>) -> Self {
+        self.loop_collection = Some(param.into());
+        self
+    }
+
+    /// Attributes of a function node that expands to a statement template
+    pub fn template(body_template: impl Into<String>) -> Self {
+        Self {
+            body_template: Some(body_template.into()),
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a function node that suspends its event until it completes
+    pub fn latent() -> Self {
+        Self {
+            latent: true,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a node whose last parameter takes any number of pins
+    pub fn variadic(style: VariadicStyle) -> Self {
+        Self {
+            variadic: Some(style),
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a node whose function can be evaluated at compile time
+    pub fn const_eval() -> Self {
+        Self {
+            const_eval: true,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Attributes of a pure node that maps to a Rust operator
+    pub fn operator(operator: Operator) -> Self {
+        Self {
+            operator: Some(operator),
+            ..Self::DEFAULT
+        }
+    }
+}
+
+impl Default for NodeAttributes {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static DEFAULT_ATTRIBUTES: NodeAttributes = NodeAttributes::DEFAULT;
+
+/// Maps a node to the path its function is called through in generated code
+///
+/// By default nodes are called by their bare metadata name (`add(a, b)`),
+/// relying on glob imports. A resolver can emit fully-qualified paths instead,
+/// e.g. `pulsar_std::math::add(a, b)`. Closures taking `&NodeMetadata` and
+/// returning a `String` implement this trait.
+pub trait SymbolResolver: Send + Sync {
+    /// Call path for a node's function
+    fn resolve(&self, node_meta: &NodeMetadata) -> String;
+}
+
+impl<F> SymbolResolver for F
+where
+    F: Fn(&NodeMetadata) -> String + Send + Sync,
+{
+    fn resolve(&self, node_meta: &NodeMetadata) -> String {
+        self(node_meta)
+    }
+}
+
 /// Blueprint metadata provider
 ///
 /// Implements the `NodeMetadataProvider` trait for Blueprint nodes.
 pub struct BlueprintMetadataProvider {
     metadata: &'static HashMap<String, NodeMetadata>,
+    extra_metadata: HashMap<String, NodeMetadata>,
+    attributes: HashMap<String, NodeAttributes>,
+    symbol_resolver: Option<Box<dyn SymbolResolver>>,
 }
 
 impl BlueprintMetadataProvider {
     pub fn new() -> Self {
         Self {
             metadata: get_node_metadata(),
+            extra_metadata: HashMap::new(),
+            attributes: HashMap::new(),
+            symbol_resolver: None,
+        }
+    }
+
+    /// Create a provider knowing only `nodes`, without loading pulsar_std
+    ///
+    /// Makes code generation independent of the installed node library, e.g.
+    /// for testing against a handful of hand-written nodes:
+    ///
+    /// ```rust,no_run
+    /// use graphy::core::{NodeMetadata, ParamInfo};
+    /// use graphy::NodeTypes;
+    /// use pbgc::{BlueprintMetadataProvider, Compiler};
+    ///
+    /// let provider = BlueprintMetadataProvider::from_nodes(vec![
+    ///     NodeMetadata::new("main", NodeTypes::event, "Events")
+    ///         .with_exec_outputs(vec!["body".to_string()]),
+    ///     NodeMetadata::new("print_string", NodeTypes::fn_, "Debug")
+    ///         .with_params(vec![ParamInfo::new("message", "String")])
+    ///         .with_exec_outputs(vec!["exec_out".to_string()]),
+    /// ]);
+    /// let compiler = Compiler::with_provider(provider);
+    /// # let graph = graphy::GraphDescription::new("test");
+    /// let code = compiler.compile(&graph)?;
+    /// # Ok::<(), pbgc::CompileError>(())
+    /// ```
+    pub fn from_nodes(nodes: Vec<NodeMetadata>) -> Self {
+        Self {
+            metadata: NO_METADATA.get_or_init(HashMap::new),
+            extra_metadata: nodes.into_iter().map(|node| (node.name.clone(), node)).collect(),
+            attributes: HashMap::new(),
+            symbol_resolver: None,
+        }
+    }
+
+    /// Attach Blueprint-specific attributes to a node type
+    pub fn with_attributes(mut self, node_type: impl Into<String>, attributes: NodeAttributes) -> Self {
+        self.attributes.insert(node_type.into(), attributes);
+        self
+    }
+
+    /// Register a node type that isn't part of pulsar_std, e.g. a sub-graph instance
+    ///
+    /// Takes precedence over a pulsar_std node of the same name.
+    pub fn with_node_metadata(mut self, metadata: NodeMetadata) -> Self {
+        self.extra_metadata.insert(metadata.name.clone(), metadata);
+        self
+    }
+
+    /// Get the attributes of a node type, or the defaults if none were set
+    pub fn attributes(&self, node_type: &str) -> &NodeAttributes {
+        self.attributes.get(node_type).unwrap_or(&DEFAULT_ATTRIBUTES)
+    }
+
+    /// Qualifier for a helper function generated from the given node types
+    ///
+    /// A helper is `const fn` when every node in it is const-evaluable, and
+    /// `#[inline] fn` otherwise.
+    pub fn helper_qualifier<'n>(&self, node_types: impl IntoIterator<Item = &'n str>) -> HelperQualifier {
+        let mut node_types = node_types.into_iter().peekable();
+        if node_types.peek().is_none() {
+            return HelperQualifier::Inline;
+        }
+
+        if node_types.all(|node_type| self.attributes(node_type).const_eval) {
+            HelperQualifier::Const
+        } else {
+            HelperQualifier::Inline
+        }
+    }
+
+    /// Emit node calls through a custom symbol resolver
+    pub fn with_symbol_resolver(mut self, resolver: impl SymbolResolver + 'static) -> Self {
+        self.symbol_resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// Call path for a node's function, defaulting to its bare name
+    pub fn symbol(&self, node_meta: &NodeMetadata) -> String {
+        match &self.symbol_resolver {
+            Some(resolver) => resolver.resolve(node_meta),
+            None => node_meta.name.clone(),
         }
     }
 }
@@ -132,16 +642,20 @@ impl Default for BlueprintMetadataProvider {
 
 impl NodeMetadataProvider for BlueprintMetadataProvider {
     fn get_node_metadata(&self, node_type: &str) -> Option<&NodeMetadata> {
-        self.metadata.get(node_type)
+        self.extra_metadata.get(node_type).or_else(|| self.metadata.get(node_type))
     }
 
     fn get_all_nodes(&self) -> Vec<&NodeMetadata> {
-        self.metadata.values().collect()
+        self.metadata
+            .values()
+            .filter(|m| !self.extra_metadata.contains_key(&m.name))
+            .chain(self.extra_metadata.values())
+            .collect()
     }
 
     fn get_nodes_by_category(&self, category: &str) -> Vec<&NodeMetadata> {
-        self.metadata
-            .values()
+        self.get_all_nodes()
+            .into_iter()
             .filter(|m| m.category == category)
             .collect()
     }
@@ -185,3 +699,59 @@ pub fn get_categories() -> Vec<String> {
 pub fn find_node(name: &str) -> Option<&'static NodeMetadata> {
     get_node_metadata().get(name)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::BlueprintMetadataProvider;
+    use crate::options::CompileOptions;
+    use crate::test_support::GraphBuilder;
+    use crate::{Compiler, compile_graph_with_provider};
+    use graphy::core::{NodeMetadata, NodeMetadataProvider, ParamInfo, TypeInfo};
+    use graphy::{GraphDescription, NodeTypes, PropertyValue};
+
+    fn mock_provider() -> BlueprintMetadataProvider {
+        BlueprintMetadataProvider::from_nodes(vec![
+            NodeMetadata::new("main", NodeTypes::event, "Events")
+                .with_exec_outputs(vec!["body".to_string()]),
+            NodeMetadata::new("print_string", NodeTypes::fn_, "Debug")
+                .with_params(vec![ParamInfo::new("message", "String")])
+                .with_return_type(TypeInfo::new("()".to_string()))
+                .with_exec_outputs(vec!["exec_out".to_string()])
+                .with_source("fn print_string(message: String) {\n    println!(\"{}\", message);\n}"),
+        ])
+    }
+
+    fn two_node_graph() -> GraphDescription {
+        GraphBuilder::new()
+            .node("main", "main")
+            .node("print", "print_string")
+            .property("print", "message", PropertyValue::String("hello".to_string()))
+            .exec("main", "body", "print")
+            .build()
+    }
+
+    #[test]
+    fn mock_provider_compiles_a_two_node_graph() {
+        let code = compile_graph_with_provider(&two_node_graph(), &mock_provider(), &CompileOptions::default()).unwrap();
+
+        assert!(code.contains("pub fn main()"), "{}", code);
+        assert!(code.contains("    print_string(\"hello\");\n"), "{}", code);
+    }
+
+    #[test]
+    fn compiler_with_mock_provider_matches_the_free_function() {
+        let graph = two_node_graph();
+        let free = compile_graph_with_provider(&graph, &mock_provider(), &CompileOptions::default()).unwrap();
+
+        assert_eq!(Compiler::with_provider(mock_provider()).compile(&graph).unwrap(), free);
+    }
+
+    #[test]
+    fn mock_provider_knows_only_its_nodes() {
+        let provider = mock_provider();
+
+        assert!(provider.get_node_metadata("print_string").is_some());
+        assert!(provider.get_node_metadata("add").is_none());
+        assert_eq!(provider.get_all_nodes().len(), 2);
+    }
+}
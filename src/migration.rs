@@ -0,0 +1,143 @@
+//! # Graph Migrations
+//!
+//! Upgrades graphs saved against older node signatures before compiling them.
+//!
+//! A graph's schema version is the leading number of `GraphMetadata::version`
+//! (`"2"` and `"2.1.0"` are both version 2). Each registered migration upgrades
+//! a graph by exactly one version, and they run in order until the graph
+//! reaches the registry's current version.
+
+use crate::error::CompileError;
+use graphy::GraphDescription;
+use std::collections::BTreeMap;
+
+/// Schema version of the graphs this compiler reads without migrations
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A migration step, rewriting a graph in place (renaming pins, splitting nodes, ...)
+pub type Migration = Box<dyn Fn(&mut GraphDescription) + Send + Sync>;
+
+/// Migrations that bring old graphs up to the current schema version
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pbgc::{Compiler, MigrationRegistry};
+///
+/// let migrations = MigrationRegistry::new(2).with_migration(1, |graph| {
+///     for node in graph.nodes.values_mut() {
+///         if node.node_type == "print" {
+///             node.node_type = "print_string".to_string();
+///         }
+///     }
+/// });
+/// let compiler = Compiler::new().with_migrations(migrations);
+/// ```
+pub struct MigrationRegistry {
+    current_version: u32,
+    migrations: BTreeMap<u32, Migration>,
+}
+
+impl MigrationRegistry {
+    /// Create a registry whose graphs are current at `current_version`
+    pub fn new(current_version: u32) -> Self {
+        Self {
+            current_version,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Register the migration upgrading graphs from `from_version` to `from_version + 1`
+    pub fn with_migration(
+        mut self,
+        from_version: u32,
+        migration: impl Fn(&mut GraphDescription) + Send + Sync + 'static,
+    ) -> Self {
+        self.migrations.insert(from_version, Box::new(migration));
+        self
+    }
+
+    /// The schema version graphs are migrated to
+    pub fn current_version(&self) -> u32 {
+        self.current_version
+    }
+
+    /// Upgrade a graph to the current version
+    ///
+    /// Graphs without a numeric version are assumed current. Graphs newer than
+    /// the current version, or whose upgrade path has a missing step, are
+    /// rejected. Returns whether any migration ran.
+    pub fn migrate(&self, graph: &mut GraphDescription) -> Result<bool, CompileError> {
+        let Some(mut version) = schema_version(graph) else { return Ok(false) };
+
+        if version > self.current_version {
+            return Err(CompileError::UnsupportedSchemaVersion {
+                found: version,
+                current: self.current_version,
+            });
+        }
+
+        let migrated = version < self.current_version;
+        while version < self.current_version {
+            let migration = self.migrations
+                .get(&version)
+                .ok_or(CompileError::MissingMigration { from: version })?;
+
+            tracing::info!("[PBGC] Migrating graph '{}' from schema v{} to v{}",
+                graph.metadata.name, version, version + 1);
+            migration(graph);
+            version += 1;
+        }
+
+        if migrated {
+            graph.metadata.version = version.to_string();
+        }
+        Ok(migrated)
+    }
+}
+
+/// Schema version of a graph: the leading number of its metadata version
+pub fn schema_version(graph: &GraphDescription) -> Option<u32> {
+    let version = graph.metadata.version.trim();
+    let major = version.split('.').next().unwrap_or(version);
+    major.trim_start_matches('v').parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph_at(version: &str) -> GraphDescription {
+        let mut graph = GraphDescription::new("old");
+        graph.metadata.version = version.to_string();
+        graph
+    }
+
+    /// Each step appends its source version to the graph's name
+    fn registry() -> MigrationRegistry {
+        MigrationRegistry::new(3)
+            .with_migration(1, |graph| graph.metadata.name.push_str("_1"))
+            .with_migration(2, |graph| graph.metadata.name.push_str("_2"))
+    }
+
+    #[test]
+    fn migrations_run_in_order_up_to_the_current_version() {
+        let mut graph = graph_at("1.4.0");
+        assert!(registry().migrate(&mut graph).unwrap());
+        assert_eq!(graph.metadata.name, "old_1_2");
+        assert_eq!(schema_version(&graph), Some(3));
+
+        let mut current = graph_at("v3");
+        assert!(!registry().migrate(&mut current).unwrap());
+        assert_eq!(current.metadata.name, "old");
+    }
+
+    #[test]
+    fn newer_graphs_and_missing_steps_are_rejected() {
+        let err = registry().migrate(&mut graph_at("4")).unwrap_err();
+        assert!(matches!(err, CompileError::UnsupportedSchemaVersion { found: 4, current: 3 }));
+
+        let err = registry().migrate(&mut graph_at("0")).unwrap_err();
+        assert!(matches!(err, CompileError::MissingMigration { from: 0 }));
+    }
+}
@@ -0,0 +1,329 @@
+//! # Graph Normalization
+//!
+//! Preflight cleanup run on every graph before analysis, so that cosmetic
+//! inconsistencies from the editor or hand-edited files don't surface as
+//! confusing failures deep in code generation.
+
+use crate::diagnostics::Diagnostic;
+use crate::error::CompileError;
+use graphy::core::NodeMetadataProvider;
+use graphy::{ConnectionType, GraphDescription, NodeTypes};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Normalize a graph in place
+///
+/// - Trims surrounding whitespace from node types and pin names
+/// - Removes connections that duplicate an earlier one (same source and
+///   target pins; connection IDs are ignored)
+/// - Gives pins with an empty or name-only ID (common in older and
+///   hand-authored graphs) their conventional ID, updating connections that
+///   refer to them by that ID or by name
+/// - Verifies every other pin ID follows the `{node_id}_{pin_name}` convention
+///   the code generator relies on, failing with [`CompileError::InvalidPinId`]
+/// - Verifies every connection's endpoints exist on their nodes' current
+///   pins, failing with [`CompileError::DanglingConnection`]
+/// - Verifies no input data pin has more than one incoming connection,
+///   failing with [`CompileError::AmbiguousInput`]
+pub fn normalize(graph: &mut GraphDescription) -> Result<(), CompileError> {
+    normalize_with_diagnostics(graph).map(|_| ())
+}
+
+/// [`normalize`], also returning a diagnostic for each pin ID it filled in
+pub fn normalize_with_diagnostics(graph: &mut GraphDescription) -> Result<Vec<Diagnostic>, CompileError> {
+    // (node ID, pin name) → repaired pin ID
+    let mut repaired: BTreeMap<(String, String), String> = BTreeMap::new();
+
+    for node in graph.nodes.values_mut() {
+        trim_in_place(&mut node.node_type);
+
+        for pin in node.inputs.iter_mut().chain(node.outputs.iter_mut()) {
+            trim_in_place(&mut pin.pin.name);
+
+            let expected = format!("{}_{}", node.id, pin.pin.name);
+            if pin.id.trim().is_empty() || pin.id == pin.pin.name {
+                repaired.insert((node.id.clone(), pin.pin.name.clone()), expected.clone());
+                pin.id = expected;
+            } else if pin.id != expected {
+                return Err(CompileError::InvalidPinId {
+                    node_id: node.id.clone(),
+                    pin_id: pin.id.clone(),
+                    expected,
+                });
+            }
+        }
+    }
+
+    let diagnostics = repaired
+        .iter()
+        .map(|((node_id, pin_name), id)| {
+            let diagnostic = Diagnostic::info(format!(
+                "Pin '{}' on node '{}' has no ID of its own; matched by name as '{}'",
+                pin_name, node_id, id
+            ))
+            .with_pin(pin_name.clone());
+            match graph.nodes.get(node_id) {
+                Some(node) => diagnostic.with_node(node),
+                None => diagnostic,
+            }
+        })
+        .collect();
+
+    for connection in &mut graph.connections {
+        if let Some(id) = repaired.get(&(connection.source_node.clone(), connection.source_pin.clone())) {
+            connection.source_pin = id.clone();
+        }
+        if let Some(id) = repaired.get(&(connection.target_node.clone(), connection.target_pin.clone())) {
+            connection.target_pin = id.clone();
+        }
+    }
+
+    let before = graph.connections.len();
+    let mut seen = HashSet::new();
+    graph.connections.retain(|c| {
+        seen.insert((
+            c.source_node.clone(),
+            c.source_pin.clone(),
+            c.target_node.clone(),
+            c.target_pin.clone(),
+        ))
+    });
+    let removed = before - graph.connections.len();
+    if removed > 0 {
+        tracing::info!("[PBGC] Removed {} duplicate connection(s)", removed);
+    }
+
+    check_connection_endpoints(graph)?;
+    check_single_data_source(graph)?;
+    Ok(diagnostics)
+}
+
+/// Fail if a connection leaves from or arrives at a pin its node doesn't have
+///
+/// Saved graphs can outlive the node signatures they were built against; a
+/// stale connection would otherwise be silently ignored by the resolvers.
+fn check_connection_endpoints(graph: &GraphDescription) -> Result<(), CompileError> {
+    for connection in &graph.connections {
+        let endpoints = [
+            (&connection.source_node, &connection.source_pin, true),
+            (&connection.target_node, &connection.target_pin, false),
+        ];
+        for (node_id, pin_id, is_output) in endpoints {
+            let missing_endpoint = match graph.nodes.get(node_id) {
+                None => Some(format!("node '{}'", node_id)),
+                Some(node) => {
+                    let pins = if is_output { &node.outputs } else { &node.inputs };
+                    let direction = if is_output { "output" } else { "input" };
+                    (!pins.iter().any(|pin| &pin.id == pin_id))
+                        .then(|| format!("{} pin '{}' on node '{}'", direction, pin_id, node_id))
+                }
+            };
+            if let Some(missing_endpoint) = missing_endpoint {
+                return Err(CompileError::DanglingConnection {
+                    connection_id: connection.id.clone(),
+                    missing_endpoint,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fail if two data connections feed the same input pin
+///
+/// Exec outputs may fan out, but a data input reads exactly one value; which
+/// of several sources wins would otherwise be decided silently by the resolver.
+fn check_single_data_source(graph: &GraphDescription) -> Result<(), CompileError> {
+    let mut sources: BTreeMap<(&str, &str), Vec<&str>> = BTreeMap::new();
+    for connection in &graph.connections {
+        if matches!(connection.connection_type, ConnectionType::Data) {
+            sources
+                .entry((&connection.target_node, &connection.target_pin))
+                .or_default()
+                .push(&connection.source_node);
+        }
+    }
+
+    match sources.into_iter().find(|(_, from)| from.len() > 1) {
+        Some(((node, pin), from)) => Err(CompileError::AmbiguousInput {
+            node: node.to_string(),
+            pin: pin.to_string(),
+            sources: from.into_iter().map(str::to_string).collect(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Fail if pure nodes depend on each other's outputs in a loop
+///
+/// Pure nodes are inlined into their consumers, so a data cycle among them
+/// has no evaluation order and would expand forever. Cycles through exec
+/// nodes are left to exec analysis. The cycle is reported as
+/// [`CompileError::DataCycle`], starting from its smallest node ID.
+pub fn check_data_cycles(graph: &GraphDescription, provider: &dyn NodeMetadataProvider) -> Result<(), CompileError> {
+    let is_pure = |id: &str| {
+        graph.nodes
+            .get(id)
+            .and_then(|node| provider.get_node_metadata(&node.node_type))
+            .is_some_and(|meta| meta.node_type == NodeTypes::pure)
+    };
+
+    // Edges from each pure node to the pure nodes it feeds
+    let mut feeds: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for connection in &graph.connections {
+        if matches!(connection.connection_type, ConnectionType::Data)
+            && is_pure(&connection.source_node)
+            && is_pure(&connection.target_node)
+        {
+            feeds.entry(&connection.source_node).or_default().push(&connection.target_node);
+        }
+    }
+    for targets in feeds.values_mut() {
+        targets.sort();
+        targets.dedup();
+    }
+
+    // Depth-first search; a node still on the path when reached again closes a cycle
+    let mut finished = HashSet::new();
+    let mut on_path: HashMap<&str, usize> = HashMap::new();
+    for &start in feeds.keys() {
+        if finished.contains(start) {
+            continue;
+        }
+        let mut path = vec![start];
+        let mut next_edge = vec![0];
+        on_path.insert(start, 0);
+
+        while let (Some(&node), Some(edge)) = (path.last(), next_edge.last_mut()) {
+            let targets = feeds.get(node).map(Vec::as_slice).unwrap_or_default();
+            let Some(&target) = targets.get(*edge) else {
+                finished.insert(node);
+                on_path.remove(node);
+                path.pop();
+                next_edge.pop();
+                continue;
+            };
+            *edge += 1;
+
+            if let Some(&index) = on_path.get(target) {
+                let mut nodes: Vec<String> = path[index..].iter().map(|id| id.to_string()).collect();
+                let smallest = (0..nodes.len()).min_by_key(|&i| &nodes[i]).unwrap_or(0);
+                nodes.rotate_left(smallest);
+                return Err(CompileError::DataCycle { nodes });
+            }
+            if !finished.contains(target) {
+                on_path.insert(target, path.len());
+                path.push(target);
+                next_edge.push(0);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn trim_in_place(s: &mut String) {
+    let trimmed = s.trim();
+    if trimmed.len() != s.len() {
+        *s = trimmed.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{GraphBuilder, provider};
+
+    fn two_node_graph() -> GraphDescription {
+        GraphBuilder::new()
+            .node("main", "main")
+            .node("print", "print_string")
+            .exec("main", "body", "print")
+            .build()
+    }
+
+    #[test]
+    fn node_types_and_pin_names_are_trimmed() {
+        let mut graph = two_node_graph();
+        let print = graph.nodes.get_mut("print").unwrap();
+        print.node_type = " print_string\n".to_string();
+        print.inputs[1].pin.name = "message ".to_string();
+
+        normalize(&mut graph).unwrap();
+        let print = &graph.nodes["print"];
+        assert_eq!(print.node_type, "print_string");
+        assert_eq!(print.inputs[1].pin.name, "message");
+    }
+
+    #[test]
+    fn duplicate_connections_are_removed() {
+        let mut graph = two_node_graph();
+        let mut duplicate = graph.connections[0].clone();
+        duplicate.id = "copy".to_string();
+        graph.connections.push(duplicate);
+
+        normalize(&mut graph).unwrap();
+        assert_eq!(graph.connections.len(), 1);
+        assert_eq!(graph.connections[0].id, "c0");
+    }
+
+    #[test]
+    fn name_only_pin_ids_are_filled_in_and_reported() {
+        let mut graph = two_node_graph();
+        graph.nodes.get_mut("print").unwrap().inputs[0].id = "exec".to_string();
+        graph.connections[0].target_pin = "exec".to_string();
+
+        let diagnostics = normalize_with_diagnostics(&mut graph).unwrap();
+        assert_eq!(graph.nodes["print"].inputs[0].id, "print_exec");
+        assert_eq!(graph.connections[0].target_pin, "print_exec");
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn misnamed_pin_ids_and_ambiguous_inputs_are_rejected() {
+        let mut graph = two_node_graph();
+        graph.nodes.get_mut("print").unwrap().inputs[1].id = "Print_Message".to_string();
+        assert!(matches!(normalize(&mut graph), Err(CompileError::InvalidPinId { ref pin_id, .. }) if pin_id == "Print_Message"));
+
+        let mut graph = GraphBuilder::new()
+            .node("a", "add")
+            .node("b", "add")
+            .node("sum", "add")
+            .data("a", "result", "sum", "a")
+            .data("b", "result", "sum", "a")
+            .build();
+        match normalize(&mut graph) {
+            Err(CompileError::AmbiguousInput { node, pin, sources }) => {
+                assert_eq!((node.as_str(), pin.as_str()), ("sum", "sum_a"));
+                assert_eq!(sources, ["a", "b"]);
+            }
+            other => panic!("expected AmbiguousInput, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn pure_data_cycle_is_reported_from_its_smallest_node() {
+        let graph = GraphBuilder::new()
+            .node("b", "add")
+            .node("a", "add")
+            .data("a", "result", "b", "a")
+            .data("b", "result", "a", "b")
+            .build();
+
+        match check_data_cycles(&graph, &provider()) {
+            Err(CompileError::DataCycle { nodes }) => assert_eq!(nodes, ["a", "b"]),
+            other => panic!("expected DataCycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cycles_through_exec_nodes_are_not_data_cycles() {
+        let graph = GraphBuilder::new()
+            .node("spawn", "spawn")
+            .node("sum", "add")
+            .data("spawn", "result", "sum", "a")
+            .data("sum", "result", "spawn", "prefab")
+            .build();
+
+        assert!(check_data_cycles(&graph, &provider()).is_ok());
+    }
+}
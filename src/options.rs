@@ -0,0 +1,414 @@
+//! # Compile Options
+//!
+//! Settings that tweak how a Blueprint graph is turned into Rust code.
+
+use crate::codegen::{ClassContext, PostProcessor, RuntimeProfile};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// Options controlling Blueprint code generation
+///
+/// All options default to the compiler's standard behavior, so callers only
+/// need to set the fields they care about:
+///
+/// ```rust
+/// use pbgc::CompileOptions;
+///
+/// let options = CompileOptions {
+///     trace_nodes: true,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// Prepend a `trace!` statement to every executed node
+    ///
+    /// Each function, setter, and control-flow node emits
+    /// `trace!("node {id} ({type})")` before its code, giving runtime
+    /// visibility into which nodes of the graph actually ran.
+    pub trace_nodes: bool,
+
+    /// Run each event function inside a `tracing` span named after the event
+    ///
+    /// The span records the event's parameters as fields (with `?`, so their
+    /// types must be `Debug`) and is entered for the whole body; async events
+    /// are instrumented with it instead. Latent events, which compile to
+    /// state machines, get no span.
+    pub emit_spans: bool,
+
+    /// End each node's statement with a comment giving its execution order
+    ///
+    /// The comment (`// [#7] node print_1`) numbers nodes from 1 in the order
+    /// they run within their event or sub-graph function, to correlate the
+    /// generated lines with the graph while debugging. Latent waits aren't
+    /// annotated.
+    pub emit_order_comments: bool,
+
+    /// Macro print nodes are emitted as, e.g. `engine_log`; empty means `println`
+    ///
+    /// Applies to nodes with [`print_args`](crate::NodeAttributes::print_args),
+    /// whose arguments are kept as declared. The macro must be in scope in the
+    /// generated code, e.g. through a node import.
+    pub print_macro: String,
+
+    /// Events exported to JavaScript with `#[wasm_bindgen]`, by function name
+    ///
+    /// Only free event functions can be exported; compiling a class with
+    /// exports fails with [`CompileError::UnsupportedTarget`](crate::CompileError::UnsupportedTarget).
+    pub wasm_exports: BTreeSet<String>,
+
+    /// Events exported with the C ABI (`#[no_mangle] pub extern "C" fn`), by function name
+    ///
+    /// Each gets a doc comment with its C prototype. Parameters must be
+    /// primitives or raw pointers; other types, async and latent events fail
+    /// with [`CompileError::UnsupportedTarget`](crate::CompileError::UnsupportedTarget),
+    /// as does compiling a class or closures with exports.
+    pub c_exports: BTreeSet<String>,
+
+    /// Generate a `dispatch(event, args)` function calling events by name
+    ///
+    /// Arguments are passed as a generated `Value` enum and decoded into each
+    /// event's parameter types; unknown events and mismatched arguments return
+    /// a `DispatchError`. Events with parameters `dispatch` can't decode, and
+    /// latent or async events, are left out with a warning. Not available
+    /// when compiling a class.
+    pub emit_dispatch: bool,
+
+    /// Generate a `#[bench]` for each event, in a `#[cfg(test)] mod benches`
+    ///
+    /// Each bench calls its event in `b.iter`, for tracking the performance
+    /// of compiled Blueprints over time. Events with parameters, and latent
+    /// or async events, get no bench. The benches need a nightly toolchain
+    /// (`#![feature(test)]`). Not available when compiling a class or closures.
+    pub emit_benches: bool,
+
+    /// Emit a `#[test]` per example registered for each emitted sub-graph function
+    ///
+    /// See [`SubgraphExample`](crate::SubgraphExample). Each test asserts
+    /// that calling the function with the example's inputs returns its
+    /// expected value.
+    pub emit_tests: bool,
+
+    /// Leave out the code of control-flow branches that can never run
+    ///
+    /// Dead branches (the `else` of a constant-true condition, switch cases
+    /// that can't match a constant) are reported as warnings either way.
+    pub prune_dead_branches: bool,
+
+    /// Emit a `debug_assert!` for each precondition a function node declares
+    ///
+    /// See `NodeAttributes::debug_assertions`. Failing checks name the node
+    /// and the violated condition.
+    pub emit_debug_asserts: bool,
+
+    /// Target environment constraints for the generated code
+    ///
+    /// `None` uses the target the graph declares, if any, and a plain `std`
+    /// target otherwise. `Some` always wins, including
+    /// `Some(TargetSpec::default())`.
+    pub target: Option<TargetSpec>,
+
+    /// Short aliases for long type paths, keyed by full path
+    ///
+    /// Each entry is imported once (`use my_engine::math::Vector3 as Vector3;`)
+    /// and the alias replaces the full path in result annotations, variable
+    /// declarations and default values. An alias that binds the same name as a
+    /// node import from a different path is an import conflict.
+    pub type_aliases: HashMap<String, String>,
+
+    /// Reject unconnected inputs instead of substituting their type's default
+    ///
+    /// An input that has neither a connection nor an editor-set value fails
+    /// with [`CompileError::MissingInput`](crate::CompileError::MissingInput),
+    /// catching half-wired graphs before they silently compute with defaults.
+    pub strict_inputs: bool,
+
+    /// Compile a graph without events into an empty module instead of failing
+    ///
+    /// Useful for placeholder Blueprints. Without it, such a graph fails with
+    /// [`CompileError::NoEntryPoints`](crate::CompileError::NoEntryPoints).
+    pub allow_empty: bool,
+
+    /// Fail compilation if any warning diagnostic is reported
+    ///
+    /// For CI gating: the warnings come back in
+    /// [`CompileError::DiagnosticsAsErrors`](crate::CompileError::DiagnosticsAsErrors).
+    /// Info diagnostics don't count.
+    pub deny_warnings: bool,
+
+    /// End function bodies with `return value;` instead of a trailing expression
+    ///
+    /// Affects the value returned when a sub-graph function's body falls
+    /// through to its outputs. Returns from inside an exec chain are always
+    /// explicit.
+    pub explicit_returns: bool,
+
+    /// Annotate every result binding with its type (`let x: T = f(..);`)
+    ///
+    /// Results whose return type was resolved from a generic parameter are
+    /// annotated regardless, since rustc frequently can't infer them.
+    pub annotate_result_types: bool,
+
+    /// Compile events into closures over a shared environment instead of functions
+    ///
+    /// See [`ClosureTarget`]. Can't be combined with `class`.
+    pub closures: Option<ClosureTarget>,
+
+    /// Pass some events' inputs as fields of one context struct
+    ///
+    /// See [`ContextSpec`]. Events that aren't mapped keep one parameter per
+    /// data output.
+    pub context_param: Option<ContextSpec>,
+
+    /// Compile the Blueprint as a class
+    ///
+    /// Variables become fields of the described struct and events become
+    /// `&mut self` methods on it. Without a class context, events are free
+    /// functions and variables live in `thread_local!` storage.
+    pub class: Option<ClassContext>,
+
+    /// Compute pure values read by several events once per instance
+    ///
+    /// Only applies to classes. A deterministic pure node fed by nothing but
+    /// constants and read by more than one event gets a private
+    /// `OnceCell` field, filled by whichever event reads it first; the other
+    /// events reuse the value instead of recomputing it.
+    pub cache_shared_pure: bool,
+
+    /// Abort once the generated code grows past this many bytes
+    pub max_output_bytes: Option<usize>,
+
+    /// Abort once this many nodes have been expanded
+    ///
+    /// Every inlined pure node and every exec node counts, so a pure node
+    /// feeding many consumers counts once per use. This bounds the work done
+    /// on pathological graphs with deep pure fan-out.
+    pub max_nodes_expanded: Option<usize>,
+
+    /// Arm added to switch nodes whose `match` has no wildcard arm
+    pub switch_fallback: SwitchFallback,
+
+    /// What fallible nodes with an unconnected failure branch do on `Err`
+    pub error_policy: ErrorPolicy,
+
+    /// How vector and color literals are written
+    pub composite_constructors: CompositeConstructors,
+
+    /// How instances of sub-graphs registered with the compiler are compiled
+    pub subgraph_strategy: SubgraphStrategy,
+
+    /// Runtime scaffolding to generate for; `None` uses [`DefaultRuntime`](crate::DefaultRuntime)
+    pub runtime: Option<Arc<dyn RuntimeProfile>>,
+
+    /// Transforms run over the finished output, in order
+    ///
+    /// For house conventions beyond what the generator emits, e.g.
+    /// [`SortImports`](crate::SortImports). An error from any of them fails
+    /// the compilation.
+    pub post_processors: Vec<Arc<dyn PostProcessor>>,
+}
+
+/// Events compiled to closures capturing a shared environment
+///
+/// The output is one builder function,
+/// `pub fn {builder}(env: Rc<RefCell<{env_type}>>) -> HashMap<&'static str, Box<dyn FnMut()>>`,
+/// returning a closure per event keyed by event name. Variables are fields
+/// of the environment, read and written as `env.name`. Events with
+/// parameters, latent or async events, and calls between events are not
+/// supported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClosureTarget {
+    /// Name of the generated builder function, e.g. `build_events`
+    pub builder: String,
+    /// Type of the captured environment, e.g. `World`
+    pub env_type: String,
+    /// Call node functions as methods on the environment (`env.print_string(..)`)
+    pub route_calls: bool,
+}
+
+impl ClosureTarget {
+    pub fn new(builder: impl Into<String>, env_type: impl Into<String>) -> Self {
+        Self {
+            builder: builder.into(),
+            env_type: env_type.into(),
+            route_calls: false,
+        }
+    }
+
+    /// Call node functions as methods on the environment
+    pub fn with_routed_calls(mut self) -> Self {
+        self.route_calls = true;
+        self
+    }
+}
+
+/// Events taking their inputs as fields of a context struct
+///
+/// A mapped event compiles to `pub fn on_tick(ctx: &TickContext)` and reads
+/// each of its data outputs as `ctx.field`, cloned unless the field's type is
+/// `Copy`. Events implementing a trait method keep the method's signature.
+/// Mapped events can't contain latent nodes or be called by other events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextSpec {
+    /// Name of the context parameter
+    pub param: String,
+    /// Context of each mapped event, by event name
+    pub events: HashMap<String, EventContext>,
+}
+
+impl Default for ContextSpec {
+    fn default() -> Self {
+        Self {
+            param: "ctx".to_string(),
+            events: HashMap::new(),
+        }
+    }
+}
+
+impl ContextSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the context parameter something other than `ctx`
+    pub fn with_param(mut self, param: impl Into<String>) -> Self {
+        self.param = param.into();
+        self
+    }
+
+    /// Pass the inputs of the event named `event` through `context`
+    pub fn with_event(mut self, event: impl Into<String>, context: EventContext) -> Self {
+        self.events.insert(event.into(), context);
+        self
+    }
+}
+
+/// The context struct one event reads its inputs from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventContext {
+    /// Context type, e.g. `TickContext`; the parameter is `&TickContext`
+    pub ty: String,
+    /// Field each output pin reads, by pin name
+    ///
+    /// Pins not listed read the field named like the pin.
+    pub fields: HashMap<String, String>,
+}
+
+impl EventContext {
+    pub fn new(ty: impl Into<String>) -> Self {
+        Self {
+            ty: ty.into(),
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Read the output pin named `pin` from `field`
+    pub fn with_field(mut self, pin: impl Into<String>, field: impl Into<String>) -> Self {
+        self.fields.insert(pin.into(), field.into());
+        self
+    }
+}
+
+/// How sub-graph instances are compiled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SubgraphStrategy {
+    /// Expand every instance in place
+    ///
+    /// Expansion is not available yet, so instances of registered sub-graphs
+    /// fail with [`CompileError::UnexpandedSubgraph`](crate::CompileError::UnexpandedSubgraph).
+    #[default]
+    Inline,
+    /// Emit each used sub-graph once as a `pub fn` and call it from every instance
+    Functions,
+}
+
+/// Constructors used for vector and color literals
+///
+/// Each unset entry renders as a tuple, e.g. `(0.2, 0.4, 0.6, 1.0)`. A set
+/// entry is called with the components instead, e.g. with `vector3` set to
+/// `"Vec3::new"`, a vector renders as `Vec3::new(1.0, 2.0, 3.0)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompositeConstructors {
+    pub vector2: Option<String>,
+    pub vector3: Option<String>,
+    pub color: Option<String>,
+}
+
+/// What a switch node does with a value none of its cases handle
+///
+/// Switch nodes generate a `match` with one arm per case. Unless the cases
+/// cover every value, rustc rejects the match as non-exhaustive, so a
+/// wildcard arm is added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SwitchFallback {
+    /// `_ => unreachable!("unhandled case in node {id}"),`
+    #[default]
+    Unreachable,
+    /// `_ => panic!("..."),` with a custom message; `{id}` is the node's ID
+    Panic(String),
+    /// `_ => {}`: unhandled values do nothing
+    Ignore,
+}
+
+/// What a fallible node does with an `Err` nothing is wired to handle
+///
+/// Only applies when the node's failure exec output is unconnected; a
+/// connected failure branch always runs instead. `{id}` is the node's ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Propagate with `?` where the enclosing function returns a `Result`,
+    /// and panic otherwise
+    ///
+    /// Generated event functions return `()`, so this currently panics.
+    #[default]
+    Propagate,
+    /// `Err(err) => panic!("node {id} failed: {:?}", err),`
+    Panic,
+    /// `Err(err) => tracing::error!("node {id} failed: {:?}", err),` and carry on
+    LogAndContinue,
+    /// `Err(err) => hook("{id}", err),` calling the given function path
+    Hook(String),
+}
+
+/// Constraints of the environment the generated code will be compiled for
+///
+/// A graph file can declare its own target in a `metadata.target` object
+/// (see [`graph_target`](crate::graph_target)), which is used unless the
+/// options set one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TargetSpec {
+    /// Rust edition the output targets (recorded in the header), e.g. `"2021"`
+    pub edition: Option<String>,
+
+    /// Generate `#![no_std]` code
+    ///
+    /// Defaults avoid `std`-only constructs, and class variables (which rely on
+    /// `thread_local!`) are rejected.
+    pub no_std: bool,
+
+    /// Whether an allocator is available in `no_std` mode
+    ///
+    /// When set, `extern crate alloc;` is emitted and heap-allocated defaults
+    /// such as `String::new()` are routed through `alloc`. Ignored unless
+    /// [`no_std`](Self::no_std) is set.
+    pub alloc: bool,
+
+    /// Generate code for `wasm32-unknown-unknown`
+    ///
+    /// Defaults and initial values that would fail in the browser (clocks,
+    /// threads, processes, files, sockets) are rejected. `thread_local!`
+    /// variable storage is kept, as WASM modules run on a single thread.
+    pub wasm: bool,
+}
+
+impl TargetSpec {
+    /// A plain `std` target
+    pub const DEFAULT: Self = Self {
+        edition: None,
+        no_std: false,
+        alloc: false,
+        wasm: false,
+    };
+}
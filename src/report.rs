@@ -0,0 +1,78 @@
+//! # Optimization Report
+//!
+//! Counts of the sites each optimizing pass transformed during one
+//! compilation, for checking what actually fired when output looks
+//! unexpected.
+
+use serde::Serialize;
+use std::fmt;
+use std::ops::AddAssign;
+
+/// What the optimizing passes did to one compiled graph
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OptimizationReport {
+    /// Non-deterministic pure nodes evaluated once into a local where they run
+    pub pure_nodes_hoisted: usize,
+    /// Variables read once into a local for a control-flow node and its branches
+    pub getters_hoisted: usize,
+    /// `while` loops with a constant condition turned into `loop` or removed
+    pub constants_folded: usize,
+    /// Connected branches removed because their condition is constant
+    /// (`CompileOptions::prune_dead_branches`)
+    pub dead_branches_pruned: usize,
+    /// Pure values shared by several events cached in a class field
+    /// (`CompileOptions::cache_shared_pure`)
+    pub pure_values_cached: usize,
+    /// Variables declared `const` because nothing writes them
+    pub variables_made_const: usize,
+}
+
+impl OptimizationReport {
+    /// Whether no pass transformed anything
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl AddAssign for OptimizationReport {
+    fn add_assign(&mut self, other: Self) {
+        self.pure_nodes_hoisted += other.pure_nodes_hoisted;
+        self.getters_hoisted += other.getters_hoisted;
+        self.constants_folded += other.constants_folded;
+        self.dead_branches_pruned += other.dead_branches_pruned;
+        self.pure_values_cached += other.pure_values_cached;
+        self.variables_made_const += other.variables_made_const;
+    }
+}
+
+/// E.g. `3 pure nodes hoisted, 1 constant folded`
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = [
+            (self.pure_nodes_hoisted, "pure node", "hoisted"),
+            (self.getters_hoisted, "getter", "hoisted"),
+            (self.constants_folded, "constant", "folded"),
+            (self.dead_branches_pruned, "dead branch", "pruned"),
+            (self.pure_values_cached, "pure value", "cached"),
+            (self.variables_made_const, "variable", "made const"),
+        ];
+        let parts: Vec<String> = counts
+            .iter()
+            .filter(|(count, _, _)| *count > 0)
+            .map(|(count, noun, verb)| {
+                let plural = match (*count, noun.ends_with("ch")) {
+                    (1, _) => "",
+                    (_, true) => "es",
+                    _ => "s",
+                };
+                format!("{} {}{} {}", count, noun, plural, verb)
+            })
+            .collect();
+
+        if parts.is_empty() {
+            write!(f, "no optimizations applied")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! # Sub-graphs
+//!
+//! A sub-graph is a reusable graph instantiated as a single node in other
+//! graphs. Its boundary is marked by two special nodes:
+//!
+//! - a [`SUBGRAPH_INPUTS`] node, whose data outputs are the sub-graph's
+//!   inputs and whose exec output starts its body
+//! - a [`SUBGRAPH_OUTPUTS`] node, whose data inputs are the sub-graph's
+//!   outputs; reaching its exec input ends the body
+//!
+//! An instance is a node whose `node_type` is the sub-graph's name, with
+//! input and output pins named after the boundary pins.
+//!
+//! With [`SubgraphStrategy::Functions`](crate::SubgraphStrategy::Functions),
+//! each sub-graph used by a graph is emitted once as
+//! `pub fn name(inputs...) -> outputs` and every instance becomes a call to it.
+//! A pure sub-graph's function is `const fn` when all of its nodes are
+//! const-evaluable and `#[inline]` otherwise.
+//! Examples registered for a sub-graph become `#[test]`s next to its function
+//! under [`CompileOptions::emit_tests`](crate::CompileOptions::emit_tests).
+
+use crate::codegen::declared_type;
+use crate::metadata::NodeAttributes;
+use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+use graphy::{DataType, GraphDescription, NodeInstance, NodeTypes};
+use std::collections::BTreeMap;
+
+/// Node type marking a sub-graph's inputs
+pub const SUBGRAPH_INPUTS: &str = "subgraph_inputs";
+
+/// Node type marking a sub-graph's outputs
+pub const SUBGRAPH_OUTPUTS: &str = "subgraph_outputs";
+
+/// Sub-graph definitions available to the graphs being compiled
+#[derive(Debug, Clone, Default)]
+pub struct SubgraphLibrary {
+    subgraphs: BTreeMap<String, GraphDescription>,
+    examples: BTreeMap<String, Vec<SubgraphExample>>,
+}
+
+/// Inputs of a sub-graph and the outputs they are expected to produce
+///
+/// Values are Rust expressions, like editor-set constants; numeric literals
+/// take the type of the parameter or return value they are compared with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubgraphExample {
+    /// Value of each input, by input pin name
+    pub inputs: BTreeMap<String, String>,
+    /// Expected return value; a tuple when the sub-graph has several outputs
+    pub expected: String,
+}
+
+impl SubgraphExample {
+    pub fn new(expected: impl Into<String>) -> Self {
+        Self {
+            inputs: BTreeMap::new(),
+            expected: expected.into(),
+        }
+    }
+
+    /// Set the value of the input named `pin`
+    pub fn with_input(mut self, pin: impl Into<String>, value: impl Into<String>) -> Self {
+        self.inputs.insert(pin.into(), value.into());
+        self
+    }
+}
+
+impl SubgraphLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a sub-graph, instantiated by nodes of type `name`
+    pub fn with_subgraph(mut self, name: impl Into<String>, graph: GraphDescription) -> Self {
+        self.subgraphs.insert(name.into(), graph);
+        self
+    }
+
+    /// Add an example to the sub-graph registered as `name`
+    pub fn with_example(mut self, name: impl Into<String>, example: SubgraphExample) -> Self {
+        self.examples.entry(name.into()).or_default().push(example);
+        self
+    }
+
+    /// Examples registered for the sub-graph `name`, in registration order
+    pub fn examples(&self, name: &str) -> &[SubgraphExample] {
+        self.examples.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The sub-graph instantiated by nodes of type `name`
+    pub fn get(&self, name: &str) -> Option<&GraphDescription> {
+        self.subgraphs.get(name)
+    }
+
+    /// Names of all registered sub-graphs, in sorted order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.subgraphs.keys().map(String::as_str)
+    }
+
+    /// All registered sub-graph definitions
+    pub(crate) fn graphs(&self) -> impl Iterator<Item = &GraphDescription> {
+        self.subgraphs.values()
+    }
+
+    /// Node metadata and attributes describing an instance of each sub-graph
+    ///
+    /// An instance is a function node when its sub-graph has exec pins and a
+    /// pure node otherwise. Several outputs are returned as a tuple.
+    pub(crate) fn instance_metadata(&self) -> impl Iterator<Item = (NodeMetadata, NodeAttributes)> + '_ {
+        self.subgraphs.iter().map(|(name, graph)| {
+            let inputs = boundary_node(graph, SUBGRAPH_INPUTS);
+            let outputs = boundary_node(graph, SUBGRAPH_OUTPUTS);
+
+            let params: Vec<ParamInfo> = inputs
+                .into_iter()
+                .flat_map(|node| data_pins(&node.outputs))
+                .map(|(pin_name, ty)| ParamInfo::new(pin_name, ty))
+                .collect();
+            let returns: Vec<String> = outputs
+                .into_iter()
+                .flat_map(|node| data_pins(&node.inputs))
+                .map(|(_, ty)| ty)
+                .collect();
+
+            let has_exec = graph.nodes.values().flat_map(|node| node.inputs.iter().chain(&node.outputs))
+                .any(|pin| matches!(pin.pin.data_type, DataType::Execution));
+            let node_type = if has_exec { NodeTypes::fn_ } else { NodeTypes::pure };
+
+            let mut metadata = NodeMetadata::new(name.clone(), node_type, "Sub-graphs").with_params(params);
+            if has_exec {
+                metadata = metadata.with_exec_outputs(vec!["exec_out".to_string()]);
+            }
+            match returns.as_slice() {
+                [] => {}
+                [ty] => metadata = metadata.with_return_type(TypeInfo::new(ty.clone())),
+                _ => metadata = metadata.with_return_type(TypeInfo::new(format!("({})", returns.join(", ")))),
+            }
+
+            let attributes = if returns.len() > 1 {
+                NodeAttributes::tuple_outputs()
+            } else {
+                NodeAttributes::default()
+            };
+            (metadata, attributes)
+        })
+    }
+}
+
+/// The first node of a boundary type in a sub-graph
+pub(crate) fn boundary_node<'g>(graph: &'g GraphDescription, node_type: &str) -> Option<&'g NodeInstance> {
+    graph.nodes
+        .values()
+        .filter(|node| node.node_type == node_type)
+        .min_by(|a, b| a.id.cmp(&b.id))
+}
+
+/// Names and Rust types of the data pins in a boundary pin list
+fn data_pins(pins: &[graphy::PinInstance]) -> impl Iterator<Item = (String, String)> + '_ {
+    pins.iter()
+        .filter(|pin| !matches!(pin.pin.data_type, DataType::Execution))
+        .map(|pin| {
+            let ty = declared_type(&pin.pin.data_type).unwrap_or_else(|| "()".to_string());
+            (pin.pin.name.clone(), ty)
+        })
+}
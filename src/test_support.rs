@@ -0,0 +1,271 @@
+//! # Test Support
+//!
+//! A small node library and a graph builder for unit tests, so they don't
+//! depend on the installed pulsar_std.
+
+use crate::metadata::BlueprintMetadataProvider;
+use graphy::core::{NodeMetadata, ParamInfo, TypeInfo};
+use graphy::{
+    Connection, ConnectionType, DataType, GraphDescription, NodeInstance, NodeTypes, Pin, PinInstance, PinType,
+    Position, PropertyValue,
+};
+use std::collections::HashMap;
+
+/// The node library tests build their graphs from
+///
+/// - `main` and `begin_play`: events with a `body` exec output
+/// - `key_pressed(state: bool)`: event with a `body` exec output
+/// - `damaged(amount: u32, source: String)`: event with a `body` exec output
+/// - `print_string(message: String)`: function
+/// - `delay(seconds: f32)`: function, latent when given `NodeAttributes::latent`
+/// - `wait_for_key() -> Option<String>`: function, latent when given `NodeAttributes::latent`
+/// - `spawn(prefab: String) -> u32`, `despawn(entity: u32)`: functions
+/// - `list_names() -> Vec<String>`: function
+/// - `read_file(path: String) -> Result<String, String>`: function with
+///   `success` and `failure` outputs, for `NodeAttributes::fallible`
+/// - `parse_token(text: String) -> Token`: function with `number`, `word` and
+///   `empty` outputs, for `NodeAttributes::exits`
+/// - `add(a: i32, b: i32) -> i32`: pure
+/// - `random_bool() -> bool`: pure
+/// - `identity<T>(value: T) -> T`: pure and generic
+/// - `concat(value: String) -> String`: pure, variadic in `value` when given `NodeAttributes::variadic`
+/// - `equal<T>(a: T, b: T) -> bool`: pure and generic, `==` when given `NodeAttributes::operator`
+/// - `facing() -> Direction`: pure, returning a unit-variant enum
+/// - `and(a: bool, b: bool) -> bool`, `or(a: bool, b: bool) -> bool`: pure
+/// - `branch(condition: bool)`: control flow with `True` and `False` outputs
+/// - `while_loop(condition: bool)`: control flow with `body` and `completed` outputs
+/// - `for_each(array: Vec<String>)`: control flow with `body` and `completed`
+///   outputs, iterating `array` as `(i, item)`
+pub(crate) fn library() -> Vec<NodeMetadata> {
+    vec![
+        NodeMetadata::new("main", NodeTypes::event, "Events")
+            .with_exec_outputs(vec!["body".to_string()]),
+        NodeMetadata::new("begin_play", NodeTypes::event, "Events")
+            .with_exec_outputs(vec!["body".to_string()]),
+        NodeMetadata::new("key_pressed", NodeTypes::event, "Events")
+            .with_params(vec![ParamInfo::new("state", "bool")])
+            .with_exec_outputs(vec!["body".to_string()]),
+        NodeMetadata::new("damaged", NodeTypes::event, "Events")
+            .with_params(vec![ParamInfo::new("amount", "u32"), ParamInfo::new("source", "String")])
+            .with_exec_outputs(vec!["body".to_string()]),
+        NodeMetadata::new("print_string", NodeTypes::fn_, "Debug")
+            .with_params(vec![ParamInfo::new("message", "String")])
+            .with_return_type(TypeInfo::new("()".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn print_string(message: String) {\n    println!(\"{}\", message);\n}"),
+        NodeMetadata::new("delay", NodeTypes::fn_, "Flow")
+            .with_params(vec![ParamInfo::new("seconds", "f32")])
+            .with_return_type(TypeInfo::new("bool".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn delay(seconds: f32) -> bool {\n    timer::elapsed(seconds)\n}"),
+        NodeMetadata::new("wait_for_key", NodeTypes::fn_, "Flow")
+            .with_return_type(TypeInfo::new("Option<String>".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn wait_for_key() -> Option<String> {\n    input::last_key()\n}"),
+        NodeMetadata::new("spawn", NodeTypes::fn_, "World")
+            .with_params(vec![ParamInfo::new("prefab", "String")])
+            .with_return_type(TypeInfo::new("u32".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn spawn(prefab: String) -> u32 {\n    world::spawn(&prefab)\n}"),
+        NodeMetadata::new("list_names", NodeTypes::fn_, "World")
+            .with_return_type(TypeInfo::new("Vec<String>".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn list_names() -> Vec<String> {\n    world::names()\n}"),
+        NodeMetadata::new("read_file", NodeTypes::fn_, "Files")
+            .with_params(vec![ParamInfo::new("path", "String")])
+            .with_return_type(TypeInfo::new("Result<String, String>".to_string()))
+            .with_exec_outputs(vec!["success".to_string(), "failure".to_string()])
+            .with_source("fn read_file(path: String) -> Result<String, String> {\n    std::fs::read_to_string(&path).map_err(|err| err.to_string())\n}"),
+        NodeMetadata::new("parse_token", NodeTypes::fn_, "Text")
+            .with_params(vec![ParamInfo::new("text", "String")])
+            .with_return_type(TypeInfo::new("Token".to_string()))
+            .with_exec_outputs(vec!["number".to_string(), "word".to_string(), "empty".to_string()]),
+        NodeMetadata::new("despawn", NodeTypes::fn_, "World")
+            .with_params(vec![ParamInfo::new("entity", "u32")])
+            .with_return_type(TypeInfo::new("()".to_string()))
+            .with_exec_outputs(vec!["exec_out".to_string()])
+            .with_source("fn despawn(entity: u32) {\n    world::despawn(entity);\n}"),
+        NodeMetadata::new("add", NodeTypes::pure, "Math")
+            .with_params(vec![ParamInfo::new("a", "i32"), ParamInfo::new("b", "i32")])
+            .with_return_type(TypeInfo::new("i32".to_string()))
+            .with_source("fn add(a: i32, b: i32) -> i32 {\n    a + b\n}"),
+        NodeMetadata::new("random_bool", NodeTypes::pure, "Math")
+            .with_return_type(TypeInfo::new("bool".to_string()))
+            .with_source("fn random_bool() -> bool {\n    rand::random()\n}"),
+        NodeMetadata::new("identity", NodeTypes::pure, "Utilities")
+            .with_params(vec![ParamInfo::new("value", "T")])
+            .with_return_type(TypeInfo::new("T".to_string()))
+            .with_source("fn identity<T>(value: T) -> T {\n    value\n}"),
+        NodeMetadata::new("concat", NodeTypes::pure, "String")
+            .with_params(vec![ParamInfo::new("value", "String")])
+            .with_return_type(TypeInfo::new("String".to_string()))
+            .with_source("fn concat(value: &[String]) -> String {\n    value.concat()\n}"),
+        NodeMetadata::new("equal", NodeTypes::pure, "Logic")
+            .with_params(vec![ParamInfo::new("a", "T"), ParamInfo::new("b", "T")])
+            .with_return_type(TypeInfo::new("bool".to_string()))
+            .with_source("fn equal<T: PartialEq>(a: T, b: T) -> bool {\n    a == b\n}"),
+        NodeMetadata::new("facing", NodeTypes::pure, "World")
+            .with_return_type(TypeInfo::new("Direction".to_string()))
+            .with_source("fn facing() -> Direction {\n    world::player_facing()\n}"),
+        NodeMetadata::new("and", NodeTypes::pure, "Logic")
+            .with_params(vec![ParamInfo::new("a", "bool"), ParamInfo::new("b", "bool")])
+            .with_return_type(TypeInfo::new("bool".to_string()))
+            .with_source("fn and(a: bool, b: bool) -> bool {\n    a && b\n}"),
+        NodeMetadata::new("or", NodeTypes::pure, "Logic")
+            .with_params(vec![ParamInfo::new("a", "bool"), ParamInfo::new("b", "bool")])
+            .with_return_type(TypeInfo::new("bool".to_string()))
+            .with_source("fn or(a: bool, b: bool) -> bool {\n    a || b\n}"),
+        NodeMetadata::new("branch", NodeTypes::control_flow, "Flow")
+            .with_params(vec![ParamInfo::new("condition", "bool")])
+            .with_exec_outputs(vec!["True".to_string(), "False".to_string()])
+            .with_source(
+                "fn branch(condition: bool) {\n    if condition {\n        exec_output!(\"True\");\n    } else {\n        exec_output!(\"False\");\n    }\n}",
+            ),
+        NodeMetadata::new("while_loop", NodeTypes::control_flow, "Flow")
+            .with_params(vec![ParamInfo::new("condition", "bool")])
+            .with_exec_outputs(vec!["body".to_string(), "completed".to_string()])
+            .with_source(
+                "fn while_loop(condition: bool) {\n    while condition {\n        exec_output!(\"body\");\n    }\n    exec_output!(\"completed\");\n}",
+            ),
+        NodeMetadata::new("for_each", NodeTypes::control_flow, "Flow")
+            .with_params(vec![ParamInfo::new("array", "Vec<String>")])
+            .with_exec_outputs(vec!["body".to_string(), "completed".to_string()])
+            .with_source(
+                "fn for_each(array: Vec<String>) {\n    for (i, item) in array.iter().enumerate() {\n        exec_output!(\"body\");\n    }\n    exec_output!(\"completed\");\n}",
+            ),
+    ]
+}
+
+/// A provider knowing only [`library`]
+pub(crate) fn provider() -> BlueprintMetadataProvider {
+    BlueprintMetadataProvider::from_nodes(library())
+}
+
+/// Builds a graph from [`library`] nodes, giving each the pins the editor would
+pub(crate) struct GraphBuilder {
+    graph: GraphDescription,
+    library: HashMap<String, NodeMetadata>,
+}
+
+impl GraphBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            graph: GraphDescription::new("test"),
+            library: library().into_iter().map(|node| (node.name.clone(), node)).collect(),
+        }
+    }
+
+    /// Add a node of a library type
+    pub(crate) fn node(mut self, id: &str, node_type: &str) -> Self {
+        let meta = &self.library[node_type];
+        let exec = || DataType::Execution;
+        let typed = |ty: &str| DataType::Typed(TypeInfo::new(ty.to_string()));
+
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        if meta.node_type == NodeTypes::event {
+            outputs.extend(meta.exec_outputs.iter().map(|name| pin(id, name, exec(), PinType::Output)));
+            outputs.extend(meta.params.iter().map(|param| {
+                pin(id, &param.name, typed(&param.param_type.type_string), PinType::Output)
+            }));
+        } else {
+            if meta.node_type != NodeTypes::pure {
+                inputs.push(pin(id, "exec", exec(), PinType::Input));
+            }
+            inputs.extend(meta.params.iter().map(|param| {
+                pin(id, &param.name, typed(&param.param_type.type_string), PinType::Input)
+            }));
+            outputs.extend(meta.exec_outputs.iter().map(|name| pin(id, name, exec(), PinType::Output)));
+            if let Some(ty) = meta.return_type.as_ref().filter(|ty| ty.type_string != "()") {
+                outputs.push(pin(id, "result", typed(&ty.type_string), PinType::Output));
+            }
+        }
+
+        self.graph.nodes.insert(
+            id.to_string(),
+            NodeInstance {
+                id: id.to_string(),
+                node_type: node_type.to_string(),
+                position: Position { x: 0.0, y: 0.0 },
+                properties: HashMap::new(),
+                inputs,
+                outputs,
+            },
+        );
+        self
+    }
+
+    /// Add a node of a type outside the library, such as a sub-graph boundary, with no pins
+    pub(crate) fn bare(mut self, id: &str, node_type: &str) -> Self {
+        self.graph.nodes.insert(
+            id.to_string(),
+            NodeInstance {
+                id: id.to_string(),
+                node_type: node_type.to_string(),
+                position: Position { x: 0.0, y: 0.0 },
+                properties: HashMap::new(),
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+            },
+        );
+        self
+    }
+
+    /// Connect an exec output to a node's `exec` input
+    pub(crate) fn exec(self, from: &str, output: &str, to: &str) -> Self {
+        self.connect(ConnectionType::Execution, from, output, to, "exec")
+    }
+
+    /// Connect a data output to a data input
+    pub(crate) fn data(self, from: &str, output: &str, to: &str, input: &str) -> Self {
+        self.connect(ConnectionType::Data, from, output, to, input)
+    }
+
+    /// Add a data input the library doesn't declare, such as one pin of a variadic parameter
+    pub(crate) fn input(mut self, node: &str, name: &str, ty: &str) -> Self {
+        let data_type = DataType::Typed(TypeInfo::new(ty.to_string()));
+        self.graph.nodes.get_mut(node).unwrap().inputs.push(pin(node, name, data_type, PinType::Input));
+        self
+    }
+
+    /// Add a data output the library doesn't declare, such as a loop's element
+    pub(crate) fn output(mut self, node: &str, name: &str, ty: &str) -> Self {
+        let data_type = DataType::Typed(TypeInfo::new(ty.to_string()));
+        self.graph.nodes.get_mut(node).unwrap().outputs.push(pin(node, name, data_type, PinType::Output));
+        self
+    }
+
+    /// Set the editor value of an unconnected input
+    pub(crate) fn property(mut self, node: &str, input: &str, value: PropertyValue) -> Self {
+        self.graph.nodes.get_mut(node).unwrap().properties.insert(input.to_string(), value);
+        self
+    }
+
+    pub(crate) fn build(self) -> GraphDescription {
+        self.graph
+    }
+
+    fn connect(mut self, connection_type: ConnectionType, from: &str, output: &str, to: &str, input: &str) -> Self {
+        self.graph.connections.push(Connection {
+            id: format!("c{}", self.graph.connections.len()),
+            source_node: from.to_string(),
+            source_pin: format!("{}_{}", from, output),
+            target_node: to.to_string(),
+            target_pin: format!("{}_{}", to, input),
+            connection_type,
+        });
+        self
+    }
+}
+
+fn pin(node_id: &str, name: &str, data_type: DataType, pin_type: PinType) -> PinInstance {
+    PinInstance {
+        id: format!("{}_{}", node_id, name),
+        pin: Pin {
+            id: format!("{}_{}", node_id, name),
+            name: name.to_string(),
+            data_type,
+            pin_type,
+        },
+    }
+}